@@ -1,8 +1,9 @@
-use std::{collections::{HashMap}};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Mutex;
 
 use convert_case::{Case, Casing};
 
-use crate::{parseatt::Structure, parse_cpu_function::ParseCPUfn};
+use crate::{parseatt::{Derivative, FunctionHeader, Structure}, parse_cpu_function::ParseCPUfn};
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use proc_macro_error::{abort, abort_call_site};
@@ -11,20 +12,37 @@ use syn::{fold::Fold, Item as SynItem,FnArg, Type, ReturnType, parse_quote, Iden
 
 use crate::parseatt::ParseAttributes;
 use crate::parse_function::Parsefn;
+use crate::inline::local_function_names;
+
+/// Names of the `#[ChandraFunction]`s expanded so far in the crate being compiled. Kernels call them
+/// by their bare name without a `use` in the attribute, as long as they are declared before the kernel.
+static DECLARED_FUNCTIONS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+pub fn is_declared_function(ident: &Ident) -> bool {
+    DECLARED_FUNCTIONS.lock().map_or(false, |declared| declared.contains(&ident.to_string()))
+}
 
 pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2, is_extension: bool) -> TokenStream {
-    let description = proc_macro2::TokenStream::from(attr).to_string();
+    let description = proc_macro2::TokenStream::from(attr);
+
+    let FunctionHeader { derivative, header } = syn::parse2::<FunctionHeader>(description).unwrap_or_else(|e| abort!(e.span(), "{}", e;
+        help = "give the derivative like `derivative = my_fn_grad` or `derivative = (d_a, d_b)`, or mark the function `non_differentiable`"));
 
     let mut parser = ParseAttributes::new();
     parser.structures = get_default_structures();
     
-    parser.parse(description);
+    parser.parse(header);
+
+    if parser.workgroup_size.is_some() {
+        abort_call_site!("`#![workgroup_size(..)]` only applies to kernels";
+            help = "functions run with the workgroup size of the kernel calling them")
+    }
 
    // println!("{:?}", parser);
 
     let tokens2 = proc_macro2::TokenStream::from(tokens);
 
-    let parse2 = syn::parse2::<SynItem>(tokens2).unwrap_or_else(|_| abort_call_site!("Failed to parse tokens"));
+    let parse2 = syn::parse2::<SynItem>(tokens2).unwrap_or_else(|e| abort!(e.span(), "{}", e));
 
     let res = match parse2 {
         SynItem::Fn(func) => {
@@ -33,12 +51,19 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
             let func_generics = sig.generics;
             let ident = sig.ident;
 
+            if !is_extension {
+                if let Ok(mut declared) = DECLARED_FUNCTIONS.lock() {
+                    declared.insert(ident.to_string());
+                }
+            }
+
             let inputs: Vec<FnArg> = sig.inputs.clone().into_iter().collect();
             
             let types:Vec<Type> = inputs.clone().iter().map(|x| {
                 match x {
                     FnArg::Typed(p) => *p.ty.clone(),
-                    FnArg::Receiver(_) => abort!(x, "self not supported")
+                    FnArg::Receiver(_) => abort!(x, "`self` arguments are not supported";
+                        help = "pass the value as a regular argument like `data: &Tensor<f32>`")
                 }
             }).collect();
 
@@ -48,6 +73,7 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
             };
 
             let mut known_generics = HashMap::new();
+            let mut known_constants = parser.get_constant_types();
 
             for gen in func_generics.params.clone().into_iter() {
                 match gen {
@@ -55,7 +81,12 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
                         let ident = x.ident;
                         known_generics.insert(ident.clone(), quote!(#ident));
                     }
-                    any => abort!(any, "Only Type Generics are allowed")
+                    // Const generics are baked into the operation tree like header constants.
+                    syn::GenericParam::Const(c) => {
+                        let ty = c.ty;
+                        known_constants.insert(c.ident, quote!(#ty));
+                    }
+                    any => abort!(any, "Lifetime generics are not supported, found `{}`", quote!(#any))
                 }
             }
 
@@ -88,8 +119,8 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
                                 final_input_names.push(i.ident.clone());
                                 final_input_names_str.push(format!("{}", i.ident.clone()));
 
-                                if let Some(known) = parser.structures.get(&t_p) {
-                                    input_structures.insert(i.ident, (*known).clone());
+                                if let Some(known) = parser.get_structure(&t_p) {
+                                    input_structures.insert(i.ident, known);
                                 } else {
                                     let id_l = i.ident;
                                     let t_name = format!("{}", id_l);
@@ -109,8 +140,8 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
                                    // println!("{:#?}", t_p);
                                    // println!();
 
-                                    if let Some(known) = parser.structures.get(&t_p) {
-                                        input_structures.insert(i.ident, (*known).clone());
+                                    if let Some(known) = parser.get_structure(&t_p) {
+                                        input_structures.insert(i.ident, known);
                                     } else {
                                         let id_l = i.ident;
                                         let t_name = format!("{}", id_l);
@@ -120,10 +151,11 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
                             }
 
                         } else {
-                            abort!(p, "Not an Ident")
+                            abort!(p.pat, "Arguments have to be plain names like `a: &Tensor<f32>`, found `{}`", quote!(#p.pat))
                         }
                     }
-                    FnArg::Receiver(_) => abort!(inp, "self not supported")
+                    FnArg::Receiver(_) => abort!(inp, "`self` arguments are not supported";
+                        help = "pass the value as a regular argument like `data: &Tensor<f32>`")
                 }
             }
 
@@ -131,13 +163,13 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
 
             let mut struct_count: u32 = 0;
 
-            for (ident, fields) in input_structures.iter() {
+            for (ident, structure) in input_structures.iter() {
                 let st = format_ident!("HelperStruct{}", struct_count);
                 let mut field_types = Vec::new();
                 let mut field_names = Vec::new();
                 let mut access_names = Vec::new();
 
-                for (name, typ) in fields.iter().map(|(k, v)| (format_ident!("{}", k), format_ident!("{}", v))) {
+                for (name, typ) in structure.fields.iter() {
                     field_types.push(typ);
                     field_names.push(name.clone());
                     access_names.push(format!("{}.{}", ident, name));
@@ -174,16 +206,61 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
                 known_generics,
                 crate_root: crate_root.clone(),
                 returns_struct: false,
-                known_extensions: parser.replace_functions,
+                known_extensions: parser.replace_functions.clone(),
                 known_functions: parser.normal_functions.clone(),
+                atomic_targets: Vec::new(),
+                debug_messages: Vec::new(),
+                expected_type: quote!(),
+                known_constants,
+                local_functions: HashMap::new(),
+                inline_count: 0,
+                tuple_vars: HashMap::new(),
+                temp_count: 0,
+                output_type: quote!(#result),
             };
 
             let public = func.vis;
+            let constants = parser.constants.clone();
 
             let b = p.fold_block(*func.block.clone());
 
+            if let Some(target) = p.atomic_targets.first() {
+                abort!(target, "Atomic operations are only supported on the output of a kernel")
+            }
+
+            if !p.debug_messages.is_empty() {
+                abort_call_site!("debug_print! and assert! are only supported inside kernels")
+            }
+
             let fn_return = p.return_type;
 
+            let function_inputs = quote!((#(#final_input_types,)*));
+            let (function_scope, with_derivative) = match derivative {
+                Derivative::Symbolic => (fn_return.clone(), quote!()),
+                Derivative::Custom(partials) => {
+                    if partials.len() != final_input_types.len() {
+                        abort!(partials[0], "`{}` has {} input(s) but {} partial derivative(s) are given", ident, final_input_types.len(), partials.len();
+                            help = "give one function per input like `derivative = (d_a, d_b)`, each taking the same inputs")
+                    }
+
+                    let partial_types = partials.iter().map(|partial| quote!{
+                        #crate_root::core::operations::function::Function<
+                            <#partial as #crate_root::core::type_traits::ChandraFn>::Result,
+                            <#partial as #crate_root::core::type_traits::ChandraFn>::Inputs,
+                            <#partial as #crate_root::core::type_traits::ChandraFn>::FunctionScope,
+                        >
+                    });
+                    (
+                        quote!(#crate_root::core::operations::derivative::CustomDerivative<#result, #function_inputs, #fn_return, (#(#partial_types,)*)>),
+                        quote!(.with_derivative((#(<#partials>::build_fn(),)*))),
+                    )
+                }
+                Derivative::NonDifferentiable => (
+                    quote!(#crate_root::core::operations::derivative::CustomDerivative<#result, #function_inputs, #fn_return, ()>),
+                    quote!(.with_derivative(())),
+                ),
+            };
+
             //let (mem_generic, mem_imputs) = get_mem_generics_and_types(types.clone());
 
             let mem_bounds = get_memmappable_bounds(types.clone(), parse_quote!(S), crate_root.clone());
@@ -204,8 +281,6 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
 
             let mut inner_generics = func_generics.params.clone();
             inner_generics_to_static_bound(&mut inner_generics);
-            let phantom_data_idents: Vec<Ident> = inner_generics.iter().enumerate().map(|(i, _)| format_ident!("_{}", i)).collect();
-
             let inner_generics_idents = inner_generics_idents(&inner_generics);
 
 
@@ -216,7 +291,7 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
                     impl<#inner_generics> #crate_root::core::type_traits::ChandraExtensionFn for #ident <#(#inner_generics_idents,)*> {
                         type Result = #result;
                         type Inputs = (#(#final_input_types,)*);
-                        type FunctionScope = #fn_return;
+                        type FunctionScope = #function_scope;
                     }
                 };
                 quote!()
@@ -227,22 +302,26 @@ pub fn function(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2
                     impl<#inner_generics> #crate_root::core::type_traits::ChandraFn for #ident <#(#inner_generics_idents,)*> {
                         type Result = #result;
                         type Inputs = (#(#final_input_types,)*);
-                        type FunctionScope = #fn_return;
+                        type FunctionScope = #function_scope;
                     }
 
                     impl<#inner_generics> #crate_root::core::type_traits::ChandraExtensionFn for #ident <#(#inner_generics_idents,)*> {
                         type Result = #result;
                         type Inputs = (#(#final_input_types,)*);
-                        type FunctionScope = #fn_return;
+                        type FunctionScope = #function_scope;
                     }
                 };
 
-                let mut cpu_p = ParseCPUfn { known_functions: parser.normal_functions };
+                let generic_literals = func_generics.params.iter().any(|gen| matches!(gen, syn::GenericParam::Type(_)));
+            let mut cpu_p = ParseCPUfn { known_functions: parser.normal_functions, crate_root: crate_root.clone(), local_functions: local_function_names(&func.block), local_arrays: HashSet::new(), generic_literals };
 
                 let blo = cpu_p.fold_block(*func.block);
                 quote!{
-                    #public fn cpu(#(#inputs,)*) -> #result
+                    #public fn cpu(#(#inputs,)*) -> #result {
+                        use #crate_root::core::type_traits::{Dot as _, KernelTensor as _};
+                        #(#constants)*
                         #blo
+                    }
                 }
             };
 
@@ -269,14 +348,15 @@ fn my_kernel(pos: Pos, {} out: &mut Vec<{}>) {{
                 #[doc = #doc]
                 #[allow(non_camel_case_types)]
                 #public struct #ident <#inner_generics> {
-                    value: #crate_root::core::operations::function::Function<#result, (#(#final_input_types,)*), #fn_return>
+                    value: #crate_root::core::operations::function::Function<#result, (#(#final_input_types,)*), #function_scope>
                 }
 
                 impl<#inner_generics> #ident <#(#inner_generics_idents,)*> {
-                    #public fn build_fn() -> #crate_root::core::operations::function::Function<#result, (#(#final_input_types,)*), #fn_return> {
+                    #public fn build_fn() -> #crate_root::core::operations::function::Function<#result, (#(#final_input_types,)*), #function_scope> {
+                        #(#constants)*
                         #(let #final_input_names = <#final_input_types>::new(#final_input_names_str);)*
                         
-                        #crate_root::core::operations::function::function(#ident_str, (#(#final_input_names,)*), |(#(#final_input_names,)*)| #b) 
+                        #crate_root::core::operations::function::function(#ident_str, (#(#final_input_names,)*), |(#(#final_input_names,)*)| #b)#with_derivative
                     }
 
                     #cpu_fn
@@ -285,7 +365,7 @@ fn my_kernel(pos: Pos, {} out: &mut Vec<{}>) {{
                 #impl_traits
             }
         }
-        any => abort!(any, "Macro needs to be placed above a function"),
+        any => abort!(any, "{} can only be applied to functions", if is_extension { "#[ChandraExtension]" } else { "#[ChandraFunction]" }),
     }
     .into();
 
@@ -301,7 +381,10 @@ fn inner_generics_idents(generics: &Punctuated<GenericParam, syn::token::Comma>)
                 GenericParam::Type(x) => {
                   x.ident.clone()
                 }
-                _ => abort!(gen, "Const and lifetime generics are not supported")
+                GenericParam::Const(c) => {
+                  c.ident.clone()
+                }
+                _ => abort!(gen, "Lifetime generics are not supported")
             }
         }).collect()
 }
@@ -314,7 +397,8 @@ fn inner_generics_to_static_bound(befor: &mut Punctuated<GenericParam, syn::toke
                 GenericParam::Type(x) => {
                     x.bounds.push(TypeParamBound::Lifetime(parse_quote!('static)));
                 }
-                _ => abort!(gen, "Const and lifetime generics are not supported")
+                GenericParam::Const(_) => {}
+                _ => abort!(gen, "Lifetime generics are not supported")
             }
         });
 }
@@ -329,7 +413,8 @@ fn get_memmappable_bounds(types: Vec<Type>, mappable_path: Path, crate_root: Tok
                 let mutable = ref_type.mutability.is_some();
                 if mutable {
                     if has_mut {
-                        abort!(ref_type, "Can't have more than 1 mutable reference")
+                        abort!(ref_type, "Only one argument can be a mutable reference";
+                            help = "the `&mut` argument is the output, pass the other arguments as `&`")
                     }
                     has_mut = true;
                 }
@@ -342,7 +427,7 @@ fn get_memmappable_bounds(types: Vec<Type>, mappable_path: Path, crate_root: Tok
                     }
                     //uniqueTypes.insert(pathType.path);
                 } else {
-                    abort!(ref_type, "Unsupported function argument Type")
+                    abort!(ref_type, "Arguments have to be references to named types like `&Tensor<f32>`, found `{}`", quote!(#ref_type))
                 }
 
             }
@@ -367,18 +452,20 @@ fn get_mem_generics_and_types(types: Vec<Type>, crate_root: TokenStream2) -> (To
     for typ in types {
         match typ.clone() {
             Type::Path(path_type) => {
-                let maybe_pos = path_type.path.segments.last().unwrap_or_else(|| abort!(typ, "Expect path to contain segments"));
+                let maybe_pos = path_type.path.segments.last().unwrap_or_else(|| abort!(typ, "Expected a type name"));
                 if maybe_pos.ident == "Pos" {
                     position_argument = quote!{pos: #typ}
                 } else {
-                    abort!(path_type, "All variables should be references with the exception of special variables")
+                    abort!(path_type, "`{}` has to be passed by reference like `&{}`", quote!(#path_type), quote!(#path_type);
+                        note = "only `Pos` is passed by value")
                 }
             }
             Type::Reference(ref_type) => {
                 let mutable = ref_type.mutability.is_some();
                 if mutable {
                     if has_mut {
-                        abort!(ref_type, "Can't have more than 1 mutable reference")
+                        abort!(ref_type, "Only one argument can be a mutable reference";
+                            help = "the `&mut` argument is the output, pass the other arguments as `&`")
                     }
                     has_mut = true;
                 }
@@ -411,10 +498,10 @@ fn get_mem_generics_and_types(types: Vec<Type>, crate_root: TokenStream2) -> (To
                     }
                     //uniqueTypes.insert(pathType.path);
                 } else {
-                    abort!(ref_type, "Unsupported function argument Type")
+                    abort!(ref_type, "Arguments have to be references to named types like `&Tensor<f32>`, found `{}`", quote!(#ref_type))
                 }
             }
-            any => abort!(any, "Unsupported function argument Type")
+            any => abort!(any, "Arguments have to be references to named types like `&Tensor<f32>`, found `{}`", quote!(#any))
         }
     }
 
@@ -425,13 +512,13 @@ fn get_mem_generics_and_types(types: Vec<Type>, crate_root: TokenStream2) -> (To
         quote!(#position_argument, #(#gen_inputs,)*) )
 }
 
-fn get_default_structures() ->  HashMap<syn::TypePath, Structure> {
+fn get_default_structures() ->  HashMap<Ident, Structure> {
     let mut map = HashMap::new();
 
-    map.insert(parse_quote!(Pos), HashMap::from([
-        ("x".to_string(), "u32".to_string()),
-        ("y".to_string(), "u32".to_string()),
-        ("z".to_string(), "u32".to_string())
+    map.insert(format_ident!("Pos"), Structure::new(vec![
+        (format_ident!("x"), parse_quote!(u32)),
+        (format_ident!("y"), parse_quote!(u32)),
+        (format_ident!("z"), parse_quote!(u32)),
     ]));
 
     //map.insert(parse_quote!(Tensor<f32>), HashMap::new());
@@ -0,0 +1,544 @@
+use std::collections::{HashMap, HashSet};
+
+use proc_macro::TokenStream;
+use proc_macro2::{Group, Ident, Span, TokenStream as TokenStream2, TokenTree};
+use proc_macro_error::abort;
+use quote::{format_ident, quote};
+use syn::fold::{self, Fold};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, parse_quote, Attribute, Block, Expr, FnArg, ItemFn, Pat, Path, Stmt, Token, Type, Visibility};
+
+use crate::parseatt::KernelHeader;
+
+/// The name of the `macro_rules!` a `#[kernel]` emits to hand its source to `fuse!`.
+pub fn source_macro_ident(kernel: &Ident) -> Ident {
+    format_ident!("__chandra_kernel_{}", kernel)
+}
+
+/// The `macro_rules!` handing the source of a kernel to the next kernel of a `fuse!` chain.
+///
+/// Every invocation appends `{ { header } fn .. }` to the collected sources and calls the next
+/// source macro, the last one calls the fusing macro. The macro is re-exported next to the kernel,
+/// so `fuse!` finds it by the path of the kernel anywhere in the crate.
+pub fn source_macro(kernel: &Ident, header: &TokenStream2, item: &TokenStream2) -> TokenStream2 {
+    let name = source_macro_ident(kernel);
+
+    quote! {
+        #[doc(hidden)]
+        #[allow(unused_macros)]
+        macro_rules! #name {
+            ([($($next:tt)*) $(, $rest:tt)*] [$($fuse:tt)*] { $($sources:tt)* }) => {
+                $($next)*! { [$($rest),*] [$($fuse)*] { $($sources)* { { #header } #item } } }
+            };
+            ([] [$($fuse:tt)*] { $($sources:tt)* }) => {
+                $($fuse)*! { $($sources)* { { #header } #item } }
+            };
+        }
+
+        #[doc(hidden)]
+        #[allow(unused_imports)]
+        pub(crate) use #name;
+    }
+}
+
+/// The path of the source macro of the kernel at `kernel`, `a::scale` becomes `a::__chandra_kernel_scale`.
+fn source_macro_path(kernel: &Path) -> Path {
+    let mut path = kernel.clone();
+    let last = path.segments.last_mut().unwrap();
+
+    if !last.arguments.is_empty() {
+        abort!(last.arguments, "The generic kernel `{}` can't be fused", last.ident)
+    }
+    last.ident = source_macro_ident(&last.ident);
+
+    path
+}
+
+/// `fuse!(pub name = first, second, third)`.
+struct FuseInput {
+    vis: Visibility,
+    name: Ident,
+    kernels: Punctuated<Path, Token![,]>,
+}
+
+impl Parse for FuseInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let kernels = Punctuated::parse_separated_nonempty(input)?;
+
+        Ok(FuseInput { vis, name, kernels })
+    }
+}
+
+pub fn fuse(tokens: TokenStream, crate_root: TokenStream2) -> TokenStream {
+    let input = syn::parse2::<FuseInput>(tokens.into()).unwrap_or_else(|e| abort!(e.span(), "{}", e;
+        help = "write it like `fuse!(pub scale_relu = scale, relu);`"));
+
+    if input.kernels.len() < 2 {
+        abort!(input.kernels, "Fusing needs at least two kernels")
+    }
+
+    let (vis, name) = (input.vis, input.name);
+    let mut sources = input.kernels.iter().map(source_macro_path);
+    let first = sources.next().unwrap();
+    let rest: Vec<Path> = sources.collect();
+
+    quote! {
+        #first! { [#((#rest)),*] [#crate_root::__fuse_kernels] { #vis #name; } }
+    }
+    .into()
+}
+
+/// The sources collected by the `macro_rules!` chain: `vis name; { { header } fn .. } ..`.
+struct FuseSources {
+    vis: Visibility,
+    name: Ident,
+    stages: Vec<(TokenStream2, ItemFn)>,
+}
+
+impl Parse for FuseSources {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+        let name = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        let mut stages = Vec::new();
+        while !input.is_empty() {
+            let stage;
+            braced!(stage in input);
+
+            let header;
+            braced!(header in stage);
+            stages.push((header.parse()?, stage.parse()?));
+        }
+
+        Ok(FuseSources { vis, name, stages })
+    }
+}
+
+/// A kernel of the chain, split into its arguments.
+struct Stage {
+    name: Ident,
+    attrs: Vec<Attribute>,
+    pos: Option<(Ident, Type)>,
+    inputs: Vec<(Ident, Type)>,
+    output: (Ident, Type),
+    block: Block,
+}
+
+impl Stage {
+    fn new(item: ItemFn) -> Self {
+        let name = item.sig.ident.clone();
+
+        if !item.sig.generics.params.is_empty() {
+            abort!(item.sig.generics, "The generic kernel `{}` can't be fused", name)
+        }
+
+        let mut pos = None;
+        let mut inputs = Vec::new();
+        let mut output = None;
+
+        for arg in item.sig.inputs.iter() {
+            let FnArg::Typed(t) = arg else {
+                abort!(arg, "`self` arguments are not supported")
+            };
+            let Pat::Ident(ident) = t.pat.as_ref() else {
+                abort!(t.pat, "Arguments have to be plain names like `a: &Tensor<f32>`")
+            };
+            let ident = ident.ident.clone();
+
+            match t.ty.as_ref() {
+                Type::Reference(r) if r.mutability.is_some() => output = Some((ident, *r.elem.clone())),
+                Type::Reference(r) => inputs.push((ident, *r.elem.clone())),
+                Type::Path(p) if p.path.segments.last().map_or(false, |s| s.ident == "Pos") => pos = Some((ident, *t.ty.clone())),
+                any => inputs.push((ident, any.clone())),
+            }
+        }
+
+        let output = output.unwrap_or_else(|| abort!(item.sig, "The kernel `{}` has no mutable output", name));
+
+        Stage { name, attrs: item.attrs, pos, inputs, output, block: *item.block }
+    }
+}
+
+/// Fuses the kernels collected by `fuse!` into a single `#[kernel]`.
+///
+/// Each kernel feeds its output into the first argument of the next one. The intermediate is
+/// kept in a local instead of memory, which needs the next kernel to read it at the index the
+/// previous one wrote it, like `out[pos.x] = ..` and `x[pos.x]`.
+pub fn fuse_kernels(tokens: TokenStream, crate_root: TokenStream2) -> TokenStream {
+    let sources = syn::parse2::<FuseSources>(tokens.into()).unwrap_or_else(|e| abort!(e.span(), "{}", e));
+
+    let mut stages: Vec<Stage> = sources.stages.iter().map(|(_, item)| Stage::new(item.clone())).collect();
+    let header = merge_headers(sources.stages.iter().map(|(header, _)| header.clone()).collect());
+
+    let pos = stages.iter().find_map(|s| s.pos.clone());
+
+    for (k, stage) in stages.iter().enumerate() {
+        if k + 1 < stages.len() && contains_return(&stage.block) {
+            abort!(stage.name, "`{}` returns early, which would end the fused kernel", stage.name;
+                help = "only the last kernel of a fusion can use `return`")
+        }
+    }
+
+    // Arguments of the fused kernel, names used by several kernels get the kernel as prefix.
+    let mut arguments: Vec<(usize, Ident, Type)> = Vec::new();
+    for (k, stage) in stages.iter().enumerate() {
+        let skip = if k == 0 { 0 } else { 1 };
+        arguments.extend(stage.inputs.iter().skip(skip).map(|(i, t)| (k, i.clone(), t.clone())));
+    }
+    let last = stages.len() - 1;
+    let output = stages[last].output.clone();
+
+    let mut name_count: HashMap<String, usize> = HashMap::new();
+    for (_, i, _) in arguments.iter() {
+        *name_count.entry(i.to_string()).or_default() += 1;
+    }
+    *name_count.entry(output.0.to_string()).or_default() += 1;
+
+    let mut renames: Vec<HashMap<Ident, Ident>> = vec![HashMap::new(); stages.len()];
+    let fused_name = |k: usize, i: &Ident| if name_count[&i.to_string()] > 1 { format_ident!("{}_{}", stages[k].name, i) } else { i.clone() };
+
+    let arguments: Vec<(usize, Ident, Ident, Type)> = arguments.iter()
+        .map(|(k, i, t)| (*k, i.clone(), fused_name(*k, i), t.clone()))
+        .collect();
+    let output_name = fused_name(last, &output.0);
+
+    for (k, original, fused, _) in arguments.iter() {
+        renames[*k].insert(original.clone(), fused.clone());
+    }
+    renames[last].insert(output.0.clone(), output_name.clone());
+
+    let pos_name = pos.as_ref().map(|(i, _)| i.clone()).unwrap_or_else(|| format_ident!("pos"));
+    for (k, stage) in stages.iter().enumerate() {
+        if let Some((p, _)) = &stage.pos {
+            renames[k].insert(p.clone(), pos_name.clone());
+        }
+    }
+
+    // Locals declared by several kernels or named like an argument are numbered by their kernel.
+    let declared: Vec<HashSet<Ident>> = stages.iter().map(|s| declared_names(&s.block)).collect();
+    let mut taken: HashSet<Ident> = arguments.iter().map(|(_, _, f, _)| f.clone()).collect();
+    taken.insert(output_name.clone());
+    taken.insert(pos_name.clone());
+
+    for (k, names) in declared.iter().enumerate() {
+        for name in names {
+            let shared = declared.iter().enumerate().any(|(j, other)| j != k && other.contains(name));
+            if shared || taken.contains(name) {
+                renames[k].insert(name.clone(), format_ident!("{}_{}", name, k));
+            }
+        }
+    }
+
+    for (k, stage) in stages.iter_mut().enumerate() {
+        stage.block = Renamer(&renames[k]).fold_block(stage.block.clone());
+    }
+
+    // Replaces each intermediate by a local.
+    for k in 1..stages.len() {
+        let (previous, next) = stages.split_at_mut(k);
+        let (previous, next) = (previous.last_mut().unwrap(), &mut next[0]);
+
+        let (written, written_ty) = previous.output.clone();
+        let (read, _) = next.inputs.first().cloned().unwrap_or_else(|| abort!(next.name, "`{}` has no input to take the output of `{}`", next.name, previous.name));
+        let read_ty = next.inputs[0].1.clone();
+
+        if quote!(#written_ty).to_string() != quote!(#read_ty).to_string() {
+            abort!(read_ty, "`{}` takes `{}` but `{}` produces `{}`", next.name, quote!(#read_ty), previous.name, quote!(#written_ty))
+        }
+
+        let element = element_type(&written_ty).unwrap_or_else(|| abort!(written_ty, "Only tensors like `Tensor<f32>` can be passed between fused kernels"));
+        let local = format_ident!("__chandra_fused_{}", k);
+
+        let index = take_write(&mut previous.block, &written, &local, &element, &pos_name)
+            .unwrap_or_else(|reason| abort!(previous.name, "`{}` can't hand `{}` to the next kernel: {}", previous.name, written, reason;
+                help = "fused kernels pass values at the index they were written, like `{}[{}.x] = ..`; dispatch `{}` separately", written, pos_name, previous.name));
+
+        let mut reads = ReadReplacer { tensor: read.clone(), index, local, other_use: false };
+        next.block = reads.fold_block(next.block.clone());
+
+        if reads.other_use {
+            abort!(read, "`{}` can't read `{}` from registers", next.name, read;
+                help = "fused kernels only support reading the input at the index it was written, like `{}[{}.x]`", read, pos_name)
+        }
+    }
+
+    // Shape checks survive if they only talk about arguments of the fused kernel.
+    let shape_attrs: Vec<TokenStream2> = stages.iter().enumerate().flat_map(|(k, stage)| {
+        let intermediates: Vec<Ident> = [
+            (k > 0).then(|| stage.inputs[0].0.clone()),
+            (k < last).then(|| stage.output.0.clone()),
+        ].into_iter().flatten().collect();
+
+        stage.attrs.iter()
+            .filter(|a| a.path().is_ident("shape"))
+            .filter(|a| !crate::inline::mentions(quote!(#a), &intermediates))
+            .map(|a| rename_tokens(quote!(#a), &renames[k]))
+            .collect::<Vec<_>>()
+    }).collect();
+
+    let vis = sources.vis;
+    let name = sources.name;
+    let pos_arg = pos.map(|(_, t)| quote!(#pos_name: #t,));
+    let inputs = arguments.iter().map(|(_, _, i, t)| quote!(#i: &#t));
+    let output_ty = output.1;
+
+    let statements: Vec<Stmt> = stages.into_iter().enumerate().flat_map(|(k, stage)| {
+        let mut stmts = stage.block.stmts;
+        if k < last {
+            if let Some(Stmt::Expr(_, semi @ None)) = stmts.last_mut() {
+                *semi = Some(Default::default());
+            }
+        }
+        stmts
+    }).collect();
+
+    let span = name.span();
+    let fused = quote! {
+        #[#crate_root::kernel{ #header }]
+        #(#shape_attrs)*
+        #vis fn #name(#pos_arg #(#inputs,)* #output_name: &mut #output_ty) {
+            #(#statements)*
+        }
+    };
+    resolved_at(fused, span).into()
+}
+
+/// Every stage comes from a different macro expansion, so their variables only see each other once they
+/// share the hygiene of the `fuse!` call.
+fn resolved_at(tokens: TokenStream2, span: Span) -> TokenStream2 {
+    tokens.into_iter().map(|tree| match tree {
+        TokenTree::Group(group) => {
+            let mut new = Group::new(group.delimiter(), resolved_at(group.stream(), span));
+            new.set_span(group.span().resolved_at(span));
+            TokenTree::Group(new)
+        }
+        TokenTree::Ident(mut ident) => {
+            ident.set_span(ident.span().resolved_at(span));
+            TokenTree::Ident(ident)
+        }
+        tree => tree,
+    }).collect()
+}
+
+/// Joins the headers of the fused kernels, declarations present in several of them are kept once.
+fn merge_headers(headers: Vec<TokenStream2>) -> TokenStream2 {
+    let mut attrs: Vec<Attribute> = Vec::new();
+    let mut items = Vec::new();
+    let mut seen = HashSet::new();
+
+    for header in headers {
+        let header = syn::parse2::<KernelHeader>(header).unwrap_or_else(|e| abort!(e.span(), "{}", e));
+
+        for attr in header.attrs {
+            if let Some(other) = attrs.iter().find(|a| a.path() == attr.path()) {
+                if quote!(#other).to_string() != quote!(#attr).to_string() {
+                    abort!(attr, "The fused kernels declare different `{}`", quote!(#attr);
+                        help = "use the same `#![workgroup_size(..)]` in all of them")
+                }
+            } else {
+                attrs.push(attr);
+            }
+        }
+
+        for item in header.items {
+            if seen.insert(quote!(#item).to_string()) {
+                items.push(item);
+            }
+        }
+    }
+
+    quote!(#(#attrs)* #(#items)*)
+}
+
+/// The element type of a tensor type like `Tensor<f32>`.
+fn element_type(ty: &Type) -> Option<Type> {
+    let Type::Path(p) = ty else { return None };
+    let syn::PathArguments::AngleBracketed(args) = &p.path.segments.last()?.arguments else { return None };
+
+    match args.args.iter().collect::<Vec<_>>().as_slice() {
+        [syn::GenericArgument::Type(t)] => Some(t.clone()),
+        _ => None,
+    }
+}
+
+/// Turns the single write `out[index] = value;` of a kernel into `let local: element = value;`.
+/// Returns the written index, which may only depend on the position.
+fn take_write(block: &mut Block, output: &Ident, local: &Ident, element: &Type, pos: &Ident) -> Result<String, String> {
+    let writes: Vec<usize> = block.stmts.iter().enumerate()
+        .filter(|(_, s)| crate::inline::mentions(quote!(#s), &[output.clone()]))
+        .map(|(i, _)| i)
+        .collect();
+
+    let [position] = writes.as_slice() else {
+        return Err(format!("it has to write `{}` in exactly one statement", output));
+    };
+
+    let Stmt::Expr(Expr::Assign(assign), _) = &block.stmts[*position] else {
+        return Err(format!("`{}` has to be written by an assignment at the top of the kernel body", output));
+    };
+
+    let Expr::Index(target) = assign.left.as_ref() else {
+        return Err(format!("`{}` has to be written at an index like `{}[{}.x]`", output, output, pos));
+    };
+
+    if !matches!(target.expr.as_ref(), Expr::Path(p) if p.path.is_ident(output)) {
+        return Err(format!("`{}` has to be written at an index like `{}[{}.x]`", output, output, pos));
+    }
+
+    let index = &target.index;
+    let mut paths = PathNames(Vec::new());
+    paths.fold_expr(*index.clone());
+    if paths.0.iter().any(|i| i != pos) {
+        return Err(format!("the index `{}` depends on more than the position `{}`", quote!(#index), pos));
+    }
+
+    let index = quote!(#index).to_string();
+    let value = &assign.right;
+    block.stmts[*position] = parse_quote!(let #local: #element = #value;);
+
+    Ok(index)
+}
+
+/// Replaces the reads `input[index]` of the intermediate by its local.
+struct ReadReplacer {
+    tensor: Ident,
+    index: String,
+    local: Ident,
+    other_use: bool,
+}
+
+impl Fold for ReadReplacer {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Index(i) if matches!(i.expr.as_ref(), Expr::Path(p) if p.path.is_ident(&self.tensor)) => {
+                let index = &i.index;
+                if quote!(#index).to_string() == self.index {
+                    let local = &self.local;
+                    parse_quote!(#local)
+                } else {
+                    self.other_use = true;
+                    Expr::Index(i)
+                }
+            }
+            Expr::Path(p) if p.path.is_ident(&self.tensor) => {
+                self.other_use = true;
+                Expr::Path(p)
+            }
+            any => fold::fold_expr(self, any),
+        }
+    }
+}
+
+/// Collects the single identifier paths of an expression.
+struct PathNames(Vec<Ident>);
+
+impl Fold for PathNames {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        if let Expr::Path(p) = &e {
+            if let Some(i) = p.path.get_ident() {
+                self.0.push(i.clone());
+            }
+        }
+        fold::fold_expr(self, e)
+    }
+}
+
+/// Renames arguments and locals of a kernel to their names in the fused kernel.
+struct Renamer<'a>(&'a HashMap<Ident, Ident>);
+
+impl<'a> Fold for Renamer<'a> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Path(p) => match p.path.get_ident().and_then(|i| self.0.get(i)) {
+                Some(renamed) => parse_quote!(#renamed),
+                None => Expr::Path(p),
+            },
+            any => fold::fold_expr(self, any),
+        }
+    }
+
+    fn fold_pat_ident(&mut self, mut p: syn::PatIdent) -> syn::PatIdent {
+        if let Some(renamed) = self.0.get(&p.ident) {
+            p.ident = renamed.clone();
+        }
+        fold::fold_pat_ident(self, p)
+    }
+
+    fn fold_item_fn(&mut self, mut f: ItemFn) -> ItemFn {
+        if let Some(renamed) = self.0.get(&f.sig.ident) {
+            f.sig.ident = renamed.clone();
+        }
+        fold::fold_item_fn(self, f)
+    }
+}
+
+/// Renames the identifiers of a token stream, fields after a `.` are left alone.
+fn rename_tokens(tokens: TokenStream2, renames: &HashMap<Ident, Ident>) -> TokenStream2 {
+    let mut after_dot = false;
+
+    tokens.into_iter().map(|t| {
+        let renamed = match t {
+            TokenTree::Ident(i) if !after_dot => TokenTree::Ident(renames.get(&i).cloned().unwrap_or(i)),
+            TokenTree::Group(g) => {
+                let mut group = proc_macro2::Group::new(g.delimiter(), rename_tokens(g.stream(), renames));
+                group.set_span(g.span());
+                TokenTree::Group(group)
+            }
+            any => any,
+        };
+        after_dot = matches!(&renamed, TokenTree::Punct(p) if p.as_char() == '.');
+        renamed
+    }).collect()
+}
+
+/// The names a kernel body declares: locals, loop variables, closure and helper parameters and helpers.
+fn declared_names(block: &Block) -> HashSet<Ident> {
+    let mut collector = Declarations(HashSet::new());
+    collector.fold_block(block.clone());
+    collector.0
+}
+
+struct Declarations(HashSet<Ident>);
+
+impl Fold for Declarations {
+    fn fold_pat_ident(&mut self, p: syn::PatIdent) -> syn::PatIdent {
+        self.0.insert(p.ident.clone());
+        fold::fold_pat_ident(self, p)
+    }
+
+    fn fold_item_fn(&mut self, f: ItemFn) -> ItemFn {
+        self.0.insert(f.sig.ident.clone());
+        fold::fold_item_fn(self, f)
+    }
+}
+
+fn contains_return(block: &Block) -> bool {
+    struct Returns(bool);
+
+    impl Fold for Returns {
+        fn fold_expr(&mut self, e: Expr) -> Expr {
+            if let Expr::Return(_) = e {
+                self.0 = true;
+            }
+            fold::fold_expr(self, e)
+        }
+
+        // Helpers return into their caller.
+        fn fold_item_fn(&mut self, f: ItemFn) -> ItemFn {
+            f
+        }
+
+        fn fold_expr_closure(&mut self, c: syn::ExprClosure) -> syn::ExprClosure {
+            c
+        }
+    }
+
+    let mut returns = Returns(false);
+    returns.fold_block(block.clone());
+    returns.0
+}
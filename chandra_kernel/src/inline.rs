@@ -0,0 +1,315 @@
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::{Ident, TokenStream, TokenTree};
+use proc_macro_error::abort;
+use quote::{format_ident, quote};
+use syn::fold::{self, Fold};
+use syn::spanned::Spanned;
+use syn::{parse_quote, Block, Expr, ExprClosure, FnArg, Item, ItemFn, Pat, ReturnType, Stmt, Type};
+
+/// Helper calls nested deeper than this are treated as recursion.
+pub const MAX_INLINE_DEPTH: usize = 16;
+
+/// A helper function or closure declared in a kernel body, calls to it are inlined.
+#[derive(Clone, Debug)]
+pub struct LocalFunction {
+    pub name: Ident,
+    pub generics: Vec<Ident>,
+    pub params: Vec<(Ident, Option<Type>)>,
+    pub output: Option<Type>,
+    pub body: Vec<Stmt>,
+}
+
+impl LocalFunction {
+    pub fn from_fn(f: &ItemFn) -> Self {
+        let generics = f.sig.generics.params.iter().map(|p| match p {
+            syn::GenericParam::Type(t) => t.ident.clone(),
+            any => abort!(any, "Only type parameters are supported for helper functions"),
+        }).collect();
+
+        let params = f.sig.inputs.iter().map(|arg| match arg {
+            FnArg::Typed(t) => (param_ident(&t.pat), Some((*t.ty).clone())),
+            FnArg::Receiver(r) => abort!(r, "Helper functions can't take `self`"),
+        }).collect();
+
+        Self {
+            name: f.sig.ident.clone(),
+            generics,
+            params,
+            output: output_type(&f.sig.output),
+            body: f.block.stmts.clone(),
+        }
+    }
+
+    pub fn from_closure(name: Ident, c: &ExprClosure) -> Self {
+        if let Some(m) = &c.capture {
+            abort!(m, "Closures in kernels can't capture variables";
+                help = "remove `move` and pass the values as arguments")
+        }
+
+        let params = c.inputs.iter().map(|p| match p {
+            Pat::Type(t) => (param_ident(&t.pat), Some((*t.ty).clone())),
+            any => (param_ident(any), None),
+        }).collect();
+
+        let body = match c.body.as_ref() {
+            Expr::Block(b) => b.block.stmts.clone(),
+            any => vec![Stmt::Expr(any.clone(), None)],
+        };
+
+        Self { name, generics: Vec::new(), params, output: output_type(&c.output), body }
+    }
+
+    /// Variables the body uses without declaring them.
+    pub fn free_variables(&self) -> Vec<Ident> {
+        let mut collector = PathCollector(Vec::new());
+        let mut bound: HashSet<Ident> = self.params.iter().map(|(p, _)| p.clone()).collect();
+
+        for stmt in self.body.iter() {
+            if let Stmt::Local(l) = stmt {
+                if let Some(init) = &l.init {
+                    collector.fold_expr((*init.expr).clone());
+                }
+                if let Pat::Ident(p) = strip_type(&l.pat) {
+                    bound.insert(p.ident.clone());
+                }
+            } else {
+                collector.fold_stmt(stmt.clone());
+            }
+        }
+
+        collector.0.into_iter().filter(|i| !bound.contains(i)).collect()
+    }
+
+    /// Drops types that refer to the generic parameters, those are inferred from the arguments.
+    fn concrete(&self, ty: &Option<Type>) -> Option<Type> {
+        ty.clone().filter(|t| !mentions(quote!(#t), &self.generics))
+    }
+}
+
+fn param_ident(pat: &Pat) -> Ident {
+    match pat {
+        Pat::Ident(p) => p.ident.clone(),
+        any => abort!(any, "Helper parameters have to be plain names like `x: f32`"),
+    }
+}
+
+fn output_type(output: &ReturnType) -> Option<Type> {
+    match output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) => Some((**ty).clone()),
+    }
+}
+
+fn strip_type(pat: &Pat) -> &Pat {
+    match pat {
+        Pat::Type(t) => &t.pat,
+        any => any,
+    }
+}
+
+pub fn mentions(tokens: TokenStream, idents: &[Ident]) -> bool {
+    let mut after_dot = false;
+    tokens.into_iter().any(|t| {
+        // fields and methods like `pos.x` don't name a variable
+        let field = std::mem::replace(&mut after_dot, matches!(&t, TokenTree::Punct(p) if p.as_char() == '.'));
+        match t {
+            TokenTree::Ident(i) => !field && idents.contains(&i),
+            TokenTree::Group(g) => mentions(g.stream(), idents),
+            _ => false,
+        }
+    })
+}
+
+/// The closure assigned by `let name = |..| ..;`, if the statement declares one.
+pub fn closure_declaration(s: &Stmt) -> Option<(Ident, &ExprClosure)> {
+    if let Stmt::Local(l) = s {
+        if let (Pat::Ident(p), Some(init)) = (&l.pat, &l.init) {
+            if let Expr::Closure(c) = init.expr.as_ref() {
+                return Some((p.ident.clone(), c));
+            }
+        }
+    }
+    None
+}
+
+/// Names of all helper functions and closures declared in a kernel body.
+pub fn local_function_names(block: &Block) -> HashSet<Ident> {
+    let mut collector = DeclarationCollector(HashSet::new());
+    collector.fold_block(block.clone());
+    collector.0
+}
+
+struct DeclarationCollector(HashSet<Ident>);
+
+impl Fold for DeclarationCollector {
+    fn fold_stmt(&mut self, s: Stmt) -> Stmt {
+        if let Stmt::Item(Item::Fn(f)) = &s {
+            self.0.insert(f.sig.ident.clone());
+        } else if let Some((name, _)) = closure_declaration(&s) {
+            self.0.insert(name);
+        }
+        fold::fold_stmt(self, s)
+    }
+}
+
+/// Collects the single identifier paths of an expression, called functions excluded.
+struct PathCollector(Vec<Ident>);
+
+impl Fold for PathCollector {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Path(p) => {
+                if let Some(i) = p.path.get_ident() {
+                    self.0.push(i.clone());
+                }
+                Expr::Path(p)
+            }
+            Expr::Call(mut c) => {
+                c.args = c.args.into_iter().map(|a| self.fold_expr(a)).collect();
+                Expr::Call(c)
+            }
+            any => fold::fold_expr(self, any),
+        }
+    }
+}
+
+/// Renames variables of an inlined body to their unique names.
+struct Renamer<'a>(&'a HashMap<Ident, Ident>);
+
+impl<'a> Fold for Renamer<'a> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Path(p) => match p.path.get_ident().and_then(|i| self.0.get(i)) {
+                Some(renamed) => parse_quote!(#renamed),
+                None => Expr::Path(p),
+            },
+            Expr::Call(mut c) => {
+                c.args = c.args.into_iter().map(|a| self.fold_expr(a)).collect();
+                Expr::Call(c)
+            }
+            Expr::Closure(c) => Expr::Closure(c),
+            any => fold::fold_expr(self, any),
+        }
+    }
+}
+
+/// Replaces the calls to local functions in a statement by a variable holding their result.
+///
+/// The `let` bindings computing the results are collected in `prelude` and have to run before the
+/// statement. Nested blocks are left alone, their statements are inlined when they are folded.
+pub struct Inliner<'a> {
+    pub functions: &'a HashMap<Ident, LocalFunction>,
+    pub count: &'a mut usize,
+    pub prelude: Vec<Stmt>,
+}
+
+impl<'a> Inliner<'a> {
+    fn inline(&mut self, f: &LocalFunction, call: &syn::ExprCall, args: Vec<Expr>) -> Expr {
+        if args.len() != f.params.len() {
+            abort!(call, "`{}` takes {} argument(s) but {} were given", f.name, f.params.len(), args.len())
+        }
+
+        let id = *self.count;
+        *self.count += 1;
+
+        let mut renames = HashMap::new();
+        let mut taken = HashSet::new();
+        let mut unique = |name: &Ident| {
+            let mut local = format_ident!("{}_inl{}_{}", f.name, id, name);
+            let mut n: usize = 1;
+            while !taken.insert(local.clone()) {
+                local = format_ident!("{}_inl{}_{}{}", f.name, id, name, n);
+                n += 1;
+            }
+            local
+        };
+
+        for ((param, ty), arg) in f.params.iter().zip(args) {
+            let local = unique(param);
+            self.prelude.push(let_stmt(&local, f.concrete(ty), arg));
+            renames.insert(param.clone(), local);
+        }
+
+        let mut body = f.body.clone();
+        let tail = match body.pop() {
+            Some(Stmt::Expr(Expr::Return(r), _)) if r.expr.is_some() => *r.expr.unwrap(),
+            Some(Stmt::Expr(e, None)) => e,
+            any => abort!(any.map(|s| s.span()).unwrap_or(f.name.span()), "`{}` has to end with the value it returns", f.name;
+                help = "helpers are inlined into the expression calling them, so they need a result"),
+        };
+
+        for stmt in body {
+            match stmt {
+                Stmt::Local(l) => {
+                    let (pat, ty) = match l.pat {
+                        Pat::Type(t) => (*t.pat, Some(*t.ty)),
+                        any => (any, None),
+                    };
+                    let name = param_ident(&pat);
+                    let init = l.init.unwrap_or_else(|| abort!(pat, "Variables have to be initialized when declared"));
+                    let init = Renamer(&renames).fold_expr(*init.expr);
+
+                    let local = unique(&name);
+                    self.prelude.push(let_stmt(&local, f.concrete(&ty), init));
+                    renames.insert(name, local);
+                }
+                any => abort!(any, "Inlined helpers can only contain `let` bindings and the returned expression";
+                    help = "move loops, conditions with side effects and assignments into a #[ChandraFunction]"),
+            }
+        }
+
+        let tail = Renamer(&renames).fold_expr(tail);
+        let result = format_ident!("{}_inl{}", f.name, id);
+        self.prelude.push(let_stmt(&result, f.concrete(&f.output), tail));
+
+        parse_quote!(#result)
+    }
+}
+
+fn let_stmt(name: &Ident, ty: Option<Type>, init: Expr) -> Stmt {
+    match ty {
+        Some(ty) => parse_quote!(let #name: #ty = #init;),
+        None => parse_quote!(let #name = #init;),
+    }
+}
+
+impl<'a> Fold for Inliner<'a> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match e {
+            Expr::Call(c) => {
+                let function = match c.func.as_ref() {
+                    Expr::Path(p) => p.path.get_ident().and_then(|i| self.functions.get(i)).cloned(),
+                    _ => None,
+                };
+
+                match function {
+                    Some(f) => {
+                        let args = c.args.clone().into_iter().map(|a| self.fold_expr(a)).collect();
+                        self.inline(&f, &c, args)
+                    }
+                    None => fold::fold_expr(self, Expr::Call(c)),
+                }
+            }
+            // Only the parts that are evaluated before the branches and the loop body.
+            Expr::If(mut i) => {
+                i.cond = Box::new(self.fold_expr(*i.cond));
+                i.else_branch = i.else_branch.map(|(token, e)| match *e {
+                    Expr::If(_) => (token, Box::new(self.fold_expr(*e))),
+                    any => (token, Box::new(any)),
+                });
+                Expr::If(i)
+            }
+            Expr::ForLoop(mut f) => {
+                f.expr = Box::new(self.fold_expr(*f.expr));
+                Expr::ForLoop(f)
+            }
+            Expr::Block(_) | Expr::Closure(_) | Expr::While(_) | Expr::Loop(_) => e,
+            any => fold::fold_expr(self, any),
+        }
+    }
+
+    fn fold_item(&mut self, i: Item) -> Item {
+        i
+    }
+}
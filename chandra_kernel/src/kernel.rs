@@ -1,30 +1,32 @@
-use std::{collections::{HashMap}};
+use std::collections::{HashMap, HashSet};
 
 use convert_case::{Case, Casing};
 
 use crate::{parseatt::Structure, parse_cpu_function::ParseCPUfn};
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use proc_macro_error::{abort, abort_call_site};
+use proc_macro_error::abort;
 use quote::{format_ident, quote};
 use syn::{fold::Fold, Item as SynItem,FnArg, Type, ReturnType, parse_quote, Ident, GenericParam, Pat, Path, punctuated::Punctuated, TypeParamBound};
 
-use crate::parseatt::ParseAttributes;
+use crate::parseatt::{ParseAttributes, DEFAULT_WORKGROUP_SIZE};
 use crate::parse_function::Parsefn;
+use crate::inline::local_function_names;
 
 pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2) -> TokenStream {
-    let description = proc_macro2::TokenStream::from(attr).to_string();
+    let description = proc_macro2::TokenStream::from(attr);
 
     let mut parser = ParseAttributes::new();
     parser.structures = get_default_structures();
     
-    parser.parse(description);
+    parser.parse(description.clone());
 
    // println!("{:?}", parser);
 
     let tokens2 = proc_macro2::TokenStream::from(tokens);
+    let source = tokens2.clone();
 
-    let parse2 = syn::parse2::<SynItem>(tokens2).unwrap_or_else(|_| abort_call_site!("Failed to parse tokens"));
+    let parse2 = syn::parse2::<SynItem>(tokens2).unwrap_or_else(|e| abort!(e.span(), "{}", e));
 
     let res = match parse2 {
         SynItem::Fn(func) => {
@@ -38,7 +40,8 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
             let types:Vec<Type> = inputs.clone().iter().map(|x| {
                 match x {
                     FnArg::Typed(p) => *p.ty.clone(),
-                    FnArg::Receiver(_) => abort!(x, "self not supported")
+                    FnArg::Receiver(_) => abort!(x, "`self` arguments are not supported";
+                        help = "pass the value as a regular argument like `data: &Tensor<f32>`")
                 }
             }).collect();
 
@@ -48,6 +51,7 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
             };
 
             let mut known_generics = HashMap::new();
+            let mut known_constants = parser.get_constant_types();
 
             for gen in func_generics.params.clone().into_iter() {
                 match gen {
@@ -55,7 +59,12 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
                         let ident = x.ident;
                         known_generics.insert(ident.clone(), quote!(#ident));
                     }
-                    any => abort!(any, "Only Type Generics are allowed")
+                    // Const generics are baked into the operation tree like header constants.
+                    syn::GenericParam::Const(c) => {
+                        let ty = c.ty;
+                        known_constants.insert(c.ident, quote!(#ty));
+                    }
+                    any => abort!(any, "Lifetime generics are not supported, found `{}`", quote!(#any))
                 }
             }
 
@@ -84,8 +93,8 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
 
                                 input_vars.insert(i.ident.clone(), quote!(#crate_root::core::operations::var::Variable::<#t_p>));
 
-                                if let Some(known) = parser.structures.get(&t_p) {
-                                    input_structures.insert(i.ident, (*known).clone());
+                                if let Some(known) = parser.get_structure(&t_p) {
+                                    input_structures.insert(i.ident, known);
                                 } else {
                                     let id_l = i.ident;
                                     let t_name = format!("{}", id_l);
@@ -115,8 +124,8 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
                                    // println!("{:#?}", t_p);
                                    // println!();
 
-                                    if let Some(known) = parser.structures.get(&t_p) {
-                                        input_structures.insert(i.ident, (*known).clone());
+                                    if let Some(known) = parser.get_structure(&t_p) {
+                                        input_structures.insert(i.ident, known);
                                     } else {
                                         let id_l = i.ident;
                                         let t_name = format!("{}", id_l);
@@ -126,10 +135,11 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
                             }
 
                         } else {
-                            abort!(p, "Not an Ident")
+                            abort!(p.pat, "Arguments have to be plain names like `a: &Tensor<f32>`, found `{}`", quote!(#p.pat))
                         }
                     }
-                    FnArg::Receiver(_) => abort!(inp, "self not supported")
+                    FnArg::Receiver(_) => abort!(inp, "`self` arguments are not supported";
+                        help = "pass the value as a regular argument like `data: &Tensor<f32>`")
                 }
             }
 
@@ -137,13 +147,13 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
 
             let mut struct_count: u32 = 0;
 
-            for (ident, fields) in input_structures.iter() {
+            for (ident, structure) in input_structures.iter() {
                 let st = format_ident!("HelperStruct{}", struct_count);
                 let mut field_types = Vec::new();
                 let mut field_names = Vec::new();
                 let mut access_names = Vec::new();
 
-                for (name, typ) in fields.iter().map(|(k, v)| (format_ident!("{}", k), format_ident!("{}", v))) {
+                for (name, typ) in structure.fields.iter() {
                     field_types.push(typ);
                     field_names.push(name.clone());
                     access_names.push(format!("{}.{}", ident, name));
@@ -182,20 +192,50 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
                 returns_struct: false,
                 known_extensions: parser.replace_functions.clone(),
                 known_functions: parser.normal_functions.clone(),
+                atomic_targets: Vec::new(),
+                debug_messages: Vec::new(),
+                expected_type: quote!(),
+                known_constants,
+                local_functions: HashMap::new(),
+                inline_count: 0,
+                tuple_vars: HashMap::new(),
+                temp_count: 0,
+                output_type: quote!(),
             };
 
             let public = func.vis;
+            let constants = parser.constants.clone();
+            let (wg_x, wg_y, wg_z) = parser.workgroup_size.unwrap_or(DEFAULT_WORKGROUP_SIZE);
 
             let b = p.fold_block(*func.block.clone());
-            let mut cpu_p = ParseCPUfn { known_functions: parser.normal_functions };
+            let generic_literals = func_generics.params.iter().any(|gen| matches!(gen, syn::GenericParam::Type(_)));
+            let mut cpu_p = ParseCPUfn { known_functions: parser.normal_functions, crate_root: crate_root.clone(), local_functions: local_function_names(&func.block), local_arrays: HashSet::new(), generic_literals };
 
             let block = cpu_p.fold_block(*func.block);
 
+            for target in p.atomic_targets.iter() {
+                if final_output_name.to_string() != target.to_string() {
+                    abort!(target, "Atomic operations are only supported on the mutable output `{}`", final_output_name)
+                }
+            }
+
+            let output_layout = if p.atomic_targets.is_empty() {
+                quote!(<#final_output_type as #crate_root::core::type_traits::Generalizable>::get_memory_layout())
+            } else {
+                quote!(<#final_output_type as #crate_root::core::type_traits::Generalizable>::get_memory_layout().into_atomic(<#final_output_type as #crate_root::core::type_traits::IndexAble>::get_field()))
+            };
+
             if p.returns_struct {
                 abort!(block, "Only ChandraExtensions are allowed to return a structure. Kernels must not return a value")
             }
 
             let fn_return = p.return_type;
+            let debug_messages = p.debug_messages;
+
+            let mut tensor_arguments = final_input_names.clone();
+            tensor_arguments.push(format_ident!("{}", final_output_name_str));
+            let shape_constraints = get_shape_constraints(&func.attrs, &tensor_arguments, crate_root.clone());
+            let lint_attributes = get_lint_attributes(&func.attrs);
 
             //let (mem_generic, mem_imputs) = get_mem_generics_and_types(types.clone());
 
@@ -216,18 +256,25 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
 
             let mut inner_generics = func_generics.params.clone();
             inner_generics_to_static_bound(&mut inner_generics);
-            let phantom_data_idents: Vec<Ident> = inner_generics.iter().enumerate().map(|(i, _)| format_ident!("_{}", i)).collect();
-
             let inner_generics_idents = inner_generics_idents(&inner_generics);
+            let type_generics_idents: Vec<Ident> = inner_generics.iter().filter_map(|gen| match gen {
+                GenericParam::Type(x) => Some(x.ident.clone()),
+                _ => None,
+            }).collect();
+            let phantom_data_idents: Vec<Ident> = type_generics_idents.iter().enumerate().map(|(i, _)| format_ident!("_{}", i)).collect();
             
 
+            let source_macro = crate::fuse::source_macro(&ident, &description, &source);
+
             quote!{
+                #source_macro
+
                 #public type #return_type_ident<#inner_generics> = #fn_return;
 
                 #public struct #function_struct_ident <#inner_generics> {
                     main: #return_type_ident<#(#inner_generics_idents,)*>,
                     cpu_fn: #cpu_fn_ident,
-                    #(#phantom_data_idents: ::core::marker::PhantomData<#inner_generics_idents>,)*
+                    #(#phantom_data_idents: ::core::marker::PhantomData<#type_generics_idents>,)*
                 }
 
                 impl<#inner_generics> #function_struct_ident<#(#inner_generics_idents,)*> {
@@ -237,6 +284,13 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
                     P::Executable<#function_struct_ident <#(#inner_generics_idents,)*>>: #crate_root::core::processor::Executable<P::Storage, #executable_inputs_ident<P::Storage, #(#inner_generics_idents,)*>> {
                         processor.build(self)
                     }
+
+                    #public fn try_build <P: #crate_root::core::processor::Processor> (self, processor: &mut P) -> ::std::result::Result<<P as #crate_root::core::processor::ProcessorInformation>::Executable<#function_struct_ident <#(#inner_generics_idents,)*>>, #crate_root::Error>
+                    #processor_mem_bounds 
+                    #return_type_ident<#(#inner_generics_idents,)*>: #crate_root::core::operation::Compilable<#crate_root::core::types::Void, P::Compiler>,
+                    P::Executable<#function_struct_ident <#(#inner_generics_idents,)*>>: #crate_root::core::processor::Executable<P::Storage, #executable_inputs_ident<P::Storage, #(#inner_generics_idents,)*>> {
+                        processor.try_build(self)
+                    }
                 }
 
                 impl<P: #crate_root::core::processor::ProcessorInformation, #inner_generics> #crate_root::core::Buildable<P> for #function_struct_ident <#(#inner_generics_idents,)*> 
@@ -255,6 +309,14 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
                     fn get_main_tree(&self) -> Self::Main {
                         self.main.clone()
                     }
+
+                    fn get_debug_messages(&self) -> Vec<&'static str> {
+                        vec![#(#debug_messages,)*]
+                    }
+
+                    fn get_workgroup_size(&self) -> (u32, u32, u32) {
+                        (#wg_x, #wg_y, #wg_z)
+                    }
                 }
 
                 impl<#inner_generics> #crate_root::core::Program for #function_struct_ident <#(#inner_generics_idents,)*> {
@@ -275,6 +337,12 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
                         #(self.#final_input_names = ::std::option::Option::Some(#final_input_names.clone());)*
                         self.#final_output_name = ::std::option::Option::Some(#final_output_name.clone())
                     }
+
+                    /// Binds the arguments and checks the shape constraints of the kernel.
+                    #public fn try_bind(&mut self, #(#final_input_names: &#crate_root::core::allocated::Binding<S, #final_input_types>,)* #final_output_name: &mut #crate_root::core::allocated::Binding<S, #final_output_type>) -> ::std::result::Result<(), #crate_root::Error> {
+                        self.bind(#(#final_input_names,)* #final_output_name);
+                        #crate_root::core::allocated::ExecutableBindings::<S>::validate(self, ::std::option::Option::None)
+                    }
                 }
 
                 impl<S: #crate_root::core::processor::Storage, #inner_generics> #crate_root::core::allocated::ExecutableBindings<S> for #executable_inputs_ident<S, #(#inner_generics_idents,)*> #mem_bounds {
@@ -289,36 +357,49 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
                         }
                     }
                 
-                    fn get_inputs(&self) -> Self::I {
-                        (#(self.#final_input_names.clone().unwrap(),)*)
+                    fn get_inputs(&self) -> ::std::result::Result<Self::I, #crate_root::Error> {
+                        ::std::result::Result::Ok((#(self.#final_input_names.clone().ok_or_else(|| #crate_root::Error::Unbound { argument: #final_input_names_str.to_string() })?,)*))
                     }
                 
-                    fn get_output(&self) -> #crate_root::core::allocated::Binding<S, Self::O> {
-                        self.#final_output_name.clone().unwrap()
+                    fn get_output(&self) -> ::std::result::Result<#crate_root::core::allocated::Binding<S, Self::O>, #crate_root::Error> {
+                        self.#final_output_name.clone().ok_or_else(|| #crate_root::Error::Unbound { argument: #final_output_name_str.to_string() })
                     }
 
-                    fn get_input_references(&self) -> Vec<<S as #crate_root::core::processor::Storage>::Key> {
-                        vec![#(self.#final_input_names.as_ref().unwrap().get_reference(),)*]
+                    fn get_input_references(&self) -> ::std::result::Result<Vec<<S as #crate_root::core::processor::Storage>::Key>, #crate_root::Error> {
+                        ::std::result::Result::Ok(vec![#(self.#final_input_names.as_ref().ok_or_else(|| #crate_root::Error::Unbound { argument: #final_input_names_str.to_string() })?.get_reference(),)*])
                     }
             
-                    fn get_out_reference(&self) -> <S as #crate_root::core::processor::Storage>::Key {
-                        self.#final_output_name.as_ref().unwrap().get_reference()
+                    fn get_out_reference(&self) -> ::std::result::Result<<S as #crate_root::core::processor::Storage>::Key, #crate_root::Error> {
+                        ::std::result::Result::Ok(self.#final_output_name.as_ref().ok_or_else(|| #crate_root::Error::Unbound { argument: #final_output_name_str.to_string() })?.get_reference())
+                    }
+
+                    fn set_arguments(&mut self, (#(#final_input_names,)*): Self::I, #final_output_name: #crate_root::core::allocated::Binding<S, Self::O>) {
+                        #(self.#final_input_names = ::std::option::Option::Some(#final_input_names);)*
+                        self.#final_output_name = ::std::option::Option::Some(#final_output_name);
                     }
 
                     fn get_layouts() -> ::std::collections::HashMap<String, (u8, #crate_root::core::allocated::MemoryLayoutDescriptor, bool)> {
                         ::std::collections::HashMap::from([
                             #((#final_input_names_str.to_string(), (#final_input_position, <#final_input_types as #crate_root::core::type_traits::Generalizable>::get_memory_layout(), false)),)*
-                            (#final_output_name_str.to_string(), (0, <#final_output_type as #crate_root::core::type_traits::Generalizable>::get_memory_layout(), true)),
+                            (#final_output_name_str.to_string(), (0, #output_layout, true)),
                         ])
                     }
+
+                    fn validate(&self, grid: ::std::option::Option<(u32, u32, u32)>) -> ::std::result::Result<(), #crate_root::Error> {
+                        #shape_constraints
+                    }
                 }          
 
                 #[derive(::core::marker::Copy, ::core::clone::Clone, ::std::fmt::Debug)]
                 #public struct #cpu_fn_ident;
 
                 impl<#inner_generics> #crate_root::core::processor::cpu::CPUFunction<#executable_inputs_ident<#crate_root::processor::cpu::CPUStorage, #(#inner_generics_idents,)*>> for #cpu_fn_ident {
-                    fn call_cpu<'a, 'b>(&self, pos: & #crate_root::core::types::Pos, (#(#final_input_names,)*): &(#( &#final_input_types,)*) , #final_output_name: &mut <<#final_output_type as #crate_root::core::type_traits::MemoryMapable<#crate_root::processor::cpu::CPUStorage>>::Mapped<'b> as FromMut<<#crate_root::processor::cpu::CPUStorage as #crate_root::core::processor::Storage>::MappedType<#final_output_type>>>::Result<'b>) 
+                    #(#lint_attributes)*
+                    fn call_cpu<'a, 'b>(&self, pos: & #crate_root::core::types::Pos, (#(#final_input_names,)*): &(#( &#final_input_types,)*) , #final_output_name: &mut <<#final_output_type as #crate_root::core::type_traits::MemoryMapable<#crate_root::processor::cpu::CPUStorage>>::Mapped<'b> as FromMut<<#crate_root::processor::cpu::CPUStorage as #crate_root::core::processor::Storage>::MappedType<#final_output_type>>>::Result<'b>, __chandra_debug: &#crate_root::core::debug::DebugBuffer) {
+                        use #crate_root::core::type_traits::{Dot as _, KernelTensor as _};
+                        #(#constants)*
                         #block
+                    }
                 }
 
                 //fn test_fn #func_generics(#(#input_idents: #types,)*) #block
@@ -331,6 +412,8 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
                 /// 
                 #public fn #ident <#inner_generics> () -> #function_struct_ident<#(#inner_generics_idents,)*> {
                     
+                    #(#constants)*
+
                     #(#parsed_strucures)*
 
                     #(#input_known_base_types)*
@@ -346,7 +429,7 @@ pub fn kernel(attr: TokenStream, tokens: TokenStream, crate_root: TokenStream2)
 
             }
         }
-        any => abort!(any, "macro needs to be placed above a function"),
+        any => abort!(any, "#[kernel] can only be applied to functions"),
     }
     .into();
 
@@ -362,7 +445,10 @@ fn inner_generics_idents(generics: &Punctuated<GenericParam, syn::token::Comma>)
                 GenericParam::Type(x) => {
                   x.ident.clone()
                 }
-                _ => abort!(gen, "Const and lifetime generics are not supported")
+                GenericParam::Const(c) => {
+                  c.ident.clone()
+                }
+                _ => abort!(gen, "Lifetime generics are not supported")
             }
         }).collect()
 }
@@ -375,7 +461,8 @@ fn inner_generics_to_static_bound(befor: &mut Punctuated<GenericParam, syn::toke
                 GenericParam::Type(x) => {
                     x.bounds.push(TypeParamBound::Lifetime(parse_quote!('static)));
                 }
-                _ => abort!(gen, "Const and lifetime generics are not supported")
+                GenericParam::Const(_) => {}
+                _ => abort!(gen, "Lifetime generics are not supported")
             }
         });
 }
@@ -390,7 +477,8 @@ fn get_memmappable_bounds(types: Vec<Type>, mappable_path: Path, crate_root: Tok
                 let mutable = ref_type.mutability.is_some();
                 if mutable {
                     if has_mut {
-                        abort!(ref_type, "Can't have more than 1 mutable reference")
+                        abort!(ref_type, "Only one argument can be a mutable reference";
+                            help = "the `&mut` argument is the output, pass the other arguments as `&`")
                     }
                     has_mut = true;
                 }
@@ -403,7 +491,7 @@ fn get_memmappable_bounds(types: Vec<Type>, mappable_path: Path, crate_root: Tok
                     }
                     //uniqueTypes.insert(pathType.path);
                 } else {
-                    abort!(ref_type, "Unsupported function argument Type")
+                    abort!(ref_type, "Arguments have to be references to named types like `&Tensor<f32>`, found `{}`", quote!(#ref_type))
                 }
 
             }
@@ -428,18 +516,20 @@ fn get_mem_generics_and_types(types: Vec<Type>, crate_root: TokenStream2) -> (To
     for typ in types {
         match typ.clone() {
             Type::Path(path_type) => {
-                let maybe_pos = path_type.path.segments.last().unwrap_or_else(|| abort!(typ, "Expect path to contain segments"));
+                let maybe_pos = path_type.path.segments.last().unwrap_or_else(|| abort!(typ, "Expected a type name"));
                 if maybe_pos.ident == "Pos" {
                     position_argument = quote!{pos: #typ}
                 } else {
-                    abort!(path_type, "All variables should be references with the exception of special variables")
+                    abort!(path_type, "`{}` has to be passed by reference like `&{}`", quote!(#path_type), quote!(#path_type);
+                        note = "only `Pos` is passed by value")
                 }
             }
             Type::Reference(ref_type) => {
                 let mutable = ref_type.mutability.is_some();
                 if mutable {
                     if has_mut {
-                        abort!(ref_type, "Can't have more than 1 mutable reference")
+                        abort!(ref_type, "Only one argument can be a mutable reference";
+                            help = "the `&mut` argument is the output, pass the other arguments as `&`")
                     }
                     has_mut = true;
                 }
@@ -472,10 +562,10 @@ fn get_mem_generics_and_types(types: Vec<Type>, crate_root: TokenStream2) -> (To
                     }
                     //uniqueTypes.insert(pathType.path);
                 } else {
-                    abort!(ref_type, "Unsupported function argument Type")
+                    abort!(ref_type, "Arguments have to be references to named types like `&Tensor<f32>`, found `{}`", quote!(#ref_type))
                 }
             }
-            any => abort!(any, "Unsupported function argument Type")
+            any => abort!(any, "Arguments have to be references to named types like `&Tensor<f32>`, found `{}`", quote!(#any))
         }
     }
 
@@ -486,16 +576,149 @@ fn get_mem_generics_and_types(types: Vec<Type>, crate_root: TokenStream2) -> (To
         quote!(#position_argument, #(#gen_inputs,)*) )
 }
 
-fn get_default_structures() ->  HashMap<syn::TypePath, Structure> {
+fn get_default_structures() ->  HashMap<Ident, Structure> {
     let mut map = HashMap::new();
 
-    map.insert(parse_quote!(Pos), HashMap::from([
-        ("x".to_string(), "u32".to_string()),
-        ("y".to_string(), "u32".to_string()),
-        ("z".to_string(), "u32".to_string())
+    map.insert(format_ident!("Pos"), Structure::new(vec![
+        (format_ident!("x"), parse_quote!(u32)),
+        (format_ident!("y"), parse_quote!(u32)),
+        (format_ident!("z"), parse_quote!(u32)),
     ]));
 
     //map.insert(parse_quote!(Tensor<f32>), HashMap::new());
 
     map
 }
+
+/// Operand of a `#[shape(...)]` constraint: `arg.shape[n]`, `grid.x` or an integer literal.
+enum ShapeOperand {
+    Extent(Ident, usize),
+    Grid(usize),
+    Literal(u64),
+}
+
+fn parse_shape_operand(expr: &syn::Expr, arguments: &[Ident]) -> ShapeOperand {
+    match expr {
+        syn::Expr::Index(ind) => {
+            let dim = match ind.index.as_ref() {
+                syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(i), .. }) => i.base10_parse::<usize>().unwrap_or_else(|e| abort!(i, "{}", e)),
+                any => abort!(any, "Expected a constant dimension like `a.shape[0]`"),
+            };
+
+            match ind.expr.as_ref() {
+                syn::Expr::Field(f) if matches!(&f.member, syn::Member::Named(m) if m == "shape") => {
+                    let arg = match f.base.as_ref() {
+                        syn::Expr::Path(p) => p.path.get_ident().cloned().unwrap_or_else(|| abort!(p, "Expected a kernel argument")),
+                        any => abort!(any, "Expected a kernel argument"),
+                    };
+
+                    if !arguments.contains(&arg) {
+                        abort!(arg, "`{}` is not a tensor argument of this kernel", arg)
+                    }
+
+                    ShapeOperand::Extent(arg, dim)
+                }
+                any => abort!(any, "Expected `<argument>.shape[<dimension>]`"),
+            }
+        }
+        syn::Expr::Field(f) if matches!(f.base.as_ref(), syn::Expr::Path(p) if p.path.is_ident("grid")) => {
+            match &f.member {
+                syn::Member::Named(m) if m == "x" => ShapeOperand::Grid(0),
+                syn::Member::Named(m) if m == "y" => ShapeOperand::Grid(1),
+                syn::Member::Named(m) if m == "z" => ShapeOperand::Grid(2),
+                any => abort!(any, "The grid only has the dimensions x, y and z"),
+            }
+        }
+        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(i), .. }) => ShapeOperand::Literal(i.base10_parse::<u64>().unwrap_or_else(|e| abort!(i, "{}", e))),
+        any => abort!(any, "Expected `<argument>.shape[<dimension>]`, `grid.<x|y|z>` or an integer"),
+    }
+}
+
+/// `#[allow]`, `#[warn]`, `#[deny]` and `#[forbid]` of the kernel, put on the host version of its body.
+fn get_lint_attributes(attrs: &[syn::Attribute]) -> Vec<&syn::Attribute> {
+    attrs.iter()
+        .filter(|a| ["allow", "warn", "deny", "forbid"].iter().any(|lint| a.path().is_ident(lint)))
+        .collect()
+}
+
+/// Lowers the `#[shape(a.shape[0] == out.shape[0], grid.x <= out.shape[0])]` attributes of a kernel to the body of `ExecutableBindings::validate`.
+fn get_shape_constraints(attrs: &[syn::Attribute], arguments: &[Ident], crate_root: TokenStream2) -> TokenStream2 {
+    let mut checks = Vec::new();
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("shape")) {
+        let constraints = attr
+            .parse_args_with(Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated)
+            .unwrap_or_else(|e| abort!(attr, "Invalid shape constraint: {}", e));
+
+        for constraint in constraints {
+            let (left, op, right) = match &constraint {
+                syn::Expr::Binary(b) => match b.op {
+                    syn::BinOp::Eq(_) | syn::BinOp::Ne(_) | syn::BinOp::Lt(_) | syn::BinOp::Le(_) | syn::BinOp::Gt(_) | syn::BinOp::Ge(_) => (b.left.as_ref(), b.op, b.right.as_ref()),
+                    _ => abort!(b.op, "Shape constraints need to be comparisons"),
+                },
+                any => abort!(any, "Shape constraints need to be comparisons like `a.shape[0] == out.shape[0]`"),
+            };
+
+            let text = quote!(#constraint).to_string().replace(" [", "[");
+            let operands = [parse_shape_operand(left, arguments), parse_shape_operand(right, arguments)];
+
+            let values: Vec<TokenStream2> = operands.iter().map(|o| match o {
+                ShapeOperand::Extent(arg, dim) => {
+                    let name = arg.to_string();
+                    quote! {
+                        match self.#arg.as_ref() {
+                            ::std::option::Option::Some(b) => b.get_parallelization_info().extents().get(#dim).copied().ok_or_else(|| #crate_root::Error::ShapeMismatch {
+                                argument: #name.to_string(),
+                                constraint: #text.to_string(),
+                                found: b.get_parallelization_info().extents(),
+                            })?,
+                            ::std::option::Option::None => return ::std::result::Result::Err(#crate_root::Error::Unbound { argument: #name.to_string() }),
+                        }
+                    }
+                }
+                ShapeOperand::Grid(dim) => {
+                    let dim = syn::Index::from(*dim);
+                    quote!(grid.#dim as u64)
+                }
+                ShapeOperand::Literal(v) => quote!(#v),
+            }).collect();
+
+            let (l, r) = (&values[0], &values[1]);
+
+            let failure = match operands.iter().find_map(|o| if let ShapeOperand::Extent(arg, _) = o { Some(arg) } else { None }) {
+                Some(arg) if !operands.iter().any(|o| matches!(o, ShapeOperand::Grid(_))) => {
+                    let name = arg.to_string();
+                    quote! {
+                        #crate_root::Error::ShapeMismatch {
+                            argument: #name.to_string(),
+                            constraint: #text.to_string(),
+                            found: self.#arg.as_ref().map(|b| b.get_parallelization_info().extents()).unwrap_or_default(),
+                        }
+                    }
+                }
+                _ => quote!(#crate_root::Error::InvalidGrid { constraint: #text.to_string(), grid }),
+            };
+
+            let check = quote! {
+                if !((#l) #op (#r)) {
+                    return ::std::result::Result::Err(#failure);
+                }
+            };
+
+            if operands.iter().any(|o| matches!(o, ShapeOperand::Grid(_))) {
+                checks.push(quote! {
+                    if let ::std::option::Option::Some(grid) = grid {
+                        #check
+                    }
+                });
+            } else {
+                checks.push(check);
+            }
+        }
+    }
+
+    quote! {
+        #(#checks)*
+        ::std::result::Result::Ok(())
+    }
+}
@@ -1,10 +1,12 @@
 #![recursion_limit = "128"]
 
 mod function;
+mod fuse;
 mod kernel;
 mod parseatt;
 mod parse_function;
 mod parse_cpu_function;
+mod inline;
 
 extern crate proc_macro;
 
@@ -31,6 +33,30 @@ pub fn ChandraExtension(attr: TokenStream, tokens: TokenStream) -> TokenStream {
     function::function(attr, tokens, quote::quote!(::chandra), true)
 }
 
+/// Fuses kernels into one, each kernel takes the output of the previous one as its first argument.
+///
+/// ```ignore
+/// fuse!(pub scale_relu = scale, relu);
+/// ```
+///
+/// Kernels are named by their path like `fuse!(scale_relu = scale, activations::relu)`, from
+/// anywhere in the same crate. Their bodies are pasted into the fused kernel, so the types and
+/// constants they use have to be in scope where `fuse!` is called. The values passed between them stay
+/// in registers, so every kernel has to read its first argument at the index the previous kernel
+/// wrote it.
+#[proc_macro_error]
+#[proc_macro]
+pub fn fuse(tokens: TokenStream) -> TokenStream {
+    fuse::fuse(tokens, quote::quote!(::chandra))
+}
+
+#[doc(hidden)]
+#[proc_macro_error]
+#[proc_macro]
+pub fn __fuse_kernels(tokens: TokenStream) -> TokenStream {
+    fuse::fuse_kernels(tokens, quote::quote!(::chandra))
+}
+
 #[proc_macro_error]
 #[proc_macro_derive(ChandraStruct, attributes(For, Index, XPar))]
 pub fn chandra_struct(input: TokenStream) -> TokenStream {
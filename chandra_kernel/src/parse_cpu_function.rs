@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use proc_macro2::{Ident, TokenStream};
 use proc_macro_error::abort;
@@ -7,14 +7,109 @@ use syn::fold::{self, Fold};
 use syn::{parse_quote, BinOp, Expr,Pat, Stmt, Path, PathArguments};
 
 use crate::parseatt::Structure;
+use crate::parse_function::{is_atomic_call, is_random_call, random_operation, is_debug_macro, parse_debug_macro};
 
 pub struct ParseCPUfn {
     pub known_functions: HashMap<Path, Path>,
+    pub crate_root: TokenStream,
+    /// Helper functions and closures of the body, they are called like in plain Rust.
+    pub local_functions: HashSet<Ident>,
+    /// Arrays declared in the body, Rust indexes them by `usize`.
+    pub local_arrays: HashSet<Ident>,
+    /// Set for functions with type generics, their float literals have to be converted to the generic type.
+    pub generic_literals: bool,
 }
 
 impl Fold for ParseCPUfn {
+    fn fold_stmt(&mut self, s: Stmt) -> Stmt {
+        match s {
+            Stmt::Macro(m) if is_debug_macro(&m.mac) => {
+                let exp = self.fold_expr(Expr::Macro(syn::ExprMacro { attrs: m.attrs, mac: m.mac }));
+                Stmt::Expr(exp, Some(m.semi_token.unwrap_or_default()))
+            }
+            Stmt::Local(l) => {
+                let array = match &l.pat {
+                    Pat::Type(t) => matches!(*t.ty, syn::Type::Array(_)),
+                    _ => l.init.as_ref().map_or(false, |i| matches!(*i.expr, Expr::Array(_) | Expr::Repeat(_))),
+                };
+                if let (true, Pat::Ident(p)) = (array, strip_type(&l.pat)) {
+                    self.local_arrays.insert(p.ident.clone());
+                }
+                fold::fold_stmt(self, Stmt::Local(l))
+            }
+            _ => fold::fold_stmt(self, s),
+        }
+    }
+
     fn fold_expr(&mut self, exp: Expr) -> Expr {
         match exp.clone() {
+            Expr::Call(e) if is_atomic_call(&e) => {
+                let mut args = e.args.clone().into_iter().map(|a| self.fold_expr(a));
+                let method = if let Expr::Path(p) = e.func.as_ref() { p.clone() } else { abort!(e, "Not an atomic operation") };
+
+                match args.next() {
+                    Some(Expr::Reference(r)) => {
+                        if let Expr::Index(ind) = *r.expr {
+                            let target = ind.expr;
+                            let index = ind.index;
+                            let values: Vec<Expr> = args.collect();
+
+                            parse_quote!(#target.#method(#index, #(#values),*))
+                        } else {
+                            abort!(r, "Atomic operations expect an element reference like `&mut out[i]`")
+                        }
+                    }
+                    _ => abort!(e, "Atomic operations expect an element reference like `&mut out[i]`")
+                }
+            }
+            Expr::Macro(m) if is_debug_macro(&m.mac) => {
+                let crate_root = self.crate_root.clone();
+                let (condition, message, values) = parse_debug_macro(&m.mac);
+                let values = values.into_iter().map(|v| self.fold_expr(v));
+
+                let message = match (&condition, message) {
+                    (_, Some(m)) => m.value(),
+                    (Some(c), None) => format!("assertion failed: {}", quote!(#c)),
+                    (None, None) => abort!(m, "debug_print! expects a message"),
+                };
+                let values = quote!(&[#(#crate_root::core::debug::Debuggable::to_debug_value(#values)),*]);
+
+                if let Some(c) = condition {
+                    let c = self.fold_expr(c);
+                    parse_quote!(__chandra_debug.assert(#c, pos, #message, #values))
+                } else {
+                    parse_quote!(__chandra_debug.print(pos, #message, #values))
+                }
+            }
+            Expr::Index(ind) if matches!(ind.expr.as_ref(), Expr::Path(p) if p.path.get_ident().map_or(false, |i| self.local_arrays.contains(i))) => {
+                let base = ind.expr;
+                let index = self.fold_expr(*ind.index);
+
+                parse_quote!(#base[(#index) as usize])
+            }
+            Expr::Binary(b) if self.generic_literals && is_float_literal(&b.left) && !is_float_literal(&b.right) => {
+                let crate_root = self.crate_root.clone();
+                let (left, op, right) = (b.left, b.op, self.fold_expr(*b.right));
+                parse_quote!({
+                    let __chandra_right = #right;
+                    #crate_root::core::types::literal_like(&__chandra_right, #left) #op __chandra_right
+                })
+            }
+            Expr::Lit(l) if self.generic_literals && is_float_literal(&exp) => {
+                let crate_root = self.crate_root.clone();
+                parse_quote!(<_ as #crate_root::core::types::Computable>::from_float(#l))
+            }
+            Expr::Unary(u) if self.generic_literals && is_float_literal(&exp) => {
+                let crate_root = self.crate_root.clone();
+                parse_quote!(<_ as #crate_root::core::types::Computable>::from_float(#u))
+            }
+            Expr::Call(e) if is_random_call(&e) => {
+                let crate_root = self.crate_root.clone();
+                let args = e.args.clone().into_iter().map(|a| self.fold_expr(a));
+                let func = random_operation(&e).unwrap_or_else(|| abort!(e, "Not a random operation"));
+
+                parse_quote!(#crate_root::core::random::#func(#(#args),*))
+            }
             Expr::Call(e) => {
                 let func = *e.func;
 
@@ -38,10 +133,18 @@ impl Fold for ParseCPUfn {
                             }
                         }
 
+                        else if let Some(i) = p.path.get_ident().filter(|i| !self.local_functions.contains(i)) {
+                            let arguments: Vec<Expr> = e.args.into_iter().map(|a| self.fold_expr(a)).collect();
+
+                            parse_quote!{
+                                <#i #attributes>::cpu(#(#arguments,)*)
+                            }
+                        }
+
                         else {
-                            let mut arguments: Vec<Expr> = e.args.into_iter().map(|a| self.fold_expr(a)).collect();
+                            let arguments: Vec<Expr> = e.args.into_iter().map(|a| self.fold_expr(a)).collect();
 
-                            exp
+                            parse_quote!(#func(#(#arguments,)*))
                         }
                     }
                     _ => exp,
@@ -51,3 +154,20 @@ impl Fold for ParseCPUfn {
         }
     }
 }
+
+/// An unsuffixed float literal like `0.5` or `-1.0`.
+fn is_float_literal(exp: &Expr) -> bool {
+    match exp {
+        Expr::Lit(l) => matches!(l.lit, syn::Lit::Float(_)) && l.lit.suffix().is_empty(),
+        Expr::Unary(u) => matches!(u.op, syn::UnOp::Neg(_)) && is_float_literal(&u.expr),
+        Expr::Paren(p) => is_float_literal(&p.expr),
+        _ => false,
+    }
+}
+
+fn strip_type(pat: &Pat) -> &Pat {
+    match pat {
+        Pat::Type(t) => &t.pat,
+        any => any,
+    }
+}
@@ -4,8 +4,11 @@ use proc_macro2::{Ident, TokenStream};
 use proc_macro_error::abort;
 use quote::{format_ident, quote};
 use syn::fold::{self, Fold};
-use syn::{parse_quote, BinOp, Expr,Pat, Stmt, Path, PathArguments};
+use syn::spanned::Spanned;
+use syn::{parse_quote, BinOp, Expr,Pat, Stmt, Path, PathArguments, UnOp};
 
+use crate::inline::{closure_declaration, mentions, Inliner, LocalFunction, MAX_INLINE_DEPTH};
+use crate::function::is_declared_function;
 use crate::parseatt::Structure;
 
 pub struct Parsefn {
@@ -22,6 +25,230 @@ pub struct Parsefn {
     pub returns_struct: bool,
     pub known_functions: HashMap<Path, Path>,
     pub known_extensions: HashMap<Path, Path>,
+    pub atomic_targets: Vec<Ident>,
+    pub debug_messages: Vec<String>,
+    /// Type an unsuffixed literal in the next folded expression should get, empty if unknown.
+    pub expected_type: TokenStream,
+    /// Constants declared in the attribute header and their types.
+    pub known_constants: HashMap<Ident, TokenStream>,
+    /// Helper functions and closures declared in the body, their calls are inlined.
+    pub local_functions: HashMap<Ident, LocalFunction>,
+    pub inline_count: usize,
+    /// Tuple locals and the locals holding their elements.
+    pub tuple_vars: HashMap<Ident, Vec<Ident>>,
+    pub temp_count: usize,
+    /// Declared result type of the function, the expected type of returned values. Empty for kernels.
+    pub output_type: TokenStream,
+}
+
+/// Kernel intrinsics for atomic access: (name, core operation, number of value arguments).
+pub const ATOMIC_OPERATIONS: [(&str, &str, usize); 5] = [
+    ("atomic_add", "AtomicAdd", 1),
+    ("atomic_min", "AtomicMin", 1),
+    ("atomic_max", "AtomicMax", 1),
+    ("atomic_exchange", "AtomicExchange", 1),
+    ("compare_exchange", "CompareExchange", 2),
+];
+
+pub fn is_atomic_call(call: &syn::ExprCall) -> bool {
+    if let Expr::Path(p) = call.func.as_ref() {
+        if let Some(i) = p.path.get_ident() {
+            return ATOMIC_OPERATIONS.iter().any(|(name, _, _)| i == name);
+        }
+    }
+    false
+}
+
+/// Kernel intrinsics for random numbers: (name, core operation, result type, argument types).
+///
+/// They are called through the `random::` namespace like `random::uniform(seed, counter)`, so
+/// functions of the same name stay callable.
+pub const RANDOM_OPERATIONS: [(&str, &str, &str, &[&str]); 3] = [
+    ("uniform", "Uniform", "f32", &["u32", "u32"]),
+    ("normal", "Normal", "f32", &["u32", "u32", "f32", "f32"]),
+    ("bernoulli", "Bernoulli", "bool", &["u32", "u32", "f32"]),
+];
+
+/// The name of the intrinsic behind `random::<name>(..)`.
+pub fn random_operation(call: &syn::ExprCall) -> Option<Ident> {
+    if let Expr::Path(p) = call.func.as_ref() {
+        let segments: Vec<_> = p.path.segments.iter().collect();
+        if p.path.leading_colon.is_none() && segments.len() == 2 && segments[0].ident == "random" {
+            let name = &segments[1].ident;
+            if RANDOM_OPERATIONS.iter().any(|(n, _, _, _)| name == n) {
+                return Some(name.clone());
+            }
+        }
+    }
+    None
+}
+
+pub fn is_random_call(call: &syn::ExprCall) -> bool {
+    random_operation(call).is_some()
+}
+
+/// Built-in methods on values: (name, core operation, argument count). The arguments have the type of the receiver.
+pub const VALUE_METHODS: [(&str, &str, usize); 8] = [
+    ("abs", "Abs", 0),
+    ("sqrt", "Sqrt", 0),
+    ("exp", "Exp", 0),
+    ("ln", "Ln", 0),
+    ("tanh", "Tanh", 0),
+    ("max", "Max", 1),
+    ("min", "Min", 1),
+    ("dot", "Dot", 1),
+];
+
+/// Built-in methods on tensor arguments: (name, core module, core operation, argument count).
+pub const TENSOR_METHODS: [(&str, &str, &str, usize); 2] = [
+    ("len", "length", "Length", 0),
+    ("shape", "dimension", "Dimension", 1),
+];
+
+/// Kernel macros writing into the debug buffer of the processor.
+pub const DEBUG_MACROS: [&str; 2] = ["debug_print", "assert"];
+
+/// Maximum number of values of a single `debug_print!` or `assert!`, see `chandra::core::debug::DEBUG_RECORD_VALUES`.
+pub const DEBUG_MAX_VALUES: usize = 4;
+
+pub fn is_debug_macro(mac: &syn::Macro) -> bool {
+    mac.path.get_ident().map(|i| DEBUG_MACROS.iter().any(|name| i == name)).unwrap_or(false)
+}
+
+/// Splits `debug_print!("x = {}", x)` and `assert!(cond, "x = {}", x)` into (condition, message, values).
+pub fn parse_debug_macro(mac: &syn::Macro) -> (Option<Expr>, Option<syn::LitStr>, Vec<Expr>) {
+    let args = mac
+        .parse_body_with(syn::punctuated::Punctuated::<Expr, syn::Token![,]>::parse_terminated)
+        .unwrap_or_else(|e| abort!(mac, "Invalid macro arguments: {}", e));
+    let mut args = args.into_iter();
+
+    let condition = if mac.path.is_ident("assert") {
+        Some(args.next().unwrap_or_else(|| abort!(mac, "assert! expects a condition")))
+    } else {
+        None
+    };
+
+    let message = match args.next() {
+        Some(Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. })) => Some(s),
+        Some(any) => abort!(any, "Expected a string literal message"),
+        None => None,
+    };
+
+    let values: Vec<Expr> = args.collect();
+
+    if let Some(m) = &message {
+        let placeholders = m.value().matches("{}").count();
+        if placeholders != values.len() {
+            abort!(m, "Message has {} `{{}}` placeholder(s) but {} value(s) were given", placeholders, values.len())
+        }
+    }
+    if values.len() > DEBUG_MAX_VALUES {
+        abort!(mac, "At most {} values can be recorded", DEBUG_MAX_VALUES)
+    }
+
+    (condition, message, values)
+}
+
+/// Whether `e` only consists of unsuffixed numeric literals, so its type has to come from the context.
+pub fn is_untyped_literal(e: &Expr) -> bool {
+    match e {
+        Expr::Lit(l) => matches!(l.lit, syn::Lit::Int(_) | syn::Lit::Float(_)) && l.lit.suffix().is_empty(),
+        Expr::Paren(p) => is_untyped_literal(&p.expr),
+        Expr::Unary(u) => matches!(u.op, UnOp::Neg(_)) && matches!(*u.expr, Expr::Lit(_)) && is_untyped_literal(&u.expr),
+        Expr::Binary(b) => is_untyped_literal(&b.left) && is_untyped_literal(&b.right),
+        _ => false,
+    }
+}
+
+/// The 32 bit kernel type for host integer types like `usize`, which have no kernel type of their own.
+pub fn narrowed_integer(ty: &TokenStream) -> Option<TokenStream> {
+    match ty.to_string().as_str() {
+        "usize" | "u64" | "u16" | "u8" => Some(quote!(u32)),
+        "isize" | "i64" | "i16" | "i8" => Some(quote!(i32)),
+        _ => None,
+    }
+}
+
+/// Whether `ty` is the variable of a tensor argument, `Variable::<Tensor<f32>>`.
+pub fn is_tensor(ty: &TokenStream) -> bool {
+    let last_segment = |ty: &syn::Type| match ty {
+        syn::Type::Path(p) => p.path.segments.last().cloned(),
+        _ => None,
+    };
+
+    match syn::parse2::<syn::Type>(ty.clone()).ok().as_ref().and_then(last_segment) {
+        Some(variable) => match variable.arguments {
+            PathArguments::AngleBracketed(a) => a.args.iter().any(|arg| matches!(arg,
+                syn::GenericArgument::Type(t) if last_segment(t).map_or(false, |s| s.ident == "Tensor"))),
+            _ => false,
+        },
+        None => false,
+    }
+}
+
+/// Whether `i` is written like a const item, `SCALE` or `TILE_SIZE`.
+pub fn is_constant_name(i: &Ident) -> bool {
+    let name = i.to_string();
+    name.chars().any(|c| c.is_ascii_uppercase()) && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Aborts with an error naming the variable and where kernel variables come from.
+pub fn unknown_variable(i: &Ident) -> ! {
+    abort!(i, "Unknown variable `{}`", i;
+        help = "kernels can use their arguments and variables declared with `let` before")
+}
+
+/// Aborts for a binary operator without a kernel operation, suggesting the supported form.
+pub fn unsupported_binary_op(op: &BinOp) -> ! {
+    let compound = match op {
+        BinOp::AddAssign(_) => Some("+"),
+        BinOp::SubAssign(_) => Some("-"),
+        BinOp::MulAssign(_) => Some("*"),
+        BinOp::DivAssign(_) => Some("/"),
+        _ => None,
+    };
+
+    match compound {
+        Some(c) => abort!(op, "Compound assignment `{}` is not supported", quote!(#op);
+            help = "write it out like `x = x {} y`", c),
+        None => abort!(op, "The operator `{}` is not supported in kernels", quote!(#op);
+            help = "supported are `+ - * /`, comparisons and `&& ||`"),
+    }
+}
+
+/// Aborts for an expression the kernel language has no operation for, suggesting the supported alternative.
+pub fn unsupported_expr(e: &Expr) -> ! {
+    match e {
+        Expr::While(_) => abort!(e, "`while` loops are not supported";
+            help = "use `for i in a..b`"),
+        Expr::Loop(_) => abort!(e, "`loop` is not supported";
+            help = "use `for i in a..b`"),
+        Expr::Break(_) | Expr::Continue(_) => abort!(e, "`{}` is not supported", quote!(#e);
+            help = "restrict the range of the `for` loop or guard the body with `if`"),
+        Expr::Match(_) => abort!(e, "`match` is only supported as a statement or assigned to a variable";
+            help = "write `let x: f32 = match ..`, `x = match ..` or assign in the arms"),
+        Expr::Closure(_) => abort!(e, "Closures are only supported in `let` bindings";
+            help = "declare it like `let square = |x: f32| x * x;` and call it"),
+        Expr::Tuple(_) => abort!(e, "Tuples are only supported in `let` bindings and assignments";
+            help = "bind it like `let (a, b) = (x, y);`"),
+        Expr::Array(_) => abort!(e, "Array literals are only supported in `let` bindings and assignments";
+            help = "bind it like `let values = [x, y];`"),
+        Expr::Unary(u) => match u.op {
+            UnOp::Neg(_) => abort!(e, "Negation is only supported on literals";
+                help = "write `0.0 - {}` instead", quote!(#u.expr)),
+            UnOp::Not(_) => abort!(e, "`!` is not supported";
+                help = "write `{} == false` instead", quote!(#u.expr)),
+            _ => abort!(e, "Dereferencing is not supported";
+                help = "use the variable directly"),
+        },
+        Expr::Return(_) => abort!(e, "`return` is only supported as a statement";
+            help = "write `return x;` on its own line"),
+        Expr::Macro(m) => abort!(m.mac.path, "The macro `{}!` is not supported in kernels", quote!(#m.mac.path);
+            help = "supported macros are `debug_print!` and `assert!`"),
+        Expr::Async(_) | Expr::Await(_) => abort!(e, "`async` code is not supported in kernels"),
+        Expr::Unsafe(_) => abort!(e, "`unsafe` blocks are not supported in kernels"),
+        _ => abort!(e, "This expression is not supported in kernels: `{}`", quote!(#e)),
+    }
 }
 
 impl Fold for Parsefn {
@@ -34,7 +261,26 @@ impl Fold for Parsefn {
         let prev = self.return_type.clone();
         self.return_type = quote!(#crate_root::core::operations::noop::Noop);
 
-        let stmts: Vec<Stmt> = i.clone().stmts.into_iter().map(|s| self.fold_stmt(s)).collect();
+        // Helper functions are visible in the whole block, like in Rust.
+        let outer_functions = self.local_functions.clone();
+        for stmt in i.stmts.iter() {
+            if let Stmt::Item(syn::Item::Fn(f)) = stmt {
+                self.local_functions.insert(f.sig.ident.clone(), LocalFunction::from_fn(f));
+            }
+        }
+
+        let outer_tuples = self.tuple_vars.clone();
+
+        let mut stmts = Vec::new();
+        for stmt in i.clone().stmts {
+            for inlined in self.inline_calls(stmt, 0) {
+                for expanded in self.expand_locals(inlined) {
+                    stmts.push(self.fold_stmt(expanded));
+                }
+            }
+        }
+        self.local_functions = outer_functions;
+        self.tuple_vars = outer_tuples;
 
         let old = self.block_prev.clone();
         let old_expr = self.block_prev_type.clone();
@@ -59,69 +305,55 @@ impl Fold for Parsefn {
 
     fn fold_expr(&mut self, e: Expr) -> Expr {
         let crate_root = self.crate_root.clone();
+        let expected = std::mem::take(&mut self.expected_type);
 
         match e {
             Expr::Assign(e) => {
                 let op = e.eq_token;
                 let left = *e.left;
-                let right = self.fold_expr(*e.right);
+
+                let target_ty = match &left {
+                    Expr::Path(p) => p.path.get_ident().and_then(|i| self.vars.get(i)).cloned().unwrap_or_default(),
+                    _ => TokenStream::new(),
+                };
+                let right = self.fold_expr_as(*e.right, target_ty);
 
                 parse_quote! {
                     #crate_root::core::operations::set::set(&#left, #right)
                 }
             }
             Expr::Binary(b) => {
-                let mut left = self.fold_expr(*b.left.clone());
-
-                let mut left_ty = self.return_type.clone();
-                let l_expr_ty = self.expr_type.clone();
+                // Arithmetic passes the expected type on to its operands, comparisons start without one.
+                let operand_expected = match &b.op {
+                    BinOp::Add(_) | BinOp::Sub(_) | BinOp::Mul(_) | BinOp::Div(_) => expected,
+                    BinOp::And(_) | BinOp::Or(_) => quote!(bool),
+                    _ => TokenStream::new(),
+                };
 
-                let mut right = self.fold_expr(*b.right.clone());
+                // For `2.0 * x` the literal takes the type of the right operand, so that one is folded first.
+                let (left, left_ty, right, right_ty, expr_ty) = if operand_expected.is_empty() && (is_untyped_literal(&b.left) || self.is_outer_constant(&b.left)) {
+                    let right = self.fold_expr(*b.right.clone());
+                    let right_ty = self.return_type.clone();
+                    let r_expr_ty = self.expr_type.clone();
 
-                let mut right_ty = self.return_type.clone();
-                let r_expr_ty = self.expr_type.clone();
+                    let left = self.fold_expr_as(*b.left.clone(), r_expr_ty.clone());
+                    (left, self.return_type.clone(), right, right_ty, r_expr_ty)
+                } else {
+                    let left = self.fold_expr_as(*b.left.clone(), operand_expected);
+                    let left_ty = self.return_type.clone();
+                    let l_expr_ty = self.expr_type.clone();
 
-                let expr_ty = match (l_expr_ty.is_empty(), r_expr_ty.is_empty()) {
-                    (false, _) => l_expr_ty,
-                    (_, false) => r_expr_ty,
-                    (_, _) => abort!(b, "Can't infer Type, consider hinting literal Type"),
+                    let right = self.fold_expr_as(*b.right.clone(), l_expr_ty.clone());
+                    (left, left_ty, right, self.return_type.clone(), l_expr_ty)
                 };
 
-                self.expr_type = expr_ty.clone();
-
-                match (left_ty.is_empty(), right_ty.is_empty()) {
-                    (false, false) => {}
-                    (false, true) => { 
-                        if let Expr::Lit(l) = right.clone() {
-                            right_ty = expr_ty.clone(); 
-
-                            if let syn::Lit::Float(_) = l.lit {
-                                right = parse_quote!(<#right_ty>::from_float(#right));
-                            } else {
-                                right = parse_quote!(<#right_ty>::from_int(#right));
-                            }
-                            
-                        } else {
-                            abort!(right, "Can't infer type")
-                        }
-                    }
-                    (true, false) => { 
-                        if let Expr::Lit(l) = left.clone() {
-                            left_ty = expr_ty.clone(); 
-                            
-                            if let syn::Lit::Float(_) = l.lit {
-                                left = parse_quote!(<#left_ty>::from_float(#left));
-                            } else {
-                                left = parse_quote!(<#left_ty>::from_int(#left));
-                            }
-                            
-                        } else {
-                            abort!(right, "Can't infer type")
-                        }
-                     }
-                    (true, true) => abort!(b, "Can't infer Type, consider hinting literal Type")
+                if left_ty.is_empty() || right_ty.is_empty() {
+                    abort!(b, "Can't infer the type of the literals in `{}`", quote!(#b);
+                        help = "add a suffix like `2.0f32` or `1u32` to one of them")
                 }
 
+                self.expr_type = expr_ty.clone();
+
                 match &b.op {
                     BinOp::Add(a) => {
                         self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
@@ -236,14 +468,14 @@ impl Fold for Parsefn {
 
                         self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
                             bool, 
-                            #crate_root::core::operations::equal::Equal<
+                            #crate_root::core::operations::equals::Equals<
                                 #expr_ty, 
                                 #left_ty, 
                                 #right_ty
                             >
                         >);
                         
-                        parse_quote!(#crate_root::core::operations::equal::equal(#left, #right))
+                        parse_quote!(#crate_root::core::operations::equals::equals(#left, #right))
                     }
                     BinOp::Ne(g) => {
                         self.expr_type = quote!(bool);
@@ -285,7 +517,7 @@ impl Fold for Parsefn {
                         
                         parse_quote!(#crate_root::core::operations::or::or(#left, #right))
                     }
-                    _ => abort!(b, "Unsupported binary Expression"),
+                    op => unsupported_binary_op(op),
                 }
             }
             Expr::MethodCall(e) => {
@@ -322,9 +554,16 @@ impl Fold for Parsefn {
                         #crate_root::core::operations::call::call((#(#arguments,)*), <#v>::build_fn())
                     }
                 } else {
-                    abort!(func, &format!("Methods need an imported ChandraExtension like: use {} as {};", func, func))
+                    self.fold_builtin_method(func, *e.receiver, e.args.into_iter().collect(), expected)
                 }
             }
+            Expr::Call(e) if is_atomic_call(&e) => {
+                let (res, op_ty, res_ty) = self.fold_atomic(e);
+
+                self.return_type = quote!(#crate_root::core::operation::OperationWrapper<#res_ty, #op_ty>);
+                res
+            }
+            Expr::Call(e) if is_random_call(&e) => self.fold_random(e),
             Expr::Call(e) => {
                 let func = *e.func;
 
@@ -371,7 +610,14 @@ impl Fold for Parsefn {
                             }
                         }
 
-                        else if let Some(v) = self.known_functions.clone().get(&p.path) {
+                        else if let Some(i) = p.path.get_ident().filter(|i| self.local_functions.contains_key(i)) {
+                            abort!(i, "`{}` can't be called here", i;
+                                help = "helpers are inlined into `let` statements, assignments, conditions and loop ranges")
+                        }
+
+                        // Bare names without a `use` in the attribute are #[ChandraFunction]s declared earlier in the crate.
+                        else if let Some(v) = self.known_functions.get(&p.path).cloned()
+                            .or_else(|| p.path.get_ident().filter(|i| is_declared_function(i)).map(|_| p.path.clone())) {
                             let mut arg_types = Vec::new();
                             let mut arguments = Vec::new();
 
@@ -382,6 +628,7 @@ impl Fold for Parsefn {
 
                             let result_type = quote!(<#v #attributes as #crate_root::core::type_traits::ChandraFn>::Result);
 
+                            self.expr_type = result_type.clone();
                             self.return_type = quote!{
                                 #crate_root::core::operation::OperationWrapper<
                                     #result_type,
@@ -400,9 +647,11 @@ impl Fold for Parsefn {
                         }
 
                         else if let Some(i) = p.path.get_ident() {
-                            abort!(i, "Can't do that for now, sorry");
+                            abort!(i, "`{}` is not a known kernel function", i;
+                                help = "mark it with #[ChandraFunction] and import it like `#[kernel{{ use path::to::{}; }}]`", i)
+                        }
 
-                        } else {
+                        else {
                             let args: Vec<Expr> = e.args.into_iter().map(|x| self.fold_expr(x)).collect();
 
                             let mut it = p.path.segments.into_iter();
@@ -422,15 +671,17 @@ impl Fold for Parsefn {
                                             <#g>:: #f(#(#args,)*)
                                          }
                                     } else {
-                                        abort!(g, "Needs to be a known Generic")
+                                        abort!(g, "`{}` is neither a known kernel function nor a generic parameter", quote!(#g);
+                                            help = "import functions like `#[kernel{{ use {}; }}]`", quote!(#func))
                                     }
                                 }
 
-                                _ => abort!(func, "Not a valid generic function")
+                                _ => abort!(func, "`{}` is not a known kernel function", quote!(#func);
+                                    help = "import it like `#[kernel{{ use {}; }}]`", quote!(#func))
                             }
                         }
                     }
-                    _ => abort!(f2, "Not Path"),
+                    _ => abort!(f2, "Only functions called by name are supported, found `{}`", quote!(#f2)),
                 }
             }
             Expr::If(x) => {
@@ -509,14 +760,15 @@ impl Fold for Parsefn {
                     let i = p
                         .path
                         .get_ident()
-                        .unwrap_or_else(|| abort!(p, "Needs to be a local variable 1"));
+                        .unwrap_or_else(|| abort!(p, "Only local variables can be indexed, found `{}`", quote!(#p)));
 
                     let ty = self.vars
                         .get(i)
-                        .unwrap_or_else(|| abort!(p, "Needs to be a known variable"))
+                        .unwrap_or_else(|| unknown_variable(i))
                         .clone();
+                    let ty = self.indexable(ty);
 
-                    let ind_expr = self.fold_expr(*ind.index);
+                    let ind_expr = self.fold_expr_as(*ind.index, quote!(u32));
                     
                     let ind_ty = self.return_type.clone();
 
@@ -525,26 +777,63 @@ impl Fold for Parsefn {
 
                     parse_quote!(#crate_root::core::operations::index::index(&#i, #ind_expr))
                 } else {
-                    abort!(ind, "Not a variable")
+                    abort!(ind.expr, "Only local variables can be indexed, found `{}`", quote!(#ind.expr);
+                        help = "bind the value with `let` first")
                 }
             }
-            Expr::Lit(l) => {
-                match l.lit.suffix() {
-                    "f32" => {
-                        self.return_type = quote!(f32);
-                        self.expr_type = quote!(f32);
+            Expr::Paren(p) => {
+                let inner = self.fold_expr_as(*p.expr, expected);
+
+                parse_quote!((#inner))
+            }
+            Expr::Unary(u) if matches!(u.op, UnOp::Neg(_)) && matches!(*u.expr, Expr::Lit(_)) => {
+                let inner = self.fold_expr_as(*u.expr, expected);
+
+                parse_quote!(-#inner)
+            }
+            Expr::Repeat(r) => {
+                // `let acc: [f32; 8] = [0.0; 8]` types the element by the annotation.
+                let element_expected = match syn::parse2::<syn::Type>(expected) {
+                    Ok(syn::Type::Array(a)) => {
+                        let elem = a.elem;
+                        quote!(#elem)
                     }
-                    "i32" => {
-                        self.return_type = quote!(i32);
-                        self.expr_type = quote!(i32);
+                    _ => TokenStream::new(),
+                };
+
+                let len = &r.len;
+                let inner = self.fold_expr_as(*r.expr.clone(), element_expected);
+                let inner_ret = self.return_type.clone();
+                let elem = self.expr_type.clone();
+
+                if inner_ret.is_empty() || elem.is_empty() {
+                    abort!(r, "Can't infer the element type of the array";
+                        help = "add a literal suffix like `[0.0f32; {}]` or annotate it like `let acc: [f32; {}] = ..`", quote!(#len), quote!(#len))
+                }
+
+                self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
+                    [#elem; #len],
+                    #crate_root::core::operations::repeat::Repeat<#elem, #len, #inner_ret>
+                >);
+                self.expr_type = quote!([#elem; #len]);
+
+                parse_quote!(#crate_root::core::operations::repeat::repeat::<#elem, #len, _>(#inner))
+            }
+            Expr::Lit(l) => {
+                let suffix = l.lit.suffix();
+
+                match (&l.lit, suffix) {
+                    (syn::Lit::Bool(_), _) => {
+                        self.return_type = quote!(bool);
+                        self.expr_type = quote!(bool);
                     }
-                    "u32" => {
-                        self.return_type = quote!(u32);
-                        self.expr_type = quote!(u32);
+                    (_, "f32" | "f64" | "i32" | "i64" | "u32" | "u64") => {
+                        let ty = format_ident!("{}", suffix);
+                        self.return_type = quote!(#ty);
+                        self.expr_type = quote!(#ty);
                     }
-                    "u64" => {
-                        self.return_type = quote!(u64);
-                        self.expr_type = quote!(u64);
+                    (syn::Lit::Int(_) | syn::Lit::Float(_), "") if !expected.is_empty() => {
+                        return self.fold_literal_as(l, &expected);
                     }
                     _ => {
                         self.return_type = TokenStream::new();
@@ -573,14 +862,42 @@ impl Fold for Parsefn {
                         >);
 
                         parse_quote!(#crate_root::core::operations::get::get(&#i))
+                    } else if let Some(cType) = self.known_constants.get(i).cloned() {
+                        // Computable values are operations of their own type, host integers like `usize` are narrowed first.
+                        if let Some(narrow) = narrowed_integer(&cType) {
+                            self.expr_type = narrow.clone();
+                            self.return_type = narrow.clone();
+
+                            parse_quote!((#i as #narrow))
+                        } else {
+                            self.expr_type = cType.clone();
+                            self.return_type = cType;
+
+                            parse_quote!(#i)
+                        }
+                    } else if is_constant_name(i) {
+                        // A const item from outside the kernel, its type comes from where it is used.
+                        if expected.is_empty() {
+                            abort!(i, "Can't infer the type of the constant `{}`", i;
+                                help = "give the target a type like `let v: f32 = {};` or declare `{}` in the attribute", i, i)
+                        }
+
+                        self.expr_type = expected.clone();
+                        self.return_type = expected.clone();
+
+                        parse_quote!((#i as #expected))
+                    } else if self.tuple_vars.contains_key(i) {
+                        abort!(i, "Tuples can only be destructured or accessed by field";
+                            help = "use `{}.0` or `let (a, b) = {};`", i, i)
                     } else if i.to_string() == "PhantomData" {
                         parse_quote!(PhantomData)
                     } else {
-                        abort!(p, "Needs to be a known variable")
+                        unknown_variable(i)
                     }
 
                 } else {
-                    abort!(p, "Currently only local values are supported")
+                    abort!(p, "Only local variables and header constants are supported, found `{}`", quote!(#p);
+                        help = "declare constants in the attribute like `#[kernel{{ const SCALE: f32 = 2.0; }}]`")
                 }
             }
             Expr::Field(f) => {
@@ -592,16 +909,17 @@ impl Fold for Parsefn {
                             let member_ident = if let syn::Member::Named(named) = &f.member {
                                 named
                             } else {
-                                abort!(f.member, "Only named field structs are supported")
+                                abort!(f.member, "Tuple fields are not supported, only named fields of structures")
                             };
 
-                            let vType = format_ident!("{}", self
+                            let vType = self
                                 .known_structures
                                 .get(i)
-                                .unwrap_or_else(|| abort!(p, "Needs to be a known structure"))
+                                .unwrap_or_else(|| abort!(p, "`{}` is not a known structure", i;
+                                    help = "declare its type in the attribute like `#[kernel{{ struct Name {{ field: f32 }} }}]`"))
                                 .get(&member_ident.to_string())
-                                .unwrap_or_else(|| abort!(f.member, "Needs to be a known field"))
-                                .clone());
+                                .unwrap_or_else(|| abort!(f.member, "`{}` has no field `{}`", i, member_ident))
+                                .clone();
 
                            // println!("");
                            // println!("{:?}", quote!(#vType));
@@ -617,10 +935,11 @@ impl Fold for Parsefn {
 
                             parse_quote!(#crate_root::core::operations::get::get(&#f))
                         } else {
-                            abort!(p, "Currently only local values are supported")
+                            abort!(p, "Only fields of local variables are supported, found `{}`", quote!(#p))
                         }
                     }
-                    _ => abort!(f, "Currently only local values are supported")
+                    any => abort!(any, "Only fields of local variables are supported";
+                        help = "bind the value with `let` first and access the field on the variable")
                 }
             }
             Expr::Struct(s) => {
@@ -646,10 +965,10 @@ impl Fold for Parsefn {
             Expr::Range(range) => {
                 match (range.clone().start, range.clone().end) {
                     (Some(x), Some(y)) => {
-                        let left = self.fold_expr(*x);
+                        let left = self.fold_expr_as(*x, quote!(u32));
                         let left_ty = self.return_type.clone();
 
-                        let right = self.fold_expr(*y);
+                        let right = self.fold_expr_as(*y, quote!(u32));
                         let right_ty = self.return_type.clone();
                         let expr_typ = self.expr_type.clone();
 
@@ -661,23 +980,49 @@ impl Fold for Parsefn {
                         parse_quote!(#crate_root::core::operations::range::range(#left, #right))
                     }
 
-                    _ => abort!(range, "Ranges have to have both Limits")
+                    _ => abort!(range, "Ranges need both bounds";
+                        help = "write the range like `0..n`")
                 }
             }
 
             Expr::Cast(c) => {
                 let ty = c.ty;
-                self.expr_type = quote!(#ty);
-                let res = self.fold_expr(*c.expr);
-                self.expr_type = quote!(#ty);
-                res
+                let ty = quote!(#ty);
+
+                let value = self.fold_expr(*c.expr);
+                let value_ty = self.return_type.clone();
+                let from = self.expr_type.clone();
+
+                self.expr_type = ty.clone();
+
+                // `2 as f32` converts the literal directly.
+                if value_ty.is_empty() {
+                    self.return_type = ty.clone();
+
+                    return match value {
+                        Expr::Lit(syn::ExprLit { lit: syn::Lit::Float(l), .. }) => {
+                            parse_quote!(<#ty as #crate_root::core::types::Computable>::from_float(#l))
+                        }
+                        Expr::Lit(l) => parse_quote!(<#ty as #crate_root::core::types::Computable>::from_int(#l)),
+                        any => abort!(any, "Can't infer the type of this expression"),
+                    };
+                }
+
+                self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
+                    #ty,
+                    #crate_root::core::operations::cast::Cast<#from, #ty, #value_ty>
+                >);
+
+                parse_quote!(#crate_root::core::operations::cast::cast::<#from, #ty, _>(#value))
             }
             
             Expr::Reference(r) => {
                 //eprintln!("ExprRef");
                 parse_quote!((*#r).clone())
             }
-            _ => fold::fold_expr(self, e),
+            Expr::Group(g) => self.fold_expr_as(*g.expr, expected),
+            Expr::Block(_) => fold::fold_expr(self, e),
+            any => unsupported_expr(&any),
         }
     }
 
@@ -690,12 +1035,28 @@ impl Fold for Parsefn {
                     let prev = self.return_type.clone();
                     let prev_expr = self.expr_type.clone();
 
-                    let init = self.fold_expr(*s.init.unwrap().expr);
+                    // `let x: f32 = ..` declares the type of `x` and of the literals in the initializer.
+                    let (pat, declared) = match s.pat.clone() {
+                        Pat::Type(t) => {
+                            let ty = t.ty;
+                            (*t.pat, quote!(#ty))
+                        }
+                        any => (any, TokenStream::new()),
+                    };
+
+                    let init = self.fold_expr_as(*s.init.unwrap().expr, declared.clone());
 
                     let after = self.return_type.clone();
 
                     let expr_type = self.expr_type.clone();
 
+                    if after.is_empty() {
+                        abort!(init, "Can't infer the type of `{}`", quote!(#pat);
+                            help = "annotate it like `let {}: f32 = ..` or add a literal suffix", quote!(#pat))
+                    }
+
+                    let var_type = if declared.is_empty() { expr_type.clone() } else { declared };
+
                     self.return_type = quote!(
                         #crate_root::core::operation::OperationWrapper<
                             #crate_root::core::types::Void, 
@@ -714,31 +1075,40 @@ impl Fold for Parsefn {
                         #old
                     >);
 
-                    let pat = s.pat.clone();
                     let scope = format_ident!("s_{}", self.scope_depth);
 
                     if let Pat::Ident(p) = &pat {
                         let pat_name = format!("{}", p.ident);
-                        self.vars.insert(p.ident.clone(), quote!(#expr_type));
+                        self.vars.insert(p.ident.clone(), var_type);
 
                         //eprintln!("Local");
 
                         self.expr_type = quote!(#crate_root::core::types::Void);
 
+                        // Assignments to `let mut` variables build operations on a reference, the binding itself never changes.
+                        let ident = &p.ident;
+
                         parse_quote! {
-                            let (#pat, #scope) = (
+                            let (#ident, #scope) = (
                                 #crate_root::core::operations::assign::assign(::std::string::String::from(#pat_name), #init).0,
                                 #scope.include(#crate_root::core::operations::assign::assign(::std::string::String::from(#pat_name), #init).1)
                             );
                         }
                     } else {
-                        abort!(init, "abort")
+                        abort!(pat, "Only simple bindings like `let x = ..` are supported";
+                            help = "tuples can be destructured like `let (a, b) = (x, y);`")
                     }
                 } else {
-                    Stmt::Local(fold::fold_local(self, s))
+                    let pat = &s.pat;
+                    abort!(s, "Variables have to be initialized when declared";
+                        help = "write `let {} = <value>;`", quote!(#pat))
                 }
             }
 
+            Stmt::Macro(m) if is_debug_macro(&m.mac) => {
+                self.fold_stmt(Stmt::Expr(Expr::Macro(syn::ExprMacro { attrs: m.attrs, mac: m.mac }), m.semi_token))
+            }
+
             Stmt::Expr(e, semi) => {
                 let scope = format_ident!("s_{}", self.scope_depth);
 
@@ -749,9 +1119,6 @@ impl Fold for Parsefn {
 
                         let prev = self.return_type.clone();
 
-                        let right = self.fold_expr(*a.right);
-                        let after = self.return_type.clone();
-
                         let mut expr_ty = TokenStream::new();
 
                         let ret = match left.clone() {
@@ -759,10 +1126,10 @@ impl Fold for Parsefn {
                                 let i = p
                                     .path
                                     .get_ident()
-                                    .unwrap_or_else(|| abort!(e, "Needs to be a local variable 1"));
+                                    .unwrap_or_else(|| abort!(p, "Only local variables can be assigned to, found `{}`", quote!(#p)));
                                 let ty = self.vars
                                     .get(i)
-                                    .unwrap_or_else(|| abort!(e, "Needs to be a known variable"))
+                                    .unwrap_or_else(|| unknown_variable(i))
                                     .clone();
 
                                 expr_ty = ty.clone();
@@ -774,27 +1141,33 @@ impl Fold for Parsefn {
                                     let i = p
                                         .path
                                         .get_ident()
-                                        .unwrap_or_else(|| abort!(e, "Needs to be a local variable 1"));
+                                        .unwrap_or_else(|| abort!(p, "Only local variables can be indexed, found `{}`", quote!(#p)));
                                     let ty = self.vars
                                         .get(i)
-                                        .unwrap_or_else(|| abort!(e, "Needs to be a known variable"))
+                                        .unwrap_or_else(|| unknown_variable(i))
                                         .clone();
+                                    let ty = self.indexable(ty);
 
-                                    let ind_expr = self.fold_expr(*ind.index);
+                                    let ind_expr = self.fold_expr_as(*ind.index, quote!(u32));
                                     left = parse_quote!(#crate_root::core::operations::index::index(&#i, #ind_expr));
                                     
                                     let ind_ty = self.return_type.clone();
+                                    expr_ty = quote!(<#ty as #crate_root::core::type_traits::IndexAble>::IndexResult);
 
                                     quote!(<#ty as #crate_root::core::type_traits::IndexAble>::IndexResult, #crate_root::core::operation::OperationWrapper<<#ty as #crate_root::core::type_traits::IndexAble>::IndexResult, #crate_root::core::operations::index::Index<<#ty as #crate_root::core::type_traits::IndexAble>::IndexResult, #ty, #ind_ty>>)
                                 } else {
-                                    abort!(ind, "Not a variable")
+                                    abort!(ind.expr, "Only local variables can be indexed, found `{}`", quote!(#ind.expr))
                                 }
                             }
-                            _ => {
-                                abort!(e, "Needs to be a local variable 2")
+                            any => {
+                                abort!(any, "Only local variables and tensor elements can be assigned to, found `{}`", quote!(#any);
+                                    help = "assign to a variable like `x = ..` or an element like `out[i] = ..`")
                             }
                         };
 
+                        let right = self.fold_expr_as(*a.right, expr_ty);
+                        let after = self.return_type.clone();
+
                         let old = self.block_prev.clone();
                         let old_expr = self.block_prev_type.clone();
 
@@ -822,6 +1195,7 @@ impl Fold for Parsefn {
                        match *x.pat {
                             Pat::Ident(p) => {
                                 let prev = self.return_type.clone();
+                                let loop_name = p.ident.to_string();
 
                                 let right_side = self.fold_expr(*x.expr);
                                 let iterable_ty = self.return_type.clone();
@@ -860,13 +1234,16 @@ impl Fold for Parsefn {
                                     >
                                 );
 
+                                self.expr_type = quote!(#crate_root::core::types::Void);
+
                                 parse_quote!{
-                                    #crate_root::core::operations::foreach::foreach(#right_side, |#p| {
+                                    #crate_root::core::operations::foreach::foreach(#loop_name, #right_side, |#p| {
                                         #block
                                     })
                                 }   
                             }
-                            any => abort!(any, "Only support variables in for loop")
+                            any => abort!(any, "Only a single loop variable is supported, found `{}`", quote!(#any);
+                                help = "write the loop like `for i in 0..n`")
                         }  
                     }
                     Expr::If(x) => {
@@ -956,7 +1333,7 @@ impl Fold for Parsefn {
                         let prev = self.return_type.clone();
 
                         let exp = if let Some(x) = r.expr.clone() {
-                            self.fold_expr(*x)
+                            self.fold_expr_as(*x, self.output_type.clone())
                         } else {
                             self.expr_type = quote!(#crate_root::core::types::Void);
                             self.return_type = quote!(#crate_root::core::operations::noop::Noop);
@@ -969,8 +1346,29 @@ impl Fold for Parsefn {
                         let old = self.block_prev.clone();
                         let old_expr = self.block_prev_type.clone();
 
+                        // Only a value returned from the function body is the value of its scope,
+                        // anywhere else the surrounding scopes keep their type and are left early.
+                        if self.scope_depth > 1 || self.is_void(&ret) {
+                            self.block_prev = quote!(#crate_root::core::operations::instruction_list::InstructionList<
+                                #crate_root::core::types::Void, 
+                                #old_expr,
+                                #prev, 
+                                #old
+                            >);
+
+                            self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
+                                #crate_root::core::types::Void,
+                                #crate_root::core::operations::returns::EarlyReturn<#ret, #after>
+                            >);
+                            self.expr_type = quote!(#crate_root::core::types::Void);
+
+                            return parse_quote! {
+                                let #scope = #scope.include(#crate_root::core::operations::returns::early_return(#exp));
+                            }
+                        }
+
                         self.block_prev = quote!(#crate_root::core::operations::instruction_list::InstructionList<
-                            #ret, 
+                            #crate_root::core::types::Void, 
                             #old_expr,
                             #prev, 
                             #old
@@ -984,10 +1382,28 @@ impl Fold for Parsefn {
                             let #scope = #scope.returns(#crate_root::core::operations::returns::returns(#exp));
                         }
                     }
+                    Expr::Match(m) => {
+                        let prev = self.return_type.clone();
+
+                        let before = self.block_prev.clone();
+                        let before_expr = self.block_prev_type.clone();
+
+                        let res = self.fold_match(m);
+
+                        self.block_prev = quote!(#crate_root::core::operations::instruction_list::InstructionList<
+                            #crate_root::core::types::Void, 
+                            #before_expr, 
+                            #prev, 
+                            #before
+                        >);
+
+                        res
+                    }
 
                     Expr::Struct(s) => {
                         if let Some(_) = semi.clone() {
-                            abort!(s, "Currently structs are only allowed as last return expression")
+                            abort!(s, "Structs can only be returned as the last expression of a #[ChandraExtension]";
+                                help = "remove the `;` to return the struct")
                         }
 
                         let prev = self.return_type.clone();
@@ -1024,32 +1440,53 @@ impl Fold for Parsefn {
                         parse_quote!(#path {#stream} )
                     }
 
-                    Expr::Cast(c) => {
-                        if let Some(_) = semi.clone() {
-                            abort!(c, "Currently cast expresions are only allowed as last return expression")
+                    Expr::Macro(m) if is_debug_macro(&m.mac) => {
+                        if semi.is_none() {
+                            abort!(m, "{} must be terminated with a `;`", quote!(#m))
                         }
 
                         let prev = self.return_type.clone();
 
-                        let ret = self.expr_type.clone();
+                        let before = self.block_prev.clone();
+                        let before_expr = self.block_prev_type.clone();
+                        self.block_prev = quote!(#crate_root::core::operations::noop::Noop);
 
-                        let old = self.block_prev.clone();
-                        let old_expr = self.block_prev_type.clone();
+                        let res = self.fold_debug(m.mac);
+                        self.expr_type = quote!(#crate_root::core::types::Void);
 
                         self.block_prev = quote!(#crate_root::core::operations::instruction_list::InstructionList<
-                            #ret, 
-                            #old_expr,
+                            #crate_root::core::types::Void, 
+                            #before_expr, 
                             #prev, 
-                            #old
+                            #before
                         >);
 
-                        let ty = c.ty;
-                        self.expr_type = quote!(#ty);
-                        let res = self.fold_expr(*c.expr);
-                        self.expr_type = quote!(#ty);
                         res
                     }
 
+                    Expr::Call(c) if semi.is_some() && is_atomic_call(&c) => {
+                        let prev = self.return_type.clone();
+
+                        let before = self.block_prev.clone();
+                        let before_expr = self.block_prev_type.clone();
+                        self.block_prev = quote!(#crate_root::core::operations::noop::Noop);
+
+                        let (res, op_ty, _) = self.fold_atomic(c);
+
+                        // Used as a statement the previous value is discarded, the unwrapped operation is also an Operation<Void>
+                        self.return_type = op_ty;
+                        self.expr_type = quote!(#crate_root::core::types::Void);
+
+                        self.block_prev = quote!(#crate_root::core::operations::instruction_list::InstructionList<
+                            #crate_root::core::types::Void, 
+                            #before_expr, 
+                            #prev, 
+                            #before
+                        >);
+
+                        parse_quote!(#res.0)
+                    }
+
                     any => {
                         let prev = self.return_type.clone();
 
@@ -1059,7 +1496,9 @@ impl Fold for Parsefn {
                         let before_expr = self.block_prev_type.clone();
                         self.block_prev = quote!(#crate_root::core::operations::noop::Noop);
 
-                        let res = self.fold_expr(any.clone());
+                        // The tail of the function body is its result.
+                        let expected = if semi.is_none() && self.scope_depth == 1 { self.output_type.clone() } else { TokenStream::new() };
+                        let res = self.fold_expr_as(any.clone(), expected);
 
                         //println!();
                         //println!("{:?}", any);
@@ -1088,6 +1527,20 @@ impl Fold for Parsefn {
                     parse_quote! {
                         let #scope = #scope.include(#exp);
                     }
+                } else if self.is_void(&self.expr_type) {
+                    // `if`, `for` and `match` without `;` are statements like any other.
+                    parse_quote! {
+                        let #scope = #scope.include(#exp);
+                    }
+                } else if self.scope_depth == 1 && !self.returns_struct {
+                    // The tail of the function body is its result.
+                    let ret = self.expr_type.clone();
+                    let after = self.return_type.clone();
+                    self.return_type = quote!(#crate_root::core::operations::returns::Returns<#ret, #after>);
+
+                    parse_quote! {
+                        let #scope = #scope.returns(#crate_root::core::operations::returns::returns(#exp));
+                    }
                 } else {
                     parse_quote! {
                         let #scope = #scope.returns(#exp);
@@ -1095,7 +1548,758 @@ impl Fold for Parsefn {
                 }
                 
             }
-            _ => fold::fold_stmt(self, s),
+            Stmt::Macro(m) => abort!(m.mac.path, "The macro `{}!` is not supported in kernels", quote!(#m.mac.path);
+                help = "supported macros are `debug_print!` and `assert!`"),
+            Stmt::Item(syn::Item::Const(c)) => {
+                let ty = &c.ty;
+                self.known_constants.insert(c.ident.clone(), quote!(#ty));
+
+                Stmt::Item(syn::Item::Const(c))
+            }
+            Stmt::Item(i) => abort!(i, "Items can't be declared inside kernels";
+                help = "declare functions outside with #[ChandraFunction] and import them in the attribute"),
+        }
+    }
+}
+
+impl Parsefn {
+    fn is_void(&self, ty: &TokenStream) -> bool {
+        let crate_root = &self.crate_root;
+        ty.to_string() == quote!(#crate_root::core::types::Void).to_string()
+    }
+
+    /// Lowers a `match` statement to a `switch`, the arms become a chain of `case`s ending with the `_` arm.
+    fn fold_match(&mut self, m: syn::ExprMatch) -> Expr {
+        let crate_root = self.crate_root.clone();
+
+        let selector = self.fold_expr(*m.expr.clone());
+        let selector_op = self.return_type.clone();
+        let selector_ty = self.expr_type.clone();
+
+        if matches!(selector_ty.to_string().as_str(), "f32" | "f64" | "bool") {
+            abort!(m.expr, "Only integers can be matched, found `{}`", selector_ty;
+                help = "use `if`/`else if` for other types")
+        }
+
+        match m.arms.last() {
+            Some(last) if matches!(last.pat, Pat::Wild(_)) => {}
+            Some(last) => abort!(last.pat, "The last arm of a `match` has to be `_`";
+                help = "add `_ => {}` at the end, kernels lower `match` to a `switch` that needs a default case"),
+            None => abort!(m, "`match` needs at least the `_` arm"),
+        }
+
+        let mut arms = Vec::new();
+        for (position, arm) in m.arms.iter().enumerate() {
+            if let Some((_, guard)) = &arm.guard {
+                abort!(guard, "Match guards are not supported";
+                    help = "check the condition with `if` inside the arm")
+            }
+
+            let values = match &arm.pat {
+                Pat::Wild(w) if position + 1 < m.arms.len() => abort!(w, "`_` has to be the last arm"),
+                Pat::Wild(_) => Vec::new(),
+                pat => self.case_values(pat),
+            };
+
+            let body: syn::Block = match arm.body.as_ref() {
+                Expr::Block(b) if b.label.is_none() => b.block.clone(),
+                any => parse_quote!({ #any; }),
+            };
+
+            self.block_prev = quote!(#crate_root::core::operations::noop::Noop);
+            self.block_prev_type = quote!(#crate_root::core::types::Void);
+            self.expr_type = quote!(#crate_root::core::types::Void);
+
+            let block = self.fold_block(body);
+            let block_ty = self.return_type.clone();
+
+            arms.push((values, block, block_ty));
+        }
+
+        let (_, default_block, default_ty) = arms.pop().expect("the `_` arm");
+
+        let mut cases = quote!(#crate_root::core::operations::switch::default_case(#default_block));
+        let mut cases_ty = quote!(#crate_root::core::operations::switch::DefaultCase<
+            <#default_ty as #crate_root::core::operations::scope::ScopeTrait>::A,
+            <#default_ty as #crate_root::core::operations::scope::ScopeTrait>::B
+        >);
+
+        for (values, block, block_ty) in arms.into_iter().rev() {
+            cases = quote!(#crate_root::core::operations::switch::case::<#selector_ty, _, _, _>(::std::vec![#(#values),*], #block, #cases));
+            cases_ty = quote!(#crate_root::core::operations::switch::Case<
+                #selector_ty,
+                <#block_ty as #crate_root::core::operations::scope::ScopeTrait>::A,
+                <#block_ty as #crate_root::core::operations::scope::ScopeTrait>::B,
+                #cases_ty
+            >);
+        }
+
+        self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
+            #crate_root::core::types::Void,
+            #crate_root::core::operations::switch::Switch<#selector_ty, #selector_op, #cases_ty>
+        >);
+        self.expr_type = quote!(#crate_root::core::types::Void);
+
+        parse_quote!(#crate_root::core::operations::switch::switch(#selector, #cases))
+    }
+
+    /// The values selected by a `match` pattern: literals and constants, alternatives separated by `|`.
+    fn case_values(&self, pat: &Pat) -> Vec<TokenStream> {
+        match pat {
+            Pat::Lit(l) => vec![quote!(#l)],
+            Pat::Path(p) => vec![quote!(#p)],
+            Pat::Ident(i) if i.subpat.is_none() && (self.known_constants.contains_key(&i.ident)
+                || i.ident.to_string().chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')) => {
+                let i = &i.ident;
+                vec![quote!(#i)]
+            }
+            Pat::Ident(i) => abort!(i, "`{}` would bind the matched value", i.ident;
+                help = "match on literals and constants, use `_` for all other values"),
+            Pat::Or(o) => o.cases.iter().flat_map(|c| self.case_values(c)).collect(),
+            Pat::Paren(p) => self.case_values(&p.pat),
+            Pat::Range(r) => abort!(r, "Range patterns are not supported";
+                help = "list the values like `1 | 2 | 3`"),
+            Pat::Verbatim(v) => vec![v.clone()],
+            any => abort!(any, "Only literals and constants can be matched, found `{}`", quote!(#any)),
+        }
+    }
+
+    /// Lowers `atomic_add(&mut out[i], v)` and friends. Returns the expression, the unwrapped operation type and the element type.
+    fn fold_atomic(&mut self, call: syn::ExprCall) -> (Expr, TokenStream, TokenStream) {
+        let crate_root = self.crate_root.clone();
+
+        let name = if let Expr::Path(p) = call.func.as_ref() {
+            p.path.get_ident().unwrap_or_else(|| abort!(p, "Not an atomic operation")).clone()
+        } else {
+            abort!(call.func, "Not an atomic operation")
+        };
+
+        let (_, op, value_count) = ATOMIC_OPERATIONS
+            .iter()
+            .find(|(n, _, _)| name == n)
+            .unwrap_or_else(|| abort!(name, "Not an atomic operation"));
+
+        if call.args.len() != value_count + 1 {
+            abort!(call, "{} expects a mutable element reference and {} value(s)", name, value_count)
+        }
+
+        let mut args = call.args.clone().into_iter();
+
+        let (target, index) = match args.next() {
+            Some(Expr::Reference(r)) if r.mutability.is_some() => {
+                match *r.expr {
+                    Expr::Index(ind) => {
+                        if let Expr::Path(p) = *ind.expr {
+                            let i = p
+                                .path
+                                .get_ident()
+                                .unwrap_or_else(|| abort!(p, "Atomic operations need a local tensor"))
+                                .clone();
+                            (i, *ind.index)
+                        } else {
+                            abort!(ind, "Atomic operations need a local tensor")
+                        }
+                    }
+                    any => abort!(any, "Atomic operations expect an element reference like `&mut out[i]`")
+                }
+            }
+            any => abort!(any, "Atomic operations expect an element reference like `&mut out[i]`")
+        };
+
+        let ty = self.vars
+            .get(&target)
+            .unwrap_or_else(|| abort!(target, "Needs to be a known variable"))
+            .clone();
+
+        if let Ok(syn::Type::Array(_)) = syn::parse2::<syn::Type>(ty.clone()) {
+            abort!(target, "Atomic operations need a tensor argument";
+                help = "local arrays belong to a single invocation, update them with `{}[i] = ..`", target)
+        }
+
+        self.atomic_targets.push(target.clone());
+
+        let res_ty = quote!(<#ty as #crate_root::core::type_traits::IndexAble>::IndexResult);
+
+        let ind_expr = self.fold_expr_as(index, quote!(u32));
+        let ind_ty = self.return_type.clone();
+
+        let mut values = Vec::new();
+        let mut value_types = Vec::new();
+
+        for arg in args {
+            let (value, value_ty) = self.fold_typed_arg(arg, &res_ty);
+
+            values.push(value);
+            value_types.push(value_ty);
+        }
+
+        let module = format_ident!("{}", name);
+        let op = format_ident!("{}", op);
+
+        self.expr_type = res_ty.clone();
+
+        (
+            parse_quote!(#crate_root::core::operations::#module::#name(&#target, #ind_expr, #(#values),*)),
+            quote!(#crate_root::core::operations::#module::#op<#res_ty, #ty, #ind_ty, #(#value_types),*>),
+            res_ty,
+        )
+    }
+
+    /// Lowers `random::uniform(seed, counter)`, `random::normal(seed, counter, mean, std)` and `random::bernoulli(seed, counter, p)`.
+    fn fold_random(&mut self, call: syn::ExprCall) -> Expr {
+        let crate_root = self.crate_root.clone();
+
+        let name = random_operation(&call).unwrap_or_else(|| abort!(call.func, "Not a random operation"));
+
+        let (_, op, result, arg_types) = RANDOM_OPERATIONS
+            .iter()
+            .find(|(n, _, _, _)| name == n)
+            .unwrap_or_else(|| abort!(name, "Not a random operation"));
+
+        if call.args.len() != arg_types.len() {
+            abort!(call, "random::{} expects {} arguments: ({})", name, arg_types.len(), arg_types.join(", "))
+        }
+
+        let mut values = Vec::new();
+        let mut value_types = Vec::new();
+
+        for (arg, ty) in call.args.clone().into_iter().zip(arg_types.iter()) {
+            let ty = format_ident!("{}", ty);
+            let (value, value_ty) = self.fold_typed_arg(arg, &quote!(#ty));
+
+            values.push(value);
+            value_types.push(value_ty);
+        }
+
+        let module = format_ident!("{}", name);
+        let op = format_ident!("{}", op);
+        let result = format_ident!("{}", result);
+
+        self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
+            #result,
+            #crate_root::core::operations::#module::#op<#(#value_types),*>
+        >);
+        self.expr_type = quote!(#result);
+
+        parse_quote!(#crate_root::core::operations::#module::#name(#(#values),*))
+    }
+
+    /// Lowers the built-in methods `t.len()`, `t.shape(d)`, `x.abs()`, `x.sqrt()`, `x.exp()`, `x.ln()`, `x.tanh()`, `x.max(y)`, `x.min(y)` and `a.dot(b)`.
+    fn fold_builtin_method(&mut self, method: Ident, receiver: Expr, args: Vec<Expr>, expected: TokenStream) -> Expr {
+        let crate_root = self.crate_root.clone();
+
+        if let Some((name, module, op, arg_count)) = TENSOR_METHODS.iter().find(|(n, _, _, _)| method == n) {
+            if args.len() != *arg_count {
+                abort!(method, "`.{}()` takes {} argument(s)", name, arg_count)
+            }
+
+            let tensor = match &receiver {
+                Expr::Path(p) => p.path.get_ident().filter(|i| self.vars.get(i).map_or(false, is_tensor)).cloned(),
+                _ => None,
+            }.unwrap_or_else(|| abort!(receiver, "`.{}()` is only supported on tensor arguments", name));
+
+            let ty = self.vars[&tensor].clone();
+            let element = quote!(<#ty as #crate_root::core::type_traits::IndexAble>::IndexResult);
+
+            let mut values = Vec::new();
+            let mut value_types = Vec::new();
+
+            for arg in args {
+                let (value, value_ty) = self.fold_typed_arg(arg, &quote!(u32));
+
+                values.push(value);
+                value_types.push(value_ty);
+            }
+
+            let module = format_ident!("{}", module);
+            let op = format_ident!("{}", op);
+
+            self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
+                u32,
+                #crate_root::core::operations::#module::#op<#element, #(#value_types),*>
+            >);
+            self.expr_type = quote!(u32);
+
+            return parse_quote!(#crate_root::core::operations::#module::#module(&#tensor, #(#values),*));
+        }
+
+        let (name, op, arg_count) = VALUE_METHODS
+            .iter()
+            .find(|(n, _, _)| method == n)
+            .unwrap_or_else(|| abort!(method, "`.{}()` is not a known kernel method", method;
+                help = "built-in methods are `len`, `shape`, `abs`, `sqrt`, `exp`, `ln`, `tanh`, `max`, `min` and `dot`, others need an imported #[ChandraExtension] like `#[kernel{{ use path::to::{} as {}; }}]`", method, method));
+
+        if args.len() != *arg_count {
+            abort!(method, "`.{}()` takes {} argument(s)", name, arg_count)
+        }
+
+        // `x.dot(y)` returns an element of the arrays, all other methods the type of `x`.
+        let receiver_expected = if *name == "dot" { TokenStream::new() } else { expected };
+        let (value, value_ty) = self.fold_typed_arg(receiver, &receiver_expected);
+        let receiver_ty = self.expr_type.clone();
+
+        let mut values = vec![value];
+        let mut value_types = vec![value_ty];
+
+        for arg in args {
+            let (value, value_ty) = self.fold_typed_arg(arg, &receiver_ty);
+
+            values.push(value);
+            value_types.push(value_ty);
+        }
+
+        let module = format_ident!("{}", name);
+        let op = format_ident!("{}", op);
+
+        let (result, generics) = if *name == "dot" {
+            match syn::parse2::<syn::Type>(receiver_ty.clone()) {
+                Ok(syn::Type::Array(a)) => {
+                    let (elem, len) = (a.elem, a.len);
+                    (quote!(#elem), quote!(#elem, #len))
+                }
+                _ => abort!(method, "`.dot()` is only supported on arrays like `[f32; 3]`, found `{}`", receiver_ty),
+            }
+        } else {
+            (receiver_ty.clone(), receiver_ty)
+        };
+
+        self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
+            #result,
+            #crate_root::core::operations::#module::#op<#generics, #(#value_types),*>
+        >);
+        self.expr_type = result;
+
+        parse_quote!(#crate_root::core::operations::#module::#module(#(#values),*))
+    }
+
+    /// Lowers `debug_print!` and `assert!` to their core operations, messages are registered in `debug_messages`.
+    fn fold_debug(&mut self, mac: syn::Macro) -> Expr {
+        let crate_root = self.crate_root.clone();
+
+        let (condition, message, values) = parse_debug_macro(&mac);
+
+        let message = match (&condition, message) {
+            (_, Some(m)) => m.value(),
+            (Some(c), None) => format!("assertion failed: {}", quote!(#c)),
+            (None, None) => abort!(mac, "debug_print! expects a message"),
+        };
+
+        let id = match self.debug_messages.iter().position(|m| *m == message) {
+            Some(id) => id as u32,
+            None => {
+                self.debug_messages.push(message);
+                self.debug_messages.len() as u32 - 1
+            }
+        };
+
+        let mut args = Vec::new();
+        let mut arg_types = Vec::new();
+
+        for value in values {
+            let arg = self.fold_expr(value);
+            if self.return_type.is_empty() {
+                abort!(arg, "Can't infer Type, consider hinting literal Type")
+            }
+
+            args.push(arg);
+            arg_types.push(self.return_type.clone());
+        }
+
+        if let Some(c) = condition {
+            let cond = self.fold_expr(c);
+            let cond_ty = self.return_type.clone();
+
+            self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
+                #crate_root::core::types::Void,
+                #crate_root::core::operations::assert::Assert<#cond_ty, (#(#arg_types,)*)>
+            >);
+
+            parse_quote!(#crate_root::core::operations::assert::assert(#cond, #id, (#(#args,)*)))
+        } else {
+            self.return_type = quote!(#crate_root::core::operation::OperationWrapper<
+                #crate_root::core::types::Void,
+                #crate_root::core::operations::debug_print::DebugPrint<(#(#arg_types,)*)>
+            >);
+
+            parse_quote!(#crate_root::core::operations::debug_print::debug_print(#id, (#(#args,)*)))
+        }
+    }
+
+    /// Declares helper functions and closures and splits calls to them into the `let` bindings of their body.
+    fn inline_calls(&mut self, s: Stmt, depth: usize) -> Vec<Stmt> {
+        if let Stmt::Item(syn::Item::Fn(_)) = s {
+            return Vec::new();
+        }
+
+        if let Some((name, closure)) = closure_declaration(&s) {
+            let function = LocalFunction::from_closure(name.clone(), closure);
+            if let Some(captured) = function.free_variables().into_iter().find(|v| self.vars.contains_key(v)) {
+                abort!(captured, "Closures in kernels can't capture `{}`", captured;
+                    help = "pass `{}` as an argument instead", captured)
+            }
+            self.local_functions.insert(name, function);
+            return Vec::new();
+        }
+
+        if self.local_functions.is_empty() {
+            return vec![s];
+        }
+
+        if depth > MAX_INLINE_DEPTH {
+            abort!(s, "Helper calls are nested too deeply";
+                help = "recursive helpers can't be inlined")
+        }
+
+        let mut inliner = Inliner { functions: &self.local_functions, count: &mut self.inline_count, prelude: Vec::new() };
+        let s = inliner.fold_stmt(s);
+        let prelude = inliner.prelude;
+
+        prelude.into_iter()
+            .flat_map(|p| self.inline_calls(p, depth + 1))
+            .chain(std::iter::once(s))
+            .collect()
+    }
+
+    /// Splits tuple locals into one local per element and array literals into a repeat followed by element stores.
+    fn expand_locals(&mut self, s: Stmt) -> Vec<Stmt> {
+        let s = TupleFields(&self.tuple_vars).fold_stmt(s);
+
+        let expanded = match &s {
+            Stmt::Local(l) if l.init.is_some() => {
+                let (pat, ty) = match &l.pat {
+                    Pat::Type(t) => ((*t.pat).clone(), Some((*t.ty).clone())),
+                    any => (any.clone(), None),
+                };
+                let init = strip_parens(&l.init.as_ref().unwrap().expr);
+
+                match (pat, init) {
+                    (Pat::Tuple(targets), init) => {
+                        let values = self.tuple_elements(init)
+                            .unwrap_or_else(|| abort!(init, "Only tuple expressions and tuple variables can be destructured";
+                                help = "write it like `let (a, b) = (x, y);`"));
+                        let targets = with_element_types(targets.elems.into_iter().collect(), ty, &values);
+
+                        self.let_elements(targets, values)
+                    }
+                    (Pat::Ident(p), init) if self.tuple_elements(init).is_some() => {
+                        let values = self.tuple_elements(init).unwrap();
+                        let names: Vec<Ident> = (0..values.len()).map(|i| format_ident!("{}_{}", p.ident, i)).collect();
+                        let targets = with_element_types(names.iter().map(|n| parse_quote!(#n)).collect(), ty, &values);
+
+                        let stmts = self.let_elements(targets, values);
+                        self.tuple_vars.insert(p.ident, names);
+                        stmts
+                    }
+                    (Pat::Ident(p), Expr::Array(a)) => {
+                        let name = &p.ident;
+                        let values: Vec<Expr> = a.elems.iter().cloned().collect();
+                        if values.is_empty() {
+                            abort!(a, "Arrays need at least one element")
+                        }
+                        if values.iter().any(|v| mentions(quote!(#v), std::slice::from_ref(name))) {
+                            abort!(a, "`{}` can't be used in the array literal declaring it", name;
+                                help = "use another name for the new array")
+                        }
+
+                        // The array starts as copies of the first element with a known type, the others are stored after.
+                        let seed = values.iter().position(|v| !is_untyped_literal(v)).unwrap_or(0);
+                        let seed_value = &values[seed];
+                        let len = syn::LitInt::new(&values.len().to_string(), a.span());
+                        let pat = &l.pat;
+
+                        let mut stmts: Vec<Stmt> = vec![parse_quote!(let #pat = [#seed_value; #len];)];
+                        for (i, v) in values.iter().enumerate().filter(|(i, _)| *i != seed) {
+                            let i = syn::LitInt::new(&i.to_string(), v.span());
+                            stmts.push(parse_quote!(#name[#i] = #v;));
+                        }
+                        stmts
+                    }
+                    // `let x: T = match s { .. }` declares `x` with a zero and assigns in every arm.
+                    (Pat::Ident(p), Expr::Match(m)) => {
+                        let name = &p.ident;
+                        let ty = ty.unwrap_or_else(|| abort!(p, "Can't infer the type of `{}` from a `match`", name;
+                            help = "annotate it like `let {}: f32 = match ..`", name));
+                        let zero = zero_literal(&ty)
+                            .unwrap_or_else(|| abort!(ty, "Only scalars can be declared with a `match`, found `{}`", quote!(#ty)));
+
+                        vec![parse_quote!(let mut #name: #ty = #zero;), parse_quote!(#name = #m;)]
+                    }
+                    _ => return vec![s],
+                }
+            }
+            Stmt::Expr(Expr::Assign(a), Some(_)) => {
+                let right = strip_parens(&a.right);
+
+                match (strip_parens(&a.left), right) {
+                    // `x = match s { .. }` assigns in every arm.
+                    (left, Expr::Match(m)) => {
+                        let mut m = m.clone();
+                        for arm in m.arms.iter_mut() {
+                            let body = arm.body.as_ref().clone();
+                            *arm.body = match body {
+                                Expr::Block(mut b) if b.label.is_none() => {
+                                    match b.block.stmts.pop() {
+                                        Some(Stmt::Expr(tail, None)) => b.block.stmts.push(parse_quote!(#left = #tail;)),
+                                        Some(any) => b.block.stmts.push(any),
+                                        None => {}
+                                    }
+                                    Expr::Block(b)
+                                }
+                                Expr::Return(r) => parse_quote!({ #r; }),
+                                any => parse_quote!({ #left = #any; }),
+                            };
+                            arm.comma = Some(Default::default());
+                        }
+                        vec![Stmt::Expr(Expr::Match(m), None)]
+                    }
+                    (Expr::Tuple(targets), right) => {
+                        let values = self.tuple_elements(right)
+                            .unwrap_or_else(|| abort!(right, "Only tuple expressions and tuple variables can be destructured"));
+                        self.assign_elements(targets.elems.iter().cloned().collect(), values)
+                    }
+                    (Expr::Path(p), right) if p.path.get_ident().map_or(false, |i| self.tuple_vars.contains_key(i)) => {
+                        let names = self.tuple_vars[p.path.get_ident().unwrap()].clone();
+                        let values = self.tuple_elements(right)
+                            .unwrap_or_else(|| abort!(right, "Only tuple expressions and tuple variables can be assigned to a tuple"));
+                        self.assign_elements(names.iter().map(|n| parse_quote!(#n)).collect(), values)
+                    }
+                    (Expr::Path(p), Expr::Array(arr)) => {
+                        if arr.elems.iter().any(|v| mentions(quote!(#v), &pattern_idents(&parse_quote!(#p)))) {
+                            abort!(arr, "`{}` can't be used in the array literal assigned to it", quote!(#p);
+                                help = "copy the elements into a new array first")
+                        }
+
+                        arr.elems.iter().enumerate().map(|(i, v)| {
+                            let i = syn::LitInt::new(&i.to_string(), v.span());
+                            parse_quote!(#p[#i] = #v;)
+                        }).collect()
+                    }
+                    _ => return vec![s],
+                }
+            }
+            _ => return vec![s],
+        };
+
+        // Elements can be tuples themselves.
+        expanded.into_iter().flat_map(|s| self.expand_locals(s)).collect()
+    }
+
+    /// The elements of a tuple expression or tuple variable.
+    fn tuple_elements(&self, e: &Expr) -> Option<Vec<Expr>> {
+        match strip_parens(e) {
+            Expr::Tuple(t) => Some(t.elems.iter().cloned().collect()),
+            Expr::Path(p) => p.path.get_ident()
+                .and_then(|i| self.tuple_vars.get(i))
+                .map(|names| names.iter().map(|n| parse_quote!(#n)).collect()),
+            _ => None,
+        }
+    }
+
+    /// Binds every target pattern to its value, `let (a, b) = (b, a)` reads the old values through temporaries.
+    fn let_elements(&mut self, targets: Vec<Pat>, values: Vec<Expr>) -> Vec<Stmt> {
+        if targets.len() != values.len() {
+            abort!(targets.first().map(|t| t.span()).unwrap_or_else(proc_macro2::Span::call_site),
+                "Expected a tuple with {} elements, found {}", targets.len(), values.len())
+        }
+
+        let names: Vec<Ident> = targets.iter().flat_map(pattern_idents).collect();
+        let (mut stmts, values) = self.temporaries(&targets, values, &names);
+
+        for (target, value) in targets.into_iter().zip(values) {
+            if !matches!(target, Pat::Wild(_)) {
+                stmts.push(parse_quote!(let #target = #value;));
+            }
+        }
+        stmts
+    }
+
+    fn assign_elements(&mut self, targets: Vec<Expr>, values: Vec<Expr>) -> Vec<Stmt> {
+        if targets.len() != values.len() {
+            abort!(targets.first().map(|t| t.span()).unwrap_or_else(proc_macro2::Span::call_site),
+                "Expected a tuple with {} elements, found {}", targets.len(), values.len())
+        }
+
+        let names: Vec<Ident> = targets.iter().filter_map(|t| match t {
+            Expr::Path(p) => p.path.get_ident().cloned(),
+            Expr::Index(i) => match i.expr.as_ref() {
+                Expr::Path(p) => p.path.get_ident().cloned(),
+                _ => None,
+            },
+            _ => None,
+        }).collect();
+        let typed: Vec<Pat> = targets.iter().map(|_| Pat::Wild(syn::PatWild { attrs: Vec::new(), underscore_token: Default::default() })).collect();
+        let (mut stmts, values) = self.temporaries(&typed, values, &names);
+
+        for (target, value) in targets.into_iter().zip(values) {
+            if !matches!(target, Expr::Infer(_)) {
+                stmts.push(parse_quote!(#target = #value;));
+            }
+        }
+        stmts
+    }
+
+    /// Moves the values into temporaries if they read any of `names`, keeping the declared types.
+    fn temporaries(&mut self, targets: &[Pat], values: Vec<Expr>, names: &[Ident]) -> (Vec<Stmt>, Vec<Expr>) {
+        if !values.iter().any(|v| mentions(quote!(#v), names)) {
+            return (Vec::new(), values);
+        }
+
+        let mut stmts = Vec::new();
+        let values = targets.iter().zip(values).map(|(target, value)| {
+            let temp = format_ident!("tmp{}", self.temp_count);
+            self.temp_count += 1;
+
+            match target {
+                Pat::Type(t) => {
+                    let ty = &t.ty;
+                    stmts.push(parse_quote!(let #temp: #ty = #value;));
+                }
+                _ => stmts.push(parse_quote!(let #temp = #value;)),
+            }
+            parse_quote!(#temp)
+        }).collect();
+
+        (stmts, values)
+    }
+
+    /// `Variable<[T; N]>` for local arrays, other types are indexable as they are.
+    fn indexable(&self, ty: TokenStream) -> TokenStream {
+        let crate_root = &self.crate_root;
+        match syn::parse2::<syn::Type>(ty.clone()) {
+            Ok(syn::Type::Array(_)) => quote!(#crate_root::core::operations::var::Variable<#ty>),
+            _ => ty,
+        }
+    }
+
+    fn fold_typed_arg(&mut self, arg: Expr, ty: &TokenStream) -> (Expr, TokenStream) {
+        let value = self.fold_expr_as(arg, ty.clone());
+        let value_ty = self.return_type.clone();
+
+        if value_ty.is_empty() {
+            abort!(value, "Can't infer the type of this expression")
+        }
+
+        (value, value_ty)
+    }
+
+    /// Whether `e` names a const item declared outside the kernel, whose type has to be inferred like a literal.
+    fn is_outer_constant(&self, e: &Expr) -> bool {
+        match e {
+            Expr::Path(p) => p.path.get_ident().map_or(false, |i| is_constant_name(i)
+                && !self.vars.contains_key(i) && !self.known_constants.contains_key(i)),
+            Expr::Paren(p) => self.is_outer_constant(&p.expr),
+            _ => false,
+        }
+    }
+
+    /// Folds `e` with `ty` as the type unsuffixed literals in it should get.
+    fn fold_expr_as(&mut self, e: Expr, ty: TokenStream) -> Expr {
+        self.expected_type = ty;
+        self.fold_expr(e)
+    }
+
+    /// Converts an unsuffixed literal to `ty`, rejecting float literals for integers and vice versa like rustc.
+    fn fold_literal_as(&mut self, l: syn::ExprLit, ty: &TokenStream) -> Expr {
+        let crate_root = self.crate_root.clone();
+
+        let primitive = ty.to_string();
+        let is_float = matches!(l.lit, syn::Lit::Float(_));
+
+        match primitive.as_str() {
+            "f32" | "f64" if !is_float => abort!(l, "Expected `{}`, found integer literal `{}`", primitive, quote!(#l);
+                help = "use a float literal like `{}.0`", quote!(#l)),
+            "i32" | "i64" | "u32" | "u64" if is_float => abort!(l, "Expected `{}`, found float literal `{}`", primitive, quote!(#l)),
+            "bool" => abort!(l, "Expected `bool`, found numeric literal `{}`", quote!(#l)),
+            _ => {}
+        }
+
+        self.return_type = ty.clone();
+        self.expr_type = ty.clone();
+
+        if is_float {
+            parse_quote!(<#ty as #crate_root::core::types::Computable>::from_float(#l))
+        } else {
+            parse_quote!(<#ty as #crate_root::core::types::Computable>::from_int(#l))
+        }
+    }
+}
+
+/// A suffixed zero of a scalar type.
+fn zero_literal(ty: &syn::Type) -> Option<Expr> {
+    let ident = match ty {
+        syn::Type::Path(p) => p.path.get_ident()?.to_string(),
+        _ => return None,
+    };
+    match ident.as_str() {
+        "bool" => Some(parse_quote!(false)),
+        "f32" | "f64" => Some(Expr::Lit(syn::ExprLit { attrs: Vec::new(), lit: syn::LitFloat::new(&format!("0.0{}", ident), ty.span()).into() })),
+        "i32" | "i64" | "u32" | "u64" => Some(Expr::Lit(syn::ExprLit { attrs: Vec::new(), lit: syn::LitInt::new(&format!("0{}", ident), ty.span()).into() })),
+        _ => None,
+    }
+}
+
+fn strip_parens(e: &Expr) -> &Expr {
+    match e {
+        Expr::Paren(p) => strip_parens(&p.expr),
+        any => any,
+    }
+}
+
+fn pattern_idents(p: &Pat) -> Vec<Ident> {
+    match p {
+        Pat::Ident(i) => vec![i.ident.clone()],
+        Pat::Type(t) => pattern_idents(&t.pat),
+        Pat::Tuple(t) => t.elems.iter().flat_map(pattern_idents).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Adds the element types of a tuple type annotation to the target patterns.
+fn with_element_types(targets: Vec<Pat>, ty: Option<syn::Type>, values: &[Expr]) -> Vec<Pat> {
+    let types: Vec<Option<syn::Type>> = match ty {
+        Some(syn::Type::Tuple(t)) => {
+            if t.elems.len() != values.len() {
+                abort!(t, "Expected a tuple type with {} elements", values.len())
+            }
+            t.elems.into_iter().map(Some).collect()
+        }
+        Some(any) => abort!(any, "Tuples need a tuple type like `(f32, u32)`, found `{}`", quote!(#any)),
+        None => vec![None; targets.len()],
+    };
+
+    targets.into_iter().zip(types).map(|(target, ty)| match (target, ty) {
+        (Pat::Type(t), _) => Pat::Type(t),
+        (target, Some(ty)) => Pat::Type(syn::PatType {
+            attrs: Vec::new(),
+            pat: Box::new(target),
+            colon_token: Default::default(),
+            ty: Box::new(ty),
+        }),
+        (target, None) => target,
+    }).collect()
+}
+
+/// Replaces `t.0` of tuple locals by the local holding the element.
+struct TupleFields<'a>(&'a HashMap<Ident, Vec<Ident>>);
+
+impl<'a> Fold for TupleFields<'a> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        match fold::fold_expr(self, e) {
+            Expr::Field(f) => {
+                let element = match (f.base.as_ref(), &f.member) {
+                    (Expr::Path(p), syn::Member::Unnamed(index)) => p.path.get_ident()
+                        .and_then(|i| self.0.get(i))
+                        .map(|names| names.get(index.index as usize)
+                            .unwrap_or_else(|| abort!(index, "The tuple has only {} elements", names.len()))),
+                    _ => None,
+                };
+
+                match element {
+                    Some(name) => parse_quote!(#name),
+                    None => Expr::Field(f),
+                }
+            }
+            any => any,
         }
     }
 }
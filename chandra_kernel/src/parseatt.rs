@@ -1,64 +1,154 @@
 use std::collections::HashMap;
 
-use proc_macro_error::abort_call_site;
-use quote::{format_ident};
-use syn::{parse_quote, Path};
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use syn::fold::{self, Fold};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Ident, Item, ItemConst, LitInt, Path, PathArguments, Token, Type, TypePath, UseTree};
 
+/// Workgroup size used when the header has no `#![workgroup_size(..)]`.
+pub const DEFAULT_WORKGROUP_SIZE: (u32, u32, u32) = (64, 1, 1);
 
 #[derive(Debug)]
 pub struct ParseAttributes {
-    pub structures: HashMap<syn::TypePath, Structure>,
+    pub structures: HashMap<Ident, Structure>,
     pub replace_functions: HashMap<syn::Path,syn::Path>,
     pub normal_functions: HashMap<syn::Path,syn::Path>,
+    pub constants: Vec<ItemConst>,
+    pub workgroup_size: Option<(u32, u32, u32)>,
 }
 
-pub type Structure = HashMap<String, String>;
+/// A structure declared in the header, the field types may refer to `generics`.
+#[derive(Debug, Clone)]
+pub struct Structure {
+    pub generics: Vec<Ident>,
+    pub fields: Vec<(Ident, Type)>,
+}
+
+impl Structure {
+    pub fn new(fields: Vec<(Ident, Type)>) -> Self {
+        Self { generics: Vec::new(), fields }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&Type> {
+        self.fields.iter().find(|(name, _)| name == field).map(|(_, ty)| ty)
+    }
+
+    /// Replaces the generic parameters by the arguments of `ty`, e.g. `Particle<f32>` for `struct Particle<T>`.
+    pub fn instantiate(&self, ty: &TypePath) -> Structure {
+        let arguments: Vec<Type> = match ty.path.segments.last().map(|s| &s.arguments) {
+            Some(PathArguments::AngleBracketed(a)) => a.args.iter().map(|arg| match arg {
+                syn::GenericArgument::Type(t) => t.clone(),
+                any => abort!(any, "Only type arguments are supported for header structures"),
+            }).collect(),
+            _ => Vec::new(),
+        };
+
+        if arguments.len() != self.generics.len() {
+            abort!(ty, "`{}` expects {} type argument(s) but {} were given",
+                quote::quote!(#ty), self.generics.len(), arguments.len())
+        }
+
+        let mut substitute = Substitute(self.generics.iter().cloned().zip(arguments).collect());
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum Keyword {
-    Struct,
-    Use,
-    As
+        Structure {
+            generics: Vec::new(),
+            fields: self.fields.iter().map(|(name, ty)| (name.clone(), substitute.fold_type(ty.clone()))).collect(),
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum Token {
-    Ident(String),
-    Keyword(Keyword),
-    AngleOpen,
-    AngleClose,
-    CurlyOpen,
-    CurlyClose,
-    Semicolon,
-    Comma,
-    Colon, 
-    Plus, 
-    Unsupported(char),
-    None
-} 
-
-impl Token {
-    fn from_char(c: char) -> Self {
-        match c {
-            '>' => Token::AngleClose,
-            '<' => Token::AngleOpen,
-            '{' => Token::CurlyOpen,
-            '}' => Token::CurlyClose,
-            ':' => Token::Colon,
-            ',' => Token::Comma,
-            ';' => Token::Semicolon,
-            '+' => Token::Plus,
-            x   => Token::Unsupported(x)
+/// Replaces generic parameters in a type.
+struct Substitute(HashMap<Ident, Type>);
+
+impl Fold for Substitute {
+    fn fold_type(&mut self, ty: Type) -> Type {
+        if let Type::Path(p) = &ty {
+            if let Some(replacement) = p.path.get_ident().and_then(|i| self.0.get(i)) {
+                return replacement.clone();
+            }
         }
+        fold::fold_type(self, ty)
     }
+}
+
+/// The `#[kernel{ .. }]` header: inner attributes followed by `use`, `struct` and `const` declarations.
+pub struct KernelHeader {
+    pub attrs: Vec<Attribute>,
+    pub items: Vec<Item>,
+}
+
+impl Parse for KernelHeader {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_inner)?;
+        let mut items = Vec::new();
 
-    fn from_string(s: String) -> Self {
-        match s.as_str() {
-            "struct" => Token::Keyword(Keyword::Struct),
-            "use" => Token::Keyword(Keyword::Use),
-            "as" => Token::Keyword(Keyword::As),
-            x => Token::Ident(x.to_string())
+        while !input.is_empty() {
+            let item: Item = input.parse()?;
+
+            // Older headers terminate structs with `;`.
+            if let Item::Struct(_) = item {
+                input.parse::<Option<Token![;]>>()?;
+            }
+
+            items.push(item);
         }
+
+        Ok(KernelHeader { attrs, items })
+    }
+}
+
+/// How a `#[ChandraFunction]` is differentiated.
+#[derive(Debug, Clone)]
+pub enum Derivative {
+    /// Differentiates the body.
+    Symbolic,
+    /// One `#[ChandraFunction]` per input computing the partial derivative for it.
+    Custom(Vec<Type>),
+    NonDifferentiable,
+}
+
+/// The `#[ChandraFunction(..)]` header: `derivative = f_grad` or `non_differentiable` in front of
+/// the declarations of a kernel header, separated by `,` or `;`.
+pub struct FunctionHeader {
+    pub derivative: Derivative,
+    pub header: TokenStream,
+}
+
+impl Parse for FunctionHeader {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut derivative = Derivative::Symbolic;
+
+        loop {
+            let fork = input.fork();
+            let option = match fork.parse::<Ident>() {
+                Ok(ident) if ident == "derivative" && fork.peek(Token![=]) => ident,
+                Ok(ident) if ident == "non_differentiable" => ident,
+                _ => break,
+            };
+            input.parse::<Ident>()?;
+
+            if !matches!(derivative, Derivative::Symbolic) {
+                return Err(syn::Error::new(option.span(), "The derivative of a function can only be given once"));
+            }
+
+            derivative = if option == "derivative" {
+                input.parse::<Token![=]>()?;
+                match input.parse::<Type>()? {
+                    Type::Tuple(partials) => Derivative::Custom(partials.elems.into_iter().collect()),
+                    partial => Derivative::Custom(vec![partial]),
+                }
+            } else {
+                Derivative::NonDifferentiable
+            };
+
+            if input.parse::<Option<Token![,]>>()?.is_none() && input.parse::<Option<Token![;]>>()?.is_none() {
+                break;
+            }
+        }
+
+        Ok(FunctionHeader { derivative, header: input.parse()? })
     }
 }
 
@@ -69,157 +159,130 @@ impl ParseAttributes {
             structures: HashMap::new(),
             replace_functions: HashMap::new(),
             normal_functions: HashMap::new(),
+            constants: Vec::new(),
+            workgroup_size: None,
         }
     }
 
-    pub fn parse(&mut self, code: String) {
-        let mut expressions = Vec::new();
-        let mut tokens = Vec::new();
-
-        let mut current = String::new();
-        for cha in code.chars() {
-            match cha {
-                x if char::is_whitespace(x) => {
-                    if current == "" {
-                        continue;
-                    } else {
-                        tokens.push(Token::from_string(current.to_owned()));
-                        current = String::new();
-                    }
-                }
-                ';' => {
-                    if current != "" {
-                        tokens.push(Token::from_string(current.to_owned()));
-                    } 
-                        tokens.push(Token::from_char(cha));
-                        expressions.push(tokens);
-
-                        tokens = Vec::new();
-                        current = String::new();
-                    
+    pub fn parse(&mut self, code: TokenStream) {
+        let header = syn::parse2::<KernelHeader>(code).unwrap_or_else(|e| abort!(e.span(), "{}", e;
+            help = "the header contains declarations like `use my_function;`, `struct Particle { x: f32 }` or `const SCALE: f32 = 2.0;`"));
+
+        for attr in header.attrs {
+            if attr.path().is_ident("workgroup_size") {
+                self.workgroup_size = Some(parse_workgroup_size(&attr));
+            } else {
+                let path = attr.path();
+                abort!(path, "Unknown header attribute `{}`", quote::quote!(#path);
+                    help = "supported is `#![workgroup_size(x, y, z)]`")
+            }
+        }
+
+        for item in header.items {
+            match item {
+                Item::Use(u) => {
+                    check_attributes(&u.attrs);
+                    self.add_use_tree(&u.tree, Vec::new());
                 }
-                ':' | ',' | '+' | '{' | '}' => {
-                    if current != "" {
-                        tokens.push(Token::from_string(current.to_owned()));
-                    } 
-                        tokens.push(Token::from_char(cha));
-                        current = String::new();
-                    
+                Item::Struct(s) => {
+                    check_attributes(&s.attrs);
+
+                    let generics = s.generics.params.iter().map(|p| match p {
+                        syn::GenericParam::Type(t) => t.ident.clone(),
+                        any => abort!(any, "Only type parameters are supported for header structures"),
+                    }).collect();
+
+                    let fields = match s.fields {
+                        syn::Fields::Named(named) => named.named.into_iter()
+                            .map(|f| (f.ident.expect("named field"), f.ty))
+                            .collect(),
+                        syn::Fields::Unit => Vec::new(),
+                        any => abort!(any, "Only structures with named fields are supported";
+                            help = "declare it like `struct {} {{ x: f32 }}`", s.ident),
+                    };
+
+                    self.structures.insert(s.ident, Structure { generics, fields });
                 }
-                x => {
-                    current = current + &x.to_string();
+                Item::Const(c) => {
+                    check_attributes(&c.attrs);
+                    self.constants.push(c);
                 }
+                any => abort!(any, "Only `use`, `struct` and `const` declarations are supported in the header"),
             }
         }
+    }
 
-        for expression in expressions.into_iter() {
-            let mut valid = true;
-            let mut last = Token::None;
-
-            let mut it = expression.clone().into_iter();
-
-            let token = it.next().unwrap_or_else(|| abort_call_site!("Expect a token"));
-
-            match token {
-                Token::Keyword(k) => {
-                    match k {
-                        Keyword::Struct => {
-                            let mut structure = Structure::new();
-                            let ident = it.next().unwrap_or_else(|| abort_call_site!("No ident"));
-                            let ident = if let Token::Ident(x) = ident {
-                                x
-                            } else {
-                                abort_call_site!("Not an ident: {:?}", ident)
-                            };
-
-                            let mut next = it.next().unwrap_or_else(|| abort_call_site!("Empty Struct"));
-
-                            if Token::AngleOpen == next {
-                                next = it.next().unwrap_or_else(|| abort_call_site!("Unexpected End of input"));
-
-                                while next != Token::AngleClose {
-                                    next = it.next().unwrap_or_else(|| abort_call_site!("Unexpected End of input"));
-                                }
-                                next = it.next().unwrap_or_else(|| abort_call_site!("Unexpected End of input"));
-
-                                //let genericIdent = if let Token::Ident(x) = next {
-                                //    x
-                                //} else {
-                                //    abort_call_site!("Not an ident: {:?}", ident)
-                                //};
-
-
-                            };
-
-                            if Token::CurlyOpen != next {
-                                abort_call_site!("Need curly open")
-                            }
-
-                            while next != Token::CurlyClose {
-                                let ident = it.next().unwrap_or_else(|| abort_call_site!("No ident"));
-                                let colon = it.next().unwrap_or_else(|| abort_call_site!(&format!("{:?} No Colon / expr: {:?}", ident, expression)));
-                                let typ = it.next().unwrap_or_else(|| abort_call_site!("No Type"));
-
-                                next = it.next().unwrap_or_else(|| abort_call_site!("Unexpected End of input"));
-
-                                if let (Token::Ident(id), Token::Colon, Token::Ident(t)) = (ident, colon, typ) {
-                                    structure.insert(id, t);
-                                } else {
-                                    abort_call_site!("Bad input")
-                                }
-                            }
-
-                            let ident_t = format_ident!("{}", ident);
-                            
-                            self.structures.insert(parse_quote!(#ident_t), structure);
-                        },
-                        Keyword::Use => {
-                            let mut next = it.next().unwrap_or_else(|| abort_call_site!("No Path after use"));
-
-                            let mut path = String::new();
-                            let mut as_replacement = false;
-                            let mut replacing = String::new();
-                            while next != Token::Semicolon {
-                                if let Token::Keyword(Keyword::As) = next {
-                                    if path.is_empty() {
-                                        abort_call_site!("Need a path before applying 'as' to an import");
-                                    }
-                                    as_replacement = true;
-                                    next = it.next().unwrap_or_else(|| abort_call_site!("Replacement Path after 'as' is missing"));
-                                    continue;
-                                }
-
-                                let input = match &next {
-                                    Token::Colon => ":",
-                                    Token::Ident(id) => id,
-                                    _ => abort_call_site!("{:?} not supported in path", next),
-                                };
-
-                                if as_replacement {
-                                    replacing += &input;
-                                } else {
-                                    path += &input;
-                                }
-
-                                next = it.next().unwrap_or_else(|| abort_call_site!("Unexpected EOF"));
-                            }
-
-                            let p_ident = syn::parse_str::<Path>(&path).unwrap_or_else(|x| abort_call_site!(format!("{}, Not a valid path", x)));
-
-                            if as_replacement {
-                                let r_ident = syn::parse_str::<Path>(&replacing).unwrap_or_else(|x| abort_call_site!(format!("{}, Not a valid path", x)));
-
-                                self.replace_functions.insert(parse_quote!(#r_ident), parse_quote!(#p_ident));
-                            } else {
-                                self.normal_functions.insert(parse_quote!(#p_ident), parse_quote!(#p_ident));
-                            }
-                        },
-                        Keyword::As => abort_call_site!("As should not be a starting point")
-                    }
-                },
-                x => abort_call_site!("Not a good start: {:?}", x)
+    /// Looks up the structure of an argument type, substituting its generic arguments.
+    pub fn get_structure(&self, ty: &TypePath) -> Option<Structure> {
+        let name = &ty.path.segments.last()?.ident;
+        self.structures.get(name).map(|s| s.instantiate(ty))
+    }
+
+    /// The declared type of every header constant.
+    pub fn get_constant_types(&self) -> HashMap<Ident, TokenStream> {
+        self.constants.iter().map(|c| {
+            let ty = &c.ty;
+            (c.ident.clone(), quote::quote!(#ty))
+        }).collect()
+    }
+
+    fn add_use_tree(&mut self, tree: &UseTree, prefix: Vec<Ident>) {
+        match tree {
+            UseTree::Path(p) => {
+                let mut prefix = prefix;
+                prefix.push(p.ident.clone());
+                self.add_use_tree(&p.tree, prefix)
+            }
+            UseTree::Name(n) => {
+                // Callable by the imported name and by the full path.
+                let path = to_path(&prefix, &n.ident);
+                self.normal_functions.insert(Path::from(n.ident.clone()), path.clone());
+                self.normal_functions.insert(path.clone(), path);
+            }
+            UseTree::Rename(r) => {
+                let path = to_path(&prefix, &r.ident);
+                self.replace_functions.insert(Path::from(r.rename.clone()), path);
+            }
+            UseTree::Group(g) => {
+                for tree in g.items.iter() {
+                    self.add_use_tree(tree, prefix.clone())
+                }
             }
-            
+            UseTree::Glob(g) => abort!(g, "Glob imports are not supported";
+                help = "import every function by name like `use module::{a, b};`"),
         }
     }
-}
\ No newline at end of file
+}
+
+fn to_path(prefix: &[Ident], ident: &Ident) -> Path {
+    let segments = prefix.iter().chain(std::iter::once(ident));
+    syn::parse_quote!(#(#segments)::*)
+}
+
+/// Doc comments are allowed on declarations, other attributes have no meaning in the header.
+fn check_attributes(attrs: &[Attribute]) {
+    if let Some(attr) = attrs.iter().find(|a| !a.path().is_ident("doc")) {
+        abort!(attr, "Attributes on header declarations are not supported";
+            help = "header attributes are inner attributes at the start like `#![workgroup_size(64)]`")
+    }
+}
+
+fn parse_workgroup_size(attr: &Attribute) -> (u32, u32, u32) {
+    let sizes = attr
+        .parse_args_with(Punctuated::<LitInt, Token![,]>::parse_terminated)
+        .unwrap_or_else(|e| abort!(e.span(), "{}", e; help = "write it like `#![workgroup_size(8, 8)]`"));
+
+    if sizes.is_empty() || sizes.len() > 3 {
+        abort!(attr, "The workgroup size has one to three dimensions")
+    }
+
+    let mut dims = [1_u32; 3];
+    for (dim, size) in dims.iter_mut().zip(sizes.iter()) {
+        *dim = size.base10_parse().unwrap_or_else(|e| abort!(size, "{}", e));
+        if *dim == 0 {
+            abort!(size, "The workgroup size has to be at least 1")
+        }
+    }
+
+    (dims[0], dims[1], dims[2])
+}
@@ -1,7 +1,7 @@
 use std::{rc::{Rc}, marker::PhantomData, cell::{RefMut, Ref, RefCell}, collections::HashMap};
 
 use super::{type_traits::MemoryMapable, processor::{Storage, ReferencCounter}};
-use crate::processor::cpu::{CPUStorage};
+use crate::{processor::cpu::{CPUStorage}, Error};
 
 pub trait ProgrammInputs: ToRawInputs {    
     fn to_raw<'a> (&'a self) -> Self::Raw<'a>;
@@ -15,6 +15,22 @@ pub enum ParallelizationDescriptor {
     Data(Parallelizable)
 }
 
+impl ParallelizationDescriptor {
+    /// Known extents along x and y, empty if the value is not parallelizable.
+    pub fn extents(&self) -> Vec<u64> {
+        let this = match self {
+            ParallelizationDescriptor::Struct(s) => &s.this,
+            ParallelizationDescriptor::Data(d) => d,
+        };
+
+        match this {
+            Parallelizable::X(x) => vec![*x],
+            Parallelizable::XY(x, y) => vec![*x, *y],
+            Parallelizable::FULL | Parallelizable::Sync => Vec::new(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Parallelizable {
     X(u64),
@@ -37,6 +53,7 @@ pub enum MemoryLayoutDescriptor {
     UInteger(u8),
     Struct(HashMap<String, (u8, MemoryLayoutDescriptor)>),
     Vector {item_typ: Box<MemoryLayoutDescriptor> },
+    Atomic {item_typ: Box<MemoryLayoutDescriptor> },
 
     Empty,
 
@@ -44,9 +61,9 @@ pub enum MemoryLayoutDescriptor {
 }
 
 impl MemoryLayoutDescriptor {
-    pub fn get_bytes_before(&self, entry: &str) -> u64 {
+    pub fn get_bytes_before(&self, entry: &str) -> Result<u64, Error> {
         if let Self::Struct(map) = self {
-            let (pos, _) = map.get(entry).expect("Existence");
+            let (pos, _) = map.get(entry).ok_or_else(|| Error::UnknownField(entry.to_string()))?;
 
             let mut bytes = 0;
 
@@ -56,9 +73,9 @@ impl MemoryLayoutDescriptor {
                 }
             }
 
-            bytes
+            Ok(bytes)
         } else {
-            panic!("Bad use")
+            Err(Error::UnknownField(entry.to_string()))
         }
     }
 
@@ -67,6 +84,8 @@ impl MemoryLayoutDescriptor {
             MemoryLayoutDescriptor::Float(x) => *x as u64,
             MemoryLayoutDescriptor::Integer(x) => *x as u64,
             MemoryLayoutDescriptor::UInteger(x) => *x as u64,
+            MemoryLayoutDescriptor::Atomic { item_typ } => item_typ.get_byte_size(),
+            MemoryLayoutDescriptor::Array { item_typ, item_length } => item_typ.get_byte_size() * *item_length as u64,
             MemoryLayoutDescriptor::Struct(m) => {
                 m.iter()
                     .map(|(_, l)| l.1.get_byte_size())
@@ -76,6 +95,36 @@ impl MemoryLayoutDescriptor {
             _ => unimplemented!()
         }
     }
+
+    /// The WGSL type of the layout, `None` for structures and types WGSL has no storage type for.
+    pub fn get_wgsl_type(&self) -> Option<String> {
+        match self {
+            MemoryLayoutDescriptor::Float(4) => Some("f32".to_string()),
+            MemoryLayoutDescriptor::Integer(4) => Some("i32".to_string()),
+            MemoryLayoutDescriptor::UInteger(4) => Some("u32".to_string()),
+            MemoryLayoutDescriptor::Atomic { item_typ } => Some(format!("atomic<{}>", item_typ.get_wgsl_type()?)),
+            MemoryLayoutDescriptor::Array { item_typ, item_length } => Some(format!("array<{}, {}>", item_typ.get_wgsl_type()?, item_length)),
+            MemoryLayoutDescriptor::Vector { item_typ } => Some(format!("array<{}>", item_typ.get_wgsl_type()?)),
+            _ => None,
+        }
+    }
+
+    /// Marks the items behind `field` (or the whole layout if there is no field) as atomic storage.
+    pub fn into_atomic(self, field: Option<String>) -> Self {
+        match (self, field) {
+            (Self::Struct(mut map), Some(field)) => {
+                if let Some((pos, layout)) = map.remove(&field) {
+                    map.insert(field, (pos, layout.into_atomic(None)));
+                }
+
+                Self::Struct(map)
+            }
+            (Self::Array { item_typ, item_length }, _) => Self::Array { item_typ: Box::new(item_typ.into_atomic(None)), item_length },
+            (Self::Vector { item_typ }, _) => Self::Vector { item_typ: Box::new(item_typ.into_atomic(None)) },
+            (Self::Atomic { item_typ }, _) => Self::Atomic { item_typ },
+            (any, _) => Self::Atomic { item_typ: Box::new(any) },
+        }
+    }
 }
 
 //pub trait ToRawInputs<S: Storage, C: RawInputs<S>> {
@@ -165,16 +214,22 @@ pub trait ExecutableBindings<S: Storage>{
     type I: ProgrammInputs<Storage = S>;
     type O: MemoryMapable<S>;
 
-    fn get_inputs(&self) -> Self::I;
+    fn get_inputs(&self) -> Result<Self::I, Error>;
 
-    fn get_input_references(&self) -> Vec<S::Key>;
+    fn get_input_references(&self) -> Result<Vec<S::Key>, Error>;
 
-    fn get_output(&self) -> Binding<S, Self::O>;
+    fn get_output(&self) -> Result<Binding<S, Self::O>, Error>;
 
-    fn get_out_reference(&self) -> S::Key;
+    fn get_out_reference(&self) -> Result<S::Key, Error>;
+
+    /// Binds all arguments at once, used where the argument names aren't known like in a [`Graph`](super::graph::Graph).
+    fn set_arguments(&mut self, inputs: Self::I, output: Binding<S, Self::O>);
 
     fn get_layouts() -> HashMap<String, (u8, MemoryLayoutDescriptor, bool)>;
 
+    /// Checks the declared shape constraints of the kernel, grid constraints are only checked if a `grid` is given.
+    fn validate(&self, grid: Option<(u32, u32, u32)>) -> Result<(), Error>;
+
     fn new() -> Self;
 }
 
@@ -184,12 +239,18 @@ pub struct Binding<S: Storage, T: MemoryMapable<S> + 'static> {
     reference: S::Key,
     storage: S,
     data: Rc<RefCell<S::MappedType<T>>>,
+    parallelization: Rc<ParallelizationDescriptor>,
     _0: PhantomData<T>
 }
 
 impl<S: Storage, T: MemoryMapable<S> + 'static> Binding<S, T> {
-    pub fn new(reference: S::Key, storage: S, data: Rc<RefCell<S::MappedType<T>>>) -> Self {
-        Binding { reference: reference, storage, data, _0: PhantomData }
+    pub fn new(reference: S::Key, storage: S, data: Rc<RefCell<S::MappedType<T>>>, parallelization: ParallelizationDescriptor) -> Self {
+        Binding { reference: reference, storage, data, parallelization: Rc::new(parallelization), _0: PhantomData }
+    }
+
+    /// Parallelization info of the value at the time it was allocated.
+    pub fn get_parallelization_info(&self) -> &ParallelizationDescriptor {
+        &self.parallelization
     }
 
     pub fn deref(&self) -> Ref<S::MappedType<T>> {
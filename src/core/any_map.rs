@@ -57,6 +57,15 @@ impl<K: Eq + Hash> AnyMap<K> {
             .downcast_mut::<V>()
     }
  
+    /// Number of stored values of all types.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+ 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+ 
     /// Removes the data of the given type under they key
     /// if it's found.  The data found is returned in an
     /// Option after it's removed.
@@ -0,0 +1,253 @@
+//! Records written by `debug_print!` and `assert!` inside kernels.
+//!
+//! On the CPU the records are pushed into the [`DebugBuffer`] of the processor. On the GPU every
+//! record is written as [`DEBUG_RECORD_WORDS`] words into an atomic-indexed storage buffer and
+//! decoded with [`DebugRecord::from_words`].
+
+use std::{collections::VecDeque, fmt::Display};
+
+use parking_lot::Mutex;
+
+use super::types::{Computable, Pos};
+
+/// Maximum number of values a single record can carry.
+pub const DEBUG_RECORD_VALUES: usize = 4;
+/// Words per record: header, x, y, z, tags and the values.
+pub const DEBUG_RECORD_WORDS: usize = 5 + DEBUG_RECORD_VALUES;
+/// Records kept by a [`DebugBuffer`] before the oldest ones are overwritten.
+pub const DEFAULT_DEBUG_CAPACITY: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugValue {
+    Bool(bool),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+}
+
+impl DebugValue {
+    pub fn tag(&self) -> u32 {
+        match self {
+            DebugValue::Bool(_) => bool::DEBUG_TAG,
+            DebugValue::U32(_) => u32::DEBUG_TAG,
+            DebugValue::I32(_) => i32::DEBUG_TAG,
+            DebugValue::F32(_) => f32::DEBUG_TAG,
+        }
+    }
+
+    pub fn to_bits(&self) -> u32 {
+        match *self {
+            DebugValue::Bool(v) => v as u32,
+            DebugValue::U32(v) => v,
+            DebugValue::I32(v) => v as u32,
+            DebugValue::F32(v) => v.to_bits(),
+        }
+    }
+
+    pub fn from_bits(tag: u32, bits: u32) -> Option<Self> {
+        match tag {
+            bool::DEBUG_TAG => Some(DebugValue::Bool(bits != 0)),
+            u32::DEBUG_TAG => Some(DebugValue::U32(bits)),
+            i32::DEBUG_TAG => Some(DebugValue::I32(bits as i32)),
+            f32::DEBUG_TAG => Some(DebugValue::F32(f32::from_bits(bits))),
+            _ => None,
+        }
+    }
+}
+
+impl Display for DebugValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DebugValue::Bool(v) => write!(f, "{}", v),
+            DebugValue::U32(v) => write!(f, "{}", v),
+            DebugValue::I32(v) => write!(f, "{}", v),
+            DebugValue::F32(v) => write!(f, "{:?}", v),
+        }
+    }
+}
+
+/// Values that can be attached to a debug record.
+pub trait Debuggable: Computable {
+    const DEBUG_TAG: u32;
+
+    fn to_debug_value(self) -> DebugValue;
+}
+
+impl Debuggable for bool {
+    const DEBUG_TAG: u32 = 1;
+
+    fn to_debug_value(self) -> DebugValue {
+        DebugValue::Bool(self)
+    }
+}
+
+impl Debuggable for u32 {
+    const DEBUG_TAG: u32 = 2;
+
+    fn to_debug_value(self) -> DebugValue {
+        DebugValue::U32(self)
+    }
+}
+
+impl Debuggable for i32 {
+    const DEBUG_TAG: u32 = 3;
+
+    fn to_debug_value(self) -> DebugValue {
+        DebugValue::I32(self)
+    }
+}
+
+impl Debuggable for f32 {
+    const DEBUG_TAG: u32 = 4;
+
+    fn to_debug_value(self) -> DebugValue {
+        DebugValue::F32(self)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugKind {
+    Print,
+    Assertion,
+}
+
+#[derive(Clone, Debug)]
+pub struct DebugRecord {
+    pub kind: DebugKind,
+    pub pos: Pos,
+    pub message: &'static str,
+    pub values: Vec<DebugValue>,
+}
+
+impl DebugRecord {
+    /// Decodes a record written by the GPU, `messages` are the ones returned by `Buildable::get_debug_messages`.
+    ///
+    /// Layout: `[message << 1 | kind, x, y, z, count | tag_i << (4 + 4 * i), values..]`.
+    pub fn from_words(words: &[u32], messages: &[&'static str]) -> Option<Self> {
+        if words.len() < DEBUG_RECORD_WORDS {
+            return None;
+        }
+
+        let kind = if words[0] & 1 == 1 { DebugKind::Assertion } else { DebugKind::Print };
+        let message = *messages.get((words[0] >> 1) as usize)?;
+        let count = (words[4] & 0xF) as usize;
+
+        let values = (0..count.min(DEBUG_RECORD_VALUES))
+            .map(|i| DebugValue::from_bits((words[4] >> (4 + 4 * i)) & 0xF, words[5 + i]))
+            .collect::<Option<Vec<DebugValue>>>()?;
+
+        Some(DebugRecord {
+            kind,
+            pos: Pos { x: words[1], y: words[2], z: words[3] },
+            message,
+            values,
+        })
+    }
+
+    /// Decodes the contents of the GPU debug buffer: the number of written records followed by the
+    /// ring of records. Records are returned oldest first.
+    pub fn from_buffer(words: &[u32], messages: &[&'static str]) -> Vec<Self> {
+        let (head, ring) = match words.split_first() {
+            Some((head, ring)) => (*head as usize, ring),
+            None => return Vec::new(),
+        };
+        let capacity = ring.len() / DEBUG_RECORD_WORDS;
+        let count = head.min(capacity);
+        let oldest = if head > capacity { head % capacity } else { 0 };
+
+        (0..count)
+            .map(|i| (oldest + i) % capacity * DEBUG_RECORD_WORDS)
+            .filter_map(|start| Self::from_words(&ring[start..start + DEBUG_RECORD_WORDS], messages))
+            .collect()
+    }
+}
+
+impl Display for DebugRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            DebugKind::Print => "debug",
+            DebugKind::Assertion => "assertion failed",
+        };
+
+        let mut parts = self.message.split("{}");
+        let mut text = parts.next().unwrap_or_default().to_string();
+        let mut values = self.values.iter();
+
+        for part in parts {
+            match values.next() {
+                Some(v) => text.push_str(&v.to_string()),
+                None => text.push_str("{}"),
+            }
+            text.push_str(part);
+        }
+        for v in values {
+            text.push_str(&format!(" {}", v));
+        }
+
+        write!(f, "[{}] ({}, {}, {}) {}", kind, self.pos.x, self.pos.y, self.pos.z, text)
+    }
+}
+
+/// Thread-safe ring buffer the CPU kernels write their records to.
+pub struct DebugBuffer {
+    records: Mutex<VecDeque<DebugRecord>>,
+    failures: Mutex<Vec<DebugRecord>>,
+    capacity: usize,
+}
+
+impl DebugBuffer {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_DEBUG_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        DebugBuffer {
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+            failures: Mutex::new(Vec::new()),
+            capacity,
+        }
+    }
+
+    pub fn print(&self, pos: &Pos, message: &'static str, values: &[DebugValue]) {
+        self.push(DebugRecord { kind: DebugKind::Print, pos: *pos, message, values: values.to_vec() });
+    }
+
+    /// Records a failed assertion if `condition` is false. The kernel keeps running, the failure is reported by `dispatch`.
+    pub fn assert(&self, condition: bool, pos: &Pos, message: &'static str, values: &[DebugValue]) {
+        if !condition {
+            self.record(DebugRecord { kind: DebugKind::Assertion, pos: *pos, message, values: values.to_vec() });
+        }
+    }
+
+    /// Adds a record read back from a processor, assertions are kept as failures as well.
+    pub fn record(&self, record: DebugRecord) {
+        if record.kind == DebugKind::Assertion {
+            self.failures.lock().push(record.clone());
+        }
+        self.push(record);
+    }
+
+    /// Removes and returns all records in the order they were written.
+    pub fn drain(&self) -> Vec<DebugRecord> {
+        self.records.lock().drain(..).collect()
+    }
+
+    /// Removes and returns the assertion failures since the last call.
+    pub fn take_failures(&self) -> Vec<DebugRecord> {
+        std::mem::take(&mut *self.failures.lock())
+    }
+
+    fn push(&self, record: DebugRecord) {
+        let mut records = self.records.lock();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+}
+
+impl Default for DebugBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
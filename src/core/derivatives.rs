@@ -0,0 +1,479 @@
+//! Gradients, Jacobians and Hessians of kernels for single elements of their tensor arguments.
+//!
+//! The derivatives are programs built from
+//! [`differantiate_for_element`](super::DifferentiableProgram::differantiate_for_element): the
+//! invocation at `pos` differentiates for the element `pos.x` (and `pos.y` for the second derivative)
+//! and sets the derivative of every output element into the output of the program. They build for
+//! every processor, [`gradient`], [`jacobian`] and [`hessian`] dispatch them on the [`CPUProcessor`].
+//!
+//! ```ignore
+//! let variables = [Variable::new("a"), Variable::new("b")];
+//! // a and b with 4 elements each, shape [4, 8, 8], the Hessian of every position
+//! let hessian = hessian(&energy(), &variables, (a, b), (4, 1, 1))?;
+//! ```
+
+use std::{any::Any, collections::HashMap, marker::PhantomData};
+
+use crate::{processor::cpu::{CPUProcessor, CPUStorage}, types::tensor::Tensor, Error};
+
+use super::{allocated::{Binding, ExecutableBindings, MemoryLayoutDescriptor, ToRawInputs}, debug::DebugBuffer, operation::{Compilable, Differentiable, Operation}, operations::{element_derivatives::ElementDerivatives, instruction_list::InstructionList, noop::Noop, scope::Scope, var::{Variable, ELEMENT_TRACE, ORDER_TRACE, OUTPUT_TRACE}}, processor::{cpu::{CPUFunction, DifferentiatedCPUContext}, Executable, Processor, ProcessorInformation, Storage}, type_traits::{Calculatable, FromMut, KernelTensor, MemoryMapable}, types::{Computable, Pos, Void}, unsupported, Buildable, DifferentiableProgram, Program};
+
+/// Name of the index of the element a derivative program differentiates for.
+const ELEMENT: &str = "chandra_element";
+/// Name of the index of the second element a Hessian program differentiates for.
+const SECOND_ELEMENT: &str = "chandra_second_element";
+/// Local the derivative of the written output element is set to.
+const VALUE: &str = "chandra_derivative";
+
+/// Host values of the kernel inputs, in argument order.
+pub trait HostInputs: Clone {
+    type Bindings;
+
+    fn alloc(&self, processor: &mut CPUProcessor) -> Result<Self::Bindings, Error>;
+    /// Sets the values in `context` under the argument `names`.
+    fn bind(&self, names: &[String], context: &mut DifferentiatedCPUContext);
+    fn get(&self, position: usize) -> Option<&dyn Any>;
+    fn get_mut(&mut self, position: usize) -> Option<&mut dyn Any>;
+    fn type_name(&self, position: usize) -> &'static str;
+}
+
+macro_rules! impl_host_inputs {
+    ($(($t:ident, $i:tt)),+) => {
+        impl<$($t: MemoryMapable<CPUStorage>),+> HostInputs for ($($t,)+) {
+            type Bindings = ($(Binding<CPUStorage, $t>,)+);
+
+            fn alloc(&self, processor: &mut CPUProcessor) -> Result<Self::Bindings, Error> {
+                Ok(($(processor.try_alloc(self.$i.clone())?,)+))
+            }
+
+            fn bind(&self, names: &[String], context: &mut DifferentiatedCPUContext) {
+                $(context.set(&names[$i], self.$i.clone());)+
+            }
+
+            fn get(&self, position: usize) -> Option<&dyn Any> {
+                match position {
+                    $($i => Some(&self.$i),)+
+                    _ => None,
+                }
+            }
+
+            fn get_mut(&mut self, position: usize) -> Option<&mut dyn Any> {
+                match position {
+                    $($i => Some(&mut self.$i),)+
+                    _ => None,
+                }
+            }
+
+            fn type_name(&self, position: usize) -> &'static str {
+                match position {
+                    $($i => std::any::type_name::<$t>(),)+
+                    _ => "nothing",
+                }
+            }
+        }
+    };
+}
+
+impl_host_inputs!((T1, 0));
+impl_host_inputs!((T1, 0), (T2, 1));
+impl_host_inputs!((T1, 0), (T2, 1), (T3, 2));
+impl_host_inputs!((T1, 0), (T2, 1), (T3, 2), (T4, 3));
+impl_host_inputs!((T1, 0), (T2, 1), (T3, 2), (T4, 3), (T5, 4));
+impl_host_inputs!((T1, 0), (T2, 1), (T3, 2), (T4, 3), (T5, 4), (T6, 5));
+impl_host_inputs!((T1, 0), (T2, 1), (T3, 2), (T4, 3), (T5, 4), (T6, 5), (T7, 6));
+impl_host_inputs!((T1, 0), (T2, 1), (T3, 2), (T4, 3), (T5, 4), (T6, 5), (T7, 6), (T8, 7));
+
+/// Input bindings of a kernel whose values are set in a host context by the derivative programs.
+pub trait HostArguments: ToRawInputs<Storage = CPUStorage> {
+    /// Sets the values in `context` under the argument `names`.
+    fn bind(raw: &Self::RawDerefed<'_>, names: &[String], context: &mut DifferentiatedCPUContext);
+}
+
+macro_rules! impl_host_arguments {
+    ($(($t:ident, $i:tt)),+) => {
+        impl<$($t: MemoryMapable<CPUStorage>),+> HostArguments for ($(Binding<CPUStorage, $t>,)+) {
+            fn bind(raw: &Self::RawDerefed<'_>, names: &[String], context: &mut DifferentiatedCPUContext) {
+                $(context.set(&names[$i], raw.$i.clone());)+
+            }
+        }
+    };
+}
+
+impl_host_arguments!((T1, 0));
+impl_host_arguments!((T1, 0), (T2, 1));
+impl_host_arguments!((T1, 0), (T2, 1), (T3, 2));
+
+/// Bindings of a derivative program, the ones of the kernel with the derivatives as output.
+///
+/// The shape constraints of the kernel aren't checked, the output and the grid of a derivative
+/// program don't have the shapes of the kernel's.
+pub struct ElementBindings<B>(B);
+
+impl<S: Storage, B: ExecutableBindings<S>> ExecutableBindings<S> for ElementBindings<B> {
+    type CPU = ElementBindings<B::CPU>;
+    type I = B::I;
+    type O = B::O;
+
+    fn get_inputs(&self) -> Result<Self::I, Error> {
+        self.0.get_inputs()
+    }
+
+    fn get_input_references(&self) -> Result<Vec<S::Key>, Error> {
+        self.0.get_input_references()
+    }
+
+    fn get_output(&self) -> Result<Binding<S, Self::O>, Error> {
+        self.0.get_output()
+    }
+
+    fn get_out_reference(&self) -> Result<S::Key, Error> {
+        self.0.get_out_reference()
+    }
+
+    fn set_arguments(&mut self, inputs: Self::I, output: Binding<S, Self::O>) {
+        self.0.set_arguments(inputs, output)
+    }
+
+    fn get_layouts() -> HashMap<String, (u8, MemoryLayoutDescriptor, bool)> {
+        B::get_layouts()
+    }
+
+    fn validate(&self, _grid: Option<(u32, u32, u32)>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn new() -> Self {
+        ElementBindings(B::new())
+    }
+}
+
+/// Runs the [`ElementDerivatives`] of a derivative program for one invocation on the CPU.
+#[derive(Clone, Debug)]
+pub struct ElementFunction<R: Computable, M: Operation<Void>> {
+    derivatives: ElementDerivatives<R, M>,
+    inputs: Vec<String>,
+}
+
+impl<B, R, M> CPUFunction<B> for ElementFunction<R, M>
+where
+    B: ExecutableBindings<CPUStorage, O = Tensor<R>>,
+    B::I: HostArguments,
+    R: Calculatable,
+    M: Operation<Void> + Send + Sync,
+{
+    fn call_cpu<'a, 'b>(&self, pos: &Pos, inputs: &<<B as ExecutableBindings<CPUStorage>>::I as ToRawInputs>::RawDerefed<'a>, output: &mut <<<B as ExecutableBindings<CPUStorage>>::O as MemoryMapable<CPUStorage>>::Mapped<'b> as FromMut<<CPUStorage as Storage>::MappedType<<B as ExecutableBindings<CPUStorage>>::O>>>::Result<'b>, _debug: &DebugBuffer) {
+        let mut context = DifferentiatedCPUContext::new();
+        B::I::bind(inputs, &self.inputs, &mut context);
+        context.set(&self.derivatives.output, Tensor::new(R::get_zero(), vec![output.len() as usize]));
+        context.set("pos.x", pos.x);
+        context.set("pos.y", pos.y);
+        context.set("pos.z", pos.z);
+
+        for (index, value) in self.derivatives.run(&mut context) {
+            output[index as u32] = value;
+        }
+    }
+}
+
+/// A program writing derivatives of the kernel `B` for single elements of its tensor arguments, built
+/// by [`gradient_program`], [`jacobian_program`] or [`hessian_program`].
+///
+/// It is dispatched over the elements, its output is the tensor the derivatives are written to. The
+/// kernel's output is only read as zeros.
+#[derive(Clone, Debug)]
+pub struct ElementProgram<B, M: Operation<Void>, R: Computable> {
+    derivatives: ElementDerivatives<R, M>,
+    unsupported: Option<String>,
+    _0: PhantomData<fn() -> B>,
+}
+
+impl<P, B, M, R> Buildable<P> for ElementProgram<B, M, R>
+where
+    P: ProcessorInformation,
+    B: Buildable<P> + Program,
+    <B as Buildable<CPUProcessor>>::CPUBinding: ExecutableBindings<CPUStorage, O = Tensor<R>>,
+    <<B as Buildable<CPUProcessor>>::CPUBinding as ExecutableBindings<CPUStorage>>::I: HostArguments,
+    M: Operation<Void> + Send + Sync,
+    R: Calculatable,
+    Scope<Void, Void, Void, ElementDerivatives<R, M>, InstructionList<Void, Void, Noop, Noop>>: Compilable<Void, P::Compiler>,
+{
+    type Binding = ElementBindings<<B as Buildable<P>>::Binding>;
+    type CPUBinding = ElementBindings<<B as Buildable<CPUProcessor>>::CPUBinding>;
+    type CPUFunction = ElementFunction<R, M>;
+    type Main = Scope<Void, Void, Void, ElementDerivatives<R, M>, InstructionList<Void, Void, Noop, Noop>>;
+
+    fn get_cpu(&self) -> Self::CPUFunction {
+        ElementFunction { derivatives: self.derivatives.clone(), inputs: arguments::<B>().0 }
+    }
+
+    fn get_main_tree(&self) -> Self::Main {
+        Scope::new().include(self.derivatives.clone())
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if let Some(message) = &self.unsupported {
+            return Err(Error::NotDifferentiable(message.clone()));
+        }
+        let (inputs, _) = arguments::<B>();
+        match self.derivatives.elements.iter().find(|(_, tensor)| !inputs.contains(tensor)) {
+            Some((_, tensor)) => Err(Error::UnknownArgument(tensor.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Differentiates `program` for the element `element` of `variable`, setting the derivative of the
+/// written output element to the [`VALUE`] local. Returns the tree and why it can't be built.
+fn element_tree<P: Program, R: Clone>(program: &P, variable: &Variable<R>, element: &str) -> (<P::MainTree as Differentiable<Void>>::Diff, Option<String>)
+where
+    P::MainTree: Differentiable<Void>,
+{
+    let mut trace = HashMap::from([
+        (ORDER_TRACE.to_string(), vec![P::ORDER.to_string()]),
+        (ELEMENT_TRACE.to_string(), vec![element.to_string()]),
+        (OUTPUT_TRACE.to_string(), vec![VALUE.to_string()]),
+    ]);
+    let tree = Program::get_main_tree(program).auto_diff_for(variable.clone(), &mut trace);
+    let unsupported = unsupported(program, &trace);
+    (tree, unsupported)
+}
+
+/// The program behind [`gradient`]: the invocation at `pos.x` writes the gradient of the sum of all
+/// output elements for element `pos.x` of `variable`. The output has the shape of `variable`.
+///
+/// ```ignore
+/// let mut executable = processor.build(gradient_program(&dot(), &Variable::new("w"), (1, 1, 1)));
+/// executable.get_bindings().set_arguments((x, w), gradient.clone());
+/// processor.dispatch(&mut executable, 3, 1, 1)?;
+/// ```
+pub fn gradient_program<B, R>(program: &B, variable: &Variable<Tensor<R>>, grid: (u32, u32, u32)) -> ElementProgram<B, <B::MainTree as Differentiable<Void>>::Diff, R>
+where
+    B: Program,
+    B::MainTree: Differentiable<Void>,
+    R: Calculatable,
+{
+    let (tree, unsupported) = element_tree(program, variable, ELEMENT);
+    ElementProgram {
+        derivatives: ElementDerivatives {
+            tree,
+            elements: vec![(ELEMENT.to_string(), variable.reference.clone())],
+            grid,
+            value: VALUE.to_string(),
+            output: arguments::<B>().1,
+            per_position: false,
+            _0: PhantomData,
+        },
+        unsupported,
+        _0: PhantomData,
+    }
+}
+
+/// The program behind [`jacobian`]: the invocation at `pos.x` writes the derivative of the output
+/// element written at every position for element `pos.x` of `variable`. The output has the shape
+/// `[positions, elements]`.
+pub fn jacobian_program<B, R>(program: &B, variable: &Variable<Tensor<R>>, grid: (u32, u32, u32)) -> ElementProgram<B, <B::MainTree as Differentiable<Void>>::Diff, R>
+where
+    B: Program,
+    B::MainTree: Differentiable<Void>,
+    R: Calculatable,
+{
+    let mut jacobian = gradient_program(program, variable, grid);
+    jacobian.derivatives.per_position = true;
+    jacobian
+}
+
+/// Operation tree of the second derivative of the kernel `B`.
+type SecondDerivative<B> = <<<B as Program>::MainTree as Differentiable<Void>>::Diff as Differentiable<Void>>::Diff;
+
+/// The program behind [`hessian`]: the invocation at `pos` writes the second derivative of the output
+/// element written at every position for element `pos.x` of `first` and `pos.y` of `second`. The output
+/// has the shape `[positions, first elements, second elements]`.
+pub fn hessian_program<B, R>(program: &B, first: &Variable<Tensor<R>>, second: &Variable<Tensor<R>>, grid: (u32, u32, u32)) -> ElementProgram<B, SecondDerivative<B>, R>
+where
+    B: Program,
+    B::MainTree: Differentiable<Void>,
+    <B::MainTree as Differentiable<Void>>::Diff: Differentiable<Void>,
+    R: Calculatable,
+{
+    let derivative = program.differantiate_for_element(first.clone(), ELEMENT);
+    let (tree, unsupported) = element_tree(&derivative, second, SECOND_ELEMENT);
+    ElementProgram {
+        derivatives: ElementDerivatives {
+            tree,
+            elements: vec![
+                (ELEMENT.to_string(), first.reference.clone()),
+                (SECOND_ELEMENT.to_string(), second.reference.clone()),
+            ],
+            grid,
+            value: VALUE.to_string(),
+            output: arguments::<B>().1,
+            per_position: true,
+            _0: PhantomData,
+        },
+        unsupported,
+        _0: PhantomData,
+    }
+}
+
+/// Names of the inputs in argument order and the name of the output.
+fn arguments<B: Buildable<CPUProcessor>>() -> (Vec<String>, String) {
+    let layouts = <B::CPUBinding as ExecutableBindings<CPUStorage>>::get_layouts();
+    let mut names = vec![String::new(); layouts.values().filter(|(_, _, is_output)| !is_output).count()];
+    let mut output = String::new();
+    for (name, (position, _, is_output)) in layouts {
+        if is_output {
+            output = name;
+        } else {
+            names[position as usize] = name;
+        }
+    }
+    (names, output)
+}
+
+/// Position of the tensor argument `variable` in `inputs`.
+pub(crate) fn argument_position<B: Buildable<CPUProcessor>, I: HostInputs, R: Computable>(variable: &Variable<Tensor<R>>, inputs: &I) -> Result<usize, Error> {
+    let (names, _) = arguments::<B>();
+    let position = names.iter()
+        .position(|name| *name == variable.reference)
+        .ok_or_else(|| Error::UnknownArgument(variable.reference.clone()))?;
+
+    match inputs.get(position) {
+        Some(value) if value.is::<Tensor<R>>() => Ok(position),
+        _ => Err(Error::DtypeMismatch {
+            argument: variable.reference.clone(),
+            expected: format!("Tensor<{}>", R::get_type()),
+            found: inputs.type_name(position).to_string(),
+        }),
+    }
+}
+
+/// Shape of the tensor argument `variable` in `inputs`.
+fn variable_shape<B: Buildable<CPUProcessor>, I: HostInputs, R: Computable>(variable: &Variable<Tensor<R>>, inputs: &I) -> Result<Vec<usize>, Error> {
+    let position = argument_position::<B, I, R>(variable, inputs)?;
+    Ok(inputs.get(position)
+        .and_then(|value| value.downcast_ref::<Tensor<R>>())
+        .expect("checked above")
+        .shape
+        .clone())
+}
+
+/// Runs a derivative program with `inputs` on a [`CPUProcessor`], dispatched over the `elements`, and
+/// returns its output of the given `shape`.
+fn dispatch<B, I, M, R>(derivatives: ElementProgram<B, M, R>, inputs: &I, shape: Vec<usize>, elements: (usize, usize)) -> Result<Tensor<R>, Error>
+where
+    B: Program,
+    B::CPUBinding: ExecutableBindings<CPUStorage, I = I::Bindings, O = Tensor<R>>,
+    I::Bindings: HostArguments,
+    I: HostInputs,
+    M: Operation<Void> + Send + Sync,
+    R: Calculatable,
+{
+    let mut processor = CPUProcessor::new();
+    let mut executable = processor.try_build(derivatives)?;
+    let output = processor.try_alloc(Tensor::new(R::get_zero(), shape))?;
+    executable.get_bindings().set_arguments(inputs.alloc(&mut processor)?, output.clone());
+    processor.dispatch(&mut executable, elements.0 as u32, elements.1 as u32, 1)?;
+    drop(executable);
+    processor.try_copy_to_cpu(&output)
+}
+
+/// Derivatives of the output element written at every position of the `grid` for every element of
+/// the tensor arguments `variables`, with the shape `[positions, elements]`. The elements of the
+/// variables follow each other in the columns. Runs the [`jacobian_program`] of every variable on the
+/// [`CPUProcessor`].
+pub fn jacobian<B, I, R>(program: &B, variables: &[Variable<Tensor<R>>], inputs: I, grid: (u32, u32, u32)) -> Result<Tensor<R>, Error>
+where
+    B: Program,
+    B::MainTree: Differentiable<Void>,
+    <B::MainTree as Differentiable<Void>>::Diff: Send + Sync,
+    B::CPUBinding: ExecutableBindings<CPUStorage, I = I::Bindings, O = Tensor<R>>,
+    I::Bindings: HostArguments,
+    I: HostInputs,
+    R: Calculatable,
+{
+    let positions = (grid.0 * grid.1 * grid.2) as usize;
+    let counts = variables.iter()
+        .map(|variable| Ok(variable_shape::<B, I, R>(variable, &inputs)?.iter().product()))
+        .collect::<Result<Vec<usize>, Error>>()?;
+    let columns = counts.iter().sum::<usize>();
+    let mut jacobian = Tensor::new(R::get_zero(), vec![positions, columns]);
+
+    let mut offset = 0;
+    for (variable, count) in variables.iter().zip(counts) {
+        let block = dispatch(jacobian_program(program, variable, grid), &inputs, vec![positions, count], (count, 1))?;
+        for position in 0..positions {
+            for element in 0..count {
+                jacobian.data[position * columns + offset + element] = block.data[position * count + element];
+            }
+        }
+        offset += count;
+    }
+    Ok(jacobian)
+}
+
+/// Gradient of the sum of all output elements for the tensor argument `variable`, with the shape of
+/// `variable`. Runs the [`gradient_program`] on the [`CPUProcessor`], once for every element.
+///
+/// Unlike [`jacobian`], every element of `variable` is differentiated for on its own, reads of other
+/// elements through an index have a derivative of zero. For a kernel writing a single dot product of
+/// `x` and `w`, the gradient for `w` is `x`.
+pub fn gradient<B, I, R>(program: &B, variable: &Variable<Tensor<R>>, inputs: I, grid: (u32, u32, u32)) -> Result<Tensor<R>, Error>
+where
+    B: Program,
+    B::MainTree: Differentiable<Void>,
+    <B::MainTree as Differentiable<Void>>::Diff: Send + Sync,
+    B::CPUBinding: ExecutableBindings<CPUStorage, I = I::Bindings, O = Tensor<R>>,
+    I::Bindings: HostArguments,
+    I: HostInputs,
+    R: Calculatable,
+{
+    let shape = variable_shape::<B, I, R>(variable, &inputs)?;
+    let elements = shape.iter().product();
+    dispatch(gradient_program(program, variable, grid), &inputs, shape, (elements, 1))
+}
+
+/// Second derivatives of the output element written at every position of the `grid` for every pair
+/// of elements of the tensor arguments `variables`, with the shape `[positions, elements, elements]`
+/// and the elements ordered like the columns of [`jacobian`]. Runs the [`hessian_program`] of every
+/// pair of variables on the [`CPUProcessor`], mixed derivatives of two variables are computed once and
+/// mirrored.
+pub fn hessian<B, I, R>(program: &B, variables: &[Variable<Tensor<R>>], inputs: I, grid: (u32, u32, u32)) -> Result<Tensor<R>, Error>
+where
+    B: Program,
+    B::MainTree: Differentiable<Void>,
+    <B::MainTree as Differentiable<Void>>::Diff: Differentiable<Void>,
+    <<B::MainTree as Differentiable<Void>>::Diff as Differentiable<Void>>::Diff: Send + Sync,
+    B::CPUBinding: ExecutableBindings<CPUStorage, I = I::Bindings, O = Tensor<R>>,
+    I::Bindings: HostArguments,
+    I: HostInputs,
+    R: Calculatable,
+{
+    let positions = (grid.0 * grid.1 * grid.2) as usize;
+    let counts = variables.iter()
+        .map(|variable| Ok(variable_shape::<B, I, R>(variable, &inputs)?.iter().product()))
+        .collect::<Result<Vec<usize>, Error>>()?;
+    let offsets: Vec<usize> = counts.iter().scan(0, |offset, count| { *offset += count; Some(*offset - count) }).collect();
+    let size = counts.iter().sum::<usize>();
+    let mut hessian = Tensor::new(R::get_zero(), vec![positions, size, size]);
+
+    for (first, variable) in variables.iter().enumerate() {
+        for (second, other) in variables.iter().enumerate().skip(first) {
+            let (rows, columns) = (counts[first], counts[second]);
+            let block = dispatch(hessian_program(program, variable, other, grid), &inputs, vec![positions, rows, columns], (rows, columns))?;
+
+            for position in 0..positions {
+                for row in 0..rows {
+                    for column in 0..columns {
+                        let value = block.data[(position * rows + row) * columns + column];
+                        let (i, j) = (offsets[first] + row, offsets[second] + column);
+                        hessian.data[(position * size + i) * size + j] = value;
+                        if first != second {
+                            hessian.data[(position * size + j) * size + i] = value;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(hessian)
+}
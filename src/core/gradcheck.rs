@@ -0,0 +1,173 @@
+//! Checks the derivatives of kernels against central finite differences.
+//!
+//! The analytic derivatives are the [`jacobian`] of the kernel for the variable, the numeric ones
+//! re-run the original kernel on the [`CPUProcessor`] with one element of the variable moved by
+//! `±epsilon` at a time. Every pair of an output element and an element of the variable is compared.
+//!
+//! ```ignore
+//! let report = Gradcheck::new()
+//!     .tolerance(1e-3)
+//!     .check(&mut processor, polynomial(), Variable::new("x"), (x,), Tensor::new(0f32, vec![4]), (4, 1, 1))?;
+//! assert!(report.passed(), "{}", report);
+//! ```
+
+use std::fmt::Display;
+
+use crate::{processor::cpu::{CPUProcessor, CPUStorage}, types::tensor::Tensor, Error};
+
+use super::{allocated::ExecutableBindings, derivatives::{argument_position, jacobian, HostArguments, HostInputs}, operation::Differentiable, operations::var::Variable, processor::{Executable, Processor}, type_traits::Float, types::Void, Program};
+
+/// The derivative of output element `index` for `element` of the variable, where the analytic and
+/// numeric one differ by more than the tolerance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mismatch {
+    pub index: usize,
+    pub element: usize,
+    pub analytic: f64,
+    pub numeric: f64,
+}
+
+/// Derivatives of every output element for every element of the variable, returned by
+/// [`Gradcheck::check`]. `analytic` and `numeric` have a row per output element.
+#[derive(Clone, Debug)]
+pub struct GradcheckReport {
+    pub variable: String,
+    /// Elements of the variable, the length of a row.
+    pub elements: usize,
+    pub analytic: Vec<f64>,
+    pub numeric: Vec<f64>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl GradcheckReport {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl Display for GradcheckReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.passed() {
+            return write!(f, "derivative for `{}` matches finite differences in all {} entries", self.variable, self.analytic.len());
+        }
+
+        write!(f, "derivative for `{}` differs from finite differences in {} of {} entries", self.variable, self.mismatches.len(), self.analytic.len())?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n  [{}, {}] analytic {}, numeric {}", mismatch.index, mismatch.element, mismatch.analytic, mismatch.numeric)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares analytic derivatives of a kernel with central finite differences.
+///
+/// An entry passes if the derivatives differ by at most `tolerance`, relative to the numeric
+/// derivative once that is larger than one.
+#[derive(Clone, Debug)]
+pub struct Gradcheck {
+    epsilon: f64,
+    tolerance: f64,
+}
+
+impl Default for Gradcheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gradcheck {
+    /// Defaults suit `f32` kernels, an `epsilon` of `1e-3` and a `tolerance` of `1e-2`.
+    pub fn new() -> Self {
+        Gradcheck { epsilon: 1e-3, tolerance: 1e-2 }
+    }
+
+    /// Step of the finite differences.
+    pub fn epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Checks the derivative of every element of `output` for every element of the tensor argument
+    /// `variable`, running `program` with `inputs` on the dispatch `grid`. The position `p` of the grid
+    /// writes output element `p`, like for `out[pos.x]` on a grid of `(n, 1, 1)`.
+    pub fn check<B, I, R>(&self, processor: &mut CPUProcessor, program: B, variable: Variable<Tensor<R>>, inputs: I, output: Tensor<R>, grid: (u32, u32, u32)) -> Result<GradcheckReport, Error>
+    where
+        B: Program,
+        B::MainTree: Differentiable<Void>,
+        <B::MainTree as Differentiable<Void>>::Diff: Send + Sync,
+        B::CPUBinding: ExecutableBindings<CPUStorage, I = I::Bindings, O = Tensor<R>>,
+        I::Bindings: HostArguments,
+        I: HostInputs,
+        R: Float,
+    {
+        let position = argument_position::<B, I, R>(&variable, &inputs)?;
+        let outputs = output.data.len();
+        if (grid.0 * grid.1 * grid.2) as usize != outputs {
+            return Err(Error::InvalidGrid { constraint: "grid.x * grid.y * grid.z == out elements".to_string(), grid });
+        }
+
+        let analytic: Vec<f64> = jacobian(&program, std::slice::from_ref(&variable), inputs.clone(), grid)?
+            .data
+            .iter()
+            .map(|value| value.to_float())
+            .collect();
+        let elements = analytic.len() / outputs.max(1);
+
+        let mut executable = processor.try_build(program)?;
+        let mut run = |processor: &mut CPUProcessor, element: usize, step: f64| -> Result<Vec<f64>, Error> {
+            let mut moved = inputs.clone();
+            let tensor = moved.get_mut(position)
+                .and_then(|value| value.downcast_mut::<Tensor<R>>())
+                .expect("checked above");
+            tensor.data[element] = tensor.data[element] + R::from_float(step);
+
+            let written = processor.try_alloc(output.clone())?;
+            executable.get_bindings().set_arguments(moved.alloc(processor)?, written.clone());
+            let result = processor.dispatch(&mut executable, grid.0, grid.1, grid.2);
+            *executable.get_bindings() = B::CPUBinding::new();
+            result?;
+
+            Ok(processor.try_copy_to_cpu(&written)?.data.iter().map(|value| value.to_float()).collect())
+        };
+
+        let mut numeric = vec![0.0; outputs * elements];
+        for element in 0..elements {
+            let forward = run(processor, element, self.epsilon)?;
+            let backward = run(processor, element, -self.epsilon)?;
+            for (index, (forward, backward)) in forward.iter().zip(&backward).enumerate() {
+                numeric[index * elements + element] = (forward - backward) / (2.0 * self.epsilon);
+            }
+        }
+
+        let mismatches = analytic.iter()
+            .zip(&numeric)
+            .enumerate()
+            .filter(|(_, (analytic, numeric))| {
+                let difference = (*analytic - *numeric).abs();
+                difference.is_nan() || difference > self.tolerance * numeric.abs().max(1.0)
+            })
+            .map(|(entry, (analytic, numeric))| Mismatch { index: entry / elements, element: entry % elements, analytic: *analytic, numeric: *numeric })
+            .collect();
+
+        Ok(GradcheckReport { variable: variable.reference, elements, analytic, numeric, mismatches })
+    }
+}
+
+/// [`Gradcheck::check`] with the default epsilon and tolerance.
+pub fn gradcheck<B, I, R>(processor: &mut CPUProcessor, program: B, variable: Variable<Tensor<R>>, inputs: I, output: Tensor<R>, grid: (u32, u32, u32)) -> Result<GradcheckReport, Error>
+where
+    B: Program,
+    B::MainTree: Differentiable<Void>,
+    <B::MainTree as Differentiable<Void>>::Diff: Send + Sync,
+    B::CPUBinding: ExecutableBindings<CPUStorage, I = I::Bindings, O = Tensor<R>>,
+    I::Bindings: HostArguments,
+    I: HostInputs,
+    R: Float,
+{
+    Gradcheck::new().check(processor, program, variable, inputs, output, grid)
+}
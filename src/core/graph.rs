@@ -0,0 +1,391 @@
+//! Chains built kernels into a graph that manages the buffers between them.
+//!
+//! Every kernel is a node, the values passed between them are [`Edge`]s. Intermediate values are
+//! allocated right before the first kernel writing them runs and freed after the last kernel reading
+//! them ran, only the values marked with [`Graph::output`] are copied back. The kernels of a wave
+//! don't depend on each other, on the CPU they run as concurrent rayon tasks.
+//!
+//! ```ignore
+//! let mut graph = Graph::new();
+//! let a = graph.input::<Tensor<f32>>();
+//! let scaled = graph.add(&mut processor, scale(), (a,), Tensor::new(0f32, vec![4]));
+//! let out = graph.add(&mut processor, relu(), (scaled,), Tensor::new(0f32, vec![4]));
+//! graph.output(out);
+//!
+//! let mut inputs = GraphValues::new();
+//! inputs.insert(a, tensor);
+//! let result = graph.run(&mut processor, inputs)?.take(out);
+//! ```
+
+use std::{any::Any, collections::HashMap, marker::PhantomData};
+
+use crate::{processor::cpu::CPUStorage, Error};
+
+use super::{allocated::{Binding, ExecutableBindings}, any_map::AnyMap, processor::{Executable, Job, Processor, Storage}, type_traits::MemoryMapable, Buildable};
+
+/// A value of type `T` flowing through a [`Graph`].
+pub struct Edge<T> {
+    id: usize,
+    _0: PhantomData<fn() -> T>,
+}
+
+impl<T> Edge<T> {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl<T> Clone for Edge<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Edge<T> {}
+
+/// Host values of graph edges, used for the inputs and outputs of [`Graph::run`].
+pub struct GraphValues(AnyMap<usize>);
+
+impl GraphValues {
+    pub fn new() -> Self {
+        GraphValues(AnyMap::new())
+    }
+
+    pub fn insert<T: 'static>(&mut self, edge: Edge<T>, value: T) -> Option<T> {
+        self.0.insert(edge.id, value)
+    }
+
+    pub fn get<T: 'static>(&self, edge: Edge<T>) -> Option<&T> {
+        self.0.get(edge.id)
+    }
+
+    pub fn take<T: 'static>(&mut self, edge: Edge<T>) -> Option<T> {
+        self.0.remove(edge.id)
+    }
+}
+
+impl Default for GraphValues {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Tuples of edges a kernel reads, resolved to the bindings of its arguments.
+pub trait GraphInputs<S: Storage> {
+    type Bindings;
+
+    fn ids(&self) -> Vec<usize>;
+    fn bindings(&self, slots: &Slots) -> Result<Self::Bindings, Error>;
+}
+
+/// Allocated values of the running graph, type erased so that dropping a slot frees its memory.
+pub struct Slots(HashMap<usize, Box<dyn Any>>);
+
+impl Slots {
+    fn binding<S: Storage + 'static, T: MemoryMapable<S>>(&self, id: usize) -> Result<Binding<S, T>, Error> {
+        self.0.get(&id)
+            .and_then(|slot| slot.downcast_ref::<Binding<S, T>>())
+            .cloned()
+            .ok_or(Error::MissingGraphValue(id))
+    }
+}
+
+impl<S: Storage + 'static, T: MemoryMapable<S>> GraphInputs<S> for (Edge<T>,) {
+    type Bindings = (Binding<S, T>,);
+
+    fn ids(&self) -> Vec<usize> {
+        vec![self.0.id]
+    }
+
+    fn bindings(&self, slots: &Slots) -> Result<Self::Bindings, Error> {
+        Ok((slots.binding(self.0.id)?,))
+    }
+}
+impl<S: Storage + 'static, T1: MemoryMapable<S>, T2: MemoryMapable<S>> GraphInputs<S> for (Edge<T1>, Edge<T2>) {
+    type Bindings = (Binding<S, T1>, Binding<S, T2>);
+
+    fn ids(&self) -> Vec<usize> {
+        vec![self.0.id, self.1.id]
+    }
+
+    fn bindings(&self, slots: &Slots) -> Result<Self::Bindings, Error> {
+        Ok((slots.binding(self.0.id)?, slots.binding(self.1.id)?))
+    }
+}
+impl<S: Storage + 'static, T1: MemoryMapable<S>, T2: MemoryMapable<S>, T3: MemoryMapable<S>> GraphInputs<S> for (Edge<T1>, Edge<T2>, Edge<T3>) {
+    type Bindings = (Binding<S, T1>, Binding<S, T2>, Binding<S, T3>);
+
+    fn ids(&self) -> Vec<usize> {
+        vec![self.0.id, self.1.id, self.2.id]
+    }
+
+    fn bindings(&self, slots: &Slots) -> Result<Self::Bindings, Error> {
+        Ok((slots.binding(self.0.id)?, slots.binding(self.1.id)?, slots.binding(self.2.id)?))
+    }
+}
+
+type Allocate<P> = Box<dyn Fn(&mut P, &mut GraphValues) -> Result<Box<dyn Any>, Error>>;
+type Collect<P> = Box<dyn Fn(&mut P, Box<dyn Any>, &mut GraphValues) -> Result<(), Error>>;
+
+/// Running a node, split so that the nodes of a wave are dispatched together.
+trait Step<P> {
+    /// Allocates the written value and binds the arguments.
+    fn bind(&mut self, processor: &mut P, slots: &Slots) -> Result<(), Error>;
+    /// Hands the dispatch to `then`, see [`Processor::dispatch_scoped`].
+    fn dispatch(&self, processor: &P, then: &mut dyn FnMut(Job<'_>) -> Result<(), Error>) -> Result<(), Error>;
+    /// Unbinds the arguments and stores the written value.
+    fn finish(&mut self, slots: &mut Slots);
+}
+
+struct KernelStep<P: Processor, B: Buildable<P>, I, O: MemoryMapable<P::Storage>> {
+    executable: P::Executable<B>,
+    inputs: I,
+    output: O,
+    grid: (u32, u32, u32),
+    edge: Edge<O>,
+    written: Option<Binding<P::Storage, O>>,
+}
+
+impl<P, B, I, O> Step<P> for KernelStep<P, B, I, O>
+where
+    P: Processor + 'static,
+    P::Storage: 'static,
+    B: Buildable<P> + 'static,
+    B::Binding: ExecutableBindings<P::Storage, I = I::Bindings, O = O>,
+    <B::CPUBinding as ExecutableBindings<CPUStorage>>::O: 'static,
+    P::Executable<B>: Executable<P::Storage, B::Binding> + 'static,
+    I: GraphInputs<P::Storage> + 'static,
+    O: MemoryMapable<P::Storage>,
+{
+    fn bind(&mut self, processor: &mut P, slots: &Slots) -> Result<(), Error> {
+        let written = processor.try_alloc(self.output.clone())?;
+        let bindings = self.inputs.bindings(slots)?;
+
+        self.executable.get_bindings().set_arguments(bindings, written.clone());
+        self.written = Some(written);
+        Ok(())
+    }
+
+    fn dispatch(&self, processor: &P, then: &mut dyn FnMut(Job<'_>) -> Result<(), Error>) -> Result<(), Error> {
+        let (x, y, z) = self.grid;
+        processor.dispatch_scoped(&self.executable, x, y, z, then)
+    }
+
+    fn finish(&mut self, slots: &mut Slots) {
+        // The executable must not keep the values alive once the graph frees them.
+        *self.executable.get_bindings() = B::Binding::new();
+
+        if let Some(written) = self.written.take() {
+            slots.0.insert(self.edge.id, Box::new(written));
+        }
+    }
+}
+
+/// Prepares the dispatches of `steps` one after the other, each one borrowing its values for the
+/// rest, and runs them as rayon tasks once all of them are prepared.
+fn dispatch_wave<P>(steps: &[&dyn Step<P>], processor: &P, mut jobs: Vec<Job<'_>>) -> Result<(), Error> {
+    let Some((step, rest)) = steps.split_first() else {
+        rayon::scope(|scope| {
+            for job in jobs {
+                scope.spawn(move |_| job());
+            }
+        });
+        return Ok(());
+    };
+
+    step.dispatch(processor, &mut |job| {
+        let mut jobs: Vec<Job<'_>> = std::mem::take(&mut jobs);
+        jobs.push(job);
+        dispatch_wave(rest, processor, jobs)
+    })
+}
+
+struct Node<P> {
+    inputs: Vec<usize>,
+    /// Nodes of the same wave don't depend on each other.
+    wave: usize,
+    step: Box<dyn Step<P>>,
+}
+
+/// Kernels built for the processor `P`, connected by the values they read and write.
+pub struct Graph<P: Processor> {
+    edges: usize,
+    inputs: Vec<(usize, Allocate<P>)>,
+    outputs: Vec<(usize, Collect<P>)>,
+    nodes: Vec<Node<P>>,
+    /// Wave of the node writing each edge, graph inputs are available before the first wave.
+    produced_in: HashMap<usize, usize>,
+}
+
+impl<P: Processor + 'static> Graph<P> where P::Storage: 'static {
+    pub fn new() -> Self {
+        Graph { edges: 0, inputs: Vec::new(), outputs: Vec::new(), nodes: Vec::new(), produced_in: HashMap::new() }
+    }
+
+    fn edge<T>(&mut self) -> Edge<T> {
+        self.edges += 1;
+        Edge { id: self.edges - 1, _0: PhantomData }
+    }
+
+    /// A value passed to [`Graph::run`].
+    pub fn input<T: MemoryMapable<P::Storage>>(&mut self) -> Edge<T> {
+        let edge = self.edge::<T>();
+        let allocate: Allocate<P> = Box::new(move |processor, values| {
+            let value = values.take(edge).ok_or(Error::MissingGraphValue(edge.id))?;
+            Ok(Box::new(processor.try_alloc(value)?))
+        });
+
+        self.inputs.push((edge.id, allocate));
+        edge
+    }
+
+    /// Copies the value back to the host when the graph ran, it isn't freed before.
+    pub fn output<T: MemoryMapable<P::Storage>>(&mut self, edge: Edge<T>) {
+        let collect: Collect<P> = Box::new(move |processor, slot, values| {
+            let binding = slot.downcast::<Binding<P::Storage, T>>().map_err(|_| Error::MissingGraphValue(edge.id))?;
+            values.insert(edge, processor.try_dealloc(*binding)?);
+            Ok(())
+        });
+
+        self.outputs.push((edge.id, collect));
+    }
+
+    /// Builds the kernel and adds it reading `inputs`. The written value starts as a copy of `output`,
+    /// whose extents are the dispatch grid.
+    pub fn try_add<B, I, O>(&mut self, processor: &mut P, kernel: B, inputs: I, output: O) -> Result<Edge<O>, Error>
+    where
+        B: Buildable<P> + 'static,
+        B::Binding: ExecutableBindings<P::Storage, I = I::Bindings, O = O>,
+        <B::CPUBinding as ExecutableBindings<CPUStorage>>::O: 'static,
+        P::Executable<B>: Executable<P::Storage, B::Binding> + 'static,
+        I: GraphInputs<P::Storage> + 'static,
+        O: MemoryMapable<P::Storage>,
+    {
+        let grid = match output.get_parallelization_info().extents().as_slice() {
+            [] => (1, 1, 1),
+            [x] => (*x as u32, 1, 1),
+            [x, y, ..] => (*x as u32, *y as u32, 1),
+        };
+
+        self.try_add_with_grid(processor, kernel, inputs, output, grid)
+    }
+
+    /// Like [`Graph::try_add`] with an explicit dispatch grid.
+    pub fn try_add_with_grid<B, I, O>(&mut self, processor: &mut P, kernel: B, inputs: I, output: O, (x, y, z): (u32, u32, u32)) -> Result<Edge<O>, Error>
+    where
+        B: Buildable<P> + 'static,
+        B::Binding: ExecutableBindings<P::Storage, I = I::Bindings, O = O>,
+        <B::CPUBinding as ExecutableBindings<CPUStorage>>::O: 'static,
+        P::Executable<B>: Executable<P::Storage, B::Binding> + 'static,
+        I: GraphInputs<P::Storage> + 'static,
+        O: MemoryMapable<P::Storage>,
+    {
+        let executable = processor.try_build(kernel)?;
+        let edge = self.edge::<O>();
+        let ids = inputs.ids();
+
+        let wave = ids.iter()
+            .filter_map(|id| self.produced_in.get(id))
+            .map(|wave| wave + 1)
+            .max()
+            .unwrap_or(0);
+        self.produced_in.insert(edge.id, wave);
+
+        let step = KernelStep { executable, inputs, output, grid: (x, y, z), edge, written: None };
+
+        self.nodes.push(Node { inputs: ids, wave, step: Box::new(step) });
+        Ok(edge)
+    }
+
+    /// Panicking version of [`Graph::try_add`].
+    pub fn add<B, I, O>(&mut self, processor: &mut P, kernel: B, inputs: I, output: O) -> Edge<O>
+    where
+        B: Buildable<P> + 'static,
+        B::Binding: ExecutableBindings<P::Storage, I = I::Bindings, O = O>,
+        <B::CPUBinding as ExecutableBindings<CPUStorage>>::O: 'static,
+        P::Executable<B>: Executable<P::Storage, B::Binding> + 'static,
+        I: GraphInputs<P::Storage> + 'static,
+        O: MemoryMapable<P::Storage>,
+    {
+        self.try_add(processor, kernel, inputs, output).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Panicking version of [`Graph::try_add_with_grid`].
+    pub fn add_with_grid<B, I, O>(&mut self, processor: &mut P, kernel: B, inputs: I, output: O, grid: (u32, u32, u32)) -> Edge<O>
+    where
+        B: Buildable<P> + 'static,
+        B::Binding: ExecutableBindings<P::Storage, I = I::Bindings, O = O>,
+        <B::CPUBinding as ExecutableBindings<CPUStorage>>::O: 'static,
+        P::Executable<B>: Executable<P::Storage, B::Binding> + 'static,
+        I: GraphInputs<P::Storage> + 'static,
+        O: MemoryMapable<P::Storage>,
+    {
+        self.try_add_with_grid(processor, kernel, inputs, output, grid).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Node indices grouped into waves, the nodes of a wave only read values of earlier waves.
+    pub fn waves(&self) -> Vec<Vec<usize>> {
+        let count = self.nodes.iter().map(|n| n.wave + 1).max().unwrap_or(0);
+        let mut waves = vec![Vec::new(); count];
+        for (i, node) in self.nodes.iter().enumerate() {
+            waves[node.wave].push(i);
+        }
+        waves
+    }
+
+    /// Runs every kernel once and returns the values marked with [`Graph::output`].
+    ///
+    /// A value is freed after the last wave reading it, values nobody reads are freed right away.
+    pub fn run(&mut self, processor: &mut P, mut inputs: GraphValues) -> Result<GraphValues, Error> {
+        let outputs: Vec<usize> = self.outputs.iter().map(|(id, _)| *id).collect();
+
+        let mut last_read = HashMap::new();
+        for node in &self.nodes {
+            for id in &node.inputs {
+                let last = last_read.entry(*id).or_insert(node.wave);
+                *last = node.wave.max(*last);
+            }
+        }
+
+        let mut slots = Slots(HashMap::new());
+        for (id, allocate) in &self.inputs {
+            slots.0.insert(*id, allocate(processor, &mut inputs)?);
+        }
+
+        for (wave, nodes) in self.waves().into_iter().enumerate() {
+            let mut result = Ok(());
+            for i in &nodes {
+                result = self.nodes[*i].step.bind(processor, &slots);
+                if result.is_err() {
+                    break;
+                }
+            }
+
+            if result.is_ok() {
+                let steps: Vec<&dyn Step<P>> = nodes.iter().map(|i| self.nodes[*i].step.as_ref()).collect();
+                result = dispatch_wave(&steps, processor, Vec::new());
+            }
+
+            for i in &nodes {
+                self.nodes[*i].step.finish(&mut slots);
+            }
+            result?;
+
+            slots.0.retain(|id, _| outputs.contains(id) || last_read.get(id).map_or(false, |last| *last > wave));
+        }
+
+        let mut values = GraphValues::new();
+        for (id, collect) in &self.outputs {
+            let slot = slots.0.remove(id).ok_or(Error::MissingGraphValue(*id))?;
+            collect(processor, slot, &mut values)?;
+        }
+
+        Ok(values)
+    }
+}
+
+impl<P: Processor + 'static> Default for Graph<P> where P::Storage: 'static {
+    fn default() -> Self {
+        Self::new()
+    }
+}
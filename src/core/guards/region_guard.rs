@@ -2,6 +2,8 @@ use std::{sync::Arc, cell::{UnsafeCell, RefCell}, ops::Range, slice::from_raw_pa
 
 use parking_lot::{RawRwLock as CustomizableLock, lock_api::{RawRwLock, RawRwLockUpgrade}};
 
+use crate::Error;
+
 
 
 pub struct RegionGuard<'a, T> {
@@ -68,22 +70,23 @@ impl<'a, T> RegionGuard<'a, T> {
         }
     }
 
-    pub fn index_range(&mut self, index: Range<usize>) -> &[T] {
-        let (key, region) = self.locks.borrow_mut().read(index.start);
-        let (_, end_region) = self.locks.borrow_mut().read(index.end-1);
+    /// Range inside a single region, ranges spanning multiple regions return [`Error::CrossRegion`].
+    pub fn index_range(&mut self, index: Range<usize>) -> Result<&[T], Error> {
+        let (_, region) = self.locks.borrow_mut().get_region(index.start);
+        let (_, end_region) = self.locks.borrow_mut().get_region(index.end-1);
 
         if region != end_region {
-            panic!("Regions need to be the same: {}..{}:  {},{}", index.start, index.end, region, end_region);
+            return Err(Error::CrossRegion { start: index.start, end: index.end });
         }
 
-        self.locks.borrow_mut().write(region);
+        let (region, key) = self.locks.borrow_mut().read(index.start);
         unsafe {
-            &(*self.data[region].get())[(index.start - key)..(index.end - key)]
+            Ok(&(&*self.data[region].get())[(index.start - key)..(index.end - key)])
         }
     }
 
     pub fn set(&mut self, index: usize, val: T) {
-        let (key, region) = self.locks.borrow_mut().write(index);
+        let (region, key) = self.locks.borrow_mut().write(index);
 
         unsafe {
             (*self.data[region].get())[index - key] = val;
@@ -91,7 +94,7 @@ impl<'a, T> RegionGuard<'a, T> {
     }
 
     pub fn set_range(&mut self, index: usize, val: Vec<T>) {
-        let (key, region) = self.locks.borrow_mut().write(index);
+        let (region, key) = self.locks.borrow_mut().write(index);
 
         unsafe {
             val.into_iter()
@@ -100,6 +103,17 @@ impl<'a, T> RegionGuard<'a, T> {
         }
     }
 
+    /// Pointer to an entry for atomic access.
+    ///
+    /// The region is locked shared, so atomics from other threads run concurrently while plain
+    /// writes through [`RegionGuard::index_mut`] still wait for them.
+    pub fn as_ptr(&self, index: usize) -> *mut T {
+        let (region, key) = self.locks.borrow_mut().shared(index);
+        unsafe {
+            (&mut *self.data[region].get()).as_mut_ptr().add(index - key)
+        }
+    }
+
     pub fn free(&mut self) {
         self.locks.borrow_mut().free();
     }
@@ -107,7 +121,7 @@ impl<'a, T> RegionGuard<'a, T> {
 
 #[derive(Clone)]
 pub struct RegionLock {
-    pub lock: Option<(usize, bool)>,
+    pub lock: Option<(usize, LockState)>,
     pub sync: Arc<Vec<CustomizableLock>>,
     pub regions: RegionArray,
     pub current_bounds: Option<(usize, usize, usize)>
@@ -115,37 +129,34 @@ pub struct RegionLock {
 
 impl Drop for RegionLock {
     fn drop(&mut self) {
-        if let Some((l, writable)) = self.lock {
-            unsafe {
-                if writable {
-                    self.sync[l].unlock_exclusive();
-                } else {
-                    self.sync[l].unlock_upgradable();
-                }
-            }
-            
-        }
+        self.free();
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum LockState {
+    /// Upgradable, excludes writers and other readers.
     Read,
-    Write
+    Write,
+    /// Shared with other atomic accesses, excludes writers.
+    Shared,
 }
 
 impl RegionLock {
     fn free(&mut self) {
-        if let Some((l, writable)) = self.lock {
-            unsafe {
-                if writable {
-                    self.sync[l].unlock_exclusive();
-                } else {
-                    self.sync[l].unlock_upgradable();
-                }
+        if let Some((l, state)) = self.lock.take() {
+            self.unlock(l, state);
+        }
+    }
+
+    fn unlock(&self, region: usize, state: LockState) {
+        unsafe {
+            match state {
+                LockState::Read => self.sync[region].unlock_upgradable(),
+                LockState::Write => self.sync[region].unlock_exclusive(),
+                LockState::Shared => self.sync[region].unlock_shared(),
             }
-            
         }
-        self.lock = None;
     }
 
     fn get_region(&mut self, index: usize) -> (usize, usize) {
@@ -163,22 +174,33 @@ impl RegionLock {
     fn read(&mut self, index: usize) -> (usize, usize) {
         let (lower, idx) = self.get_region(index);
 
-        if let Some((l, writable)) = self.lock {
-            if l == idx {
+        if let Some((l, state)) = self.lock {
+            if l == idx && state != LockState::Shared {
                 return (idx, lower);
             }
 
-            unsafe {
-                if writable {
-                    self.sync[l].unlock_exclusive();
-                } else {
-                    self.sync[l].unlock_upgradable();
-                }
-            }
-            
+            self.unlock(l, state);
         }
         self.sync[idx].lock_upgradable();
-        self.lock = Some((idx, false));
+        self.lock = Some((idx, LockState::Read));
+        self.current_bounds = Some((idx, lower, self.regions.upper_bound(idx)));
+
+        (idx, lower)
+    }
+
+    /// Any lock already held on the region is enough, it excludes writers.
+    fn shared(&mut self, index: usize) -> (usize, usize) {
+        let (lower, idx) = self.get_region(index);
+
+        if let Some((l, state)) = self.lock {
+            if l == idx {
+                return (idx, lower);
+            }
+
+            self.unlock(l, state);
+        }
+        self.sync[idx].lock_shared();
+        self.lock = Some((idx, LockState::Shared));
         self.current_bounds = Some((idx, lower, self.regions.upper_bound(idx)));
 
         (idx, lower)
@@ -187,28 +209,21 @@ impl RegionLock {
     fn write(&mut self, index: usize) -> (usize, usize) {
         let (lower, idx) = self.get_region(index);
 
-        if let Some((l, writable)) = self.lock {
-            if l == idx && writable {
+        if let Some((l, state)) = self.lock {
+            if l == idx && state == LockState::Write {
                 return (idx, lower);
-            } else if l == idx {
+            } else if l == idx && state == LockState::Read {
                 unsafe {
                     self.sync[l].upgrade();
-                    self.lock = Some((idx, true));
+                    self.lock = Some((idx, LockState::Write));
                     return (idx, lower)
                 }
             }
 
-            unsafe {
-                if writable {
-                    self.sync[l].unlock_exclusive();
-                } else {
-                    self.sync[l].unlock_upgradable();
-                }
-            }
-            
+            self.unlock(l, state);
         }
         self.sync[idx].lock_exclusive();
-        self.lock = Some((idx, true));
+        self.lock = Some((idx, LockState::Write));
         self.current_bounds = Some((idx, lower, self.regions.upper_bound(idx)));
 
         (idx, lower)
@@ -1,7 +1,7 @@
 use std::{collections::HashMap};
 
-use self::{allocated::{ExecutableBindings}, operation::{Operation, Compilable, Differentiable}, types::{Void}, processor::{cpu::{CPUFunction}, ProcessorInformation}, operations::var::Variable};
-use crate::{processor::cpu::{CPUStorage, CPUProcessor}, types::differential::Differential};
+use self::{allocated::{ExecutableBindings}, operation::{Operation, Compilable, Differentiable}, types::{Void}, processor::{cpu::{CPUFunction}, ProcessorInformation}, operations::var::{Variable, ELEMENT_TRACE, ORDER_TRACE, UNSUPPORTED_TRACE}};
+use crate::{processor::cpu::{CPUStorage, CPUProcessor}, types::differential::Differential, Error};
 
 pub mod operation;
 pub mod operations;
@@ -9,8 +9,13 @@ pub mod processor;
 pub mod types;
 pub mod type_traits;
 pub mod any_map;
+pub mod debug;
+pub mod random;
 pub mod allocated;
 pub mod guards;
+pub mod graph;
+pub mod gradcheck;
+pub mod derivatives;
 
 pub trait Buildable<P: ProcessorInformation> {
     type Binding: ExecutableBindings<P::Storage>;
@@ -20,26 +25,69 @@ pub trait Buildable<P: ProcessorInformation> {
 
     fn get_cpu(&self) -> Self::CPUFunction;
     fn get_main_tree(&self) -> Self::Main;
+
+    /// Messages of the `debug_print!` and `assert!` calls, indexed by the message id written into GPU debug records.
+    fn get_debug_messages(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+    /// Invocations per workgroup, set with `#![workgroup_size(x, y, z)]` in the kernel attribute.
+    fn get_workgroup_size(&self) -> (u32, u32, u32) {
+        (64, 1, 1)
+    }
+    /// Checked by [`Processor::try_build`](processor::Processor::try_build) before anything is built.
+    fn validate(&self) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 pub trait DifferentiableProgram<P: Program> where <P as Program>::MainTree: Differentiable<Void> {
     fn differantiate_for<R: Clone>(&self, var: Variable<R>) -> Differential<P>;
+    /// Derivative for the single element of the tensor argument `var` at the index held by the
+    /// `u32` variable `element`.
+    fn differantiate_for_element<R: Clone>(&self, var: Variable<R>, element: &str) -> Differential<P>;
 }
 
 impl<P: Program> DifferentiableProgram<P> for P where <P as Program>::MainTree: Differentiable<Void> {
     fn differantiate_for<R: Clone>(&self, var: Variable<R>) -> Differential<P> {
         let tree: <P as Program>::MainTree = <P as Program>::get_main_tree(&self);
-        let mut trace = HashMap::new();
-        
+        let mut trace = HashMap::from([(ORDER_TRACE.to_string(), vec![P::ORDER.to_string()])]);
+
+        let main = tree.auto_diff_for(var, &mut trace);
         Differential {
-            main: tree.auto_diff_for(var, &mut trace),
+            main,
             cpu_fn: <P as Buildable<CPUProcessor>>::get_cpu(&self),
+            unsupported: unsupported(self, &trace),
         } 
     }
+
+    fn differantiate_for_element<R: Clone>(&self, var: Variable<R>, element: &str) -> Differential<P> {
+        let mut trace = HashMap::from([
+            (ORDER_TRACE.to_string(), vec![P::ORDER.to_string()]),
+            (ELEMENT_TRACE.to_string(), vec![element.to_string()]),
+        ]);
+
+        let main = <P as Program>::get_main_tree(self).auto_diff_for(var, &mut trace);
+        Differential {
+            main,
+            cpu_fn: <P as Buildable<CPUProcessor>>::get_cpu(self),
+            unsupported: unsupported(self, &trace),
+        }
+    }
+}
+
+/// Why the derivative of `program` can't be built, from the program itself or the differentiation
+/// that left `trace`.
+pub(crate) fn unsupported<P: Program>(program: &P, trace: &HashMap<String, Vec<String>>) -> Option<String> {
+    match Buildable::<CPUProcessor>::validate(program) {
+        Err(Error::NotDifferentiable(message)) => Some(message),
+        _ => trace.get(UNSUPPORTED_TRACE).and_then(|messages| messages.first()).cloned(),
+    }
 }
 
 pub trait Program: Buildable<CPUProcessor> {
     type MainTree: Operation<Void>;
+    /// How often the program was differentiated.
+    const ORDER: usize = 0;
 
     fn get_main_tree(&self) -> Self::MainTree;
 }
\ No newline at end of file
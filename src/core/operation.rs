@@ -1,7 +1,7 @@
 use std::{marker::PhantomData, collections::HashMap};
 use std::fmt::Debug;
 use super::processor::cpu::DifferentiatedCPUContext;
-use super::{types::{Value, Computable, Void}, type_traits::{IndexAble, GetAndSetable}, operations::var::Variable};
+use super::{types::{Value, Computable, Void}, type_traits::{IndexAble, ReferenceAble, GetAndSetable}, operations::var::Variable};
 
 pub trait Operation<R: Value>: Clone + Debug {
     fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R;
@@ -49,22 +49,43 @@ impl<R: Value, O: Differentiable<R>> Differentiable<R> for OperationWrapper<R, O
 impl<R: Value, I: Operation<R>> IndexAble for OperationWrapper<R, I> where I: IndexAble {
     type IndexResult = I::IndexResult;
 
-    fn index<O: Operation<u32>>(&self, _index: O) -> Self::IndexResult {
-        todo!()
+    fn get_field() -> Option<String> {
+        I::get_field()
+    }
+
+    fn get_element<C: Computable>(context: &DifferentiatedCPUContext, reference: &str, index: usize) -> C {
+        I::get_element(context, reference, index)
+    }
+
+    fn set_element<C: Computable>(context: &mut DifferentiatedCPUContext, reference: &str, index: usize, value: C) {
+        I::set_element(context, reference, index, value)
     }
+}
 
+impl<R: Value, I: Operation<R>> ReferenceAble for OperationWrapper<R, I> where I: ReferenceAble {
     fn get_reference(&self) -> String {
         self.0.get_reference()
     }
 
-    fn get_field() -> Option<String> {
-        I::get_field()
+    fn with_variable(&self, reference: &str) -> Self {
+        OperationWrapper(self.0.with_variable(reference), PhantomData)
     }
-    
 }
 
 impl<R: Computable, G: Operation<R>> GetAndSetable<R> for OperationWrapper<R, G> where G: GetAndSetable<R> {
     fn get_variable(&self) -> Option<String> {
         self.0.get_variable()
     }
+
+    fn with_variable(&self, reference: &str) -> Self {
+        OperationWrapper(self.0.with_variable(reference), PhantomData)
+    }
+
+    fn set_value(&self, context: &mut DifferentiatedCPUContext, value: R) {
+        self.0.set_value(context, value)
+    }
+
+    fn is_tensor_element(&self) -> bool {
+        self.0.is_tensor_element()
+    }
 }
\ No newline at end of file
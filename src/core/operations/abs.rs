@@ -0,0 +1,45 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, type_traits::Signed,
+};
+
+use super::{multiply::{Multiply, multiply}, sign::{Sign, sign}};
+
+pub fn abs<R: Signed, O: Operation<R>>(
+    value: O,
+) -> OperationWrapper<R, Abs<R, O>> {
+    OperationWrapper(
+        Abs {
+            value,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// The absolute value like `value.abs()`.
+#[derive(Clone, Debug)]
+pub struct Abs<R: Signed, O: Operation<R>> {
+    pub value: O,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Signed, O: Operation<R>> Operation<R> for Abs<R, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        self.value.evaluate(context).abs()
+    }
+}
+
+impl<R: Signed, O: Differentiable<R>> Differentiable<R> for Abs<R, O> {
+    type Diff = OperationWrapper<R, Multiply<R, OperationWrapper<R, Sign<R, O>>, O::Diff>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        multiply(sign(self.value.clone()), self.value.auto_diff_for(var, var_trace))
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.value.contains_var(var)
+    }
+}
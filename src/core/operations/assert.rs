@@ -0,0 +1,53 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    types::Void, type_traits::DebugValues, processor::cpu::DifferentiatedCPUContext, debug::DebugKind,
+};
+
+use super::noop::Noop;
+
+pub fn assert<CONDITION: Operation<bool>, V: DebugValues>(
+    condition: CONDITION,
+    message: u32,
+    values: V,
+) -> OperationWrapper<Void, Assert<CONDITION, V>> {
+    OperationWrapper(
+        Assert {
+            condition,
+            message,
+            values,
+        },
+        PhantomData,
+    )
+}
+
+/// Appends an assertion record to the debug buffer of the processor if `condition` is false.
+#[derive(Clone, Debug)]
+pub struct Assert<CONDITION: Operation<bool>, V: DebugValues> {
+    pub condition: CONDITION,
+    pub message: u32,
+    pub values: V,
+}
+
+impl<CONDITION: Operation<bool>, V: DebugValues> Operation<Void> for Assert<CONDITION, V> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        if !self.condition.evaluate(context) {
+            let values = self.values.evaluate(context);
+            context.record_debug(DebugKind::Assertion, self.message, values);
+        }
+        Void
+    }
+}
+
+impl<CONDITION: Operation<bool>, V: DebugValues> Differentiable<Void> for Assert<CONDITION, V> {
+    type Diff = Noop;
+
+    fn auto_diff_for<R1: Clone>(&self, _var: super::var::Variable<R1>, _var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        Noop
+    }
+
+    fn contains_var<R1: Clone>(&self, _var: super::var::Variable<R1>) -> bool {
+        false
+    }
+}
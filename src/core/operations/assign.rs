@@ -2,7 +2,7 @@ use std::marker::PhantomData;
 
 use crate::core::{types::{Computable, Void}, operation::{Operation, OperationWrapper, Differentiable}, processor::cpu::DifferentiatedCPUContext};
 
-use super::var::Variable;
+use super::{instruction_list::InstructionList, var::{tangent, Variable}};
 
 pub fn assign<R: Computable, O: Operation<R>>(name: String, operation: O) -> (Variable<R>, OperationWrapper<Void, Assign<R,O>>) {
     let variable = Variable::<R>::new(&name);
@@ -22,25 +22,22 @@ pub struct Assign<R: Computable, O: Operation<R>> {
 }
 
 impl<R: Computable, O: Operation<R>> Operation<Void> for Assign<R, O> {
-    fn evaluate(&self, _context: &mut DifferentiatedCPUContext) -> Void {
-        todo!()
-       //let a = self.assign.evaluate(context);
-       //context.set::<R>(&self.variable.reference, a);
-       //Void
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        let value = self.assign.evaluate(context);
+        context.set::<R>(&self.variable.reference, value);
+        Void
     }
 }
 
 impl<R: Computable, O: Differentiable<R>> Differentiable<Void> for Assign<R, O> {
-    type Diff = OperationWrapper<Void, Assign<R, O::Diff>>;
+    type Diff = InstructionList<Void, Void, OperationWrapper<Void, Assign<R, O>>, OperationWrapper<Void, Assign<R, O::Diff>>>;
 
+    /// Declares a local for the derivative before the local itself, later statements still need its value.
     fn auto_diff_for<R1: Clone>(&self, var: Variable<R1>, var_trace: &mut std::collections::HashMap<String, Vec<String>>) -> Self::Diff {
-        if self.assign.contains_var(var.clone()) {
-            var_trace.insert(self.variable.reference.clone(), vec![var.reference.clone()]);
-        } else {
-            var_trace.insert(self.variable.reference.clone(), vec![]);
-        }
+        let derivative = assign(tangent(&self.variable.reference, &var.reference, var_trace), self.assign.auto_diff_for(var.clone(), var_trace)).1;
+        var_trace.insert(self.variable.reference.clone(), vec![var.reference.clone()]);
 
-        assign(self.variable.reference.clone(), self.assign.auto_diff_for(var, var_trace)).1
+        InstructionList::after(assign(self.variable.reference.clone(), self.assign.clone()).1, Some(derivative))
     }
 
     fn contains_var<R1: Clone>(&self, var: Variable<R1>) -> bool {
@@ -0,0 +1,48 @@
+use std::marker::PhantomData;
+
+use crate::core::{
+    operation::{Operation, OperationWrapper},
+    types::Void, type_traits::{ReferenceAble, AtomicComputable}, processor::cpu::DifferentiatedCPUContext,
+};
+
+use super::index::update_element;
+
+pub fn atomic_exchange<R: AtomicComputable, T: ReferenceAble, I: Operation<u32>, V: Operation<R>>(
+    tensor: &T,
+    index: I,
+    value: V,
+) -> OperationWrapper<R, AtomicExchange<R, T, I, V>> {
+    OperationWrapper(
+        AtomicExchange {
+            tensor: tensor.clone(),
+            index,
+            value,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// Replaces the element with `value` and returns the previous value.
+#[derive(Clone, Debug)]
+pub struct AtomicExchange<R: AtomicComputable, T: ReferenceAble, I: Operation<u32>, V: Operation<R>> {
+    pub tensor: T,
+    pub index: I,
+    pub value: V,
+    _0: PhantomData<R>,
+}
+
+impl<R: AtomicComputable, T: ReferenceAble, I: Operation<u32>, V: Operation<R>> Operation<R> for AtomicExchange<R, T, I, V> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        let index = self.index.evaluate(context);
+        let value = self.value.evaluate(context);
+        update_element(context, &self.tensor, index, |tensor, index| tensor.atomic_exchange(index, value))
+    }
+}
+
+impl<R: AtomicComputable, T: ReferenceAble, I: Operation<u32>, V: Operation<R>> Operation<Void> for AtomicExchange<R, T, I, V> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        Operation::<R>::evaluate(self, context);
+        Void
+    }
+}
@@ -0,0 +1,35 @@
+use std::marker::PhantomData;
+
+use crate::core::{
+    operation::{Operation, OperationWrapper},
+    processor::cpu::DifferentiatedCPUContext, random,
+};
+
+pub fn bernoulli<SEED: Operation<u32>, COUNTER: Operation<u32>, P: Operation<f32>>(
+    seed: SEED,
+    counter: COUNTER,
+    p: P,
+) -> OperationWrapper<bool, Bernoulli<SEED, COUNTER, P>> {
+    OperationWrapper(
+        Bernoulli {
+            seed,
+            counter,
+            p,
+        },
+        PhantomData,
+    )
+}
+
+/// `true` with probability `p`, see [`random::bernoulli`].
+#[derive(Clone, Debug)]
+pub struct Bernoulli<SEED: Operation<u32>, COUNTER: Operation<u32>, P: Operation<f32>> {
+    pub seed: SEED,
+    pub counter: COUNTER,
+    pub p: P,
+}
+
+impl<SEED: Operation<u32>, COUNTER: Operation<u32>, P: Operation<f32>> Operation<bool> for Bernoulli<SEED, COUNTER, P> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> bool {
+        random::bernoulli(self.seed.evaluate(context), self.counter.evaluate(context), self.p.evaluate(context))
+    }
+}
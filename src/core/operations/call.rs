@@ -27,27 +27,22 @@ pub struct Call<R: Value, INPUTS: FunctionInputs, CALLINPUTS: CallInputs + CallM
 }
 
 impl<R: Value, INPUTS: FunctionInputs, CALLINPUTS: CallInputs + CallMatchFunctionInputs<INPUTS>, A: Operation<R>> Operation<R> for Call<R, INPUTS, CALLINPUTS, A> {
-    fn evaluate(&self, _context: &mut DifferentiatedCPUContext) -> R {
-       //
-       todo!("Unfinished business");
-       //context.set_return_state(false);
-     }
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        // the function only sees its inputs, like in WGSL
+        let mut frame = DifferentiatedCPUContext::new();
+        self.inputs.bind(&self.function.inputs, context, &mut frame);
+        self.function.evaluate(&mut frame)
+    }
 }
 
 impl<R: Computable, INPUTS: FunctionInputs, CALLINPUTS: CallInputs + CallMatchFunctionInputs<INPUTS> + DiffableFunctionInputs<R, INPUTS>, A: Differentiable<R>> Differentiable<R> for Call<R, INPUTS, CALLINPUTS, A> {
-    type Diff = Either<CALLINPUTS::Diff<A>, Call<R, INPUTS, CALLINPUTS, A>>;
+    type Diff = Either<CALLINPUTS::Diff<A>, R>;
 
     fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut std::collections::HashMap<String, Vec<String>>) -> Self::Diff {
-        match self.inputs.map_variable(var.clone(), self.function.inputs.clone()) {
-            Some(mapped) => {
-                Either::A(self.inputs.auto_diff_for(self.clone(), mapped, var.clone(), var_trace))
-            }
-            None => {
-                Either::B(Call {
-                    inputs: self.inputs.clone(),
-                    function: self.function.clone(),
-                })
-            }
+        if self.inputs.contains_var(var.clone()) {
+            Either::A(self.inputs.auto_diff_for(self.clone(), var, var_trace))
+        } else {
+            Either::B(R::get_zero())
         }
     }
 
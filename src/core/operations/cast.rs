@@ -0,0 +1,49 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, type_traits::Castable, types::{Computable, Either},
+};
+
+pub fn cast<FROM: Castable<TO>, TO: Computable, O: Operation<FROM>>(
+    value: O,
+) -> OperationWrapper<TO, Cast<FROM, TO, O>> {
+    OperationWrapper(
+        Cast {
+            value,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// Converts `value` to `TO` like `value as TO`.
+#[derive(Clone, Debug)]
+pub struct Cast<FROM: Castable<TO>, TO: Computable, O: Operation<FROM>> {
+    pub value: O,
+    pub _0: PhantomData<(FROM, TO)>,
+}
+
+impl<FROM: Castable<TO>, TO: Computable, O: Operation<FROM>> Operation<TO> for Cast<FROM, TO, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> TO {
+        self.value.evaluate(context).cast()
+    }
+}
+
+impl<FROM: Castable<TO>, TO: Computable, O: Differentiable<FROM>> Differentiable<TO> for Cast<FROM, TO, O> {
+    type Diff = Either<Cast<FROM, TO, O::Diff>, TO>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        if FROM::PRESERVES_DERIVATIVE {
+            Either::A(Cast {
+                value: self.value.auto_diff_for(var, var_trace),
+                _0: PhantomData,
+            })
+        } else {
+            Either::B(TO::get_zero())
+        }
+    }
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.value.contains_var(var)
+    }
+}
@@ -0,0 +1,52 @@
+use std::marker::PhantomData;
+
+use crate::core::{
+    operation::{Operation, OperationWrapper},
+    types::Void, type_traits::{ReferenceAble, AtomicComputable}, processor::cpu::DifferentiatedCPUContext,
+};
+
+use super::index::update_element;
+
+pub fn compare_exchange<R: AtomicComputable, T: ReferenceAble, I: Operation<u32>, C: Operation<R>, N: Operation<R>>(
+    tensor: &T,
+    index: I,
+    current: C,
+    new: N,
+) -> OperationWrapper<R, CompareExchange<R, T, I, C, N>> {
+    OperationWrapper(
+        CompareExchange {
+            tensor: tensor.clone(),
+            index,
+            current,
+            new,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// Replaces the element with `new` if it equals `current`. Always returns the previous value.
+#[derive(Clone, Debug)]
+pub struct CompareExchange<R: AtomicComputable, T: ReferenceAble, I: Operation<u32>, C: Operation<R>, N: Operation<R>> {
+    pub tensor: T,
+    pub index: I,
+    pub current: C,
+    pub new: N,
+    _0: PhantomData<R>,
+}
+
+impl<R: AtomicComputable, T: ReferenceAble, I: Operation<u32>, C: Operation<R>, N: Operation<R>> Operation<R> for CompareExchange<R, T, I, C, N> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        let index = self.index.evaluate(context);
+        let current = self.current.evaluate(context);
+        let new = self.new.evaluate(context);
+        update_element(context, &self.tensor, index, |tensor, index| tensor.compare_exchange(index, current, new))
+    }
+}
+
+impl<R: AtomicComputable, T: ReferenceAble, I: Operation<u32>, C: Operation<R>, N: Operation<R>> Operation<Void> for CompareExchange<R, T, I, C, N> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        Operation::<R>::evaluate(self, context);
+        Void
+    }
+}
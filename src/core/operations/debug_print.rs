@@ -0,0 +1,48 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    types::Void, type_traits::DebugValues, processor::cpu::DifferentiatedCPUContext, debug::DebugKind,
+};
+
+use super::noop::Noop;
+
+pub fn debug_print<V: DebugValues>(
+    message: u32,
+    values: V,
+) -> OperationWrapper<Void, DebugPrint<V>> {
+    OperationWrapper(
+        DebugPrint {
+            message,
+            values,
+        },
+        PhantomData,
+    )
+}
+
+/// Appends a record with the message id and `values` to the debug buffer of the processor.
+#[derive(Clone, Debug)]
+pub struct DebugPrint<V: DebugValues> {
+    pub message: u32,
+    pub values: V,
+}
+
+impl<V: DebugValues> Operation<Void> for DebugPrint<V> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        let values = self.values.evaluate(context);
+        context.record_debug(DebugKind::Print, self.message, values);
+        Void
+    }
+}
+
+impl<V: DebugValues> Differentiable<Void> for DebugPrint<V> {
+    type Diff = Noop;
+
+    fn auto_diff_for<R1: Clone>(&self, _var: super::var::Variable<R1>, _var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        Noop
+    }
+
+    fn contains_var<R1: Clone>(&self, _var: super::var::Variable<R1>) -> bool {
+        false
+    }
+}
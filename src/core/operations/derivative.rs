@@ -0,0 +1,36 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, Differentiable},
+    types::{Computable, Value, Void}, type_traits::{FunctionInputs, Partials}, processor::cpu::DifferentiatedCPUContext,
+};
+
+use super::{instruction_list::InstructionList, noop::Noop, returns::{returns, Returns}, scope::Scope, var::Variable};
+
+/// The body of a function whose derivative is given by `partials` instead of differentiating `scope`.
+#[derive(Clone, Debug)]
+pub struct CustomDerivative<R: Value, INPUTS: FunctionInputs, A: Operation<R>, P> {
+    pub scope: A,
+    pub inputs: INPUTS,
+    pub partials: P,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Value, INPUTS: FunctionInputs, A: Operation<R>, P: Clone + std::fmt::Debug> Operation<R> for CustomDerivative<R, INPUTS, A, P> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        self.scope.evaluate(context)
+    }
+}
+
+impl<R: Computable, INPUTS: FunctionInputs, A: Operation<R>, P: Partials<R, INPUTS>> Differentiable<R> for CustomDerivative<R, INPUTS, A, P> {
+    type Diff = Scope<R, R, Void, Returns<R, P::Diff>, InstructionList<Void, Void, Noop, Noop>>;
+
+    /// `var` is one of the inputs, the derivative of the body is the partial for it.
+    fn auto_diff_for<R1: Clone>(&self, var: Variable<R1>, _var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        Scope::new::<R>().returns(returns(self.partials.partial(&self.inputs, self.inputs.position(&var.reference))))
+    }
+
+    fn contains_var<R1: Clone>(&self, var: Variable<R1>) -> bool {
+        P::DIFFERENTIABLE && self.inputs.position(&var.reference).is_some()
+    }
+}
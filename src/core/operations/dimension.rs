@@ -0,0 +1,44 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::{core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, types::Computable,
+}, types::tensor::Tensor};
+
+use super::var::Variable;
+
+pub fn dimension<C: Computable, O: Operation<u32>>(tensor: &Variable<Tensor<C>>, dimension: O) -> OperationWrapper<u32, Dimension<C, O>> {
+    OperationWrapper(
+        Dimension {
+            tensor: tensor.clone(),
+            dimension,
+        },
+        PhantomData,
+    )
+}
+
+/// The size of one dimension of a tensor argument like `t.shape(1)`.
+#[derive(Clone, Debug)]
+pub struct Dimension<C: Computable, O: Operation<u32>> {
+    pub tensor: Variable<Tensor<C>>,
+    pub dimension: O,
+}
+
+impl<C: Computable, O: Operation<u32>> Operation<u32> for Dimension<C, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> u32 {
+        let dimension = self.dimension.evaluate(context) as usize;
+        context.tensor::<C>(&self.tensor.reference).shape[dimension] as u32
+    }
+}
+
+impl<C: Computable, O: Operation<u32>> Differentiable<u32> for Dimension<C, O> {
+    type Diff = u32;
+
+    fn auto_diff_for<R1: Clone>(&self, _var: Variable<R1>, _var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        0
+    }
+
+    fn contains_var<R1: Clone>(&self, _var: Variable<R1>) -> bool {
+        false
+    }
+}
@@ -0,0 +1,48 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, type_traits::{Calculatable, Dot as _},
+};
+
+use super::add::{Add, add};
+
+pub fn dot<T: Calculatable, const N: usize, LEFT: Operation<[T; N]>, RIGHT: Operation<[T; N]>>(
+    left: LEFT,
+    right: RIGHT,
+) -> OperationWrapper<T, Dot<T, N, LEFT, RIGHT>> {
+    OperationWrapper(
+        Dot {
+            left,
+            right,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// The dot product of two arrays like `left.dot(right)`.
+#[derive(Clone, Debug)]
+pub struct Dot<T: Calculatable, const N: usize, LEFT: Operation<[T; N]>, RIGHT: Operation<[T; N]>> {
+    pub left: LEFT,
+    pub right: RIGHT,
+    pub _0: PhantomData<T>,
+}
+
+impl<T: Calculatable, const N: usize, LEFT: Operation<[T; N]>, RIGHT: Operation<[T; N]>> Operation<T> for Dot<T, N, LEFT, RIGHT> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> T {
+        self.left.evaluate(context).dot(self.right.evaluate(context))
+    }
+}
+
+impl<T: Calculatable, const N: usize, LEFT: Differentiable<[T; N]>, RIGHT: Differentiable<[T; N]>> Differentiable<T> for Dot<T, N, LEFT, RIGHT> {
+    type Diff = OperationWrapper<T, Add<T, OperationWrapper<T, Dot<T, N, LEFT, RIGHT::Diff>>, OperationWrapper<T, Dot<T, N, LEFT::Diff, RIGHT>>>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        add(dot(self.left.clone(), self.right.auto_diff_for(var.clone(), var_trace)), dot(self.left.auto_diff_for(var, var_trace), self.right.clone()))
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.left.contains_var(var.clone()) || self.right.contains_var(var)
+    }
+}
@@ -0,0 +1,82 @@
+use std::marker::PhantomData;
+
+use crate::core::{types::{Computable, Void}, operation::Operation, type_traits::Calculatable, processor::cpu::DifferentiatedCPUContext};
+
+/// Derivatives of a kernel for single elements of its tensor arguments, the main of the programs in
+/// [`derivatives`](crate::core::derivatives).
+///
+/// The invocation at `pos` differentiates for element `pos.x` of the first tensor in `elements` and
+/// element `pos.y` of the second. `tree` runs for every position of the kernel's `grid` with the
+/// `value` local reset to zero, the derivative it sets there is written into `output`: summed over the
+/// grid at the element index, or for every position at `[position, elements..]`.
+#[derive(Clone, Debug)]
+pub struct ElementDerivatives<R: Computable, M: Operation<Void>> {
+    pub tree: M,
+    /// Locals holding the element index, each with the tensor argument it indexes.
+    pub elements: Vec<(String, String)>,
+    pub grid: (u32, u32, u32),
+    pub value: String,
+    pub output: String,
+    pub per_position: bool,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Calculatable, M: Operation<Void>> ElementDerivatives<R, M> {
+    /// Runs the invocation at the `pos` set in `context` and returns the output elements it writes.
+    pub fn run(&self, context: &mut DifferentiatedCPUContext) -> Vec<(usize, R)> {
+        let dispatched = [*context.get::<u32>("pos.x"), *context.get::<u32>("pos.y"), *context.get::<u32>("pos.z")];
+
+        let (mut index, mut count) = (0, 1);
+        for ((element, tensor), at) in self.elements.iter().zip(dispatched) {
+            let length = context.tensor::<R>(tensor).data.len();
+            if at as usize >= length {
+                return Vec::new();
+            }
+            index = index * length + at as usize;
+            count *= length;
+            context.set(element, at);
+        }
+
+        let (x, y, z) = self.grid;
+        let mut written = Vec::new();
+        let mut sum = R::get_zero();
+        for px in 0..x {
+            for py in 0..y {
+                for pz in 0..z {
+                    context.set("pos.x", px);
+                    context.set("pos.y", py);
+                    context.set("pos.z", pz);
+                    context.set(&self.value, R::get_zero());
+                    context.set_return_state(false);
+                    self.tree.evaluate(context);
+
+                    let value = *context.get::<R>(&self.value);
+                    if self.per_position {
+                        let position = ((px * y + py) * z + pz) as usize;
+                        written.push((position * count + index, value));
+                    } else {
+                        sum = sum + value;
+                    }
+                }
+            }
+        }
+
+        context.set_return_state(false);
+        context.set("pos.x", dispatched[0]);
+        context.set("pos.y", dispatched[1]);
+        context.set("pos.z", dispatched[2]);
+        if !self.per_position {
+            written.push((index, sum));
+        }
+        written
+    }
+}
+
+impl<R: Calculatable, M: Operation<Void>> Operation<Void> for ElementDerivatives<R, M> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        for (index, value) in self.run(context) {
+            context.tensor_mut::<R>(&self.output).data[index] = value;
+        }
+        Void
+    }
+}
@@ -0,0 +1,45 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, type_traits::Float,
+};
+
+use super::multiply::{Multiply, multiply};
+
+pub fn exp<R: Float, O: Operation<R>>(
+    value: O,
+) -> OperationWrapper<R, Exp<R, O>> {
+    OperationWrapper(
+        Exp {
+            value,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// The natural exponential like `value.exp()`.
+#[derive(Clone, Debug)]
+pub struct Exp<R: Float, O: Operation<R>> {
+    pub value: O,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Float, O: Operation<R>> Operation<R> for Exp<R, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        self.value.evaluate(context).exp()
+    }
+}
+
+impl<R: Float, O: Differentiable<R>> Differentiable<R> for Exp<R, O> {
+    type Diff = OperationWrapper<R, Multiply<R, O::Diff, OperationWrapper<R, Exp<R, O>>>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        multiply(self.value.auto_diff_for(var, var_trace), exp(self.value.clone()))
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.value.contains_var(var)
+    }
+}
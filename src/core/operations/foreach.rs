@@ -7,18 +7,21 @@ use crate::core::{
 
 use super::{scope::Scope, var::Variable};
 
+/// Runs the scope built by `function` for every value of `iterable`, bound to the loop variable `name`.
 pub fn foreach<R: Computable, ITERABLE: Iterable<R>, A: Operation<Void>, B: Operation<Void>, F>(
+    name: &str,
     iterable: ITERABLE,
     function: F,
 ) -> OperationWrapper<Void, ForEach<R, ITERABLE, A, B>> 
     where F: FnOnce(Variable<R>) -> Scope<Void, Void, Void, A, B>
 {
-    let loop_var = Variable::<R>::new("loop_variable");
+    let variable = Variable::<R>::new(name);
 
-    let scope = function(loop_var);
+    let scope = function(variable.clone());
     OperationWrapper(
         ForEach {
             iterable,
+            variable,
             scope,
             _0: PhantomData,
         },
@@ -29,15 +32,41 @@ pub fn foreach<R: Computable, ITERABLE: Iterable<R>, A: Operation<Void>, B: Oper
 #[derive(Clone, Debug)]
 pub struct ForEach<R: Computable, ITERABLE: Iterable<R>, A: Operation<Void>, B: Operation<Void>> {
     pub iterable: ITERABLE,
+    pub variable: Variable<R>,
     pub scope: Scope<Void, Void, Void, A, B>,
     _0: PhantomData<R>,
 }
 
 impl<R: Computable, ITERABLE: Iterable<R>, A: Operation<Void>, B: Operation<Void>> Operation<Void> for ForEach<R, ITERABLE, A, B> {
-    fn evaluate(&self, _context: &mut DifferentiatedCPUContext) -> Void {
-        todo!("Unfinished business")
-        //self.left.evaluate(context) / self.right.evaluate(context)
-     }
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        for value in self.iterable.values(context) {
+            context.set(&self.variable.reference, value);
+            self.scope.evaluate(context);
+
+            if context.is_in_return_state() {
+                break;
+            }
+        }
+        Void
+    }
+}
+
+/// Differentiates the body of a loop until `var_trace` no longer changes.
+///
+/// Variables carried from one iteration to the next can be read before the statement that makes their
+/// derivative non-zero, so the body is differentiated again with what the previous pass learned.
+pub(crate) fn differentiate_body<A: Differentiable<Void>, B: Differentiable<Void>, R1: Clone>(
+    scope: &Scope<Void, Void, Void, A, B>,
+    var: Variable<R1>,
+    var_trace: &mut HashMap<String, Vec<String>>,
+) -> <Scope<Void, Void, Void, A, B> as Differentiable<Void>>::Diff {
+    loop {
+        let before = var_trace.clone();
+        let body = scope.auto_diff_for(var.clone(), var_trace);
+        if *var_trace == before {
+            return body;
+        }
+    }
 }
 
 impl<R: Computable, ITERABLE: Iterable<R>, A: Differentiable<Void>, B: Differentiable<Void>> Differentiable<Void> for ForEach<R, ITERABLE, A, B> {
@@ -46,7 +75,8 @@ impl<R: Computable, ITERABLE: Iterable<R>, A: Differentiable<Void>, B: Different
     fn auto_diff_for<R1: Clone>(&self, var: Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
         ForEach {
             iterable: self.iterable.clone(),
-            scope: self.scope.auto_diff_for(var, var_trace),
+            variable: self.variable.clone(),
+            scope: differentiate_body(&self.scope, var, var_trace),
             _0: PhantomData,
         }
     }
@@ -2,10 +2,10 @@ use std::marker::PhantomData;
 
 use crate::core::{
     operation::{Operation, Differentiable},
-    types::{Void, Value},type_traits::{FunctionInputs},
+    types::{Computable, Void, Value},type_traits::{FunctionInputs, Partials},
 };
 
-use super::{scope::{Scope}};
+use super::{scope::{Scope}, derivative::CustomDerivative};
 
 pub fn function<R: Value, INPUTS: FunctionInputs, A: Operation<R>, B: Operation<Void>, F>(
     name: &str,
@@ -32,18 +32,37 @@ pub struct Function<R: Value, INPUTS: FunctionInputs, A: Operation<R>> {
     _0: PhantomData<R>
 }
 
+impl<R: Computable, INPUTS: FunctionInputs, A: Operation<R>> Function<R, INPUTS, A> {
+    /// Differentiates the function with `partials` instead of its body, `()` makes it non-differentiable.
+    pub fn with_derivative<P: Partials<R, INPUTS>>(self, partials: P) -> Function<R, INPUTS, CustomDerivative<R, INPUTS, A, P>> {
+        Function {
+            name: self.name,
+            inputs: self.inputs.clone(),
+            scope: CustomDerivative { scope: self.scope, inputs: self.inputs, partials, _0: PhantomData },
+            _0: PhantomData,
+        }
+    }
+}
+
 impl<R: Value, INPUTS: FunctionInputs, A: Operation<R>> Operation<R> for Function<R, INPUTS, A> {
-    fn evaluate(&self, _context: &mut crate::core::processor::cpu::DifferentiatedCPUContext) -> R {
-        todo!("Unfinished business")
+    fn evaluate(&self, context: &mut crate::core::processor::cpu::DifferentiatedCPUContext) -> R {
+        let value = self.scope.evaluate(context);
+        if context.is_in_return_state() {
+            context.get_return_value()
+        } else {
+            value
+        }
     }
 }
 
 impl<R: Value, INPUTS: FunctionInputs, A: Differentiable<R>> Differentiable<R> for Function<R, INPUTS, A> {
     type Diff = Function<R, INPUTS, A::Diff>;
 
+    /// The partial derivative for the input `var`, named after it so the partials of one function can
+    /// be called side by side.
     fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut std::collections::HashMap<String, Vec<String>>) -> Self::Diff {
         Function {
-            name: format!("{}_chandra_diff", self.name),
+            name: format!("{}_chandra_diff_{}", self.name, var.reference),
             inputs: self.inputs.clone(),
             scope: self.scope.auto_diff_for(var, var_trace),
             _0: PhantomData,
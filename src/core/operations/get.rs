@@ -19,9 +19,9 @@ pub struct Get<R: Computable> {
 }
 
 impl<R: Computable> Operation<R> for Get<R> {
-    fn evaluate(&self, _context: &mut DifferentiatedCPUContext) -> R {
-        todo!()
-     }
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        self.getable.evaluate(context)
+    }
 }
 
 impl<R: Computable> Differentiable<R> for Get<R> {
@@ -50,7 +50,7 @@ impl<R: Value, CONDITION: Operation<bool>, A: Operation<R>, B: Operation<Void>,
         } else if let Some(els) = &self.els {
             els.evaluate(context)
         } else {
-            todo!("Figure out how to return R")
+            Void::downcast().expect("`if` without `else` has no value")
         }
     }
 }
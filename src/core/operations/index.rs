@@ -1,8 +1,12 @@
 use std::marker::PhantomData;
 
-use crate::core::{types::Computable, operation::{Operation, OperationWrapper, Differentiable}, type_traits::{IndexAble, GetAndSetable}, processor::{cpu::DifferentiatedCPUContext}};
+use crate::core::{types::{Computable, Either}, operation::{Operation, OperationWrapper, Differentiable}, type_traits::{AtomicComputable, FromMut, ReferenceAble, GetAndSetable}, processor::{cpu::DifferentiatedCPUContext}};
 
-pub fn index<R: Computable, T: IndexAble, O: Operation<u32>>(tensor: &T, op: O) -> OperationWrapper<R, Index<R, T, O>> {
+use crate::types::tensor::{MyCPUTensor, Tensor};
+
+use super::{equals::{equals, Equals}, select::{select, Select}, var::{tangent, traced, Variable, ELEMENT_TRACE, UNSUPPORTED_TRACE}};
+
+pub fn index<R: Computable, T: ReferenceAble, O: Operation<u32>>(tensor: &T, op: O) -> OperationWrapper<R, Index<R, T, O>> {
     OperationWrapper (
         Index {
             tensor: tensor.clone(),
@@ -15,26 +19,70 @@ pub fn index<R: Computable, T: IndexAble, O: Operation<u32>>(tensor: &T, op: O)
 
 
 #[derive(Clone, Debug)]
-pub struct Index<R: Computable, T: IndexAble, O: Operation<u32>> {
+pub struct Index<R: Computable, T: ReferenceAble, O: Operation<u32>> {
     pub tensor: T,
     pub index: O,
     _0: PhantomData<R>,
 }
 
-impl<R: Computable, T: IndexAble, O: Operation<u32>> Operation<R> for Index<R, T, O> {
-    fn evaluate(&self, _context: &mut DifferentiatedCPUContext) -> R {
-        todo!("Unfinished business")
+/// The value stored in the context behind `tensor[i]`, the argument of a tensor or the local array itself.
+fn host_reference<T: ReferenceAble>(tensor: &T) -> String {
+    let reference = tensor.get_reference();
+    match T::get_field() {
+        Some(field) => reference.strip_suffix(&format!(".{}", field)).unwrap_or(&reference).to_string(),
+        None => reference,
+    }
+}
+
+/// Runs the atomic `update` on the element behind `tensor[index]` and returns its previous value. Local
+/// arrays belong to a single invocation, so their element is updated through a copy.
+pub(crate) fn update_element<R: AtomicComputable, T: ReferenceAble>(context: &mut DifferentiatedCPUContext, tensor: &T, index: u32, update: impl FnOnce(MyCPUTensor<'_, R>, u32) -> R) -> R {
+    let reference = tensor.get_reference();
+    match reference.strip_suffix(".data") {
+        Some(argument) => update(MyCPUTensor::from_mut(context.tensor_mut::<R>(argument)), index),
+        None => {
+            let mut element = Tensor { data: vec![T::get_element::<R>(context, &reference, index as usize)], shape: vec![1] };
+            let previous = update(MyCPUTensor::from_mut(&mut element), 0);
+            T::set_element(context, &reference, index as usize, element.data[0]);
+            previous
+        }
     }
 }
 
-impl<R: Computable, T: IndexAble, O: Differentiable<u32>> Differentiable<R> for Index<R, T, O> {
-    type Diff = R;
+impl<R: Computable, T: ReferenceAble, O: Operation<u32>> Operation<R> for Index<R, T, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        let index = self.index.evaluate(context) as usize;
+        T::get_element(context, &host_reference(&self.tensor), index)
+    }
+}
 
-    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, _var_trace: &mut std::collections::HashMap<String, Vec<String>>) -> Self::Diff {
-        if self.contains_var(var) {
-            R::from_int(1)
+impl<R: Computable, T: ReferenceAble, O: Differentiable<u32>> Differentiable<R> for Index<R, T, O> {
+    type Diff = Either<Index<R, T, O>, Either<OperationWrapper<R, Select<R, OperationWrapper<bool, Equals<u32, O, Variable<u32>>>, R, R>>, R>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut std::collections::HashMap<String, Vec<String>>) -> Self::Diff {
+        // local arrays have the derivative in another local
+        let reference = self.tensor.get_reference();
+        if self.contains_var(var.clone()) {
+            match var_trace.get(ELEMENT_TRACE).and_then(|element| element.first()) {
+                Some(element) => Either::B(Either::A(select(equals(self.index.clone(), Variable::new(element)), R::from_int(1), R::get_zero()))),
+                None => Either::B(Either::B(R::from_int(1))),
+            }
+        } else if !traced(&reference, &var.reference, var_trace) {
+            Either::B(Either::B(R::get_zero()))
+        } else if self.is_tensor_element() {
+            // the element holds the derivative now, a product rule would read it as the value as well
+            let message = format!("`{}` is read after it was written, carry the value in a local and write the element once", host_reference(&self.tensor));
+            let unsupported = var_trace.entry(UNSUPPORTED_TRACE.to_string()).or_default();
+            if !unsupported.contains(&message) {
+                unsupported.push(message);
+            }
+            Either::B(Either::B(R::get_zero()))
         } else {
-            R::get_zero()
+            Either::A(Index {
+                tensor: self.tensor.with_variable(&tangent(&reference, &var.reference, var_trace)),
+                index: self.index.clone(),
+                _0: PhantomData,
+            })
         }
     }
 
@@ -47,8 +95,21 @@ impl<R: Computable, T: IndexAble, O: Differentiable<u32>> Differentiable<R> for
     }
 }
 
-impl<R: Computable, T: IndexAble, O: Operation<u32>> GetAndSetable<R> for Index<R, T, O> {
+impl<R: Computable, T: ReferenceAble, O: Operation<u32>> GetAndSetable<R> for Index<R, T, O> {
     fn get_variable(&self) -> Option<String> {
         Some(self.tensor.get_reference())
     }
+
+    fn set_value(&self, context: &mut DifferentiatedCPUContext, value: R) {
+        let index = self.index.evaluate(context) as usize;
+        T::set_element(context, &host_reference(&self.tensor), index, value);
+    }
+
+    fn with_variable(&self, reference: &str) -> Self {
+        Index { tensor: self.tensor.with_variable(reference), index: self.index.clone(), _0: PhantomData }
+    }
+
+    fn is_tensor_element(&self) -> bool {
+        T::get_field().is_some()
+    }
 }
\ No newline at end of file
@@ -17,6 +17,11 @@ impl<A: Operation<Void>> InstructionList<Void, Void, A, A> {
 }
 
 impl<R1: Value, R2: Value, A: Operation<R1>, B: Operation<R2>> InstructionList<R1, R2, A, B> {
+    /// `this` after `previous`, if there is one.
+    pub fn after(this: A, previous: Option<B>) -> Self {
+        Self {this, previous, _0: PhantomData, _1: PhantomData}
+    }
+
     pub fn append<R: Value, C: Operation<R>>(self, instr: C) -> InstructionList<R, R1, C, Self> {
         return InstructionList::<R, R1, C, InstructionList<R1, R2, A, B>> {this: instr, previous: Some(self), _0: PhantomData, _1: PhantomData}
     }
@@ -28,7 +33,7 @@ impl<R1: Value, R2: Value, A: Operation<R1>, B: Operation<R2>> Operation<R1> for
             let _prev = previous.evaluate(context);
 
             if context.is_in_return_state() {
-                todo!("fix instruction list with Either type") //prev;
+                return context.get_return_value();
             }
         }
         self.this.evaluate(context)
@@ -39,21 +44,9 @@ impl<R1: Value, R2: Value, A: Differentiable<R1>, B: Differentiable<R2>> Differe
     type Diff = InstructionList<R1, R2, Either<A::Diff, A>, Either<B::Diff, B>>;
 
     fn auto_diff_for<R:Clone>(&self, var: super::var::Variable<R>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
-        let b = if let Some(prev) = &self.previous {
-            if prev.contains_var(var.clone()) {
-                Some(Either::A(prev.auto_diff_for(var.clone(), var_trace)))
-            } else {
-                Some(Either::B(prev.clone()))
-            }
-        } else {
-           None
-        };
-
-        let a = if self.this.contains_var(var.clone()) {
-            Either::A(self.this.auto_diff_for(var.clone(), var_trace))
-        } else {
-            Either::B(self.this.clone())
-        };
+        // every statement is differentiated, it may read locals whose derivative isn't zero
+        let b = self.previous.as_ref().map(|prev| Either::A(prev.auto_diff_for(var.clone(), var_trace)));
+        let a = Either::A(self.this.auto_diff_for(var.clone(), var_trace));
 
         InstructionList {
             this: a,
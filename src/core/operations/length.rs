@@ -0,0 +1,41 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::{core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, types::Computable,
+}, types::tensor::Tensor};
+
+use super::var::Variable;
+
+pub fn length<C: Computable>(tensor: &Variable<Tensor<C>>) -> OperationWrapper<u32, Length<C>> {
+    OperationWrapper(
+        Length {
+            tensor: tensor.clone(),
+        },
+        PhantomData,
+    )
+}
+
+/// The number of elements of a tensor argument like `t.len()`.
+#[derive(Clone, Debug)]
+pub struct Length<C: Computable> {
+    pub tensor: Variable<Tensor<C>>,
+}
+
+impl<C: Computable> Operation<u32> for Length<C> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> u32 {
+        context.tensor::<C>(&self.tensor.reference).data.len() as u32
+    }
+}
+
+impl<C: Computable> Differentiable<u32> for Length<C> {
+    type Diff = u32;
+
+    fn auto_diff_for<R1: Clone>(&self, _var: Variable<R1>, _var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        0
+    }
+
+    fn contains_var<R1: Clone>(&self, _var: Variable<R1>) -> bool {
+        false
+    }
+}
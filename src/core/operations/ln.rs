@@ -0,0 +1,45 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, type_traits::Float,
+};
+
+use super::divide::{Divide, divide};
+
+pub fn ln<R: Float, O: Operation<R>>(
+    value: O,
+) -> OperationWrapper<R, Ln<R, O>> {
+    OperationWrapper(
+        Ln {
+            value,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// The natural logarithm like `value.ln()`.
+#[derive(Clone, Debug)]
+pub struct Ln<R: Float, O: Operation<R>> {
+    pub value: O,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Float, O: Operation<R>> Operation<R> for Ln<R, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        self.value.evaluate(context).ln()
+    }
+}
+
+impl<R: Float, O: Differentiable<R>> Differentiable<R> for Ln<R, O> {
+    type Diff = OperationWrapper<R, Divide<R, O::Diff, O>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        divide(self.value.auto_diff_for(var, var_trace), self.value.clone())
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.value.contains_var(var)
+    }
+}
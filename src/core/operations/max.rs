@@ -0,0 +1,80 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, type_traits::{Calculatable, Signed},
+};
+
+use super::{add::{Add, add}, divide::{Divide, divide}, multiply::{Multiply, multiply}, sign::{Sign, sign}, subtract::{Subtract, subtract}};
+
+pub fn max<R: Calculatable, LEFT: Operation<R>, RIGHT: Operation<R>>(
+    left: LEFT,
+    right: RIGHT,
+) -> OperationWrapper<R, Max<R, LEFT, RIGHT>> {
+    OperationWrapper(
+        Max {
+            left,
+            right,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// The larger of both values like `left.max(right)`.
+#[derive(Clone, Debug)]
+pub struct Max<R: Calculatable, LEFT: Operation<R>, RIGHT: Operation<R>> {
+    pub left: LEFT,
+    pub right: RIGHT,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Calculatable, LEFT: Operation<R>, RIGHT: Operation<R>> Operation<R> for Max<R, LEFT, RIGHT> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        let left = self.left.evaluate(context);
+        let right = self.right.evaluate(context);
+
+        if left < right { right } else { left }
+    }
+}
+
+/// `d_left + d_right`, the part of the derivative `max` and `min` share.
+pub type SumDiff<R, LEFT, RIGHT> = OperationWrapper<R, Add<R, <LEFT as Differentiable<R>>::Diff, <RIGHT as Differentiable<R>>::Diff>>;
+
+/// `sign(left - right) * (d_left - d_right)`, the part of the derivative `max` and `min` switch on.
+pub type SwitchDiff<R, LEFT, RIGHT> = OperationWrapper<R, Multiply<R,
+    OperationWrapper<R, Sign<R, OperationWrapper<R, Subtract<R, LEFT, RIGHT>>>>,
+    OperationWrapper<R, Subtract<R, <LEFT as Differentiable<R>>::Diff, <RIGHT as Differentiable<R>>::Diff>>,
+>>;
+
+/// The derivative of `(left + right ± |left - right|) / 2`, both sides get half of it on ties.
+pub type MaxMinDiff<R, COMBINE> = OperationWrapper<R, Divide<R,
+    OperationWrapper<R, COMBINE>,
+    R,
+>>;
+
+pub(crate) fn switch_diff<R: Signed, LEFT: Differentiable<R>, RIGHT: Differentiable<R>, R1: Clone>(
+    left: &LEFT,
+    right: &RIGHT,
+    var: super::var::Variable<R1>,
+    var_trace: &mut HashMap<String, Vec<String>>,
+) -> (SumDiff<R, LEFT, RIGHT>, SwitchDiff<R, LEFT, RIGHT>) {
+    let d_left = left.auto_diff_for(var.clone(), var_trace);
+    let d_right = right.auto_diff_for(var, var_trace);
+
+    let switch = multiply(sign(subtract(left.clone(), right.clone())), subtract(d_left.clone(), d_right.clone()));
+    (add(d_left, d_right), switch)
+}
+
+impl<R: Signed, LEFT: Differentiable<R>, RIGHT: Differentiable<R>> Differentiable<R> for Max<R, LEFT, RIGHT> {
+    type Diff = MaxMinDiff<R, Add<R, SumDiff<R, LEFT, RIGHT>, SwitchDiff<R, LEFT, RIGHT>>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        let (sum, switch) = switch_diff(&self.left, &self.right, var, var_trace);
+        divide(add(sum, switch), R::from_int(2))
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.left.contains_var(var.clone()) || self.right.contains_var(var)
+    }
+}
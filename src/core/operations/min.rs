@@ -0,0 +1,52 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, type_traits::{Calculatable, Signed},
+};
+
+use super::{divide::divide, max::{MaxMinDiff, SumDiff, SwitchDiff, switch_diff}, subtract::{Subtract, subtract}};
+
+pub fn min<R: Calculatable, LEFT: Operation<R>, RIGHT: Operation<R>>(
+    left: LEFT,
+    right: RIGHT,
+) -> OperationWrapper<R, Min<R, LEFT, RIGHT>> {
+    OperationWrapper(
+        Min {
+            left,
+            right,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// The smaller of both values like `left.min(right)`.
+#[derive(Clone, Debug)]
+pub struct Min<R: Calculatable, LEFT: Operation<R>, RIGHT: Operation<R>> {
+    pub left: LEFT,
+    pub right: RIGHT,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Calculatable, LEFT: Operation<R>, RIGHT: Operation<R>> Operation<R> for Min<R, LEFT, RIGHT> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        let left = self.left.evaluate(context);
+        let right = self.right.evaluate(context);
+
+        if right < left { right } else { left }
+    }
+}
+
+impl<R: Signed, LEFT: Differentiable<R>, RIGHT: Differentiable<R>> Differentiable<R> for Min<R, LEFT, RIGHT> {
+    type Diff = MaxMinDiff<R, Subtract<R, SumDiff<R, LEFT, RIGHT>, SwitchDiff<R, LEFT, RIGHT>>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        let (sum, switch) = switch_diff(&self.left, &self.right, var, var_trace);
+        divide(subtract(sum, switch), R::from_int(2))
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.left.contains_var(var.clone()) || self.right.contains_var(var)
+    }
+}
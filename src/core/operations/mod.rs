@@ -1,13 +1,28 @@
 //Calc
 pub mod add;
+pub mod cast;
 pub mod divide;
 pub mod multiply;
 pub mod subtract;
 
+//Math
+pub mod abs;
+pub mod dot;
+pub mod exp;
+pub mod ln;
+pub mod max;
+pub mod min;
+pub mod sign;
+pub mod sqrt;
+pub mod tanh;
+
 //Variables
 pub mod assign;
+pub mod dimension;
 pub mod get;
 pub mod index;
+pub mod length;
+pub mod repeat;
 pub mod set;
 pub mod var;
 
@@ -15,6 +30,8 @@ pub mod var;
 pub mod foreach;
 pub mod if_else;
 pub mod range;
+pub mod select;
+pub mod switch;
 pub mod until;
 
 //Compare
@@ -27,8 +44,26 @@ pub mod less_than_or_equal;
 pub mod not_equal;
 pub mod or;
 
+//Atomics
+pub mod atomic_add;
+pub mod atomic_exchange;
+pub mod atomic_max;
+pub mod atomic_min;
+pub mod compare_exchange;
+
+//Debug
+pub mod assert;
+pub mod debug_print;
+
+//Random
+pub mod bernoulli;
+pub mod normal;
+pub mod uniform;
+
 //Structure
 pub mod call;
+pub mod derivative;
+pub mod element_derivatives;
 pub mod function;
 pub mod instruction_list;
 pub mod noop;
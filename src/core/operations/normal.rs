@@ -0,0 +1,55 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, random,
+};
+
+pub fn normal<SEED: Operation<u32>, COUNTER: Operation<u32>, MEAN: Operation<f32>, STD: Operation<f32>>(
+    seed: SEED,
+    counter: COUNTER,
+    mean: MEAN,
+    std: STD,
+) -> OperationWrapper<f32, Normal<SEED, COUNTER, MEAN, STD>> {
+    OperationWrapper(
+        Normal {
+            seed,
+            counter,
+            mean,
+            std,
+        },
+        PhantomData,
+    )
+}
+
+/// Approximately normally distributed value, see [`random::normal`]. The Irwin-Hall sum it is
+/// computed with never leaves `mean ± 6 * std`.
+#[derive(Clone, Debug)]
+pub struct Normal<SEED: Operation<u32>, COUNTER: Operation<u32>, MEAN: Operation<f32>, STD: Operation<f32>> {
+    pub seed: SEED,
+    pub counter: COUNTER,
+    pub mean: MEAN,
+    pub std: STD,
+}
+
+impl<SEED: Operation<u32>, COUNTER: Operation<u32>, MEAN: Operation<f32>, STD: Operation<f32>> Operation<f32> for Normal<SEED, COUNTER, MEAN, STD> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> f32 {
+        random::normal(
+            self.seed.evaluate(context),
+            self.counter.evaluate(context),
+            self.mean.evaluate(context),
+            self.std.evaluate(context),
+        )
+    }
+}
+
+impl<SEED: Operation<u32>, COUNTER: Operation<u32>, MEAN: Operation<f32>, STD: Operation<f32>> Differentiable<f32> for Normal<SEED, COUNTER, MEAN, STD> {
+    type Diff = f32;
+
+    fn auto_diff_for<R1: Clone>(&self, _var: super::var::Variable<R1>, _var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        0.0
+    }
+    fn contains_var<R1: Clone>(&self, _var: super::var::Variable<R1>) -> bool {
+        false
+    }
+}
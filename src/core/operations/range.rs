@@ -47,4 +47,8 @@ impl<LEFT: Operation<u32>, RIGHT: Operation<u32>> Iterable<u32> for Range<LEFT,
     fn get_boundry(&self) -> Self::BoundryOp {
         self.right.clone()
     }
+
+    fn values(&self, context: &mut DifferentiatedCPUContext) -> Vec<u32> {
+        (self.left.evaluate(context)..self.right.evaluate(context)).collect()
+    }
 }
\ No newline at end of file
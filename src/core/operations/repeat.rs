@@ -0,0 +1,46 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, types::Computable,
+};
+
+pub fn repeat<T: Computable, const N: usize, O: Operation<T>>(
+    value: O,
+) -> OperationWrapper<[T; N], Repeat<T, N, O>> {
+    OperationWrapper(
+        Repeat {
+            value,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// An array with every element set to `value` like `[value; N]`.
+#[derive(Clone, Debug)]
+pub struct Repeat<T: Computable, const N: usize, O: Operation<T>> {
+    pub value: O,
+    pub _0: PhantomData<T>,
+}
+
+impl<T: Computable, const N: usize, O: Operation<T>> Operation<[T; N]> for Repeat<T, N, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> [T; N] {
+        [self.value.evaluate(context); N]
+    }
+}
+
+impl<T: Computable, const N: usize, O: Differentiable<T>> Differentiable<[T; N]> for Repeat<T, N, O> {
+    type Diff = Repeat<T, N, O::Diff>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        Repeat {
+            value: self.value.auto_diff_for(var, var_trace),
+            _0: PhantomData,
+        }
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.value.contains_var(var)
+    }
+}
@@ -1,6 +1,6 @@
 use std::marker::PhantomData;
 
-use crate::core::{types::{Value}, operation::{Operation, Differentiable}, processor::cpu::DifferentiatedCPUContext};
+use crate::core::{types::{Value, Void}, operation::{Operation, OperationWrapper, Differentiable}, processor::cpu::DifferentiatedCPUContext};
 
 
 
@@ -8,6 +8,14 @@ pub fn returns<R: Value, O: Operation<R>>(operation: O) -> Returns<R, O> {
     Returns { operation, _0: PhantomData }
 }
 
+/// Returns from a nested scope, e.g. inside an `if` or loop. The scopes around it evaluate to `Void`.
+pub fn early_return<R: Value, O: Operation<R>>(operation: O) -> OperationWrapper<Void, EarlyReturn<R, O>> {
+    OperationWrapper(
+        EarlyReturn { operation, _0: PhantomData },
+        PhantomData,
+    )
+}
+
 #[derive(Clone, Debug)]
 pub struct Returns<R: Value, O: Operation<R>> {
     pub operation: O,
@@ -16,9 +24,9 @@ pub struct Returns<R: Value, O: Operation<R>> {
 
 impl<R: Value, O: Operation<R>> Operation<R> for Returns<R, O> {
     fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
-        context.set_return_state(true);
-        self.operation.evaluate(context)
-        //self.operation.evaluate(context)
+        let value = self.operation.evaluate(context);
+        context.set_return_value(value);
+        value
     }
 }
 
@@ -32,4 +40,30 @@ impl<R: Value, O: Differentiable<R>> Differentiable<R> for Returns<R, O> {
     fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
         self.operation.contains_var(var)
     }
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Debug)]
+pub struct EarlyReturn<R: Value, O: Operation<R>> {
+    pub operation: O,
+    _0: PhantomData<R>
+}
+
+impl<R: Value, O: Operation<R>> Operation<Void> for EarlyReturn<R, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        let value = self.operation.evaluate(context);
+        context.set_return_value(value);
+        Void
+    }
+}
+
+impl<R: Value, O: Differentiable<R>> Differentiable<Void> for EarlyReturn<R, O> {
+    type Diff = EarlyReturn<R, O::Diff>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut std::collections::HashMap<String, Vec<String>>) -> Self::Diff {
+        EarlyReturn { operation: self.operation.auto_diff_for(var, var_trace), _0: PhantomData }
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.operation.contains_var(var)
+    }
+}
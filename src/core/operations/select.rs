@@ -0,0 +1,57 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    types::Computable, processor::cpu::DifferentiatedCPUContext,
+};
+
+pub fn select<R: Computable, CONDITION: Operation<bool>, A: Operation<R>, B: Operation<R>>(
+    condition: CONDITION,
+    then: A,
+    els: B,
+) -> OperationWrapper<R, Select<R, CONDITION, A, B>> {
+    OperationWrapper(
+        Select {
+            condition,
+            then,
+            els,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// `then` if `condition` holds and `els` otherwise, both sides are evaluated like WGSL's `select`.
+#[derive(Clone, Debug)]
+pub struct Select<R: Computable, CONDITION: Operation<bool>, A: Operation<R>, B: Operation<R>> {
+    pub condition: CONDITION,
+    pub then: A,
+    pub els: B,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Computable, CONDITION: Operation<bool>, A: Operation<R>, B: Operation<R>> Operation<R> for Select<R, CONDITION, A, B> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        let then = self.then.evaluate(context);
+        let els = self.els.evaluate(context);
+
+        if self.condition.evaluate(context) { then } else { els }
+    }
+}
+
+impl<R: Computable, CONDITION: Operation<bool>, A: Differentiable<R>, B: Differentiable<R>> Differentiable<R> for Select<R, CONDITION, A, B> {
+    type Diff = Select<R, CONDITION, A::Diff, B::Diff>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        Select {
+            condition: self.condition.clone(),
+            then: self.then.auto_diff_for(var.clone(), var_trace),
+            els: self.els.auto_diff_for(var, var_trace),
+            _0: PhantomData,
+        }
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.then.contains_var(var.clone()) || self.els.contains_var(var)
+    }
+}
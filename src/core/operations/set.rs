@@ -1,6 +1,8 @@
 use std::marker::PhantomData;
 
-use crate::core::{types::{Computable, Void}, operation::{Operation, OperationWrapper, Differentiable}, type_traits::GetAndSetable, processor::cpu::DifferentiatedCPUContext};
+use crate::core::{types::{Computable, Void, Either}, operation::{Operation, OperationWrapper, Differentiable}, type_traits::GetAndSetable, processor::cpu::DifferentiatedCPUContext};
+
+use super::{instruction_list::InstructionList, var::{tangent, Variable, OUTPUT_TRACE}};
 
 
 
@@ -23,39 +25,45 @@ pub struct Set<R: Computable, G: GetAndSetable<R>, O: Operation<R>> {
 }
 
 impl<R: Computable, G: GetAndSetable<R>, O: Operation<R>> Operation<Void> for Set<R, G, O> {
-    fn evaluate(&self, _context: &mut DifferentiatedCPUContext) -> Void {
-        if let Some(_var) = self.getable.get_variable() {
-            todo!()
-            //context.set(&var, self.assign.evaluate(context));
-        } else {
-            panic!("Unexpected")
-        }
-        
-        //Void
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        let value = self.assign.evaluate(context);
+        self.getable.set_value(context, value);
+        Void
     }
 }
 
 impl<R: Computable, G: GetAndSetable<R>, O: Differentiable<R>> Differentiable<Void> for Set<R, G, O> {
-    type Diff = OperationWrapper<Void, Set<R, G, O::Diff>>;
+    type Diff = InstructionList<Void, Void, Either<Either<OperationWrapper<Void, Set<R, G, O::Diff>>, OperationWrapper<Void, Set<R, Variable<R>, O::Diff>>>, OperationWrapper<Void, Set<R, G, O>>>, OperationWrapper<Void, Set<R, G, O::Diff>>>;
 
+    /// Tensor elements are overwritten with their derivative, or set it to the local under
+    /// [`OUTPUT_TRACE`]. Locals keep their value and set their derivative local first.
     fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut std::collections::HashMap<String, Vec<String>>) -> Self::Diff {
         if let Some(refr) = self.getable.get_variable() {
-            if self.assign.contains_var(var.clone()) {
-                if let Some (trace) = var_trace.get_mut(&refr) {
-                    trace.push(var.reference.clone());
-                } else {
-                    var_trace.insert(refr, vec![var.reference.clone()]);
-                }   
-            }
+            let derivative = self.assign.auto_diff_for(var.clone(), var_trace);
+            let diff = if self.getable.is_tensor_element() {
+                let written = match var_trace.get(OUTPUT_TRACE).and_then(|output| output.first()) {
+                    Some(output) => Either::B(set(&Variable::<R>::new(output), derivative)),
+                    None => Either::A(set(&self.getable, derivative)),
+                };
+                InstructionList::after(Either::A(written), None)
+            } else {
+                let tangent = self.getable.with_variable(&tangent(&refr, &var.reference, var_trace));
+                InstructionList::after(Either::B(set(&self.getable, self.assign.clone())), Some(set(&tangent, derivative)))
+            };
 
-            set(&self.getable, self.assign.auto_diff_for(var, var_trace))
+            let trace = var_trace.entry(refr).or_default();
+            if !trace.contains(&var.reference) {
+                trace.push(var.reference.clone());
+            }
+            diff
         } else {
             panic!("Internal Differentiation Error")
         }
     }
 
     fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
-        self.assign.contains_var(var.clone()) ||
+        self.getable.is_tensor_element() ||
+            self.assign.contains_var(var.clone()) ||
             if let Some(refr) = self.getable.get_variable() {
                 refr == var.reference 
             } else { false }
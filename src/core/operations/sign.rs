@@ -0,0 +1,43 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, type_traits::Signed,
+};
+
+pub fn sign<R: Signed, O: Operation<R>>(
+    value: O,
+) -> OperationWrapper<R, Sign<R, O>> {
+    OperationWrapper(
+        Sign {
+            value,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// `-1`, `0` or `1` depending on the sign of `value`, the derivative of `abs`.
+#[derive(Clone, Debug)]
+pub struct Sign<R: Signed, O: Operation<R>> {
+    pub value: O,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Signed, O: Operation<R>> Operation<R> for Sign<R, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        self.value.evaluate(context).sign()
+    }
+}
+
+impl<R: Signed, O: Differentiable<R>> Differentiable<R> for Sign<R, O> {
+    type Diff = R;
+
+    fn auto_diff_for<R1: Clone>(&self, _var: super::var::Variable<R1>, _var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        R::get_zero()
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.value.contains_var(var)
+    }
+}
@@ -0,0 +1,45 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, type_traits::Float,
+};
+
+use super::{divide::{Divide, divide}, multiply::{Multiply, multiply}};
+
+pub fn sqrt<R: Float, O: Operation<R>>(
+    value: O,
+) -> OperationWrapper<R, Sqrt<R, O>> {
+    OperationWrapper(
+        Sqrt {
+            value,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// The square root like `value.sqrt()`.
+#[derive(Clone, Debug)]
+pub struct Sqrt<R: Float, O: Operation<R>> {
+    pub value: O,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Float, O: Operation<R>> Operation<R> for Sqrt<R, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        self.value.evaluate(context).sqrt()
+    }
+}
+
+impl<R: Float, O: Differentiable<R>> Differentiable<R> for Sqrt<R, O> {
+    type Diff = OperationWrapper<R, Divide<R, O::Diff, OperationWrapper<R, Multiply<R, R, OperationWrapper<R, Sqrt<R, O>>>>>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        divide(self.value.auto_diff_for(var, var_trace), multiply(R::from_int(2), sqrt(self.value.clone())))
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.value.contains_var(var)
+    }
+}
@@ -0,0 +1,134 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    types::{Computable, Void, Either}, processor::cpu::DifferentiatedCPUContext,
+};
+
+use super::{scope::Scope, var::Variable};
+
+pub fn switch<S: Computable + PartialEq, SELECTOR: Operation<S>, CASES: Cases<S>>(
+    selector: SELECTOR,
+    cases: CASES,
+) -> OperationWrapper<Void, Switch<S, SELECTOR, CASES>> {
+    OperationWrapper(
+        Switch {
+            selector,
+            cases,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// A `match` arm, runs `scope` if the selector equals one of `values` and tries `next` otherwise.
+pub fn case<S: Computable + PartialEq, A: Operation<Void>, B: Operation<Void>, NEXT: Cases<S>>(
+    values: Vec<S>,
+    scope: Scope<Void, Void, Void, A, B>,
+    next: NEXT,
+) -> Case<S, A, B, NEXT> {
+    Case { values, scope, next }
+}
+
+/// The `_` arm, every `switch` ends with one.
+pub fn default_case<A: Operation<Void>, B: Operation<Void>>(scope: Scope<Void, Void, Void, A, B>) -> DefaultCase<A, B> {
+    DefaultCase { scope }
+}
+
+/// The arms of a `switch` as a list of `Case`s ending with a `DefaultCase`.
+pub trait Cases<S: Computable>: Clone + std::fmt::Debug {
+    fn evaluate_for(&self, selector: S, context: &mut DifferentiatedCPUContext) -> Void;
+}
+
+pub trait DifferentiableCases<S: Computable>: Cases<S> {
+    type Diff: Cases<S>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff;
+    fn contains_var<R1: Clone>(&self, var: Variable<R1>) -> bool;
+}
+
+#[derive(Clone, Debug)]
+pub struct Switch<S: Computable + PartialEq, SELECTOR: Operation<S>, CASES: Cases<S>> {
+    pub selector: SELECTOR,
+    pub cases: CASES,
+    _0: PhantomData<S>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Case<S: Computable + PartialEq, A: Operation<Void>, B: Operation<Void>, NEXT: Cases<S>> {
+    pub values: Vec<S>,
+    pub scope: Scope<Void, Void, Void, A, B>,
+    pub next: NEXT,
+}
+
+#[derive(Clone, Debug)]
+pub struct DefaultCase<A: Operation<Void>, B: Operation<Void>> {
+    pub scope: Scope<Void, Void, Void, A, B>,
+}
+
+impl<S: Computable + PartialEq, SELECTOR: Operation<S>, CASES: Cases<S>> Operation<Void> for Switch<S, SELECTOR, CASES> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        let selector = self.selector.evaluate(context);
+        self.cases.evaluate_for(selector, context)
+    }
+}
+
+impl<S: Computable + PartialEq, A: Operation<Void>, B: Operation<Void>, NEXT: Cases<S>> Cases<S> for Case<S, A, B, NEXT> {
+    fn evaluate_for(&self, selector: S, context: &mut DifferentiatedCPUContext) -> Void {
+        if self.values.contains(&selector) {
+            self.scope.evaluate(context)
+        } else {
+            self.next.evaluate_for(selector, context)
+        }
+    }
+}
+
+impl<S: Computable, A: Operation<Void>, B: Operation<Void>> Cases<S> for DefaultCase<A, B> {
+    fn evaluate_for(&self, _selector: S, context: &mut DifferentiatedCPUContext) -> Void {
+        self.scope.evaluate(context)
+    }
+}
+
+impl<S: Computable + PartialEq, SELECTOR: Operation<S>, CASES: DifferentiableCases<S>> Differentiable<Void> for Switch<S, SELECTOR, CASES> {
+    type Diff = Switch<S, SELECTOR, CASES::Diff>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        Switch {
+            selector: self.selector.clone(),
+            cases: self.cases.auto_diff_for(var, var_trace),
+            _0: PhantomData,
+        }
+    }
+
+    fn contains_var<R1: Clone>(&self, var: Variable<R1>) -> bool {
+        self.cases.contains_var(var)
+    }
+}
+
+impl<S: Computable + PartialEq, A: Differentiable<Void>, B: Differentiable<Void>, NEXT: DifferentiableCases<S>> DifferentiableCases<S> for Case<S, A, B, NEXT> {
+    type Diff = Case<S, Either<A::Diff, A>, Either<B::Diff, B>, NEXT::Diff>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        Case {
+            values: self.values.clone(),
+            scope: self.scope.auto_diff_for(var.clone(), var_trace),
+            next: self.next.auto_diff_for(var, var_trace),
+        }
+    }
+
+    fn contains_var<R1: Clone>(&self, var: Variable<R1>) -> bool {
+        self.scope.contains_var(var.clone()) || self.next.contains_var(var)
+    }
+}
+
+impl<S: Computable, A: Differentiable<Void>, B: Differentiable<Void>> DifferentiableCases<S> for DefaultCase<A, B> {
+    type Diff = DefaultCase<Either<A::Diff, A>, Either<B::Diff, B>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        DefaultCase { scope: self.scope.auto_diff_for(var, var_trace) }
+    }
+
+    fn contains_var<R1: Clone>(&self, var: Variable<R1>) -> bool {
+        self.scope.contains_var(var)
+    }
+}
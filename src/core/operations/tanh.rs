@@ -0,0 +1,48 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, type_traits::Float,
+};
+
+use super::{multiply::{Multiply, multiply}, subtract::{Subtract, subtract}};
+
+pub fn tanh<R: Float, O: Operation<R>>(
+    value: O,
+) -> OperationWrapper<R, Tanh<R, O>> {
+    OperationWrapper(
+        Tanh {
+            value,
+            _0: PhantomData,
+        },
+        PhantomData,
+    )
+}
+
+/// The hyperbolic tangent like `value.tanh()`.
+#[derive(Clone, Debug)]
+pub struct Tanh<R: Float, O: Operation<R>> {
+    pub value: O,
+    pub _0: PhantomData<R>,
+}
+
+impl<R: Float, O: Operation<R>> Operation<R> for Tanh<R, O> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        self.value.evaluate(context).tanh()
+    }
+}
+
+type Squared<R, O> = OperationWrapper<R, Multiply<R, OperationWrapper<R, Tanh<R, O>>, OperationWrapper<R, Tanh<R, O>>>>;
+
+impl<R: Float, O: Differentiable<R>> Differentiable<R> for Tanh<R, O> {
+    type Diff = OperationWrapper<R, Multiply<R, O::Diff, OperationWrapper<R, Subtract<R, R, Squared<R, O>>>>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::var::Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        let tanh = tanh(self.value.clone());
+        multiply(self.value.auto_diff_for(var, var_trace), subtract(R::from_int(1), multiply(tanh.clone(), tanh)))
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::var::Variable<R1>) -> bool {
+        self.value.contains_var(var)
+    }
+}
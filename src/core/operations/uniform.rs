@@ -0,0 +1,43 @@
+use std::{marker::PhantomData, collections::HashMap};
+
+use crate::core::{
+    operation::{Operation, OperationWrapper, Differentiable},
+    processor::cpu::DifferentiatedCPUContext, random,
+};
+
+pub fn uniform<SEED: Operation<u32>, COUNTER: Operation<u32>>(
+    seed: SEED,
+    counter: COUNTER,
+) -> OperationWrapper<f32, Uniform<SEED, COUNTER>> {
+    OperationWrapper(
+        Uniform {
+            seed,
+            counter,
+        },
+        PhantomData,
+    )
+}
+
+/// Uniformly distributed value in `[0, 1)`, see [`random::uniform`].
+#[derive(Clone, Debug)]
+pub struct Uniform<SEED: Operation<u32>, COUNTER: Operation<u32>> {
+    pub seed: SEED,
+    pub counter: COUNTER,
+}
+
+impl<SEED: Operation<u32>, COUNTER: Operation<u32>> Operation<f32> for Uniform<SEED, COUNTER> {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> f32 {
+        random::uniform(self.seed.evaluate(context), self.counter.evaluate(context))
+    }
+}
+
+impl<SEED: Operation<u32>, COUNTER: Operation<u32>> Differentiable<f32> for Uniform<SEED, COUNTER> {
+    type Diff = f32;
+
+    fn auto_diff_for<R1: Clone>(&self, _var: super::var::Variable<R1>, _var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        0.0
+    }
+    fn contains_var<R1: Clone>(&self, _var: super::var::Variable<R1>) -> bool {
+        false
+    }
+}
@@ -1,11 +1,11 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, collections::HashMap};
 
 use crate::core::{
-    operation::{Operation, OperationWrapper},
-    types::{Void}, processor::cpu::DifferentiatedCPUContext,
+    operation::{Operation, OperationWrapper, Differentiable},
+    types::{Void, Either}, processor::cpu::DifferentiatedCPUContext,
 };
 
-use super::{scope::Scope};
+use super::{scope::Scope, foreach::differentiate_body, var::Variable};
 
 pub fn until<CONDITION: Operation<bool>, A: Operation<Void>, B: Operation<Void>>(
     condition: CONDITION,
@@ -28,7 +28,29 @@ pub struct Until<CONDITION: Operation<bool>, A: Operation<Void>, B: Operation<Vo
 }
 
 impl<CONDITION: Operation<bool>, A: Operation<Void>, B: Operation<Void>> Operation<Void> for Until<CONDITION, A, B> {
-    fn evaluate(&self, _context: &mut DifferentiatedCPUContext) -> Void {
-        todo!()
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Void {
+        while !self.condition.evaluate(context) {
+            self.scope.evaluate(context);
+
+            if context.is_in_return_state() {
+                break;
+            }
+        }
+        Void
+    }
+}
+
+impl<CONDITION: Operation<bool>, A: Differentiable<Void>, B: Differentiable<Void>> Differentiable<Void> for Until<CONDITION, A, B> {
+    type Diff = Until<CONDITION, Either<A::Diff, A>, Either<B::Diff, B>>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
+        Until {
+            condition: self.condition.clone(),
+            scope: differentiate_body(&self.scope, var, var_trace),
+        }
+    }
+
+    fn contains_var<R1: Clone>(&self, var: Variable<R1>) -> bool {
+        self.scope.contains_var(var)
     }
 }
@@ -1,6 +1,6 @@
 use std::{marker::PhantomData, collections::HashMap};
 
-use crate::core::{types::{Computable, Either}, operation::{Operation, Differentiable}, type_traits::{GetAndSetable, IndexAble}, processor::cpu::DifferentiatedCPUContext};
+use crate::core::{types::{Computable, Either}, operation::{Operation, Differentiable}, type_traits::{GetAndSetable, IndexAble, ReferenceAble}, processor::cpu::DifferentiatedCPUContext};
 
 #[derive(Clone, Debug)]
 pub struct Variable<R: Clone> {
@@ -17,10 +17,43 @@ impl<R: Clone> Variable<R> {
     }
 }
 
+/// Key of the differentiation order in a `var_trace`, set by
+/// [`differantiate_for`](crate::core::DifferentiableProgram::differantiate_for).
+pub(crate) const ORDER_TRACE: &str = "#order";
+
+/// Key of the `u32` variable holding the element index in a `var_trace`, set by
+/// [`differantiate_for_element`](crate::core::DifferentiableProgram::differantiate_for_element).
+pub(crate) const ELEMENT_TRACE: &str = "#element";
+
+/// Key of the reasons a `var_trace` can't be differentiated, the derivative fails to build with the
+/// first one.
+pub(crate) const UNSUPPORTED_TRACE: &str = "#unsupported";
+
+/// Key of the local the derivative of a written tensor element is set to in a `var_trace`, instead
+/// of writing the element. Used by the programs of [`derivatives`](crate::core::derivatives).
+pub(crate) const OUTPUT_TRACE: &str = "#output";
+
+/// Name of the local holding the derivative of the local `reference` for `var`.
+///
+/// Differentiating the same variable twice declares a new set of derivative locals, the order keeps
+/// them apart from the ones of the previous differentiation.
+pub fn tangent(reference: &str, var: &str, var_trace: &HashMap<String, Vec<String>>) -> String {
+    let order = var_trace.get(ORDER_TRACE)
+        .and_then(|order| order.first())
+        .map(String::as_str)
+        .unwrap_or("0");
+    format!("{}_d{}_{}", reference, order, var)
+}
+
+/// Whether `reference` was assigned something depending on `var`, which makes its derivative a local.
+pub fn traced(reference: &str, var: &str, var_trace: &HashMap<String, Vec<String>>) -> bool {
+    var_trace.get(reference).map_or(false, |trace| trace.iter().any(|t| t == var))
+}
+
 impl<R: Computable> Operation<R> for Variable<R> {
-    fn evaluate(&self, _context: &mut DifferentiatedCPUContext) -> R {
-        unimplemented!("Probably never called?")
-     }
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> R {
+        *context.get::<R>(&self.reference)
+    }
 }
 
 impl<R: Computable> Differentiable<R> for Variable<R> {
@@ -29,12 +62,8 @@ impl<R: Computable> Differentiable<R> for Variable<R> {
     fn auto_diff_for<R1: Clone>(&self, var: Variable<R1>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff {
         if self.reference == var.reference {
             Either::B(R::from_int(1))
-        } else if let Some(trace) = var_trace.get(&self.reference) {
-            if trace.iter().any(|t| t == &var.reference) {
-                Either::A(self.clone())
-            } else {
-                Either::B(R::from_int(0))
-            }
+        } else if traced(&self.reference, &var.reference, var_trace) {
+            Either::A(Variable::new(&tangent(&self.reference, &var.reference, var_trace)))
         } else {
             Either::B(R::from_int(0))
         }
@@ -49,15 +78,29 @@ impl<R: Computable> GetAndSetable<R> for Variable<R> {
     fn get_variable(&self) -> Option<String> {
         Some(self.reference.clone())
     }
+
+    fn with_variable(&self, reference: &str) -> Self {
+        Variable::new(reference)
+    }
 }
 
 impl<I: IndexAble> IndexAble for Variable<I> {
     type IndexResult = I::IndexResult;
 
-    fn index<O: Operation<u32>>(&self, _index: O) -> Self::IndexResult {
-        todo!()
+    fn get_field() -> Option<String> {
+        I::get_field()
+    }
+
+    fn get_element<R: Computable>(context: &DifferentiatedCPUContext, reference: &str, index: usize) -> R {
+        I::get_element(context, reference, index)
     }
 
+    fn set_element<R: Computable>(context: &mut DifferentiatedCPUContext, reference: &str, index: usize, value: R) {
+        I::set_element(context, reference, index, value)
+    }
+}
+
+impl<I: IndexAble> ReferenceAble for Variable<I> {
     fn get_reference(&self) -> String {  
         if let Some(x) = I::get_field() {
             format!("{}.{}", self.reference.clone(), x)
@@ -66,7 +109,7 @@ impl<I: IndexAble> IndexAble for Variable<I> {
         }   
     }
 
-    fn get_field() -> Option<String> {
-        I::get_field()
+    fn with_variable(&self, reference: &str) -> Self {
+        Variable::new(reference)
     }
 }
\ No newline at end of file
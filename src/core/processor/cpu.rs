@@ -1,17 +1,34 @@
 use std::{any::Any, fmt::Debug};
 
-use crate::{core::{any_map::AnyMap, allocated::{ExecutableBindings, ToRawInputs}, types::{Pos}, type_traits::{MemoryMapable, FromMut}}, processor::cpu::CPUStorage};
+use crate::{core::{any_map::AnyMap, allocated::{ExecutableBindings, ToRawInputs}, types::{Computable, Pos, Value, Void}, debug::{DebugBuffer, DebugKind, DebugRecord, DebugValue}, type_traits::{MemoryMapable, FromMut}}, processor::cpu::CPUStorage, types::tensor::Tensor};
 
 use super::{Storage};
 
 
 pub trait CPUFunction<B: ExecutableBindings<CPUStorage>>: Send + Sync + Clone + Debug {
-    fn call_cpu<'a, 'b>(&self, pos: &Pos, inputs: &<<B as ExecutableBindings<CPUStorage>>::I as ToRawInputs>::RawDerefed<'a>, output: &mut <<<B as ExecutableBindings<CPUStorage>>::O as MemoryMapable<CPUStorage>>::Mapped<'b> as FromMut<<CPUStorage as Storage>::MappedType<<B as ExecutableBindings<CPUStorage>>::O>>>::Result<'b>);
+    fn call_cpu<'a, 'b>(&self, pos: &Pos, inputs: &<<B as ExecutableBindings<CPUStorage>>::I as ToRawInputs>::RawDerefed<'a>, output: &mut <<<B as ExecutableBindings<CPUStorage>>::O as MemoryMapable<CPUStorage>>::Mapped<'b> as FromMut<<CPUStorage as Storage>::MappedType<<B as ExecutableBindings<CPUStorage>>::O>>>::Result<'b>, debug: &DebugBuffer);
 }
 
-pub struct DifferentiatedCPUContext(AnyMap<String>, bool);
+const RETURN_VALUE: &str = "return";
+
+/// Values of the variables while evaluating an operation tree on the host.
+///
+/// `debug_print!` and `assert!` records are collected like on the CPU processor, their messages
+/// are looked up in the ones set with [`DifferentiatedCPUContext::set_debug_messages`].
+pub struct DifferentiatedCPUContext(AnyMap<String>, bool, DebugBuffer, Vec<&'static str>);
+
+impl Default for DifferentiatedCPUContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl DifferentiatedCPUContext {
+    pub fn new() -> Self {
+        DifferentiatedCPUContext(AnyMap::new(), false, DebugBuffer::new(), Vec::new())
+    }
+
+    /// Panics unless a `K` was set for `reference`.
     pub fn get<K: Any>(&self, reference: &str) -> &K {
         self.0.get(reference.to_string()).unwrap()
     }
@@ -28,6 +45,47 @@ impl DifferentiatedCPUContext {
         self.0.remove::<K>(reference.to_string());
     }
 
+    /// The tensor argument `reference`, panics if no `Tensor<C>` was set for it.
+    pub fn tensor<C: Computable>(&self, reference: &str) -> &Tensor<C> {
+        self.0.get(reference.to_string())
+            .unwrap_or_else(|| panic!("`{}` is not a Tensor<{}> argument", reference, C::get_type()))
+    }
+
+    pub fn tensor_mut<C: Computable>(&mut self, reference: &str) -> &mut Tensor<C> {
+        self.0.get_mut(reference.to_string())
+            .unwrap_or_else(|| panic!("`{}` is not a Tensor<{}> argument", reference, C::get_type()))
+    }
+
+    /// The messages of the evaluated kernel, see `Buildable::get_debug_messages`.
+    pub fn set_debug_messages(&mut self, messages: Vec<&'static str>) {
+        self.3 = messages;
+    }
+
+    /// Records a `debug_print!` (or a failed `assert!`) at the current `pos`.
+    pub fn record_debug(&mut self, kind: DebugKind, message: u32, values: Vec<DebugValue>) {
+        let pos = Pos {
+            x: self.0.get::<u32>("pos.x".to_string()).copied().unwrap_or(0),
+            y: self.0.get::<u32>("pos.y".to_string()).copied().unwrap_or(0),
+            z: self.0.get::<u32>("pos.z".to_string()).copied().unwrap_or(0),
+        };
+        let message = self.3.get(message as usize).copied().unwrap_or("<unknown debug message>");
+
+        match kind {
+            DebugKind::Print => self.2.print(&pos, message, &values),
+            DebugKind::Assertion => self.2.assert(false, &pos, message, &values),
+        }
+    }
+
+    /// Removes and returns the records written so far.
+    pub fn drain_debug(&mut self) -> Vec<DebugRecord> {
+        self.2.drain()
+    }
+
+    /// Removes and returns the assertion failures written so far.
+    pub fn take_failures(&mut self) -> Vec<DebugRecord> {
+        self.2.take_failures()
+    }
+
     pub fn is_in_return_state(&self) -> bool {
         self.1
     }
@@ -35,4 +93,18 @@ impl DifferentiatedCPUContext {
     pub fn set_return_state(&mut self, state: bool) {
         self.1 = state;
     }
+
+    /// Stores the value of an executed `return` and enters the return state.
+    pub fn set_return_value<R: Value>(&mut self, value: R) {
+        self.0.insert(RETURN_VALUE.to_string(), value);
+        self.1 = true;
+    }
+
+    /// The value of the executed `return`, statements skipped by it evaluate to `Void`.
+    pub fn get_return_value<R: Value>(&self) -> R {
+        self.0.get::<R>(RETURN_VALUE.to_string())
+            .copied()
+            .or_else(Void::downcast::<R>)
+            .unwrap_or_else(|| panic!("returned value is not a {}", R::get_val_type()))
+    }
 }
\ No newline at end of file
@@ -1,11 +1,14 @@
 use std::{any::Any, rc::Rc};
 
-use crate::processor::cpu::{CPUStorage};
+use crate::{processor::cpu::{CPUStorage}, Error};
 
-use crate::core::{type_traits::{MemoryMapable}, allocated::{ExecutableBindings, Binding}, Buildable};
+use crate::core::{type_traits::{MemoryMapable}, allocated::{ExecutableBindings, Binding}, Buildable, debug::DebugRecord};
 
 pub mod cpu;
 
+/// A prepared dispatch borrowing the bound values, see [`Processor::dispatch_scoped`].
+pub type Job<'a> = Box<dyn FnOnce() + Send + 'a>;
+
 pub trait ProcessorInformation where Self: Sized {
     type Storage: Storage;
     type Executable<B: Buildable<Self>>;
@@ -13,14 +16,45 @@ pub trait ProcessorInformation where Self: Sized {
 }
 
 pub trait Processor where Self: Sized + ProcessorInformation {
-    fn build<B: Buildable<Self>>(&mut self, buildable: B) -> Self::Executable<B>;
-    fn alloc<T: MemoryMapable<Self::Storage>>(&mut self, val: T) -> Binding<Self::Storage, T>;
+    fn try_build<B: Buildable<Self>>(&mut self, buildable: B) -> Result<Self::Executable<B>, Error>;
+    fn try_alloc<T: MemoryMapable<Self::Storage>>(&mut self, val: T) -> Result<Binding<Self::Storage, T>, Error>;
+
+    fn try_dealloc<T: MemoryMapable<Self::Storage>>(&mut self, val: Binding<Self::Storage, T>) -> Result<T, Error>;
+    fn try_copy_to_cpu<T: MemoryMapable<Self::Storage>>(&mut self, val: &Binding<Self::Storage, T>) -> Result<T, Error>;
+
+    /// Panicking version of [`Processor::try_build`].
+    fn build<B: Buildable<Self>>(&mut self, buildable: B) -> Self::Executable<B> {
+        self.try_build(buildable).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Panicking version of [`Processor::try_alloc`].
+    fn alloc<T: MemoryMapable<Self::Storage>>(&mut self, val: T) -> Binding<Self::Storage, T> {
+        self.try_alloc(val).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-    fn dealloc<T: MemoryMapable<Self::Storage>>(&mut self, val: Binding<Self::Storage, T>) -> T;
-    fn copy_to_cpu<T: MemoryMapable<Self::Storage>>(&mut self, val: &Binding<Self::Storage, T>) -> T;
+    /// Panicking version of [`Processor::try_dealloc`].
+    fn dealloc<T: MemoryMapable<Self::Storage>>(&mut self, val: Binding<Self::Storage, T>) -> T {
+        self.try_dealloc(val).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Panicking version of [`Processor::try_copy_to_cpu`].
+    fn copy_to_cpu<T: MemoryMapable<Self::Storage>>(&mut self, val: &Binding<Self::Storage, T>) -> T {
+        self.try_copy_to_cpu(val).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-    fn dispatch<'a, B: Buildable<Self>>(&'a mut self, executable: &'a mut Self::Executable<B>, x: u32, y: u32, z: u32)
+    /// Runs the executable. Fails if an argument is unbound or a kernel assertion failed.
+    fn dispatch<'a, B: Buildable<Self>>(&'a mut self, executable: &'a mut Self::Executable<B>, x: u32, y: u32, z: u32) -> Result<(), Error>
         where <B::Binding as ExecutableBindings<Self::Storage>>::O: 'static, <B::CPUBinding as ExecutableBindings<CPUStorage>>::O: 'static;
+
+    /// Borrows the bound values and hands the dispatch to `then` instead of running it, so that it can
+    /// run on another thread together with other dispatches. `then` has to run the job before it returns.
+    ///
+    /// Fails like [`Processor::dispatch`], after `then` returned.
+    fn dispatch_scoped<B: Buildable<Self>>(&self, executable: &Self::Executable<B>, x: u32, y: u32, z: u32, then: &mut dyn FnMut(Job<'_>) -> Result<(), Error>) -> Result<(), Error>
+        where <B::Binding as ExecutableBindings<Self::Storage>>::O: 'static, <B::CPUBinding as ExecutableBindings<CPUStorage>>::O: 'static;
+
+    /// Removes and returns the `debug_print!` and `assert!` records written by previous dispatches.
+    fn drain_debug(&mut self) -> Vec<DebugRecord>;
 }
 
 pub trait Executable<S: Storage, B: ExecutableBindings<S>> {
@@ -0,0 +1,71 @@
+//! Counter based random numbers.
+//!
+//! Every value is a pure function of a `seed` and a `counter` (usually derived from `Pos`),
+//! so kernels need no generator state and every invocation can draw independently.
+//! The generator only relies on wrapping `u32` arithmetic and exact float conversions,
+//! which is why the CPU and WGSL implementations produce bit-identical streams.
+
+/// `2^-24`, maps the upper 24 bits of a hash onto `[0, 1)`.
+const UNIFORM_SCALE: f32 = 5.9604645e-8;
+/// `2^-20`, scale of a single 20 bit summand of [`normal`].
+const NORMAL_SCALE: f32 = 9.536743e-7;
+/// Number of uniform summands used by [`normal`].
+pub const NORMAL_SAMPLES: u32 = 12;
+
+/// PCG-RXS-M-XS hash of a single `u32`.
+pub fn pcg_hash(input: u32) -> u32 {
+    let state = input.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+/// Random `u32` for the given `seed` and `counter`.
+pub fn random(seed: u32, counter: u32) -> u32 {
+    pcg_hash(seed ^ pcg_hash(counter))
+}
+
+/// Uniformly distributed `f32` in `[0, 1)`.
+pub fn uniform(seed: u32, counter: u32) -> f32 {
+    (random(seed, counter) >> 8) as f32 * UNIFORM_SCALE
+}
+
+/// Approximately normally distributed `f32`.
+///
+/// Uses the Irwin-Hall approximation (sum of twelve uniforms) computed in integers,
+/// so the result is exact on every backend. Unlike Box-Muller it needs no `ln`, `sqrt` or `cos`,
+/// which differ between GPUs. The price are the tails: values are bounded to `mean ± 6 * std`
+/// and anything beyond `± 4 * std` is rarer than for a true normal distribution.
+pub fn normal(seed: u32, counter: u32, mean: f32, std: f32) -> f32 {
+    let stream = pcg_hash(seed);
+    let sum = (0..NORMAL_SAMPLES)
+        .fold(0u32, |sum, i| sum + (random(stream.wrapping_add(i), counter) >> 12));
+
+    mean + std * (sum as f32 * NORMAL_SCALE - 6.0)
+}
+
+/// `true` with probability `p`.
+pub fn bernoulli(seed: u32, counter: u32, p: f32) -> bool {
+    uniform(seed, counter) < p
+}
+
+/// Distribution used to fill a tensor with random values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distribution {
+    /// Uniform in `[low, high)`.
+    Uniform { low: f32, high: f32 },
+    /// Approximately normal with the given mean and standard deviation, see [`normal`].
+    Normal { mean: f32, std: f32 },
+    /// `1.0` with probability `p`, otherwise `0.0`.
+    Bernoulli { p: f32 },
+}
+
+impl Distribution {
+    /// Draws the value for `counter`, matching the kernel intrinsics for the same `seed`.
+    pub fn sample(&self, seed: u32, counter: u32) -> f32 {
+        match *self {
+            Distribution::Uniform { low, high } => low + (high - low) * uniform(seed, counter),
+            Distribution::Normal { mean, std } => normal(seed, counter, mean, std),
+            Distribution::Bernoulli { p } => if bernoulli(seed, counter, p) { 1.0 } else { 0.0 },
+        }
+    }
+}
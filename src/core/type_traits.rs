@@ -1,19 +1,43 @@
-use std::{collections::HashMap, fmt::Debug, marker::PhantomData};
+use std::{collections::HashMap, fmt::Debug, marker::PhantomData, sync::atomic::{AtomicU32, AtomicI32, Ordering}};
 
-use super::{types::{Computable, Value}, operation::{Operation, OperationWrapper, Differentiable}, processor::Storage, allocated::{MemoryLayoutDescriptor, ParallelizationDescriptor}, operations::{var::Variable, call::Call, multiply::{Multiply, multiply}}};
+use super::{types::{Computable, Either, Value}, debug::{DebugValue, Debuggable}, processor::cpu::DifferentiatedCPUContext, operation::{Operation, OperationWrapper, Differentiable}, processor::Storage, allocated::{MemoryLayoutDescriptor, ParallelizationDescriptor}, operations::{var::Variable, call::{Call, call}, function::Function, add::{Add, add}, multiply::{Multiply, multiply}}};
 
 pub trait IndexAble: Clone + Debug {
     type IndexResult;
 
-    fn index<O: Operation<u32>>(&self, index: O) -> Self::IndexResult;
+    fn get_field() -> Option<String>;
+
+    /// Element `index` of the value stored under `reference` while evaluating on the host.
+    fn get_element<R: Computable>(context: &DifferentiatedCPUContext, reference: &str, index: usize) -> R;
 
+    /// Overwrites element `index` of the value stored under `reference` while evaluating on the host.
+    fn set_element<R: Computable>(context: &mut DifferentiatedCPUContext, reference: &str, index: usize, value: R);
+}
+
+/// An indexable value in the operation tree, a tensor argument or a local array.
+pub trait ReferenceAble: IndexAble {
     fn get_reference(&self) -> String;
 
-    fn get_field() -> Option<String>;
+    /// The same access on the local `reference`, which holds the derivative of a local.
+    fn with_variable(&self, reference: &str) -> Self;
 }
 
 pub trait GetAndSetable<R: Computable>: Clone + Debug {
     fn get_variable(&self) -> Option<String>;
+
+    /// The same access on the local `reference`, which holds the derivative of a local.
+    fn with_variable(&self, reference: &str) -> Self;
+
+    /// Stores `value` while evaluating on the host, under the variable name unless overridden.
+    fn set_value(&self, context: &mut DifferentiatedCPUContext, value: R) {
+        let reference = self.get_variable().expect("assignment to something that is not a variable");
+        context.set(&reference, value);
+    }
+
+    /// Whether this is an element of a tensor argument, whose derivative is written even if the value doesn't depend on the variable.
+    fn is_tensor_element(&self) -> bool {
+        false
+    }
 }
 
 pub trait Iterable<R: Computable>: Operation<R> + Clone + Debug {
@@ -24,6 +48,9 @@ pub trait Iterable<R: Computable>: Operation<R> + Clone + Debug {
     fn get_start(&self) -> Self::StartOp;
     fn get_next(&self) -> Self::NextOp;
     fn get_boundry(&self) -> Self::BoundryOp;
+
+    /// The values of the loop variable while evaluating on the host.
+    fn values(&self, context: &mut DifferentiatedCPUContext) -> Vec<R>;
 }
 
 impl<R: Computable, O: Operation<R> + Iterable<R>> Iterable<R> for OperationWrapper<R, O> {
@@ -44,8 +71,16 @@ impl<R: Computable, O: Operation<R> + Iterable<R>> Iterable<R> for OperationWrap
     fn get_boundry(&self) -> Self::BoundryOp {
         self.0.get_boundry()
     }
+
+    fn values(&self, context: &mut DifferentiatedCPUContext) -> Vec<R> {
+        self.0.values(context)
+    }
 }
 
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a kernel function",
+    note = "mark the function with #[ChandraFunction] to call it inside kernels"
+)]
 pub trait ChandraFn {
     type Result: Value;
     type Inputs: FunctionInputs;
@@ -58,16 +93,42 @@ pub trait ChandraExtensionFn {
     type FunctionScope: Operation<Self::Result>;
 }
 
-pub trait FunctionInputs: Clone + Debug {}
+pub trait FunctionInputs: Clone + Debug {
+    /// The inputs passed on as they are, to call another function with the same inputs.
+    type Arguments: CallInputs + CallMatchFunctionInputs<Self>;
 
-impl<R: Computable> FunctionInputs for (Variable<R>,) {}
-impl<R: Computable, R2: Computable> FunctionInputs for (Variable<R>, Variable<R2>) {}
-impl<R: Computable, R2: Computable, R3: Computable> FunctionInputs for (Variable<R>, Variable<R2>, Variable<R3>) {}
-impl<R: Computable, R2: Computable, R3: Computable, R4: Computable> FunctionInputs for (Variable<R>, Variable<R2>, Variable<R3>, Variable<R4>) {}
-impl<R: Computable, R2: Computable, R3: Computable, R4: Computable, R5: Computable> FunctionInputs for (Variable<R>, Variable<R2>, Variable<R3>, Variable<R4>, Variable<R5>) {}
-impl<R: Computable, R2: Computable, R3: Computable, R4: Computable, R5: Computable, R6: Computable> FunctionInputs for (Variable<R>, Variable<R2>, Variable<R3>, Variable<R4>, Variable<R5>, Variable<R6>) {}
-impl<R: Computable, R2: Computable, R3: Computable, R4: Computable, R5: Computable, R6: Computable, R7: Computable> FunctionInputs for (Variable<R>, Variable<R2>, Variable<R3>, Variable<R4>, Variable<R5>, Variable<R6>, Variable<R7>) {}
-impl<R: Computable, R2: Computable, R3: Computable, R4: Computable, R5: Computable, R6: Computable, R7: Computable, R8: Computable> FunctionInputs for (Variable<R>, Variable<R2>, Variable<R3>, Variable<R4>, Variable<R5>, Variable<R6>, Variable<R7>, Variable<R8>) {}
+    fn arguments(&self) -> Self::Arguments;
+    /// Position of the input named `reference`.
+    fn position(&self, reference: &str) -> Option<usize>;
+}
+
+macro_rules! impl_function_inputs {
+    ($(($r:ident, $i:tt)),+) => {
+        impl<$($r: Computable),+> FunctionInputs for ($(Variable<$r>,)+) {
+            type Arguments = ($(OperationWrapper<$r, Variable<$r>>,)+);
+
+            fn arguments(&self) -> Self::Arguments {
+                ($(OperationWrapper(self.$i.clone(), PhantomData),)+)
+            }
+
+            fn position(&self, reference: &str) -> Option<usize> {
+                $(if self.$i.reference == reference {
+                    return Some($i);
+                })+
+                None
+            }
+        }
+    };
+}
+
+impl_function_inputs!((R, 0));
+impl_function_inputs!((R, 0), (R2, 1));
+impl_function_inputs!((R, 0), (R2, 1), (R3, 2));
+impl_function_inputs!((R, 0), (R2, 1), (R3, 2), (R4, 3));
+impl_function_inputs!((R, 0), (R2, 1), (R3, 2), (R4, 3), (R5, 4));
+impl_function_inputs!((R, 0), (R2, 1), (R3, 2), (R4, 3), (R5, 4), (R6, 5));
+impl_function_inputs!((R, 0), (R2, 1), (R3, 2), (R4, 3), (R5, 4), (R6, 5), (R7, 6));
+impl_function_inputs!((R, 0), (R2, 1), (R3, 2), (R4, 3), (R5, 4), (R6, 5), (R7, 6), (R8, 7));
 
 
 pub trait CallInputs: Clone + Debug {}
@@ -82,73 +143,161 @@ impl<R: Computable, O: Operation<R>, R2: Computable, O2: Operation<R2>, R3: Comp
 impl<R: Computable, O: Operation<R>, R2: Computable, O2: Operation<R2>, R3: Computable, O3: Operation<R3>, R4: Computable, O4: Operation<R4>, R5: Computable, O5: Operation<R5>, R6: Computable, O6: Operation<R6>, R7: Computable, O7: Operation<R7>, R8: Computable, O8: Operation<R8>> CallInputs for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>,OperationWrapper<R4, O4>, OperationWrapper<R5, O5>, OperationWrapper<R6, O6>, OperationWrapper<R7, O7>, OperationWrapper<R8, O8>) {}
 
 
-pub trait CallMatchFunctionInputs<F: FunctionInputs>: Clone {}
-impl<R: Computable, O: Operation<R>> CallMatchFunctionInputs<(Variable<R>,)> for (OperationWrapper<R, O>,) {}
-impl<R: Computable, O: Operation<R>, R2: Computable, O2: Operation<R2>> CallMatchFunctionInputs<(Variable<R>, Variable<R2>)> for (OperationWrapper<R, O>, OperationWrapper<R2, O2>) {}
-impl<R: Computable, O: Operation<R>, R2: Computable, O2: Operation<R2>, R3: Computable, O3: Operation<R3>> CallMatchFunctionInputs<(Variable<R>, Variable<R2>, Variable<R3>)> for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>) {}
-impl<R: Computable, O: Operation<R>, R2: Computable, O2: Operation<R2>, R3: Computable, O3: Operation<R3>, R4: Computable, O4: Operation<R4>> CallMatchFunctionInputs<(Variable<R>, Variable<R2>, Variable<R3>, Variable<R4>)> for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>, OperationWrapper<R4, O4>) {}
-impl<R: Computable, O: Operation<R>, R2: Computable, O2: Operation<R2>, R3: Computable, O3: Operation<R3>, R4: Computable, O4: Operation<R4>, R5: Computable, O5: Operation<R5>> CallMatchFunctionInputs<(Variable<R>, Variable<R2>, Variable<R3>, Variable<R4>, Variable<R5>)> for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>,OperationWrapper<R4, O4>, OperationWrapper<R5, O5>) {}
-impl<R: Computable, O: Operation<R>, R2: Computable, O2: Operation<R2>, R3: Computable, O3: Operation<R3>, R4: Computable, O4: Operation<R4>, R5: Computable, O5: Operation<R5>, R6: Computable, O6: Operation<R6>> CallMatchFunctionInputs<(Variable<R>, Variable<R2>, Variable<R3>, Variable<R4>, Variable<R5>, Variable<R6>)> for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>,OperationWrapper<R4, O4>, OperationWrapper<R5, O5>, OperationWrapper<R6, O6>) {}
-impl<R: Computable, O: Operation<R>, R2: Computable, O2: Operation<R2>, R3: Computable, O3: Operation<R3>, R4: Computable, O4: Operation<R4>, R5: Computable, O5: Operation<R5>, R6: Computable, O6: Operation<R6>, R7: Computable, O7: Operation<R7>> CallMatchFunctionInputs<(Variable<R>, Variable<R2>, Variable<R3>, Variable<R4>, Variable<R5>, Variable<R6>, Variable<R7>)> for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>,OperationWrapper<R4, O4>, OperationWrapper<R5, O5>, OperationWrapper<R6, O6>, OperationWrapper<R7, O7>) {}
-impl<R: Computable, O: Operation<R>, R2: Computable, O2: Operation<R2>, R3: Computable, O3: Operation<R3>, R4: Computable, O4: Operation<R4>, R5: Computable, O5: Operation<R5>, R6: Computable, O6: Operation<R6>, R7: Computable, O7: Operation<R7>, R8: Computable, O8: Operation<R8>> CallMatchFunctionInputs<(Variable<R>, Variable<R2>, Variable<R3>, Variable<R4>, Variable<R5>, Variable<R6>, Variable<R7>, Variable<R8>)> for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>,OperationWrapper<R4, O4>, OperationWrapper<R5, O5>, OperationWrapper<R6, O6>, OperationWrapper<R7, O7>, OperationWrapper<R8, O8>) {}
+pub trait DebugValues: Clone + Debug {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Vec<DebugValue>;
+}
+
+impl DebugValues for () {
+    fn evaluate(&self, _context: &mut DifferentiatedCPUContext) -> Vec<DebugValue> {
+        Vec::new()
+    }
+}
+impl<R: Debuggable, O: Operation<R>> DebugValues for (OperationWrapper<R, O>,) {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Vec<DebugValue> {
+        vec![self.0.evaluate(context).to_debug_value()]
+    }
+}
+impl<R: Debuggable, O: Operation<R>, R2: Debuggable, O2: Operation<R2>> DebugValues for (OperationWrapper<R, O>, OperationWrapper<R2, O2>) {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Vec<DebugValue> {
+        vec![self.0.evaluate(context).to_debug_value(), self.1.evaluate(context).to_debug_value()]
+    }
+}
+impl<R: Debuggable, O: Operation<R>, R2: Debuggable, O2: Operation<R2>, R3: Debuggable, O3: Operation<R3>> DebugValues for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>) {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Vec<DebugValue> {
+        vec![self.0.evaluate(context).to_debug_value(), self.1.evaluate(context).to_debug_value(), self.2.evaluate(context).to_debug_value()]
+    }
+}
+impl<R: Debuggable, O: Operation<R>, R2: Debuggable, O2: Operation<R2>, R3: Debuggable, O3: Operation<R3>, R4: Debuggable, O4: Operation<R4>> DebugValues for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>, OperationWrapper<R4, O4>) {
+    fn evaluate(&self, context: &mut DifferentiatedCPUContext) -> Vec<DebugValue> {
+        vec![self.0.evaluate(context).to_debug_value(), self.1.evaluate(context).to_debug_value(), self.2.evaluate(context).to_debug_value(), self.3.evaluate(context).to_debug_value()]
+    }
+}
+
+pub trait CallMatchFunctionInputs<F: FunctionInputs>: Clone {
+    /// Evaluates the arguments in `caller` and binds them to the function inputs in `callee`.
+    fn bind(&self, inputs: &F, caller: &mut DifferentiatedCPUContext, callee: &mut DifferentiatedCPUContext);
+}
+
+macro_rules! impl_call_match_function_inputs {
+    ($(($r:ident, $o:ident, $i:tt)),+) => {
+        impl<$($r: Computable, $o: Operation<$r>),+> CallMatchFunctionInputs<($(Variable<$r>,)+)> for ($(OperationWrapper<$r, $o>,)+) {
+            fn bind(&self, inputs: &($(Variable<$r>,)+), caller: &mut DifferentiatedCPUContext, callee: &mut DifferentiatedCPUContext) {
+                $(callee.set(&inputs.$i.reference, self.$i.evaluate(caller));)+
+            }
+        }
+    };
+}
+
+impl_call_match_function_inputs!((R, O, 0));
+impl_call_match_function_inputs!((R, O, 0), (R2, O2, 1));
+impl_call_match_function_inputs!((R, O, 0), (R2, O2, 1), (R3, O3, 2));
+impl_call_match_function_inputs!((R, O, 0), (R2, O2, 1), (R3, O3, 2), (R4, O4, 3));
+impl_call_match_function_inputs!((R, O, 0), (R2, O2, 1), (R3, O3, 2), (R4, O4, 3), (R5, O5, 4));
+impl_call_match_function_inputs!((R, O, 0), (R2, O2, 1), (R3, O3, 2), (R4, O4, 3), (R5, O5, 4), (R6, O6, 5));
+impl_call_match_function_inputs!((R, O, 0), (R2, O2, 1), (R3, O3, 2), (R4, O4, 3), (R5, O5, 4), (R6, O6, 5), (R7, O7, 6));
+impl_call_match_function_inputs!((R, O, 0), (R2, O2, 1), (R3, O3, 2), (R4, O4, 3), (R5, O5, 4), (R6, O6, 5), (R7, O7, 6), (R8, O8, 7));
 
 pub trait DiffableFunctionInputs<R: Computable, F: FunctionInputs>: CallInputs + CallMatchFunctionInputs<F> {
     type Diff<OF: Differentiable<R>>: Operation<R>;
 
     fn contains_var<RV: Clone>(&self, var: Variable<RV>) -> bool;
-    fn map_variable<RV: Clone>(&self, var: Variable<RV>, f_inputs: F) -> Option<Variable<RV>>;
-    fn auto_diff_for<RV: Clone, O: Differentiable<R>>(&self, call: Call<R, F, Self, O>, var: Variable<RV>, og: Variable<RV>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff<O>;
+    /// The chain rule, the sum over all arguments of the partial derivative of the function for the
+    /// argument times the derivative of the argument.
+    fn auto_diff_for<RV: Clone, O: Differentiable<R>>(&self, call: Call<R, F, Self, O>, var: Variable<RV>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff<O>;
 }
 
-impl<R: Calculatable, O: Differentiable<R>> DiffableFunctionInputs<R, (Variable<R>,)> for (OperationWrapper<R, O>,) {
-    type Diff<OF: Differentiable<R>> = OperationWrapper<R, Multiply<R, Call<R, (Variable<R>,), Self, OF::Diff>, OperationWrapper<R, O::Diff>>>;
+/// `∂f/∂xᵢ · dxᵢ`, zero if the argument doesn't depend on the variable or the function not on the input.
+pub type ChainTerm<R, F, C, OF, O> = Either<OperationWrapper<R, Multiply<R, Call<R, F, C, <OF as Differentiable<R>>::Diff>, OperationWrapper<R, <O as Differentiable<R>>::Diff>>>, R>;
 
-    fn contains_var<RV: Clone>(&self, var: Variable<RV>) -> bool {
-        self.0.contains_var(var)
-    }
+macro_rules! chain_sum_type {
+    ($term:ty) => { $term };
+    ($term:ty, $($tail:ty),+) => { OperationWrapper<R, Add<R, $term, chain_sum_type!($($tail),+)>> };
+}
+
+macro_rules! chain_sum {
+    ($term:expr) => { $term };
+    ($term:expr, $($tail:expr),+) => { add($term, chain_sum!($($tail),+)) };
+}
 
-    fn map_variable<RV: Clone>(&self, var: Variable<RV>, f_inputs: (Variable<R>,)) -> Option<Variable<RV>> {
-        if self.0.contains_var(var.clone()) {
-            let mut result = var;
-            result.reference = f_inputs.0.reference.clone();
-            Some(result)
-        } else {
-            None
+macro_rules! impl_diffable_function_inputs {
+    ($inputs:ty; $(($o:ident, $i:tt)),+) => {
+        impl<R: Calculatable, $($o: Differentiable<R>),+> DiffableFunctionInputs<R, $inputs> for ($(OperationWrapper<R, $o>,)+) {
+            type Diff<OF: Differentiable<R>> = chain_sum_type!($(ChainTerm<R, $inputs, Self, OF, $o>),+);
+
+            fn contains_var<RV: Clone>(&self, var: Variable<RV>) -> bool {
+                $(self.$i.contains_var(var.clone()))||+
+            }
+
+            fn auto_diff_for<RV: Clone, OF: Differentiable<R>>(&self, call: Call<R, $inputs, Self, OF>, var: Variable<RV>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff<OF> {
+                chain_sum!($({
+                    let input = call.function.inputs.$i.clone();
+                    if self.$i.contains_var(var.clone()) && call.function.contains_var(input.clone()) {
+                        let partial = Call { inputs: self.clone(), function: call.function.auto_diff_for(input, var_trace) };
+                        Either::A(multiply(partial, OperationWrapper(self.$i.0.auto_diff_for(var.clone(), var_trace), PhantomData)))
+                    } else {
+                        Either::B(R::get_zero())
+                    }
+                }),+)
+            }
         }
-    }
+    };
+}
 
-    fn auto_diff_for<RV: Clone, OF: Differentiable<R>>(&self, call: Call<R, (Variable<R>,), Self, OF>, var: Variable<RV>, og: Variable<RV>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff<OF> {
-        multiply(Call { inputs: self.clone(), function: call.function.auto_diff_for(var.clone(), var_trace) }, OperationWrapper(self.0.0.auto_diff_for(og, var_trace), PhantomData)) //(OperationWrapper(self.0.0.auto_diff_for(var, var_trace), PhantomData),)
-    }
+impl_diffable_function_inputs!((Variable<R>,); (O, 0));
+impl_diffable_function_inputs!((Variable<R>, Variable<R>); (O, 0), (O2, 1));
+impl_diffable_function_inputs!((Variable<R>, Variable<R>, Variable<R>); (O, 0), (O2, 1), (O3, 2));
+impl_diffable_function_inputs!((Variable<R>, Variable<R>, Variable<R>, Variable<R>); (O, 0), (O2, 1), (O3, 2), (O4, 3));
+impl_diffable_function_inputs!((Variable<R>, Variable<R>, Variable<R>, Variable<R>, Variable<R>); (O, 0), (O2, 1), (O3, 2), (O4, 3), (O5, 4));
+impl_diffable_function_inputs!((Variable<R>, Variable<R>, Variable<R>, Variable<R>, Variable<R>, Variable<R>); (O, 0), (O2, 1), (O3, 2), (O4, 3), (O5, 4), (O6, 5));
+impl_diffable_function_inputs!((Variable<R>, Variable<R>, Variable<R>, Variable<R>, Variable<R>, Variable<R>, Variable<R>); (O, 0), (O2, 1), (O3, 2), (O4, 3), (O5, 4), (O6, 5), (O7, 6));
+impl_diffable_function_inputs!((Variable<R>, Variable<R>, Variable<R>, Variable<R>, Variable<R>, Variable<R>, Variable<R>, Variable<R>); (O, 0), (O2, 1), (O3, 2), (O4, 3), (O5, 4), (O6, 5), (O7, 6), (O8, 7));
+
+/// Hand-written partial derivatives of a function, one function per input taking the same inputs.
+///
+/// `()` has no partials, the function is not differentiable and its derivative is zero.
+pub trait Partials<R: Computable, F: FunctionInputs>: Clone + Debug {
+    type Diff: Operation<R>;
+    const DIFFERENTIABLE: bool;
+
+    /// The partial derivative for the input at `position`, zero for `None`.
+    fn partial(&self, inputs: &F, position: Option<usize>) -> Self::Diff;
 }
 
-impl<R: Calculatable, O: Differentiable<R>, O2: Differentiable<R>> DiffableFunctionInputs<R, (Variable<R>, Variable<R>)> for (OperationWrapper<R, O>, OperationWrapper<R, O2>) {
-    type Diff<OF: Differentiable<R>> = OperationWrapper<R, Multiply<R, Call<R, (Variable<R>, Variable<R>), Self, OF::Diff>, OperationWrapper<R, Multiply<R, OperationWrapper<R, O::Diff>, OperationWrapper<R, O2::Diff>>>>>;
+impl<R: Computable, F: FunctionInputs> Partials<R, F> for () {
+    type Diff = R;
+    const DIFFERENTIABLE: bool = false;
 
-    fn contains_var<RV: Clone>(&self, var: Variable<RV>) -> bool {
-        self.0.contains_var(var.clone()) || self.1.contains_var(var)
+    fn partial(&self, _inputs: &F, _position: Option<usize>) -> Self::Diff {
+        R::get_zero()
     }
+}
 
-    fn map_variable<RV: Clone>(&self, var: Variable<RV>, f_inputs: (Variable<R>, Variable<R>)) -> Option<Variable<RV>> {
-        if self.0.contains_var(var.clone()) {
-            let mut result = var;
-            result.reference = f_inputs.0.reference.clone();
-            Some(result)
-        } else if self.1.contains_var(var.clone()) {
-            let mut result = var;
-            result.reference = f_inputs.1.reference.clone();
-            Some(result)
-        } else {
-            None
+macro_rules! impl_partials {
+    ($s:ident $value:ident $(, $tail:ident $tail_value:ident)*) => {
+        impl<R: Computable, F: FunctionInputs, $s: Operation<R>, $($tail: Operation<R>),*> Partials<R, F> for (Function<R, F, $s>, $(Function<R, F, $tail>,)*) {
+            type Diff = Either<OperationWrapper<R, Call<R, F, F::Arguments, $s>>, <($(Function<R, F, $tail>,)*) as Partials<R, F>>::Diff>;
+            const DIFFERENTIABLE: bool = true;
+
+            fn partial(&self, inputs: &F, position: Option<usize>) -> Self::Diff {
+                let ($value, $($tail_value,)*) = self.clone();
+                match position {
+                    Some(0) => Either::A(call(inputs.arguments(), $value)),
+                    _ => Either::B(($($tail_value,)*).partial(inputs, position.map(|p| p - 1))),
+                }
+            }
         }
-    }
-
-    fn auto_diff_for<RV: Clone, OF: Differentiable<R>>(&self, call: Call<R, (Variable<R>, Variable<R>), Self, OF>, var: Variable<RV>, og: Variable<RV>, var_trace: &mut HashMap<String, Vec<String>>) -> Self::Diff<OF> {
-        multiply(Call { inputs: self.clone(), function: call.function.auto_diff_for(var.clone(), var_trace) }, multiply(OperationWrapper(self.0.0.auto_diff_for(og.clone(), var_trace), PhantomData), OperationWrapper(self.1.0.auto_diff_for(og, var_trace), PhantomData))) 
-    }
-
+    };
 }
 
+impl_partials!(S1 s1);
+impl_partials!(S1 s1, S2 s2);
+impl_partials!(S1 s1, S2 s2, S3 s3);
+impl_partials!(S1 s1, S2 s2, S3 s3, S4 s4);
+impl_partials!(S1 s1, S2 s2, S3 s3, S4 s4, S5 s5);
+impl_partials!(S1 s1, S2 s2, S3 s3, S4 s4, S5 s5, S6 s6);
+impl_partials!(S1 s1, S2 s2, S3 s3, S4 s4, S5 s5, S6 s6, S7 s7);
+impl_partials!(S1 s1, S2 s2, S3 s3, S4 s4, S5 s5, S6 s6, S7 s7, S8 s8);
+
 
 pub trait Generalizable: Clone + Send + Sync {
     fn get_memory_layout() -> MemoryLayoutDescriptor;
@@ -186,3 +335,226 @@ pub trait Calculatable: Computable + ::core::ops::Add<Self, Output = Self> + ::c
 impl<C: Computable> Calculatable for C where C: ::core::ops::Add<C, Output = C> + ::core::ops::Sub<C, Output = C> + ::core::ops::Mul<C, Output = C> + std::cmp::PartialOrd + ::core::ops::Div<C, Output = C> {
 
 }
+
+/// Computables that can be converted into `To` with the semantics of Rust's `as`.
+pub trait Castable<To: Computable>: Computable {
+    /// Whether the derivative passes through the conversion, true only between float types.
+    const PRESERVES_DERIVATIVE: bool;
+
+    fn cast(self) -> To;
+}
+
+macro_rules! impl_castable {
+    ($from:ty => $($to:ty: $preserves:expr),*) => {
+        $(
+            impl Castable<$to> for $from {
+                const PRESERVES_DERIVATIVE: bool = $preserves;
+
+                fn cast(self) -> $to {
+                    self as $to
+                }
+            }
+        )*
+    };
+}
+
+impl_castable!(f32 => f32: true, f64: true, i32: false, i64: false, u32: false, u64: false);
+impl_castable!(f64 => f32: true, f64: true, i32: false, i64: false, u32: false, u64: false);
+impl_castable!(i32 => f32: false, f64: false, i32: false, i64: false, u32: false, u64: false);
+impl_castable!(i64 => f32: false, f64: false, i32: false, i64: false, u32: false, u64: false);
+impl_castable!(u32 => f32: false, f64: false, i32: false, i64: false, u32: false, u64: false);
+impl_castable!(u64 => f32: false, f64: false, i32: false, i64: false, u32: false, u64: false);
+impl_castable!(bool => bool: false, i32: false, i64: false, u32: false, u64: false);
+
+/// Calculatables with a sign, for `x.abs()` in kernels.
+pub trait Signed: Calculatable {
+    fn abs(self) -> Self;
+
+    /// `-1`, `0` or `1`, zero maps to zero like WGSL's `sign`.
+    fn sign(self) -> Self;
+}
+
+/// Floating point calculatables, for `x.sqrt()`, `x.exp()`, `x.ln()` and `x.tanh()` in kernels.
+pub trait Float: Signed {
+    fn sqrt(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn tanh(self) -> Self;
+
+    /// The value as `f64`, for host side comparisons like [`gradcheck`](super::gradcheck).
+    fn to_float(self) -> f64;
+}
+
+macro_rules! impl_signed {
+    ($($t:ty),*) => {
+        $(
+            impl Signed for $t {
+                fn abs(self) -> Self {
+                    <$t>::abs(self)
+                }
+
+                fn sign(self) -> Self {
+                    if self > Self::get_zero() {
+                        Self::from_int(1)
+                    } else if self < Self::get_zero() {
+                        Self::from_int(-1)
+                    } else {
+                        Self::get_zero()
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_signed!(f32, f64, i32, i64);
+
+impl Float for f32 {
+    fn sqrt(self) -> Self {
+        f32::sqrt(self)
+    }
+
+    fn exp(self) -> Self {
+        f32::exp(self)
+    }
+
+    fn ln(self) -> Self {
+        f32::ln(self)
+    }
+
+    fn tanh(self) -> Self {
+        f32::tanh(self)
+    }
+
+    fn to_float(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Float for f64 {
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+
+    fn exp(self) -> Self {
+        f64::exp(self)
+    }
+
+    fn ln(self) -> Self {
+        f64::ln(self)
+    }
+
+    fn tanh(self) -> Self {
+        f64::tanh(self)
+    }
+
+    fn to_float(self) -> f64 {
+        self
+    }
+}
+
+/// `a.dot(b)` on fixed-size arrays in kernels.
+pub trait Dot {
+    type Output;
+
+    fn dot(self, other: Self) -> Self::Output;
+}
+
+impl<T: Calculatable, const N: usize> Dot for [T; N] {
+    type Output = T;
+
+    fn dot(self, other: Self) -> T {
+        self.iter().zip(other.iter()).fold(T::get_zero(), |acc, (a, b)| acc + *a * *b)
+    }
+}
+
+/// The tensor methods available in kernels, `t.len()` and `t.shape(d)`.
+pub trait KernelTensor {
+    /// The number of elements.
+    fn len(&self) -> u32;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The size of dimension `dimension`.
+    fn shape(&self, dimension: u32) -> u32;
+}
+
+/// Computables that can be updated atomically in place.
+///
+/// The orderings are `Relaxed` on purpose, WGSL only offers relaxed atomics and both processors
+/// should behave the same.
+pub trait AtomicComputable: Calculatable {
+    type Atomic: Send + Sync;
+
+    /// # Safety
+    /// `ptr` has to be valid, aligned and only be accessed atomically while the reference is alive.
+    unsafe fn as_atomic<'a>(ptr: *mut Self) -> &'a Self::Atomic;
+
+    fn atomic_add(atomic: &Self::Atomic, val: Self) -> Self;
+    fn atomic_min(atomic: &Self::Atomic, val: Self) -> Self;
+    fn atomic_max(atomic: &Self::Atomic, val: Self) -> Self;
+    fn atomic_exchange(atomic: &Self::Atomic, val: Self) -> Self;
+    fn compare_exchange(atomic: &Self::Atomic, current: Self, new: Self) -> Self;
+}
+
+impl AtomicComputable for u32 {
+    type Atomic = AtomicU32;
+
+    unsafe fn as_atomic<'a>(ptr: *mut Self) -> &'a Self::Atomic {
+        &*(ptr as *const AtomicU32)
+    }
+
+    fn atomic_add(atomic: &Self::Atomic, val: Self) -> Self {
+        atomic.fetch_add(val, Ordering::Relaxed)
+    }
+
+    fn atomic_min(atomic: &Self::Atomic, val: Self) -> Self {
+        atomic.fetch_min(val, Ordering::Relaxed)
+    }
+
+    fn atomic_max(atomic: &Self::Atomic, val: Self) -> Self {
+        atomic.fetch_max(val, Ordering::Relaxed)
+    }
+
+    fn atomic_exchange(atomic: &Self::Atomic, val: Self) -> Self {
+        atomic.swap(val, Ordering::Relaxed)
+    }
+
+    fn compare_exchange(atomic: &Self::Atomic, current: Self, new: Self) -> Self {
+        match atomic.compare_exchange(current, new, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(old) | Err(old) => old
+        }
+    }
+}
+
+impl AtomicComputable for i32 {
+    type Atomic = AtomicI32;
+
+    unsafe fn as_atomic<'a>(ptr: *mut Self) -> &'a Self::Atomic {
+        &*(ptr as *const AtomicI32)
+    }
+
+    fn atomic_add(atomic: &Self::Atomic, val: Self) -> Self {
+        atomic.fetch_add(val, Ordering::Relaxed)
+    }
+
+    fn atomic_min(atomic: &Self::Atomic, val: Self) -> Self {
+        atomic.fetch_min(val, Ordering::Relaxed)
+    }
+
+    fn atomic_max(atomic: &Self::Atomic, val: Self) -> Self {
+        atomic.fetch_max(val, Ordering::Relaxed)
+    }
+
+    fn atomic_exchange(atomic: &Self::Atomic, val: Self) -> Self {
+        atomic.swap(val, Ordering::Relaxed)
+    }
+
+    fn compare_exchange(atomic: &Self::Atomic, current: Self, new: Self) -> Self {
+        match atomic.compare_exchange(current, new, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(old) | Err(old) => old
+        }
+    }
+}
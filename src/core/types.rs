@@ -1,6 +1,6 @@
 use std::{marker::PhantomData, fmt::Debug};
 
-use super::{operation::{Operation, Differentiable}, type_traits::{Generalizable, IndexAble, GetAndSetable}, allocated::MemoryLayoutDescriptor, processor::cpu::DifferentiatedCPUContext};
+use super::{operation::{Operation, Differentiable}, type_traits::{Generalizable, IndexAble, ReferenceAble, GetAndSetable}, allocated::MemoryLayoutDescriptor, processor::cpu::DifferentiatedCPUContext};
 
 pub type Shape = Vec<usize>;
 
@@ -19,6 +19,24 @@ impl<R: Value, A: Operation<R>, B: Operation<R>> Operation<R> for Either<A, B> {
     }
 }
 
+impl<R: Value, A: Differentiable<R>, B: Differentiable<R>> Differentiable<R> for Either<A, B> {
+    type Diff = Either<A::Diff, B::Diff>;
+
+    fn auto_diff_for<R1: Clone>(&self, var: super::operations::var::Variable<R1>, var_trace: &mut std::collections::HashMap<String, Vec<String>>) -> Self::Diff {
+        match self {
+            Either::A(x) => Either::A(x.auto_diff_for(var, var_trace)),
+            Either::B(x) => Either::B(x.auto_diff_for(var, var_trace)),
+        }
+    }
+
+    fn contains_var<R1: Clone>(&self, var: super::operations::var::Variable<R1>) -> bool {
+        match self {
+            Either::A(x) => x.contains_var(var),
+            Either::B(x) => x.contains_var(var),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ExternalWrapper<T: Generalizable> {
     name: String,
@@ -34,27 +52,43 @@ impl<T: Generalizable> ExternalWrapper<T> {
 impl<T: Generalizable> IndexAble for ExternalWrapper<T> where T: IndexAble {
     type IndexResult = T::IndexResult;
 
-    fn index<O: Operation<u32>>(&self, _index: O) -> Self::IndexResult {
-        todo!()
+    fn get_field() -> Option<String> {
+        T::get_field()
+    }
+
+    fn get_element<R: Computable>(context: &DifferentiatedCPUContext, reference: &str, index: usize) -> R {
+        T::get_element(context, reference, index)
     }
 
+    fn set_element<R: Computable>(context: &mut DifferentiatedCPUContext, reference: &str, index: usize, value: R) {
+        T::set_element(context, reference, index, value)
+    }
+}
+
+impl<T: Generalizable> ReferenceAble for ExternalWrapper<T> where T: IndexAble {
     fn get_reference(&self) -> String {
-        todo!()
+        match T::get_field() {
+            Some(field) => format!("{}.{}", self.name, field),
+            None => self.name.clone(),
+        }
     }
 
-    fn get_field() -> Option<String> {
-        todo!()
+    fn with_variable(&self, reference: &str) -> Self {
+        Self::new(reference)
     }
-    
 }
 
 impl<T: Generalizable, R: Computable> GetAndSetable<R> for ExternalWrapper<T> where T: GetAndSetable<R> {
     fn get_variable(&self) -> Option<String> {
-        todo!()
+        Some(self.name.clone())
+    }
+
+    fn with_variable(&self, reference: &str) -> Self {
+        Self::new(reference)
     }
 }
 
-pub trait Value: Clone + Copy + Send + Sync + Debug {
+pub trait Value: Clone + Copy + Send + Sync + Debug + 'static {
     fn get_val_type() -> &'static str;
 }
 
@@ -98,6 +132,12 @@ impl<R: Computable> Operation<R> for R {
     }
 }
 
+/// `literal` with the type of `like`, the CPU version of a generic kernel uses it for float literals left of an operator.
+#[doc(hidden)]
+pub fn literal_like<C: Computable>(_like: &C, literal: f64) -> C {
+    C::from_float(literal)
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Void;
 impl Value for Void {
@@ -106,6 +146,13 @@ impl Value for Void {
     }
 }
 
+impl Void {
+    /// `Void` as `R`, for operations that are only ever evaluated for `R = Void` like `if` without `else`.
+    pub fn downcast<R: Value>() -> Option<R> {
+        (&Void as &dyn std::any::Any).downcast_ref::<R>().copied()
+    }
+}
+
 impl Operation<Void> for Void {
     fn evaluate(&self, _context: &mut super::processor::cpu::DifferentiatedCPUContext) -> Void {
         Void
@@ -204,6 +251,46 @@ impl Computable for f32 {
     }
 }
 
+impl Computable for f64 {
+    type Type = Self;
+
+    fn get_type() -> &'static str {
+        "f64"
+    }
+
+    fn get_value(val: Self) -> Self {
+        val
+    }
+
+    fn get_zero() -> Self {
+        0_f64
+    }
+
+    fn from_int(from: isize) -> Self {
+       from as f64
+    }
+
+    fn from_float(from: f64) -> Self {
+       from
+    }
+
+    fn byte_size() -> usize {
+        8
+    }
+
+    fn get_memory_layout() -> MemoryLayoutDescriptor {
+        MemoryLayoutDescriptor::Float(8)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_le_bytes().into()
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Self {
+        f64::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
 impl Computable for i32 {
     type Type = Self;
 
@@ -359,3 +446,61 @@ impl Computable for u64 {
         u64::from_le_bytes(bytes.try_into().unwrap())
     }
 }
+
+/// Fixed-size arrays are kernel locals like `let mut acc = [0.0f32; 8];`.
+impl<T: Computable, const N: usize> Computable for [T; N] {
+    type Type = Self;
+
+    fn get_type() -> &'static str {
+        "array"
+    }
+
+    fn get_value(val: Self) -> Self {
+        val
+    }
+
+    fn get_zero() -> Self {
+        [T::get_zero(); N]
+    }
+
+    fn from_int(from: isize) -> Self {
+        [T::from_int(from); N]
+    }
+
+    fn from_float(from: f64) -> Self {
+        [T::from_float(from); N]
+    }
+
+    fn byte_size() -> usize {
+        T::byte_size() * N
+    }
+
+    fn get_memory_layout() -> MemoryLayoutDescriptor {
+        MemoryLayoutDescriptor::Array { item_typ: Box::new(T::get_memory_layout()), item_length: N }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.iter().flat_map(|v| v.to_bytes()).collect()
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Self {
+        let size = T::byte_size();
+        std::array::from_fn(|i| T::from_bytes(bytes[i * size..(i + 1) * size].to_vec()))
+    }
+}
+
+impl<T: Computable, const N: usize> IndexAble for [T; N] {
+    type IndexResult = T;
+
+    fn get_field() -> Option<String> {
+        None
+    }
+
+    fn get_element<R: Computable>(context: &DifferentiatedCPUContext, reference: &str, index: usize) -> R {
+        context.get::<[R; N]>(reference)[index]
+    }
+
+    fn set_element<R: Computable>(context: &mut DifferentiatedCPUContext, reference: &str, index: usize, value: R) {
+        context.get_mut::<[R; N]>(reference)[index] = value;
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt::Display;
+
+use crate::core::debug::DebugRecord;
+
+#[derive(Clone, Debug)]
+pub enum Error {
+    /// A kernel argument was not bound before dispatch.
+    Unbound { argument: String },
+    /// A bound value violates a shape constraint of the kernel, `found` are its known extents.
+    ShapeMismatch { argument: String, constraint: String, found: Vec<u64> },
+    /// The dispatch grid violates a constraint of the kernel.
+    InvalidGrid { constraint: String, grid: (u32, u32, u32) },
+    /// A value does not have the data type the kernel expects.
+    DtypeMismatch { argument: String, expected: String, found: String },
+    /// The processor could not allocate the requested number of bytes.
+    OutOfMemory { requested: u64 },
+    /// The kernel could not be compiled for the processor.
+    Compilation(String),
+    /// The binding does not refer to memory of this processor, e.g. because it was already deallocated.
+    InvalidBinding,
+    /// A range access spans more than one memory region.
+    CrossRegion { start: usize, end: usize },
+    /// The memory layout has no entry with this name.
+    UnknownField(String),
+    /// `assert!` failed in at least one kernel invocation.
+    AssertionFailed(Vec<DebugRecord>),
+    /// A graph input wasn't given to `run` or the value of an edge is gone.
+    MissingGraphValue(usize),
+    /// The optimizer state doesn't fit the parameters or couldn't be decoded.
+    InvalidOptimizerState(String),
+    /// A layer's backward pass ran before its forward pass.
+    NoForwardPass,
+    /// The kernel has no input argument with this name.
+    UnknownArgument(String),
+    /// The processor doesn't support the operation yet.
+    Unsupported(String),
+    /// The kernel can't be differentiated, the message names the construct.
+    NotDifferentiable(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Unbound { argument } => write!(f, "kernel argument `{}` is not bound", argument),
+            Error::ShapeMismatch { argument, constraint, found } => {
+                write!(f, "kernel argument `{}` with extents {:?} violates `{}`", argument, found, constraint)
+            }
+            Error::InvalidGrid { constraint, grid } => {
+                write!(f, "dispatch grid {:?} violates `{}`", grid, constraint)
+            }
+            Error::DtypeMismatch { argument, expected, found } => {
+                write!(f, "kernel argument `{}` has type {} but {} was expected", argument, found, expected)
+            }
+            Error::OutOfMemory { requested } => write!(f, "out of memory while allocating {} bytes", requested),
+            Error::Compilation(message) => write!(f, "kernel compilation failed: {}", message),
+            Error::InvalidBinding => write!(f, "binding does not refer to allocated memory of this processor"),
+            Error::CrossRegion { start, end } => write!(f, "range {}..{} spans more than one region", start, end),
+            Error::UnknownField(field) => write!(f, "memory layout has no field `{}`", field),
+            Error::AssertionFailed(records) => {
+                write!(f, "{} kernel assertion(s) failed", records.len())?;
+                for record in records {
+                    write!(f, "\n{}", record)?;
+                }
+                Ok(())
+            }
+            Error::MissingGraphValue(edge) => write!(f, "graph has no value for edge {}", edge),
+            Error::InvalidOptimizerState(message) => write!(f, "invalid optimizer state: {}", message),
+            Error::NoForwardPass => write!(f, "backward pass without a forward pass"),
+            Error::UnknownArgument(argument) => write!(f, "kernel has no input argument `{}`", argument),
+            Error::Unsupported(operation) => write!(f, "{} is not supported by this processor", operation),
+            Error::NotDifferentiable(message) => write!(f, "kernel can't be differentiated: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
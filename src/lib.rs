@@ -1,8 +1,16 @@
 
-pub use chandra_kernel::{kernel, ChandraStruct, ChandraFunction, ChandraExtension};
+pub use chandra_kernel::{kernel, fuse, ChandraStruct, ChandraFunction, ChandraExtension};
+#[doc(hidden)]
+pub use chandra_kernel::__fuse_kernels;
+pub use error::Error;
+
+extern crate self as chandra;
 
 pub mod core;
+pub mod error;
 pub mod types;
 pub mod processor;
+pub mod optim;
+pub mod nn;
 #[cfg(feature = "std")]
 pub mod std;
\ No newline at end of file
@@ -0,0 +1,154 @@
+//! Elementwise activation functions.
+
+use crate::{core::{operation::Compilable, processor::{Executable, Processor, ProcessorInformation}, type_traits::{FromMut, MemoryMapable}, types::Void}, kernel, optim::{build, Parameter}, types::tensor::Tensor, Error};
+
+use super::{saved, Batch, Layer};
+
+/// `hyper` holds the slope for negative inputs, zero for a plain ReLU.
+#[kernel]
+pub fn leaky_relu(pos: Pos, input: &Tensor<f32>, hyper: &Tensor<f32>, output: &mut Tensor<f32>) {
+    let x = input[pos.x];
+    output[pos.x] = x.max(0.0) + hyper[0] * x.min(0.0);
+}
+
+#[kernel]
+pub fn leaky_relu_gradient(pos: Pos, gradient: &Tensor<f32>, input: &Tensor<f32>, hyper: &Tensor<f32>, input_gradient: &mut Tensor<f32>) {
+    if input[pos.x] > 0.0 {
+        input_gradient[pos.x] = gradient[pos.x];
+    } else {
+        input_gradient[pos.x] = hyper[0] * gradient[pos.x];
+    }
+}
+
+#[kernel]
+pub fn sigmoid(pos: Pos, input: &Tensor<f32>, output: &mut Tensor<f32>) {
+    output[pos.x] = 1.0 / (1.0 + (0.0 - input[pos.x]).exp());
+}
+
+/// Uses the output of [`sigmoid`], `σ'(x) = σ(x) * (1 - σ(x))`.
+#[kernel]
+pub fn sigmoid_gradient(pos: Pos, gradient: &Tensor<f32>, output: &Tensor<f32>, input_gradient: &mut Tensor<f32>) {
+    let y = output[pos.x];
+    input_gradient[pos.x] = gradient[pos.x] * y * (1.0 - y);
+}
+
+#[kernel]
+pub fn hyperbolic_tangent(pos: Pos, input: &Tensor<f32>, output: &mut Tensor<f32>) {
+    output[pos.x] = input[pos.x].tanh();
+}
+
+/// Uses the output of [`hyperbolic_tangent`], `tanh'(x) = 1 - tanh(x)^2`.
+#[kernel]
+pub fn hyperbolic_tangent_gradient(pos: Pos, gradient: &Tensor<f32>, output: &Tensor<f32>, input_gradient: &mut Tensor<f32>) {
+    let y = output[pos.x];
+    input_gradient[pos.x] = gradient[pos.x] * (1.0 - y * y);
+}
+
+type Storage<P> = <P as ProcessorInformation>::Storage;
+/// Runs a built forward kernel on `(input, output)`.
+type Forward<P> = Box<dyn FnMut(&mut P, &Parameter<Storage<P>>, &Parameter<Storage<P>>, u32) -> Result<(), Error>>;
+/// Runs a built backward kernel on `(gradient, input, output, input_gradient)`.
+type Backward<P> = Box<dyn FnMut(&mut P, &Parameter<Storage<P>>, &Parameter<Storage<P>>, &Parameter<Storage<P>>, &Parameter<Storage<P>>, u32) -> Result<(), Error>>;
+
+/// An elementwise activation, built with [`Activation::relu`], [`Activation::leaky_relu`],
+/// [`Activation::sigmoid`] or [`Activation::tanh`].
+pub struct Activation<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    input: Option<Batch<P::Storage>>,
+    output: Option<Batch<P::Storage>>,
+    forward: Forward<P>,
+    backward: Backward<P>,
+}
+
+impl<P: Processor + 'static> Activation<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    pub fn relu(processor: &mut P) -> Result<Self, Error>
+    where
+        LeakyReluProgramm: Compilable<Void, P::Compiler>,
+        LeakyReluGradientProgramm: Compilable<Void, P::Compiler>,
+        P::Executable<LeakyRelu>: Executable<P::Storage, LeakyReluInputs<P::Storage>>,
+        P::Executable<LeakyReluGradient>: Executable<P::Storage, LeakyReluGradientInputs<P::Storage>>,
+    {
+        Self::leaky_relu(processor, 0.0)
+    }
+
+    /// `x` for positive inputs, otherwise `slope * x`.
+    pub fn leaky_relu(processor: &mut P, slope: f32) -> Result<Self, Error>
+    where
+        LeakyReluProgramm: Compilable<Void, P::Compiler>,
+        LeakyReluGradientProgramm: Compilable<Void, P::Compiler>,
+        P::Executable<LeakyRelu>: Executable<P::Storage, LeakyReluInputs<P::Storage>>,
+        P::Executable<LeakyReluGradient>: Executable<P::Storage, LeakyReluGradientInputs<P::Storage>>,
+    {
+        let hyper = processor.try_alloc(Tensor { data: vec![slope], shape: vec![1] })?;
+        let backward_hyper = hyper.clone();
+        let mut forward = build(processor, leaky_relu())?;
+        let mut backward = build(processor, leaky_relu_gradient())?;
+
+        Ok(Activation {
+            input: None,
+            output: None,
+            forward: Box::new(move |processor, input, output, length| forward(processor, (input.clone(), hyper.clone()), output, length)),
+            backward: Box::new(move |processor, gradient, input, _, input_gradient, length| {
+                backward(processor, (gradient.clone(), input.clone(), backward_hyper.clone()), input_gradient, length)
+            }),
+        })
+    }
+
+    pub fn sigmoid(processor: &mut P) -> Result<Self, Error>
+    where
+        SigmoidProgramm: Compilable<Void, P::Compiler>,
+        SigmoidGradientProgramm: Compilable<Void, P::Compiler>,
+        P::Executable<Sigmoid>: Executable<P::Storage, SigmoidInputs<P::Storage>>,
+        P::Executable<SigmoidGradient>: Executable<P::Storage, SigmoidGradientInputs<P::Storage>>,
+    {
+        let mut forward = build(processor, sigmoid())?;
+        let mut backward = build(processor, sigmoid_gradient())?;
+
+        Ok(Activation {
+            input: None,
+            output: None,
+            forward: Box::new(move |processor, input, output, length| forward(processor, (input.clone(),), output, length)),
+            backward: Box::new(move |processor, gradient, _, output, input_gradient, length| {
+                backward(processor, (gradient.clone(), output.clone()), input_gradient, length)
+            }),
+        })
+    }
+
+    pub fn tanh(processor: &mut P) -> Result<Self, Error>
+    where
+        HyperbolicTangentProgramm: Compilable<Void, P::Compiler>,
+        HyperbolicTangentGradientProgramm: Compilable<Void, P::Compiler>,
+        P::Executable<HyperbolicTangent>: Executable<P::Storage, HyperbolicTangentInputs<P::Storage>>,
+        P::Executable<HyperbolicTangentGradient>: Executable<P::Storage, HyperbolicTangentGradientInputs<P::Storage>>,
+    {
+        let mut forward = build(processor, hyperbolic_tangent())?;
+        let mut backward = build(processor, hyperbolic_tangent_gradient())?;
+
+        Ok(Activation {
+            input: None,
+            output: None,
+            forward: Box::new(move |processor, input, output, length| forward(processor, (input.clone(),), output, length)),
+            backward: Box::new(move |processor, gradient, _, output, input_gradient, length| {
+                backward(processor, (gradient.clone(), output.clone()), input_gradient, length)
+            }),
+        })
+    }
+}
+
+impl<P: Processor + 'static> Layer<P> for Activation<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn forward(&mut self, processor: &mut P, input: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let output = Batch::zeros(processor, input.shape.clone())?;
+        (self.forward)(processor, &input.value, &output.value, output.len())?;
+
+        self.input = Some(input.clone());
+        self.output = Some(output.clone());
+        Ok(output)
+    }
+
+    fn backward(&mut self, processor: &mut P, gradient: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let (input, output) = (saved(&self.input)?, saved(&self.output)?);
+
+        let input_gradient = Batch::zeros(processor, input.shape.clone())?;
+        (self.backward)(processor, &gradient.value, &input.value, &output.value, &input_gradient.value, input_gradient.len())?;
+        Ok(input_gradient)
+    }
+}
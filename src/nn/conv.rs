@@ -0,0 +1,247 @@
+//! Two dimensional convolution.
+
+use crate::{core::{operation::Compilable, processor::{Executable, Processor}, random::Distribution, type_traits::{FromMut, MemoryMapable}, types::Void}, kernel, optim::{build, Parameter, TernaryKernel, UnaryKernel}, types::tensor::Tensor, Error};
+
+use super::{add_channel_bias, channel_sum, saved, AddChannelBias, AddChannelBiasInputs, AddChannelBiasProgramm, Batch, ChannelSum, ChannelSumInputs, ChannelSumProgramm, Layer};
+
+/// Convolves an input of shape `[batch, channels, height, width]` with a weight of shape
+/// `[filters, channels, size, size]`, `geometry` holds the stride and the padding. The output shape
+/// follows from those and isn't read, derivatives of the kernel only see the output as zeros.
+#[kernel]
+pub fn convolution_forward(pos: Pos, input: &Tensor<f32>, weight: &Tensor<f32>, geometry: &Tensor<f32>, output: &mut Tensor<f32>) {
+    let stride = geometry[0] as u32;
+    let padding = geometry[1] as u32;
+    let channels = weight.shape(1);
+    let size = weight.shape(2);
+    let height = input.shape(2);
+    let width = input.shape(3);
+    let filters = weight.shape(0);
+    let output_height = (height + 2 * padding - size) / stride + 1;
+    let output_width = (width + 2 * padding - size) / stride + 1;
+
+    let row = pos.x / output_width;
+    let x = pos.x - row * output_width;
+    let plane = row / output_height;
+    let y = row - plane * output_height;
+    let n = plane / filters;
+    let filter = plane - n * filters;
+
+    let mut sum = 0.0f32;
+    for c in 0..channels {
+        for ky in 0..size {
+            for kx in 0..size {
+                let iy = y * stride + ky;
+                let ix = x * stride + kx;
+                if iy >= padding && iy < height + padding && ix >= padding && ix < width + padding {
+                    sum = sum + input[((n * channels + c) * height + iy - padding) * width + ix - padding] * weight[((filter * channels + c) * size + ky) * size + kx];
+                }
+            }
+        }
+    }
+    output[pos.x] = sum;
+}
+
+#[kernel]
+pub fn convolution_input_gradient(pos: Pos, gradient: &Tensor<f32>, weight: &Tensor<f32>, geometry: &Tensor<f32>, input_gradient: &mut Tensor<f32>) {
+    let stride = geometry[0] as u32;
+    let padding = geometry[1] as u32;
+    let filters = weight.shape(0);
+    let size = weight.shape(2);
+    let channels = input_gradient.shape(1);
+    let height = input_gradient.shape(2);
+    let width = input_gradient.shape(3);
+    let output_height = gradient.shape(2);
+    let output_width = gradient.shape(3);
+
+    let row = pos.x / width;
+    let x = pos.x - row * width;
+    let plane = row / height;
+    let y = row - plane * height;
+    let n = plane / channels;
+    let c = plane - n * channels;
+
+    let mut sum = 0.0f32;
+    for filter in 0..filters {
+        for ky in 0..size {
+            for kx in 0..size {
+                let ty = y + padding;
+                let tx = x + padding;
+                if ty >= ky && tx >= kx {
+                    let oy = (ty - ky) / stride;
+                    let ox = (tx - kx) / stride;
+                    if oy * stride == ty - ky && ox * stride == tx - kx && oy < output_height && ox < output_width {
+                        sum = sum + gradient[((n * filters + filter) * output_height + oy) * output_width + ox] * weight[((filter * channels + c) * size + ky) * size + kx];
+                    }
+                }
+            }
+        }
+    }
+    input_gradient[pos.x] = sum;
+}
+
+#[kernel]
+pub fn convolution_weight_gradient(pos: Pos, gradient: &Tensor<f32>, input: &Tensor<f32>, geometry: &Tensor<f32>, weight_gradient: &mut Tensor<f32>) {
+    let stride = geometry[0] as u32;
+    let padding = geometry[1] as u32;
+    let batch = input.shape(0);
+    let channels = input.shape(1);
+    let height = input.shape(2);
+    let width = input.shape(3);
+    let filters = gradient.shape(1);
+    let output_height = gradient.shape(2);
+    let output_width = gradient.shape(3);
+    let size = weight_gradient.shape(2);
+
+    let row = pos.x / size;
+    let x = pos.x - row * size;
+    let plane = row / size;
+    let y = row - plane * size;
+    let filter = plane / channels;
+    let c = plane - filter * channels;
+
+    let mut sum = 0.0f32;
+    for n in 0..batch {
+        for oy in 0..output_height {
+            for ox in 0..output_width {
+                let iy = oy * stride + y;
+                let ix = ox * stride + x;
+                if iy >= padding && iy < height + padding && ix >= padding && ix < width + padding {
+                    sum = sum + gradient[((n * filters + filter) * output_height + oy) * output_width + ox] * input[((n * channels + c) * height + iy - padding) * width + ix - padding];
+                }
+            }
+        }
+    }
+    weight_gradient[pos.x] = sum;
+}
+
+/// Convolution with square filters on inputs of shape `[batch, channels, height, width]`.
+pub struct Conv2d<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    channels: usize,
+    filters: usize,
+    size: usize,
+    stride: usize,
+    padding: usize,
+    weight: Parameter<P::Storage>,
+    bias: Parameter<P::Storage>,
+    weight_gradient: Parameter<P::Storage>,
+    bias_gradient: Parameter<P::Storage>,
+    input: Option<Batch<P::Storage>>,
+    forward: TernaryKernel<P>,
+    add_bias: UnaryKernel<P>,
+    backward_input: TernaryKernel<P>,
+    backward_weight: TernaryKernel<P>,
+    backward_bias: UnaryKernel<P>,
+}
+
+impl<P: Processor + 'static> Conv2d<P>
+where
+    Tensor<f32>: MemoryMapable<P::Storage>,
+    ConvolutionForwardProgramm: Compilable<Void, P::Compiler>,
+    ConvolutionInputGradientProgramm: Compilable<Void, P::Compiler>,
+    ConvolutionWeightGradientProgramm: Compilable<Void, P::Compiler>,
+    AddChannelBiasProgramm: Compilable<Void, P::Compiler>,
+    ChannelSumProgramm: Compilable<Void, P::Compiler>,
+    P::Executable<ConvolutionForward>: Executable<P::Storage, ConvolutionForwardInputs<P::Storage>>,
+    P::Executable<ConvolutionInputGradient>: Executable<P::Storage, ConvolutionInputGradientInputs<P::Storage>>,
+    P::Executable<ConvolutionWeightGradient>: Executable<P::Storage, ConvolutionWeightGradientInputs<P::Storage>>,
+    P::Executable<AddChannelBias>: Executable<P::Storage, AddChannelBiasInputs<P::Storage>>,
+    P::Executable<ChannelSum>: Executable<P::Storage, ChannelSumInputs<P::Storage>>,
+{
+    /// Draws the weight and bias uniformly from `±1/sqrt(channels * size * size)`, the stride is 1 without padding.
+    pub fn new(processor: &mut P, channels: usize, filters: usize, size: usize, seed: u32) -> Result<Self, Error> {
+        let bound = 1.0 / ((channels * size * size) as f32).sqrt();
+        let distribution = Distribution::Uniform { low: -bound, high: bound };
+
+        Self::from_tensors(processor, Tensor::<f32>::rand(vec![filters, channels, size, size], seed, distribution), Tensor::<f32>::rand(vec![filters], seed.wrapping_add(1), distribution))
+    }
+
+    /// Uses `weight` of shape `[filters, channels, size, size]` and `bias` of shape `[filters]`.
+    pub fn from_tensors(processor: &mut P, weight: Tensor<f32>, bias: Tensor<f32>) -> Result<Self, Error> {
+        let (filters, channels, size) = match weight.shape.as_slice() {
+            [filters, channels, size, width] if size == width && bias.shape == [*filters] => (*filters, *channels, *size),
+            _ => return Err(Error::ShapeMismatch {
+                argument: "weight".to_string(),
+                constraint: "weight [filters, channels, size, size] and bias [filters]".to_string(),
+                found: weight.shape.iter().map(|e| *e as u64).collect(),
+            }),
+        };
+
+        Ok(Conv2d {
+            channels,
+            filters,
+            size,
+            stride: 1,
+            padding: 0,
+            weight_gradient: processor.try_alloc(Tensor::new(0.0, weight.shape.clone()))?,
+            bias_gradient: processor.try_alloc(Tensor::new(0.0, bias.shape.clone()))?,
+            weight: processor.try_alloc(weight)?,
+            bias: processor.try_alloc(bias)?,
+            input: None,
+            forward: build(processor, convolution_forward())?,
+            add_bias: build(processor, add_channel_bias())?,
+            backward_input: build(processor, convolution_input_gradient())?,
+            backward_weight: build(processor, convolution_weight_gradient())?,
+            backward_bias: build(processor, channel_sum())?,
+        })
+    }
+}
+
+impl<P: Processor> Conv2d<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.stride = stride;
+        self
+    }
+
+    /// Pads every side of the input with `padding` zeros.
+    pub fn padding(mut self, padding: usize) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    fn geometry(&self, processor: &mut P) -> Result<Parameter<P::Storage>, Error> {
+        processor.try_alloc(Tensor { data: vec![self.stride as f32, self.padding as f32], shape: vec![2] })
+    }
+}
+
+impl<P: Processor + 'static> Layer<P> for Conv2d<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn forward(&mut self, processor: &mut P, input: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let (height, width) = match input.shape.as_slice() {
+            [_, channels, height, width] if *channels == self.channels && height + 2 * self.padding >= self.size && width + 2 * self.padding >= self.size => (*height, *width),
+            _ => return Err(Error::ShapeMismatch {
+                argument: "input".to_string(),
+                constraint: format!("[batch, {}, height, width] with height and width of at least {}", self.channels, self.size.saturating_sub(2 * self.padding)),
+                found: input.shape.iter().map(|e| *e as u64).collect(),
+            }),
+        };
+
+        let extent = |e: usize| (e + 2 * self.padding - self.size) / self.stride + 1;
+        let output = Batch::zeros(processor, vec![input.shape[0], self.filters, extent(height), extent(width)])?;
+        let geometry = self.geometry(processor)?;
+
+        (self.forward)(processor, (input.value.clone(), self.weight.clone(), geometry), &output.value, output.len())?;
+        (self.add_bias)(processor, (self.bias.clone(),), &output.value, output.len())?;
+
+        self.input = Some(input.clone());
+        Ok(output)
+    }
+
+    fn backward(&mut self, processor: &mut P, gradient: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let geometry = self.geometry(processor)?;
+        let input = saved(&self.input)?;
+
+        (self.backward_weight)(processor, (gradient.value.clone(), input.value.clone(), geometry.clone()), &self.weight_gradient, (self.filters * self.channels * self.size * self.size) as u32)?;
+        (self.backward_bias)(processor, (gradient.value.clone(),), &self.bias_gradient, self.filters as u32)?;
+
+        let input_gradient = Batch::zeros(processor, input.shape.clone())?;
+        (self.backward_input)(processor, (gradient.value.clone(), self.weight.clone(), geometry), &input_gradient.value, input_gradient.len())?;
+        Ok(input_gradient)
+    }
+
+    fn parameters(&self) -> Vec<Parameter<P::Storage>> {
+        vec![self.weight.clone(), self.bias.clone()]
+    }
+
+    fn gradients(&self) -> Vec<Parameter<P::Storage>> {
+        vec![self.weight_gradient.clone(), self.bias_gradient.clone()]
+    }
+}
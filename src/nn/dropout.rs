@@ -0,0 +1,73 @@
+//! Dropout regularization.
+
+use crate::{core::{operation::Compilable, processor::{Executable, Processor}, type_traits::{FromMut, MemoryMapable}, types::Void}, kernel, optim::{build, BinaryKernel, Parameter}, types::tensor::Tensor, Error};
+
+use super::{Batch, Layer};
+
+/// Keeps every value with probability `hyper[0]` and scales the kept ones by its inverse, `hyper[1]`
+/// is the seed. The gradient is the same kernel with the same seed applied to the output gradient.
+#[kernel]
+pub fn dropout_mask(pos: Pos, input: &Tensor<f32>, hyper: &Tensor<f32>, output: &mut Tensor<f32>) {
+    let keep = hyper[0];
+    if random::bernoulli(hyper[1] as u32, pos.x, keep) {
+        output[pos.x] = input[pos.x] / keep;
+    } else {
+        output[pos.x] = 0.0;
+    }
+}
+
+/// Zeroes every value with probability `p` while training, evaluation passes the input through.
+///
+/// Every forward pass draws a new mask from the next seed.
+pub struct Dropout<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    probability: f32,
+    seed: u32,
+    training: bool,
+    /// The hyperparameters of the last forward pass, `None` if it passed the input through.
+    saved: Option<Option<Parameter<P::Storage>>>,
+    mask: BinaryKernel<P>,
+}
+
+impl<P: Processor + 'static> Dropout<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    pub fn new(processor: &mut P, probability: f32, seed: u32) -> Result<Self, Error>
+    where
+        DropoutMaskProgramm: Compilable<Void, P::Compiler>,
+        P::Executable<DropoutMask>: Executable<P::Storage, DropoutMaskInputs<P::Storage>>,
+    {
+        Ok(Dropout { probability, seed, training: true, saved: None, mask: build(processor, dropout_mask())? })
+    }
+}
+
+impl<P: Processor + 'static> Layer<P> for Dropout<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn forward(&mut self, processor: &mut P, input: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        if !self.training || self.probability == 0.0 {
+            self.saved = Some(None);
+            return Ok(input.clone());
+        }
+
+        // Seeds are passed as floats, they stay exact below 2^24.
+        let hyper = processor.try_alloc(Tensor { data: vec![1.0 - self.probability, (self.seed % (1 << 24)) as f32], shape: vec![2] })?;
+        self.seed = self.seed.wrapping_add(1);
+
+        let output = Batch::zeros(processor, input.shape.clone())?;
+        (self.mask)(processor, (input.value.clone(), hyper.clone()), &output.value, output.len())?;
+
+        self.saved = Some(Some(hyper));
+        Ok(output)
+    }
+
+    fn backward(&mut self, processor: &mut P, gradient: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        match self.saved.as_ref().ok_or(Error::NoForwardPass)? {
+            Some(hyper) => {
+                let input_gradient = Batch::zeros(processor, gradient.shape.clone())?;
+                (self.mask)(processor, (gradient.value.clone(), hyper.clone()), &input_gradient.value, input_gradient.len())?;
+                Ok(input_gradient)
+            }
+            None => Ok(gradient.clone()),
+        }
+    }
+
+    fn train(&mut self, training: bool) {
+        self.training = training;
+    }
+}
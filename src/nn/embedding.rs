@@ -0,0 +1,106 @@
+//! Lookup tables of trainable vectors.
+
+use crate::{core::{operation::Compilable, processor::{Executable, Processor}, random::Distribution, type_traits::{FromMut, MemoryMapable}, types::Void}, kernel, optim::{build, BinaryKernel, Parameter}, types::tensor::Tensor, Error};
+
+use super::{saved, Batch, Layer};
+
+/// Copies the row `indices[n]` of `weight` to row `n` of `output`.
+#[kernel]
+pub fn embedding_forward(pos: Pos, indices: &Tensor<f32>, weight: &Tensor<f32>, output: &mut Tensor<f32>) {
+    let dimension = weight.shape(1);
+    let row = pos.x / dimension;
+    let column = pos.x - row * dimension;
+    output[pos.x] = weight[(indices[row] as u32) * dimension + column];
+}
+
+/// Sums the gradients of every row looked up from the same entry.
+#[kernel]
+pub fn embedding_gradient(pos: Pos, gradient: &Tensor<f32>, indices: &Tensor<f32>, weight_gradient: &mut Tensor<f32>) {
+    let dimension = weight_gradient.shape(1);
+    let entry = pos.x / dimension;
+    let column = pos.x - entry * dimension;
+    let mut sum = 0.0f32;
+    for n in 0..indices.len() {
+        if indices[n] as u32 == entry {
+            sum = sum + gradient[n * dimension + column];
+        }
+    }
+    weight_gradient[pos.x] = sum;
+}
+
+/// Maps a batch of indices, stored as floats of shape `[batch]`, to vectors of shape `[batch, dimension]`.
+///
+/// Indices can't be differentiated, their gradient is zero.
+pub struct Embedding<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    count: usize,
+    dimension: usize,
+    weight: Parameter<P::Storage>,
+    weight_gradient: Parameter<P::Storage>,
+    input: Option<Batch<P::Storage>>,
+    forward: BinaryKernel<P>,
+    backward: BinaryKernel<P>,
+}
+
+impl<P: Processor + 'static> Embedding<P>
+where
+    Tensor<f32>: MemoryMapable<P::Storage>,
+    EmbeddingForwardProgramm: Compilable<Void, P::Compiler>,
+    EmbeddingGradientProgramm: Compilable<Void, P::Compiler>,
+    P::Executable<EmbeddingForward>: Executable<P::Storage, EmbeddingForwardInputs<P::Storage>>,
+    P::Executable<EmbeddingGradient>: Executable<P::Storage, EmbeddingGradientInputs<P::Storage>>,
+{
+    /// Draws `count` vectors from a standard normal distribution.
+    pub fn new(processor: &mut P, count: usize, dimension: usize, seed: u32) -> Result<Self, Error> {
+        Self::from_tensor(processor, Tensor::<f32>::rand(vec![count, dimension], seed, Distribution::Normal { mean: 0.0, std: 1.0 }))
+    }
+
+    /// Uses the rows of `weight` of shape `[count, dimension]`.
+    pub fn from_tensor(processor: &mut P, weight: Tensor<f32>) -> Result<Self, Error> {
+        let (count, dimension) = match weight.shape.as_slice() {
+            [count, dimension] => (*count, *dimension),
+            _ => return Err(Error::ShapeMismatch {
+                argument: "weight".to_string(),
+                constraint: "[count, dimension]".to_string(),
+                found: weight.shape.iter().map(|e| *e as u64).collect(),
+            }),
+        };
+
+        Ok(Embedding {
+            count,
+            dimension,
+            weight_gradient: processor.try_alloc(Tensor::new(0.0, weight.shape.clone()))?,
+            weight: processor.try_alloc(weight)?,
+            input: None,
+            forward: build(processor, embedding_forward())?,
+            backward: build(processor, embedding_gradient())?,
+        })
+    }
+}
+
+impl<P: Processor + 'static> Layer<P> for Embedding<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn forward(&mut self, processor: &mut P, input: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        input.expect("indices", &[])?;
+
+        let output = Batch::zeros(processor, vec![input.shape[0], self.dimension])?;
+        (self.forward)(processor, (input.value.clone(), self.weight.clone()), &output.value, output.len())?;
+
+        self.input = Some(input.clone());
+        Ok(output)
+    }
+
+    fn backward(&mut self, processor: &mut P, gradient: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let input = saved(&self.input)?;
+        gradient.expect("gradient", &[self.dimension])?;
+
+        (self.backward)(processor, (gradient.value.clone(), input.value.clone()), &self.weight_gradient, (self.count * self.dimension) as u32)?;
+        Batch::zeros(processor, input.shape.clone())
+    }
+
+    fn parameters(&self) -> Vec<Parameter<P::Storage>> {
+        vec![self.weight.clone()]
+    }
+
+    fn gradients(&self) -> Vec<Parameter<P::Storage>> {
+        vec![self.weight_gradient.clone()]
+    }
+}
@@ -0,0 +1,145 @@
+//! Fully connected layer.
+
+use crate::{core::{operation::Compilable, processor::{Executable, Processor}, random::Distribution, type_traits::{FromMut, MemoryMapable}, types::Void}, kernel, optim::{build, BinaryKernel, Parameter, UnaryKernel}, types::tensor::Tensor, Error};
+
+use super::{add_channel_bias, channel_sum, saved, AddChannelBias, AddChannelBiasInputs, AddChannelBiasProgramm, Batch, ChannelSum, ChannelSumInputs, ChannelSumProgramm, Layer};
+
+/// `output = input * weight^T` for an input of shape `[batch, inputs]` and a weight of shape `[outputs, inputs]`.
+#[kernel]
+pub fn linear_forward(pos: Pos, input: &Tensor<f32>, weight: &Tensor<f32>, output: &mut Tensor<f32>) {
+    let inputs = weight.shape(1);
+    let outputs = weight.shape(0);
+    let row = pos.x / outputs;
+    let column = pos.x - row * outputs;
+    let mut sum = 0.0f32;
+    for k in 0..inputs {
+        sum = sum + input[row * inputs + k] * weight[column * inputs + k];
+    }
+    output[pos.x] = sum;
+}
+
+#[kernel]
+pub fn linear_input_gradient(pos: Pos, gradient: &Tensor<f32>, weight: &Tensor<f32>, input_gradient: &mut Tensor<f32>) {
+    let inputs = weight.shape(1);
+    let outputs = weight.shape(0);
+    let row = pos.x / inputs;
+    let column = pos.x - row * inputs;
+    let mut sum = 0.0f32;
+    for o in 0..outputs {
+        sum = sum + gradient[row * outputs + o] * weight[o * inputs + column];
+    }
+    input_gradient[pos.x] = sum;
+}
+
+#[kernel]
+pub fn linear_weight_gradient(pos: Pos, gradient: &Tensor<f32>, input: &Tensor<f32>, weight_gradient: &mut Tensor<f32>) {
+    let batch = input.shape(0);
+    let inputs = input.shape(1);
+    let outputs = gradient.shape(1);
+    let row = pos.x / inputs;
+    let column = pos.x - row * inputs;
+    let mut sum = 0.0f32;
+    for n in 0..batch {
+        sum = sum + gradient[n * outputs + row] * input[n * inputs + column];
+    }
+    weight_gradient[pos.x] = sum;
+}
+
+/// `y = x * W^T + b` on inputs of shape `[batch, inputs]`.
+pub struct Linear<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    inputs: usize,
+    outputs: usize,
+    weight: Parameter<P::Storage>,
+    bias: Parameter<P::Storage>,
+    weight_gradient: Parameter<P::Storage>,
+    bias_gradient: Parameter<P::Storage>,
+    input: Option<Batch<P::Storage>>,
+    forward: BinaryKernel<P>,
+    add_bias: UnaryKernel<P>,
+    backward_input: BinaryKernel<P>,
+    backward_weight: BinaryKernel<P>,
+    backward_bias: UnaryKernel<P>,
+}
+
+impl<P: Processor + 'static> Linear<P>
+where
+    Tensor<f32>: MemoryMapable<P::Storage>,
+    LinearForwardProgramm: Compilable<Void, P::Compiler>,
+    LinearInputGradientProgramm: Compilable<Void, P::Compiler>,
+    LinearWeightGradientProgramm: Compilable<Void, P::Compiler>,
+    AddChannelBiasProgramm: Compilable<Void, P::Compiler>,
+    ChannelSumProgramm: Compilable<Void, P::Compiler>,
+    P::Executable<LinearForward>: Executable<P::Storage, LinearForwardInputs<P::Storage>>,
+    P::Executable<LinearInputGradient>: Executable<P::Storage, LinearInputGradientInputs<P::Storage>>,
+    P::Executable<LinearWeightGradient>: Executable<P::Storage, LinearWeightGradientInputs<P::Storage>>,
+    P::Executable<AddChannelBias>: Executable<P::Storage, AddChannelBiasInputs<P::Storage>>,
+    P::Executable<ChannelSum>: Executable<P::Storage, ChannelSumInputs<P::Storage>>,
+{
+    /// Draws the weight and bias uniformly from `±1/sqrt(inputs)`.
+    pub fn new(processor: &mut P, inputs: usize, outputs: usize, seed: u32) -> Result<Self, Error> {
+        let bound = 1.0 / (inputs as f32).sqrt();
+        let distribution = Distribution::Uniform { low: -bound, high: bound };
+
+        Self::from_tensors(processor, Tensor::<f32>::rand(vec![outputs, inputs], seed, distribution), Tensor::<f32>::rand(vec![outputs], seed.wrapping_add(1), distribution))
+    }
+
+    /// Uses `weight` of shape `[outputs, inputs]` and `bias` of shape `[outputs]`.
+    pub fn from_tensors(processor: &mut P, weight: Tensor<f32>, bias: Tensor<f32>) -> Result<Self, Error> {
+        let (outputs, inputs) = match weight.shape.as_slice() {
+            [outputs, inputs] if bias.shape == [*outputs] => (*outputs, *inputs),
+            _ => return Err(Error::ShapeMismatch {
+                argument: "bias".to_string(),
+                constraint: "weight [outputs, inputs] and bias [outputs]".to_string(),
+                found: bias.shape.iter().map(|e| *e as u64).collect(),
+            }),
+        };
+
+        Ok(Linear {
+            inputs,
+            outputs,
+            weight_gradient: processor.try_alloc(Tensor::new(0.0, weight.shape.clone()))?,
+            bias_gradient: processor.try_alloc(Tensor::new(0.0, bias.shape.clone()))?,
+            weight: processor.try_alloc(weight)?,
+            bias: processor.try_alloc(bias)?,
+            input: None,
+            forward: build(processor, linear_forward())?,
+            add_bias: build(processor, add_channel_bias())?,
+            backward_input: build(processor, linear_input_gradient())?,
+            backward_weight: build(processor, linear_weight_gradient())?,
+            backward_bias: build(processor, channel_sum())?,
+        })
+    }
+}
+
+impl<P: Processor + 'static> Layer<P> for Linear<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn forward(&mut self, processor: &mut P, input: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        input.expect("input", &[self.inputs])?;
+
+        let output = Batch::zeros(processor, vec![input.shape[0], self.outputs])?;
+        (self.forward)(processor, (input.value.clone(), self.weight.clone()), &output.value, output.len())?;
+        (self.add_bias)(processor, (self.bias.clone(),), &output.value, output.len())?;
+
+        self.input = Some(input.clone());
+        Ok(output)
+    }
+
+    fn backward(&mut self, processor: &mut P, gradient: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let input = saved(&self.input)?;
+        gradient.expect("gradient", &[self.outputs])?;
+
+        (self.backward_weight)(processor, (gradient.value.clone(), input.value.clone()), &self.weight_gradient, (self.outputs * self.inputs) as u32)?;
+        (self.backward_bias)(processor, (gradient.value.clone(),), &self.bias_gradient, self.outputs as u32)?;
+
+        let input_gradient = Batch::zeros(processor, input.shape.clone())?;
+        (self.backward_input)(processor, (gradient.value.clone(), self.weight.clone()), &input_gradient.value, input_gradient.len())?;
+        Ok(input_gradient)
+    }
+
+    fn parameters(&self) -> Vec<Parameter<P::Storage>> {
+        vec![self.weight.clone(), self.bias.clone()]
+    }
+
+    fn gradients(&self) -> Vec<Parameter<P::Storage>> {
+        vec![self.weight_gradient.clone(), self.bias_gradient.clone()]
+    }
+}
@@ -0,0 +1,331 @@
+//! Loss functions and their gradients.
+//!
+//! Every loss is a kernel writing one loss term per thread and a gradient kernel writing the
+//! derivative of the reduced loss for every element of the input. Both read `hyper`, which holds the
+//! scale of the [`Reduction`] followed by the parameter of the loss, like the threshold of [`huber`].
+
+use crate::{core::{operation::Compilable, processor::{Executable, Processor}, type_traits::{Calculatable, Float, FromMut, MemoryMapable, Signed}, types::Void}, kernel, optim::{build, Parameter, TernaryKernel, UnaryKernel}, types::tensor::Tensor, Error};
+
+use super::Batch;
+
+#[kernel]
+pub fn mean_squared_error<C: Calculatable>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, losses: &mut Tensor<C>) {
+    let difference = input[pos.x] - target[pos.x];
+    losses[pos.x] = hyper[0] * difference * difference;
+}
+
+#[kernel]
+pub fn mean_squared_error_gradient<C: Calculatable>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, input_gradient: &mut Tensor<C>) {
+    let difference = input[pos.x] - target[pos.x];
+    input_gradient[pos.x] = hyper[0] * (difference + difference);
+}
+
+#[kernel]
+pub fn mean_absolute_error<C: Signed>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, losses: &mut Tensor<C>) {
+    losses[pos.x] = hyper[0] * (input[pos.x] - target[pos.x]).abs();
+}
+
+/// The subgradient at `input == target` is zero.
+#[kernel]
+pub fn mean_absolute_error_gradient<C: Signed>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, input_gradient: &mut Tensor<C>) {
+    input_gradient[pos.x] = 0.0;
+    if input[pos.x] > target[pos.x] {
+        input_gradient[pos.x] = hyper[0];
+    }
+    if input[pos.x] < target[pos.x] {
+        input_gradient[pos.x] = 0.0 - hyper[0];
+    }
+}
+
+/// Quadratic for differences up to the threshold `hyper[1]`, linear beyond it.
+#[kernel]
+pub fn huber<C: Signed>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, losses: &mut Tensor<C>) {
+    let difference = input[pos.x] - target[pos.x];
+    let threshold = hyper[1];
+    if difference.abs() <= threshold {
+        losses[pos.x] = hyper[0] * 0.5 * difference * difference;
+    } else {
+        losses[pos.x] = hyper[0] * threshold * (difference.abs() - 0.5 * threshold);
+    }
+}
+
+#[kernel]
+pub fn huber_gradient<C: Signed>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, input_gradient: &mut Tensor<C>) {
+    let difference = input[pos.x] - target[pos.x];
+    let threshold = hyper[1];
+    input_gradient[pos.x] = hyper[0] * difference;
+    if difference > threshold {
+        input_gradient[pos.x] = hyper[0] * threshold;
+    }
+    if difference < 0.0 - threshold {
+        input_gradient[pos.x] = 0.0 - hyper[0] * threshold;
+    }
+}
+
+/// Takes probabilities as input, they are clamped to `[hyper[1], 1 - hyper[1]]` before taking the logarithm.
+#[kernel]
+pub fn binary_cross_entropy<C: Float>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, losses: &mut Tensor<C>) {
+    let mut probability = input[pos.x];
+    if probability < hyper[1] {
+        probability = hyper[1];
+    }
+    if probability > 1.0 - hyper[1] {
+        probability = 1.0 - hyper[1];
+    }
+    let t = target[pos.x];
+    losses[pos.x] = hyper[0] * (0.0 - t * probability.ln() - (1.0 - t) * (1.0 - probability).ln());
+}
+
+/// The gradient of the clamped loss, zero where the input was clamped.
+#[kernel]
+pub fn binary_cross_entropy_gradient<C: Float>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, input_gradient: &mut Tensor<C>) {
+    let probability = input[pos.x];
+    if probability < hyper[1] || probability > 1.0 - hyper[1] {
+        input_gradient[pos.x] = 0.0;
+    } else {
+        input_gradient[pos.x] = hyper[0] * (probability - target[pos.x]) / (probability * (1.0 - probability));
+    }
+}
+
+/// Takes logits of shape `[batch, classes]` and target probabilities of the same shape, one thread per row.
+/// The log-softmax subtracts the largest logit of the row before exponentiating, so large logits don't overflow.
+#[kernel]
+pub fn cross_entropy<C: Float>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, losses: &mut Tensor<C>) {
+    let classes = input.shape(1);
+    let row = pos.x * classes;
+
+    let mut largest = input[row];
+    for k in 1..classes {
+        if input[row + k] > largest {
+            largest = input[row + k];
+        }
+    }
+
+    let mut sum = (input[row] - largest).exp();
+    for k in 1..classes {
+        sum = sum + (input[row + k] - largest).exp();
+    }
+    let normalizer = largest + sum.ln();
+
+    let mut loss = target[row] * (normalizer - input[row]);
+    for k in 1..classes {
+        loss = loss + target[row + k] * (normalizer - input[row + k]);
+    }
+    losses[pos.x] = hyper[0] * loss;
+}
+
+/// `softmax(input) * sum(target) - target` per row, one thread per element.
+#[kernel]
+pub fn cross_entropy_gradient<C: Float>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, input_gradient: &mut Tensor<C>) {
+    let classes = input.shape(1);
+    let row = (pos.x / classes) * classes;
+
+    let mut largest = input[row];
+    for k in 1..classes {
+        if input[row + k] > largest {
+            largest = input[row + k];
+        }
+    }
+
+    let mut sum = (input[row] - largest).exp();
+    let mut mass = target[row];
+    for k in 1..classes {
+        sum = sum + (input[row + k] - largest).exp();
+        mass = mass + target[row + k];
+    }
+
+    let softmax = (input[pos.x] - largest).exp() / sum;
+    input_gradient[pos.x] = hyper[0] * (softmax * mass - target[pos.x]);
+}
+
+/// `KL(target || exp(input))` for log-probabilities as input, targets of zero contribute nothing.
+#[kernel]
+pub fn kl_divergence<C: Float>(pos: Pos, input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, losses: &mut Tensor<C>) {
+    let t = target[pos.x];
+    if t > 0.0 {
+        losses[pos.x] = hyper[0] * t * (t.ln() - input[pos.x]);
+    } else {
+        losses[pos.x] = 0.0;
+    }
+}
+
+/// `-target`, the input only keeps the bindings the same as for [`kl_divergence`].
+#[kernel]
+pub fn kl_divergence_gradient<C: Float>(pos: Pos, _input: &Tensor<C>, target: &Tensor<C>, hyper: &Tensor<C>, input_gradient: &mut Tensor<C>) {
+    input_gradient[pos.x] = 0.0 - hyper[0] * target[pos.x];
+}
+
+/// Sums all loss terms into `total[0]` on a single thread.
+#[kernel]
+pub fn loss_sum<C: Calculatable>(pos: Pos, losses: &Tensor<C>, total: &mut Tensor<C>) {
+    let mut sum = losses[0];
+    for i in 1..losses.len() {
+        sum = sum + losses[i];
+    }
+    total[pos.x] = sum;
+}
+
+/// How the loss terms are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Reduction {
+    /// The average over all terms, one term per element or per row for [`Loss::cross_entropy`].
+    #[default]
+    Mean,
+    Sum,
+    /// Keeps the terms, the gradient is the one of their sum.
+    None,
+}
+
+/// A loss built with one of [`Loss::mse`], [`Loss::mae`], [`Loss::huber`], [`Loss::binary_cross_entropy`],
+/// [`Loss::cross_entropy`] or [`Loss::kl_divergence`], the mean of all terms by default.
+pub struct Loss<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    reduction: Reduction,
+    parameter: f32,
+    /// Whether there is one term per row of a `[batch, classes]` input instead of one per element.
+    rows: bool,
+    forward: TernaryKernel<P>,
+    backward: TernaryKernel<P>,
+    sum: UnaryKernel<P>,
+}
+
+impl<P: Processor + 'static> Loss<P>
+where
+    Tensor<f32>: MemoryMapable<P::Storage>,
+    LossSumProgramm<f32>: Compilable<Void, P::Compiler>,
+    P::Executable<LossSum<f32>>: Executable<P::Storage, LossSumInputs<P::Storage, f32>>,
+{
+    pub fn mse(processor: &mut P) -> Result<Self, Error>
+    where
+        MeanSquaredErrorProgramm<f32>: Compilable<Void, P::Compiler>,
+        MeanSquaredErrorGradientProgramm<f32>: Compilable<Void, P::Compiler>,
+        P::Executable<MeanSquaredError<f32>>: Executable<P::Storage, MeanSquaredErrorInputs<P::Storage, f32>>,
+        P::Executable<MeanSquaredErrorGradient<f32>>: Executable<P::Storage, MeanSquaredErrorGradientInputs<P::Storage, f32>>,
+    {
+        let forward = build(processor, mean_squared_error::<f32>())?;
+        let backward = build(processor, mean_squared_error_gradient::<f32>())?;
+        Self::from_kernels(processor, forward, backward, 0.0, false)
+    }
+
+    pub fn mae(processor: &mut P) -> Result<Self, Error>
+    where
+        MeanAbsoluteErrorProgramm<f32>: Compilable<Void, P::Compiler>,
+        MeanAbsoluteErrorGradientProgramm<f32>: Compilable<Void, P::Compiler>,
+        P::Executable<MeanAbsoluteError<f32>>: Executable<P::Storage, MeanAbsoluteErrorInputs<P::Storage, f32>>,
+        P::Executable<MeanAbsoluteErrorGradient<f32>>: Executable<P::Storage, MeanAbsoluteErrorGradientInputs<P::Storage, f32>>,
+    {
+        let forward = build(processor, mean_absolute_error::<f32>())?;
+        let backward = build(processor, mean_absolute_error_gradient::<f32>())?;
+        Self::from_kernels(processor, forward, backward, 0.0, false)
+    }
+
+    /// Quadratic up to a difference of `threshold`, linear beyond it.
+    pub fn huber(processor: &mut P, threshold: f32) -> Result<Self, Error>
+    where
+        HuberProgramm<f32>: Compilable<Void, P::Compiler>,
+        HuberGradientProgramm<f32>: Compilable<Void, P::Compiler>,
+        P::Executable<Huber<f32>>: Executable<P::Storage, HuberInputs<P::Storage, f32>>,
+        P::Executable<HuberGradient<f32>>: Executable<P::Storage, HuberGradientInputs<P::Storage, f32>>,
+    {
+        let forward = build(processor, huber::<f32>())?;
+        let backward = build(processor, huber_gradient::<f32>())?;
+        Self::from_kernels(processor, forward, backward, threshold, false)
+    }
+
+    /// For probabilities as input, e.g. the output of a sigmoid. They are clamped to `[1e-7, 1 - 1e-7]`.
+    pub fn binary_cross_entropy(processor: &mut P) -> Result<Self, Error>
+    where
+        BinaryCrossEntropyProgramm<f32>: Compilable<Void, P::Compiler>,
+        BinaryCrossEntropyGradientProgramm<f32>: Compilable<Void, P::Compiler>,
+        P::Executable<BinaryCrossEntropy<f32>>: Executable<P::Storage, BinaryCrossEntropyInputs<P::Storage, f32>>,
+        P::Executable<BinaryCrossEntropyGradient<f32>>: Executable<P::Storage, BinaryCrossEntropyGradientInputs<P::Storage, f32>>,
+    {
+        let forward = build(processor, binary_cross_entropy::<f32>())?;
+        let backward = build(processor, binary_cross_entropy_gradient::<f32>())?;
+        Self::from_kernels(processor, forward, backward, 1e-7, false)
+    }
+
+    /// For logits of shape `[batch, classes]` and target probabilities of the same shape, e.g. one-hot rows.
+    pub fn cross_entropy(processor: &mut P) -> Result<Self, Error>
+    where
+        CrossEntropyProgramm<f32>: Compilable<Void, P::Compiler>,
+        CrossEntropyGradientProgramm<f32>: Compilable<Void, P::Compiler>,
+        P::Executable<CrossEntropy<f32>>: Executable<P::Storage, CrossEntropyInputs<P::Storage, f32>>,
+        P::Executable<CrossEntropyGradient<f32>>: Executable<P::Storage, CrossEntropyGradientInputs<P::Storage, f32>>,
+    {
+        let forward = build(processor, cross_entropy::<f32>())?;
+        let backward = build(processor, cross_entropy_gradient::<f32>())?;
+        Self::from_kernels(processor, forward, backward, 0.0, true)
+    }
+
+    /// For log-probabilities as input and probabilities as target.
+    pub fn kl_divergence(processor: &mut P) -> Result<Self, Error>
+    where
+        KlDivergenceProgramm<f32>: Compilable<Void, P::Compiler>,
+        KlDivergenceGradientProgramm<f32>: Compilable<Void, P::Compiler>,
+        P::Executable<KlDivergence<f32>>: Executable<P::Storage, KlDivergenceInputs<P::Storage, f32>>,
+        P::Executable<KlDivergenceGradient<f32>>: Executable<P::Storage, KlDivergenceGradientInputs<P::Storage, f32>>,
+    {
+        let forward = build(processor, kl_divergence::<f32>())?;
+        let backward = build(processor, kl_divergence_gradient::<f32>())?;
+        Self::from_kernels(processor, forward, backward, 0.0, false)
+    }
+
+    fn from_kernels(processor: &mut P, forward: TernaryKernel<P>, backward: TernaryKernel<P>, parameter: f32, rows: bool) -> Result<Self, Error> {
+        Ok(Loss { reduction: Reduction::Mean, parameter, rows, forward, backward, sum: build(processor, loss_sum::<f32>())? })
+    }
+}
+
+impl<P: Processor> Loss<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    pub fn reduction(mut self, reduction: Reduction) -> Self {
+        self.reduction = reduction;
+        self
+    }
+
+    /// The shape of the loss terms of an input.
+    fn terms(&self, input: &Batch<P::Storage>, target: &Batch<P::Storage>) -> Result<Vec<usize>, Error> {
+        match input.shape.as_slice() {
+            [batch, _] if self.rows && input.shape == target.shape && *batch > 0 => Ok(vec![*batch]),
+            _ if !self.rows && input.shape == target.shape && !input.is_empty() => Ok(input.shape.clone()),
+            _ => Err(Error::ShapeMismatch {
+                argument: "target".to_string(),
+                constraint: if self.rows { format!("[batch, classes] like the input {:?}", input.shape) } else { format!("{:?} like the input", input.shape) },
+                found: target.shape.iter().map(|e| *e as u64).collect(),
+            }),
+        }
+    }
+
+    fn hyper(&self, processor: &mut P, terms: &[usize]) -> Result<Parameter<P::Storage>, Error> {
+        let scale = match self.reduction {
+            Reduction::Mean => 1.0 / terms.iter().product::<usize>() as f32,
+            Reduction::Sum | Reduction::None => 1.0,
+        };
+        processor.try_alloc(Tensor { data: vec![scale, self.parameter], shape: vec![2] })
+    }
+
+    /// The loss of `input` against `target`, of shape `[1]` unless the reduction is [`Reduction::None`].
+    pub fn forward(&mut self, processor: &mut P, input: &Batch<P::Storage>, target: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let terms = self.terms(input, target)?;
+        let hyper = self.hyper(processor, &terms)?;
+
+        let losses = Batch::zeros(processor, terms)?;
+        (self.forward)(processor, (input.value.clone(), target.value.clone(), hyper), &losses.value, losses.len())?;
+
+        if self.reduction == Reduction::None {
+            return Ok(losses);
+        }
+
+        let total = Batch::zeros(processor, vec![1])?;
+        (self.sum)(processor, (losses.value.clone(),), &total.value, 1)?;
+        Ok(total)
+    }
+
+    /// The gradient of the loss with respect to `input`, it can be passed to [`Layer::backward`](super::Layer::backward).
+    pub fn backward(&mut self, processor: &mut P, input: &Batch<P::Storage>, target: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let terms = self.terms(input, target)?;
+        let hyper = self.hyper(processor, &terms)?;
+
+        let input_gradient = Batch::zeros(processor, input.shape.clone())?;
+        (self.backward)(processor, (input.value.clone(), target.value.clone(), hyper), &input_gradient.value, input_gradient.len())?;
+        Ok(input_gradient)
+    }
+}
@@ -0,0 +1,134 @@
+//! Neural network layers built from kernels.
+//!
+//! A [`Layer`] runs its forward pass as kernels on the processor it was created for and keeps what
+//! its backward pass needs, usually the last input. Parameters and their gradients are [`Parameter`]
+//! bindings, so they can be handed to an [`Optimizer`](crate::optim::Optimizer) directly:
+//!
+//! ```ignore
+//! let output = model.forward(&mut processor, &input)?;
+//! model.backward(&mut processor, &loss.backward(&mut processor, &output, &target)?)?;
+//! optimizer.step(&mut processor, &mut model.parameters(), &model.gradients())?;
+//! ```
+//!
+//! The backward passes are written out as kernels next to the forward passes. They only visit the
+//! output elements an element contributes to, while the programs of
+//! [`derivatives`](crate::core::derivatives) run the whole kernel for every element. The tests check
+//! them against the [`jacobian`](crate::core::derivatives::jacobian) of the forward kernels. The
+//! gradient of a [`Loss`] starts the backward pass.
+
+// The kernels accumulate with `x = x + ..`, they have no compound assignment.
+#![allow(clippy::assign_op_pattern)]
+
+use crate::{core::{processor::{Processor, ProcessorInformation, Storage}, type_traits::{FromMut, MemoryMapable}, types::Shape}, kernel, optim::Parameter, types::tensor::Tensor, Error};
+
+pub mod activation;
+pub mod conv;
+pub mod dropout;
+pub mod embedding;
+pub mod linear;
+pub mod loss;
+pub mod norm;
+pub mod sequential;
+pub use activation::Activation;
+pub use conv::Conv2d;
+pub use dropout::Dropout;
+pub use embedding::Embedding;
+pub use linear::Linear;
+pub use loss::{Loss, Reduction};
+pub use norm::{BatchNorm, LayerNorm};
+pub use sequential::Sequential;
+
+/// A tensor on a processor together with its shape, the first dimension is the batch.
+#[derive(Clone)]
+pub struct Batch<S: Storage> where Tensor<f32>: MemoryMapable<S> {
+    pub value: Parameter<S>,
+    pub shape: Shape,
+}
+
+impl<S: Storage> Batch<S> where Tensor<f32>: MemoryMapable<S> {
+    pub fn new<P: Processor + ProcessorInformation<Storage = S>>(processor: &mut P, tensor: Tensor<f32>) -> Result<Self, Error> {
+        let shape = tensor.shape.clone();
+        Ok(Batch { value: processor.try_alloc(tensor)?, shape })
+    }
+
+    pub fn zeros<P: Processor + ProcessorInformation<Storage = S>>(processor: &mut P, shape: Shape) -> Result<Self, Error> {
+        Self::new(processor, Tensor::new(0.0, shape))
+    }
+
+    pub fn to_cpu<P: Processor + ProcessorInformation<Storage = S>>(&self, processor: &mut P) -> Result<Tensor<f32>, Error> {
+        processor.try_copy_to_cpu(&self.value)
+    }
+
+    /// The number of elements.
+    pub fn len(&self) -> u32 {
+        self.shape.iter().product::<usize>() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fails with [`Error::ShapeMismatch`] unless the shape is `[batch, expected..]`.
+    pub(crate) fn expect(&self, argument: &str, expected: &[usize]) -> Result<(), Error> {
+        if self.shape.len() == expected.len() + 1 && self.shape[1..] == *expected {
+            return Ok(());
+        }
+
+        Err(Error::ShapeMismatch {
+            argument: argument.to_string(),
+            constraint: format!("[batch, {}]", expected.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(", ")),
+            found: self.shape.iter().map(|e| *e as u64).collect(),
+        })
+    }
+}
+
+pub trait Layer<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn forward(&mut self, processor: &mut P, input: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error>;
+
+    /// Takes the gradient of the last output and returns the one of the last input.
+    ///
+    /// The gradients of the parameters are overwritten, not accumulated.
+    fn backward(&mut self, processor: &mut P, gradient: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error>;
+
+    fn parameters(&self) -> Vec<Parameter<P::Storage>> {
+        Vec::new()
+    }
+
+    /// The gradients of the last backward pass, in the order of [`Layer::parameters`].
+    fn gradients(&self) -> Vec<Parameter<P::Storage>> {
+        Vec::new()
+    }
+
+    /// Switches between training and evaluation, e.g. for [`Dropout`] and [`BatchNorm`].
+    fn train(&mut self, _training: bool) {}
+}
+
+/// A value saved by the last forward pass.
+pub(crate) fn saved<S: Storage>(input: &Option<Batch<S>>) -> Result<&Batch<S>, Error> where Tensor<f32>: MemoryMapable<S> {
+    input.as_ref().ok_or(Error::NoForwardPass)
+}
+
+/// Adds `bias` to channel `c` of `values` shaped like `[batch, channels, ..]`.
+#[kernel]
+pub fn add_channel_bias(pos: Pos, bias: &Tensor<f32>, values: &mut Tensor<f32>) {
+    let channels = bias.len();
+    let inner = values.len() / (values.shape(0) * channels);
+    let outer = pos.x / inner;
+    let channel = outer - (outer / channels) * channels;
+    values[pos.x] = values[pos.x] + bias[channel];
+}
+
+/// Sums `values` shaped like `[batch, channels, ..]` over everything but the channel, e.g. to get the gradient of a bias.
+#[kernel]
+pub fn channel_sum(pos: Pos, values: &Tensor<f32>, sums: &mut Tensor<f32>) {
+    let channels = sums.len();
+    let batch = values.shape(0);
+    let inner = values.len() / (batch * channels);
+    let mut sum = 0.0f32;
+    for n in 0..batch {
+        for i in 0..inner {
+            sum = sum + values[(n * channels + pos.x) * inner + i];
+        }
+    }
+    sums[pos.x] = sum;
+}
@@ -0,0 +1,475 @@
+//! Batch and layer normalization.
+//!
+//! Both normalize the input to `x̂ = (x - mean) / sqrt(var + eps)` and return `scale * x̂ + shift`.
+//! The scale and shift are a single parameter of shape `[2, features]`, the scale comes first.
+//! Statistics are tensors of shape `[3, groups]` holding the inverse standard deviation, the mean
+//! and the variance of every group, a channel for batch normalization and a row for layer normalization.
+
+use crate::{core::{operation::Compilable, processor::{Executable, Processor, Storage}, type_traits::{FromMut, MemoryMapable}, types::Void}, kernel, optim::{build, BinaryKernel, Parameter, TernaryKernel}, types::tensor::Tensor, Error};
+
+use super::{Batch, Layer};
+
+/// `scale * normalized + shift` per channel of `normalized` shaped like `[batch, channels, ..]`.
+#[kernel]
+pub fn channel_affine(pos: Pos, normalized: &Tensor<f32>, affine: &Tensor<f32>, output: &mut Tensor<f32>) {
+    let channels = affine.shape(1);
+    let inner = normalized.len() / (normalized.shape(0) * channels);
+    let outer = pos.x / inner;
+    let channel = outer - (outer / channels) * channels;
+    output[pos.x] = affine[channel] * normalized[pos.x] + affine[channels + channel];
+}
+
+/// The gradient of [`channel_affine`] with respect to the scale and shift, one invocation per channel.
+#[kernel]
+pub fn channel_affine_gradient(pos: Pos, gradient: &Tensor<f32>, normalized: &Tensor<f32>, affine_gradient: &mut Tensor<f32>) {
+    let channels = affine_gradient.shape(1);
+    let batch = gradient.shape(0);
+    let inner = gradient.len() / (batch * channels);
+    let mut scale = 0.0f32;
+    let mut shift = 0.0f32;
+    for n in 0..batch {
+        for i in 0..inner {
+            let index = (n * channels + pos.x) * inner + i;
+            scale = scale + gradient[index] * normalized[index];
+            shift = shift + gradient[index];
+        }
+    }
+    affine_gradient[pos.x] = scale;
+    affine_gradient[channels + pos.x] = shift;
+}
+
+/// Multiplies every channel of `values` with the first row of `factors`.
+#[kernel]
+pub fn channel_scale(pos: Pos, values: &Tensor<f32>, factors: &Tensor<f32>, output: &mut Tensor<f32>) {
+    let channels = factors.shape(1);
+    let inner = values.len() / (values.shape(0) * channels);
+    let outer = pos.x / inner;
+    let channel = outer - (outer / channels) * channels;
+    output[pos.x] = values[pos.x] * factors[channel];
+}
+
+/// Statistics of every channel of `input`, `hyper` holds the epsilon.
+#[kernel]
+pub fn batch_norm_statistics(pos: Pos, input: &Tensor<f32>, hyper: &Tensor<f32>, statistics: &mut Tensor<f32>) {
+    let channels = statistics.shape(1);
+    let batch = input.shape(0);
+    let inner = input.len() / (batch * channels);
+    let count = (batch * inner) as f32;
+    let mut sum = 0.0f32;
+    for n in 0..batch {
+        for i in 0..inner {
+            sum = sum + input[(n * channels + pos.x) * inner + i];
+        }
+    }
+    let mean = sum / count;
+    let mut squares = 0.0f32;
+    for n in 0..batch {
+        for i in 0..inner {
+            let centered = input[(n * channels + pos.x) * inner + i] - mean;
+            squares = squares + centered * centered;
+        }
+    }
+    let variance = squares / count;
+    statistics[pos.x] = 1.0 / (variance + hyper[0]).sqrt();
+    statistics[channels + pos.x] = mean;
+    statistics[2 * channels + pos.x] = variance;
+}
+
+/// Moves the running mean and variance towards the batch statistics, `hyper` holds the epsilon,
+/// the momentum and the correction from the biased to the unbiased variance.
+#[kernel]
+pub fn batch_norm_running(pos: Pos, statistics: &Tensor<f32>, hyper: &Tensor<f32>, running: &mut Tensor<f32>) {
+    let channels = running.shape(1);
+    let momentum = hyper[1];
+    running[pos.x] = (1.0 - momentum) * running[pos.x] + momentum * statistics[channels + pos.x];
+    running[channels + pos.x] = (1.0 - momentum) * running[channels + pos.x] + momentum * statistics[2 * channels + pos.x] * hyper[2];
+}
+
+/// Statistics from the running mean and variance for evaluation.
+#[kernel]
+pub fn batch_norm_running_statistics(pos: Pos, running: &Tensor<f32>, hyper: &Tensor<f32>, statistics: &mut Tensor<f32>) {
+    let channels = running.shape(1);
+    let variance = running[channels + pos.x];
+    statistics[pos.x] = 1.0 / (variance + hyper[0]).sqrt();
+    statistics[channels + pos.x] = running[pos.x];
+    statistics[2 * channels + pos.x] = variance;
+}
+
+#[kernel]
+pub fn batch_norm_normalize(pos: Pos, input: &Tensor<f32>, statistics: &Tensor<f32>, normalized: &mut Tensor<f32>) {
+    let channels = statistics.shape(1);
+    let inner = input.len() / (input.shape(0) * channels);
+    let outer = pos.x / inner;
+    let channel = outer - (outer / channels) * channels;
+    normalized[pos.x] = (input[pos.x] - statistics[channels + channel]) * statistics[channel];
+}
+
+/// The inverse standard deviation and the means of the gradient and of the gradient times the normalized input per channel.
+#[kernel]
+pub fn batch_norm_coefficients(pos: Pos, gradient: &Tensor<f32>, normalized: &Tensor<f32>, statistics: &Tensor<f32>, coefficients: &mut Tensor<f32>) {
+    let channels = statistics.shape(1);
+    let batch = gradient.shape(0);
+    let inner = gradient.len() / (batch * channels);
+    let count = (batch * inner) as f32;
+    let mut sum = 0.0f32;
+    let mut projected = 0.0f32;
+    for n in 0..batch {
+        for i in 0..inner {
+            let index = (n * channels + pos.x) * inner + i;
+            sum = sum + gradient[index];
+            projected = projected + gradient[index] * normalized[index];
+        }
+    }
+    coefficients[pos.x] = statistics[pos.x];
+    coefficients[channels + pos.x] = sum / count;
+    coefficients[2 * channels + pos.x] = projected / count;
+}
+
+#[kernel]
+pub fn batch_norm_input_gradient(pos: Pos, gradient: &Tensor<f32>, normalized: &Tensor<f32>, coefficients: &Tensor<f32>, input_gradient: &mut Tensor<f32>) {
+    let channels = coefficients.shape(1);
+    let inner = gradient.len() / (gradient.shape(0) * channels);
+    let outer = pos.x / inner;
+    let channel = outer - (outer / channels) * channels;
+    input_gradient[pos.x] = coefficients[channel] * (gradient[pos.x] - coefficients[channels + channel] - normalized[pos.x] * coefficients[2 * channels + channel]);
+}
+
+/// Statistics of every row of `input`, `hyper` holds the epsilon.
+#[kernel]
+pub fn layer_norm_statistics(pos: Pos, input: &Tensor<f32>, hyper: &Tensor<f32>, statistics: &mut Tensor<f32>) {
+    let rows = statistics.shape(1);
+    let features = input.shape(1);
+    let mut sum = 0.0f32;
+    for i in 0..features {
+        sum = sum + input[pos.x * features + i];
+    }
+    let mean = sum / features as f32;
+    let mut squares = 0.0f32;
+    for i in 0..features {
+        let centered = input[pos.x * features + i] - mean;
+        squares = squares + centered * centered;
+    }
+    let variance = squares / features as f32;
+    statistics[pos.x] = 1.0 / (variance + hyper[0]).sqrt();
+    statistics[rows + pos.x] = mean;
+    statistics[2 * rows + pos.x] = variance;
+}
+
+#[kernel]
+pub fn layer_norm_normalize(pos: Pos, input: &Tensor<f32>, statistics: &Tensor<f32>, normalized: &mut Tensor<f32>) {
+    let rows = statistics.shape(1);
+    let row = pos.x / input.shape(1);
+    normalized[pos.x] = (input[pos.x] - statistics[rows + row]) * statistics[row];
+}
+
+/// Like [`batch_norm_coefficients`] per row.
+#[kernel]
+pub fn layer_norm_coefficients(pos: Pos, gradient: &Tensor<f32>, normalized: &Tensor<f32>, statistics: &Tensor<f32>, coefficients: &mut Tensor<f32>) {
+    let rows = statistics.shape(1);
+    let features = gradient.shape(1);
+    let mut sum = 0.0f32;
+    let mut projected = 0.0f32;
+    for i in 0..features {
+        let index = pos.x * features + i;
+        sum = sum + gradient[index];
+        projected = projected + gradient[index] * normalized[index];
+    }
+    coefficients[pos.x] = statistics[pos.x];
+    coefficients[rows + pos.x] = sum / features as f32;
+    coefficients[2 * rows + pos.x] = projected / features as f32;
+}
+
+#[kernel]
+pub fn layer_norm_input_gradient(pos: Pos, gradient: &Tensor<f32>, normalized: &Tensor<f32>, coefficients: &Tensor<f32>, input_gradient: &mut Tensor<f32>) {
+    let rows = coefficients.shape(1);
+    let row = pos.x / gradient.shape(1);
+    input_gradient[pos.x] = coefficients[row] * (gradient[pos.x] - coefficients[rows + row] - normalized[pos.x] * coefficients[2 * rows + row]);
+}
+
+/// What the backward pass needs from the last forward pass.
+struct Saved<S: Storage> where Tensor<f32>: MemoryMapable<S> {
+    normalized: Batch<S>,
+    statistics: Parameter<S>,
+    /// Whether the statistics were computed from the batch instead of the running ones.
+    from_batch: bool,
+}
+
+/// Kernels both normalizations share.
+struct Affine<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    affine: Parameter<P::Storage>,
+    affine_gradient: Parameter<P::Storage>,
+    forward: BinaryKernel<P>,
+    backward: BinaryKernel<P>,
+    scale: BinaryKernel<P>,
+}
+
+impl<P: Processor + 'static> Affine<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn new(processor: &mut P, features: usize) -> Result<Self, Error>
+    where
+        ChannelAffineProgramm: Compilable<Void, P::Compiler>,
+        ChannelAffineGradientProgramm: Compilable<Void, P::Compiler>,
+        ChannelScaleProgramm: Compilable<Void, P::Compiler>,
+        P::Executable<ChannelAffine>: Executable<P::Storage, ChannelAffineInputs<P::Storage>>,
+        P::Executable<ChannelAffineGradient>: Executable<P::Storage, ChannelAffineGradientInputs<P::Storage>>,
+        P::Executable<ChannelScale>: Executable<P::Storage, ChannelScaleInputs<P::Storage>>,
+    {
+        let mut affine = Tensor::new(0.0, vec![2, features]);
+        affine.data[..features].fill(1.0);
+
+        Ok(Affine {
+            affine: processor.try_alloc(affine)?,
+            affine_gradient: processor.try_alloc(Tensor::new(0.0, vec![2, features]))?,
+            forward: build(processor, channel_affine())?,
+            backward: build(processor, channel_affine_gradient())?,
+            scale: build(processor, channel_scale())?,
+        })
+    }
+
+    fn forward(&mut self, processor: &mut P, normalized: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let output = Batch::zeros(processor, normalized.shape.clone())?;
+        (self.forward)(processor, (normalized.value.clone(), self.affine.clone()), &output.value, output.len())?;
+        Ok(output)
+    }
+
+    /// Writes the gradient of the scale and shift and returns the one of the normalized input.
+    fn backward(&mut self, processor: &mut P, gradient: &Batch<P::Storage>, normalized: &Batch<P::Storage>, features: usize) -> Result<Batch<P::Storage>, Error> {
+        (self.backward)(processor, (gradient.value.clone(), normalized.value.clone()), &self.affine_gradient, features as u32)?;
+
+        let scaled = Batch::zeros(processor, gradient.shape.clone())?;
+        (self.scale)(processor, (gradient.value.clone(), self.affine.clone()), &scaled.value, scaled.len())?;
+        Ok(scaled)
+    }
+}
+
+/// Normalizes every channel of inputs shaped like `[batch, channels, ..]` over the batch and all other dimensions.
+///
+/// While training the running mean and variance are updated with the momentum, evaluation normalizes with them.
+pub struct BatchNorm<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    channels: usize,
+    epsilon: f32,
+    momentum: f32,
+    training: bool,
+    running: Parameter<P::Storage>,
+    affine: Affine<P>,
+    saved: Option<Saved<P::Storage>>,
+    statistics: BinaryKernel<P>,
+    update: BinaryKernel<P>,
+    running_statistics: BinaryKernel<P>,
+    normalize: BinaryKernel<P>,
+    coefficients: TernaryKernel<P>,
+    input_gradient: TernaryKernel<P>,
+}
+
+impl<P: Processor + 'static> BatchNorm<P>
+where
+    Tensor<f32>: MemoryMapable<P::Storage>,
+    ChannelAffineProgramm: Compilable<Void, P::Compiler>,
+    ChannelAffineGradientProgramm: Compilable<Void, P::Compiler>,
+    ChannelScaleProgramm: Compilable<Void, P::Compiler>,
+    BatchNormStatisticsProgramm: Compilable<Void, P::Compiler>,
+    BatchNormRunningProgramm: Compilable<Void, P::Compiler>,
+    BatchNormRunningStatisticsProgramm: Compilable<Void, P::Compiler>,
+    BatchNormNormalizeProgramm: Compilable<Void, P::Compiler>,
+    BatchNormCoefficientsProgramm: Compilable<Void, P::Compiler>,
+    BatchNormInputGradientProgramm: Compilable<Void, P::Compiler>,
+    P::Executable<ChannelAffine>: Executable<P::Storage, ChannelAffineInputs<P::Storage>>,
+    P::Executable<ChannelAffineGradient>: Executable<P::Storage, ChannelAffineGradientInputs<P::Storage>>,
+    P::Executable<ChannelScale>: Executable<P::Storage, ChannelScaleInputs<P::Storage>>,
+    P::Executable<BatchNormStatistics>: Executable<P::Storage, BatchNormStatisticsInputs<P::Storage>>,
+    P::Executable<BatchNormRunning>: Executable<P::Storage, BatchNormRunningInputs<P::Storage>>,
+    P::Executable<BatchNormRunningStatistics>: Executable<P::Storage, BatchNormRunningStatisticsInputs<P::Storage>>,
+    P::Executable<BatchNormNormalize>: Executable<P::Storage, BatchNormNormalizeInputs<P::Storage>>,
+    P::Executable<BatchNormCoefficients>: Executable<P::Storage, BatchNormCoefficientsInputs<P::Storage>>,
+    P::Executable<BatchNormInputGradient>: Executable<P::Storage, BatchNormInputGradientInputs<P::Storage>>,
+{
+    /// Starts with a scale of one, no shift, a running mean of zero and a running variance of one.
+    pub fn new(processor: &mut P, channels: usize) -> Result<Self, Error> {
+        let mut running = Tensor::new(0.0, vec![2, channels]);
+        running.data[channels..].fill(1.0);
+
+        Ok(BatchNorm {
+            channels,
+            epsilon: 1e-5,
+            momentum: 0.1,
+            training: true,
+            running: processor.try_alloc(running)?,
+            affine: Affine::new(processor, channels)?,
+            saved: None,
+            statistics: build(processor, batch_norm_statistics())?,
+            update: build(processor, batch_norm_running())?,
+            running_statistics: build(processor, batch_norm_running_statistics())?,
+            normalize: build(processor, batch_norm_normalize())?,
+            coefficients: build(processor, batch_norm_coefficients())?,
+            input_gradient: build(processor, batch_norm_input_gradient())?,
+        })
+    }
+}
+
+impl<P: Processor> BatchNorm<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    pub fn epsilon(mut self, epsilon: f32) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// How far the running statistics move towards those of every batch, `0.1` by default.
+    pub fn momentum(mut self, momentum: f32) -> Self {
+        self.momentum = momentum;
+        self
+    }
+
+    /// The running mean and variance as a tensor of shape `[2, channels]`.
+    pub fn running(&self) -> &Parameter<P::Storage> {
+        &self.running
+    }
+}
+
+impl<P: Processor + 'static> Layer<P> for BatchNorm<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn forward(&mut self, processor: &mut P, input: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        if input.shape.len() < 2 || input.shape[1] != self.channels {
+            return Err(Error::ShapeMismatch {
+                argument: "input".to_string(),
+                constraint: format!("[batch, {}, ..]", self.channels),
+                found: input.shape.iter().map(|e| *e as u64).collect(),
+            });
+        }
+
+        let count = input.len() / self.channels as u32;
+        let correction = if count > 1 { count as f32 / (count - 1) as f32 } else { 1.0 };
+        let hyper = processor.try_alloc(Tensor { data: vec![self.epsilon, self.momentum, correction], shape: vec![3] })?;
+        let statistics = processor.try_alloc(Tensor::new(0.0, vec![3, self.channels]))?;
+
+        if self.training {
+            (self.statistics)(processor, (input.value.clone(), hyper.clone()), &statistics, self.channels as u32)?;
+            (self.update)(processor, (statistics.clone(), hyper), &self.running, self.channels as u32)?;
+        } else {
+            (self.running_statistics)(processor, (self.running.clone(), hyper), &statistics, self.channels as u32)?;
+        }
+
+        let normalized = Batch::zeros(processor, input.shape.clone())?;
+        (self.normalize)(processor, (input.value.clone(), statistics.clone()), &normalized.value, normalized.len())?;
+
+        let output = self.affine.forward(processor, &normalized)?;
+        self.saved = Some(Saved { normalized, statistics, from_batch: self.training });
+        Ok(output)
+    }
+
+    fn backward(&mut self, processor: &mut P, gradient: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let Saved { normalized, statistics, from_batch } = self.saved.as_ref().ok_or(Error::NoForwardPass)?;
+        let scaled = self.affine.backward(processor, gradient, normalized, self.channels)?;
+
+        let input_gradient = Batch::zeros(processor, gradient.shape.clone())?;
+        if *from_batch {
+            let coefficients = processor.try_alloc(Tensor::new(0.0, vec![3, self.channels]))?;
+            (self.coefficients)(processor, (scaled.value.clone(), normalized.value.clone(), statistics.clone()), &coefficients, self.channels as u32)?;
+            (self.input_gradient)(processor, (scaled.value, normalized.value.clone(), coefficients), &input_gradient.value, input_gradient.len())?;
+        } else {
+            // The running statistics are constants, only the inverse standard deviation remains.
+            (self.affine.scale)(processor, (scaled.value, statistics.clone()), &input_gradient.value, input_gradient.len())?;
+        }
+
+        Ok(input_gradient)
+    }
+
+    fn parameters(&self) -> Vec<Parameter<P::Storage>> {
+        vec![self.affine.affine.clone()]
+    }
+
+    fn gradients(&self) -> Vec<Parameter<P::Storage>> {
+        vec![self.affine.affine_gradient.clone()]
+    }
+
+    fn train(&mut self, training: bool) {
+        self.training = training;
+    }
+}
+
+/// Normalizes every row of inputs of shape `[batch, features]` over its features.
+pub struct LayerNorm<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    features: usize,
+    epsilon: f32,
+    affine: Affine<P>,
+    saved: Option<Saved<P::Storage>>,
+    statistics: BinaryKernel<P>,
+    normalize: BinaryKernel<P>,
+    coefficients: TernaryKernel<P>,
+    input_gradient: TernaryKernel<P>,
+}
+
+impl<P: Processor + 'static> LayerNorm<P>
+where
+    Tensor<f32>: MemoryMapable<P::Storage>,
+    ChannelAffineProgramm: Compilable<Void, P::Compiler>,
+    ChannelAffineGradientProgramm: Compilable<Void, P::Compiler>,
+    ChannelScaleProgramm: Compilable<Void, P::Compiler>,
+    LayerNormStatisticsProgramm: Compilable<Void, P::Compiler>,
+    LayerNormNormalizeProgramm: Compilable<Void, P::Compiler>,
+    LayerNormCoefficientsProgramm: Compilable<Void, P::Compiler>,
+    LayerNormInputGradientProgramm: Compilable<Void, P::Compiler>,
+    P::Executable<ChannelAffine>: Executable<P::Storage, ChannelAffineInputs<P::Storage>>,
+    P::Executable<ChannelAffineGradient>: Executable<P::Storage, ChannelAffineGradientInputs<P::Storage>>,
+    P::Executable<ChannelScale>: Executable<P::Storage, ChannelScaleInputs<P::Storage>>,
+    P::Executable<LayerNormStatistics>: Executable<P::Storage, LayerNormStatisticsInputs<P::Storage>>,
+    P::Executable<LayerNormNormalize>: Executable<P::Storage, LayerNormNormalizeInputs<P::Storage>>,
+    P::Executable<LayerNormCoefficients>: Executable<P::Storage, LayerNormCoefficientsInputs<P::Storage>>,
+    P::Executable<LayerNormInputGradient>: Executable<P::Storage, LayerNormInputGradientInputs<P::Storage>>,
+{
+    /// Starts with a scale of one and no shift.
+    pub fn new(processor: &mut P, features: usize) -> Result<Self, Error> {
+        Ok(LayerNorm {
+            features,
+            epsilon: 1e-5,
+            affine: Affine::new(processor, features)?,
+            saved: None,
+            statistics: build(processor, layer_norm_statistics())?,
+            normalize: build(processor, layer_norm_normalize())?,
+            coefficients: build(processor, layer_norm_coefficients())?,
+            input_gradient: build(processor, layer_norm_input_gradient())?,
+        })
+    }
+}
+
+impl<P: Processor> LayerNorm<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    pub fn epsilon(mut self, epsilon: f32) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+}
+
+impl<P: Processor + 'static> Layer<P> for LayerNorm<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn forward(&mut self, processor: &mut P, input: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        input.expect("input", &[self.features])?;
+
+        let rows = input.shape[0];
+        let hyper = processor.try_alloc(Tensor { data: vec![self.epsilon], shape: vec![1] })?;
+        let statistics = processor.try_alloc(Tensor::new(0.0, vec![3, rows]))?;
+        (self.statistics)(processor, (input.value.clone(), hyper), &statistics, rows as u32)?;
+
+        let normalized = Batch::zeros(processor, input.shape.clone())?;
+        (self.normalize)(processor, (input.value.clone(), statistics.clone()), &normalized.value, normalized.len())?;
+
+        let output = self.affine.forward(processor, &normalized)?;
+        self.saved = Some(Saved { normalized, statistics, from_batch: true });
+        Ok(output)
+    }
+
+    fn backward(&mut self, processor: &mut P, gradient: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let Saved { normalized, statistics, .. } = self.saved.as_ref().ok_or(Error::NoForwardPass)?;
+        let scaled = self.affine.backward(processor, gradient, normalized, self.features)?;
+
+        let rows = gradient.shape[0];
+        let coefficients = processor.try_alloc(Tensor::new(0.0, vec![3, rows]))?;
+        (self.coefficients)(processor, (scaled.value.clone(), normalized.value.clone(), statistics.clone()), &coefficients, rows as u32)?;
+
+        let input_gradient = Batch::zeros(processor, gradient.shape.clone())?;
+        (self.input_gradient)(processor, (scaled.value, normalized.value.clone(), coefficients), &input_gradient.value, input_gradient.len())?;
+        Ok(input_gradient)
+    }
+
+    fn parameters(&self) -> Vec<Parameter<P::Storage>> {
+        vec![self.affine.affine.clone()]
+    }
+
+    fn gradients(&self) -> Vec<Parameter<P::Storage>> {
+        vec![self.affine.affine_gradient.clone()]
+    }
+}
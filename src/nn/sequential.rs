@@ -0,0 +1,68 @@
+//! Layers applied one after another.
+
+use crate::{core::{processor::Processor, type_traits::MemoryMapable}, optim::Parameter, types::tensor::Tensor, Error};
+
+use super::{Batch, Layer};
+
+/// Feeds the output of every layer to the next one and the gradients back in reverse.
+pub struct Sequential<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    layers: Vec<Box<dyn Layer<P>>>,
+}
+
+impl<P: Processor> Sequential<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    pub fn new() -> Self {
+        Sequential { layers: Vec::new() }
+    }
+
+    /// Appends `layer` after the current last one.
+    pub fn with(mut self, layer: impl Layer<P> + 'static) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+}
+
+impl<P: Processor> Default for Sequential<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Processor> Layer<P> for Sequential<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn forward(&mut self, processor: &mut P, input: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let mut value = input.clone();
+        for layer in &mut self.layers {
+            value = layer.forward(processor, &value)?;
+        }
+        Ok(value)
+    }
+
+    fn backward(&mut self, processor: &mut P, gradient: &Batch<P::Storage>) -> Result<Batch<P::Storage>, Error> {
+        let mut gradient = gradient.clone();
+        for layer in self.layers.iter_mut().rev() {
+            gradient = layer.backward(processor, &gradient)?;
+        }
+        Ok(gradient)
+    }
+
+    fn parameters(&self) -> Vec<Parameter<P::Storage>> {
+        self.layers.iter().flat_map(|l| l.parameters()).collect()
+    }
+
+    fn gradients(&self) -> Vec<Parameter<P::Storage>> {
+        self.layers.iter().flat_map(|l| l.gradients()).collect()
+    }
+
+    fn train(&mut self, training: bool) {
+        for layer in &mut self.layers {
+            layer.train(training);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Adam and AdamW, gradient descent with bias corrected moment estimates.
+
+use crate::{core::{operation::Compilable, processor::{Executable, Processor}, type_traits::{FromMut, MemoryMapable}, types::Void}, kernel, types::tensor::Tensor, Error};
+
+use super::{build, decay_gradient, BinaryKernel, Buffers, DecayGradient, DecayGradientInputs, DecayGradientProgramm, Optimizer, OptimizerState, Parameter, TernaryKernel};
+
+const FIRST_MOMENT: usize = 0;
+const SECOND_MOMENT: usize = 1;
+
+/// Hyperparameters: learning rate, weight decay, beta1, beta2, epsilon, decoupled weight decay and
+/// the bias corrections of both moments.
+#[kernel]
+pub fn adam_first_moment(pos: Pos, gradient: &Tensor<f32>, hyper: &Tensor<f32>, moment: &mut Tensor<f32>) {
+    moment[pos.x] = hyper[2] * moment[pos.x] + (1.0 - hyper[2]) * gradient[pos.x];
+}
+
+#[kernel]
+pub fn adam_second_moment(pos: Pos, gradient: &Tensor<f32>, hyper: &Tensor<f32>, moment: &mut Tensor<f32>) {
+    let g = gradient[pos.x];
+    moment[pos.x] = hyper[3] * moment[pos.x] + (1.0 - hyper[3]) * g * g;
+}
+
+#[kernel]
+pub fn adam_step(pos: Pos, first: &Tensor<f32>, second: &Tensor<f32>, hyper: &Tensor<f32>, parameter: &mut Tensor<f32>) {
+    let m = first[pos.x] / hyper[6];
+    let v = second[pos.x] / hyper[7];
+    let p = parameter[pos.x];
+    parameter[pos.x] = p - hyper[0] * (m / (v.sqrt() + hyper[4]) + hyper[5] * p);
+}
+
+pub struct Adam<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    learning_rate: f32,
+    betas: (f32, f32),
+    epsilon: f32,
+    weight_decay: f32,
+    decoupled_weight_decay: f32,
+    step: u64,
+    buffers: Buffers<P>,
+    decay: TernaryKernel<P>,
+    first: BinaryKernel<P>,
+    second: BinaryKernel<P>,
+    update: TernaryKernel<P>,
+}
+
+impl<P: Processor + 'static> Adam<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    /// Adam with betas of 0.9 and 0.999 and an epsilon of 1e-8.
+    pub fn new(processor: &mut P, learning_rate: f32) -> Result<Self, Error>
+    where
+        DecayGradientProgramm: Compilable<Void, P::Compiler>,
+        AdamFirstMomentProgramm: Compilable<Void, P::Compiler>,
+        AdamSecondMomentProgramm: Compilable<Void, P::Compiler>,
+        AdamStepProgramm: Compilable<Void, P::Compiler>,
+        P::Executable<DecayGradient>: Executable<P::Storage, DecayGradientInputs<P::Storage>>,
+        P::Executable<AdamFirstMoment>: Executable<P::Storage, AdamFirstMomentInputs<P::Storage>>,
+        P::Executable<AdamSecondMoment>: Executable<P::Storage, AdamSecondMomentInputs<P::Storage>>,
+        P::Executable<AdamStep>: Executable<P::Storage, AdamStepInputs<P::Storage>>,
+    {
+        Ok(Adam {
+            learning_rate,
+            betas: (0.9, 0.999),
+            epsilon: 1e-8,
+            weight_decay: 0.0,
+            decoupled_weight_decay: 0.0,
+            step: 0,
+            buffers: Buffers::new(2),
+            decay: build(processor, decay_gradient())?,
+            first: build(processor, adam_first_moment())?,
+            second: build(processor, adam_second_moment())?,
+            update: build(processor, adam_step())?,
+        })
+    }
+
+    /// AdamW, the weights decay independently of the moment estimates.
+    pub fn adamw(processor: &mut P, learning_rate: f32, weight_decay: f32) -> Result<Self, Error>
+    where
+        DecayGradientProgramm: Compilable<Void, P::Compiler>,
+        AdamFirstMomentProgramm: Compilable<Void, P::Compiler>,
+        AdamSecondMomentProgramm: Compilable<Void, P::Compiler>,
+        AdamStepProgramm: Compilable<Void, P::Compiler>,
+        P::Executable<DecayGradient>: Executable<P::Storage, DecayGradientInputs<P::Storage>>,
+        P::Executable<AdamFirstMoment>: Executable<P::Storage, AdamFirstMomentInputs<P::Storage>>,
+        P::Executable<AdamSecondMoment>: Executable<P::Storage, AdamSecondMomentInputs<P::Storage>>,
+        P::Executable<AdamStep>: Executable<P::Storage, AdamStepInputs<P::Storage>>,
+    {
+        let mut adam = Self::new(processor, learning_rate)?;
+        adam.decoupled_weight_decay = weight_decay;
+        Ok(adam)
+    }
+
+    pub fn betas(mut self, beta1: f32, beta2: f32) -> Self {
+        self.betas = (beta1, beta2);
+        self
+    }
+
+    pub fn epsilon(mut self, epsilon: f32) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// Adds `weight_decay` times the parameter to its gradient, before the moments are updated.
+    pub fn weight_decay(mut self, weight_decay: f32) -> Self {
+        self.weight_decay = weight_decay;
+        self
+    }
+}
+
+impl<P: Processor + 'static> Optimizer<P> for Adam<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn step(&mut self, processor: &mut P, parameters: &mut [Parameter<P::Storage>], gradients: &[Parameter<P::Storage>]) -> Result<(), Error> {
+        self.buffers.prepare(processor, parameters, gradients, self.weight_decay != 0.0)?;
+
+        let (beta1, beta2) = self.betas;
+        let step = (self.step + 1) as i32;
+        let hyper = processor.try_alloc(Tensor {
+            data: vec![
+                self.learning_rate, self.weight_decay, beta1, beta2, self.epsilon, self.decoupled_weight_decay,
+                1.0 - beta1.powi(step), 1.0 - beta2.powi(step),
+            ],
+            shape: vec![8],
+        })?;
+
+        for (i, (parameter, gradient)) in parameters.iter_mut().zip(gradients).enumerate() {
+            let length = self.buffers.length(i);
+            let gradient = self.buffers.gradient(processor, &mut self.decay, i, parameter, gradient, &hyper)?;
+            let first = self.buffers.get(i, FIRST_MOMENT);
+            let second = self.buffers.get(i, SECOND_MOMENT);
+
+            (self.first)(processor, (gradient.clone(), hyper.clone()), first, length)?;
+            (self.second)(processor, (gradient, hyper.clone()), second, length)?;
+            (self.update)(processor, (first.clone(), second.clone(), hyper.clone()), parameter, length)?;
+        }
+
+        self.step += 1;
+        Ok(())
+    }
+
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn state(&self, processor: &mut P) -> Result<OptimizerState, Error> {
+        Ok(OptimizerState { step: self.step, learning_rate: self.learning_rate, buffers: self.buffers.save(processor)? })
+    }
+
+    fn load_state(&mut self, processor: &mut P, state: OptimizerState) -> Result<(), Error> {
+        self.buffers.load(processor, state.buffers)?;
+        self.step = state.step;
+        self.learning_rate = state.learning_rate;
+        Ok(())
+    }
+}
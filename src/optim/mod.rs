@@ -0,0 +1,231 @@
+//! Optimizers turning gradients into parameter updates.
+//!
+//! Every optimizer runs its update as kernels on the processor it was created for and keeps its
+//! state, like momentum or moment estimates, in [`Binding`]s next to the parameters. Gradients are
+//! the outputs of differentiated programs, one per parameter and in the same order on every step.
+//!
+//! The hyperparameters are passed to the kernels as a small tensor which is rebuilt on every step,
+//! so the learning rate can be changed between steps, e.g. by a [`LrScheduler`].
+
+use crate::{core::{allocated::{Binding, ExecutableBindings}, processor::{Executable, Processor, ProcessorInformation}, type_traits::{FromMut, MemoryMapable}, Buildable}, kernel, processor::cpu::CPUStorage, types::tensor::Tensor, Error};
+
+pub mod adam;
+pub mod rmsprop;
+pub mod scheduler;
+pub mod sgd;
+pub use adam::Adam;
+pub use rmsprop::RmsProp;
+pub use scheduler::{CosineAnnealingLr, ExponentialLr, LrScheduler, StepLr, Warmup};
+pub use sgd::Sgd;
+
+/// A trainable tensor or its gradient.
+pub type Parameter<S> = Binding<S, Tensor<f32>>;
+
+pub trait Optimizer<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    /// Updates every parameter with the gradient at the same index.
+    fn step(&mut self, processor: &mut P, parameters: &mut [Parameter<P::Storage>], gradients: &[Parameter<P::Storage>]) -> Result<(), Error>;
+
+    fn learning_rate(&self) -> f32;
+    fn set_learning_rate(&mut self, learning_rate: f32);
+
+    /// Copies the state to the host, e.g. to continue training later with [`Optimizer::load_state`].
+    fn state(&self, processor: &mut P) -> Result<OptimizerState, Error>;
+    fn load_state(&mut self, processor: &mut P, state: OptimizerState) -> Result<(), Error>;
+}
+
+/// Host copy of the state of an optimizer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptimizerState {
+    /// Steps taken so far.
+    pub step: u64,
+    pub learning_rate: f32,
+    /// Buffers of every parameter, all buffers of the first parameter come first.
+    pub buffers: Vec<Tensor<f32>>,
+}
+
+impl OptimizerState {
+    /// Little endian encoding of the state.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(self.step.to_le_bytes());
+        bytes.extend(self.learning_rate.to_le_bytes());
+        bytes.extend((self.buffers.len() as u64).to_le_bytes());
+
+        for buffer in &self.buffers {
+            bytes.extend((buffer.shape.len() as u64).to_le_bytes());
+            for extent in &buffer.shape {
+                bytes.extend((*extent as u64).to_le_bytes());
+            }
+            for value in &buffer.data {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader(bytes);
+
+        let step = reader.u64()?;
+        let learning_rate = reader.f32()?;
+        let buffers = (0..reader.u64()?)
+            .map(|_| {
+                let shape = (0..reader.u64()?).map(|_| reader.u64().map(|e| e as usize)).collect::<Result<Vec<_>, _>>()?;
+                let data = (0..shape.iter().product::<usize>()).map(|_| reader.f32()).collect::<Result<Vec<_>, _>>()?;
+                Ok(Tensor { data, shape })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        if !reader.0.is_empty() {
+            return Err(Error::InvalidOptimizerState(format!("{} trailing bytes", reader.0.len())));
+        }
+
+        Ok(OptimizerState { step, learning_rate, buffers })
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        if self.0.len() < N {
+            return Err(Error::InvalidOptimizerState("unexpected end of the state".to_string()));
+        }
+
+        let (bytes, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        self.take().map(f32::from_le_bytes)
+    }
+}
+
+/// The hyperparameters of every optimizer start with the learning rate and the weight decay.
+#[kernel]
+pub fn decay_gradient(pos: Pos, gradient: &Tensor<f32>, parameter: &Tensor<f32>, hyper: &Tensor<f32>, decayed: &mut Tensor<f32>) {
+    decayed[pos.x] = gradient[pos.x] + hyper[1] * parameter[pos.x];
+}
+
+/// A built kernel writing a tensor of the given length, `I` are the bindings of its inputs.
+pub(crate) type Kernel<P, I> = Box<dyn FnMut(&mut P, I, &Parameter<<P as ProcessorInformation>::Storage>, u32) -> Result<(), Error>>;
+pub(crate) type UnaryKernel<P> = Kernel<P, (Parameter<<P as ProcessorInformation>::Storage>,)>;
+pub(crate) type BinaryKernel<P> = Kernel<P, (Parameter<<P as ProcessorInformation>::Storage>, Parameter<<P as ProcessorInformation>::Storage>)>;
+pub(crate) type TernaryKernel<P> = Kernel<P, (Parameter<<P as ProcessorInformation>::Storage>, Parameter<<P as ProcessorInformation>::Storage>, Parameter<<P as ProcessorInformation>::Storage>)>;
+/// The [`Kernel`] returned by [`build`] for the buildable `B`.
+pub(crate) type BuiltKernel<P, B> = Kernel<P, <<B as Buildable<P>>::Binding as ExecutableBindings<<P as ProcessorInformation>::Storage>>::I>;
+
+/// Builds the kernel, it is bound for every call and unbound again afterwards.
+pub(crate) fn build<P, B>(processor: &mut P, kernel: B) -> Result<BuiltKernel<P, B>, Error>
+where
+    P: Processor + 'static,
+    B: Buildable<P> + 'static,
+    Tensor<f32>: MemoryMapable<P::Storage>,
+    B::Binding: ExecutableBindings<P::Storage, O = Tensor<f32>>,
+    <B::CPUBinding as ExecutableBindings<CPUStorage>>::O: 'static,
+    P::Executable<B>: Executable<P::Storage, B::Binding> + 'static,
+{
+    let mut executable = processor.try_build(kernel)?;
+
+    Ok(Box::new(move |processor, inputs, output, length| {
+        executable.get_bindings().set_arguments(inputs, output.clone());
+        let result = processor.dispatch(&mut executable, length, 1, 1);
+        *executable.get_bindings() = B::Binding::new();
+        result
+    }))
+}
+
+/// The buffers an optimizer keeps per parameter, allocated with zeros on the first step.
+pub(crate) struct Buffers<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    kinds: usize,
+    lengths: Vec<u32>,
+    values: Vec<Vec<Parameter<P::Storage>>>,
+    /// Gradients with weight decay added, only used if the optimizer decays weights.
+    decayed: Vec<Parameter<P::Storage>>,
+}
+
+impl<P: Processor> Buffers<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    pub(crate) fn new(kinds: usize) -> Self {
+        Buffers { kinds, lengths: Vec::new(), values: Vec::new(), decayed: Vec::new() }
+    }
+
+    fn zeros(processor: &mut P, shapes: &[Vec<usize>], kinds: usize) -> Result<Vec<Vec<Parameter<P::Storage>>>, Error> {
+        shapes.iter()
+            .map(|shape| (0..kinds).map(|_| processor.try_alloc(Tensor::new(0f32, shape.clone()))).collect())
+            .collect()
+    }
+
+    /// Checks the arguments of a step and allocates the buffers on the first one.
+    pub(crate) fn prepare(&mut self, processor: &mut P, parameters: &[Parameter<P::Storage>], gradients: &[Parameter<P::Storage>], decays: bool) -> Result<(), Error> {
+        if parameters.len() != gradients.len() {
+            return Err(Error::InvalidOptimizerState(format!("{} parameters but {} gradients", parameters.len(), gradients.len())));
+        }
+
+        if self.lengths.is_empty() {
+            let shapes = parameters.iter()
+                .map(|p| processor.try_copy_to_cpu(p).map(|t| t.shape))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            self.lengths = shapes.iter().map(|s| s.iter().product::<usize>() as u32).collect();
+            self.values = Self::zeros(processor, &shapes, self.kinds)?;
+        } else if self.lengths.len() != parameters.len() {
+            return Err(Error::InvalidOptimizerState(format!("expected {} parameters, got {}", self.lengths.len(), parameters.len())));
+        }
+
+        if decays && self.decayed.is_empty() {
+            let shapes = self.values.iter()
+                .map(|v| processor.try_copy_to_cpu(&v[0]).map(|t| t.shape))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            self.decayed = Self::zeros(processor, &shapes, 1)?.into_iter().flatten().collect();
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn length(&self, parameter: usize) -> u32 {
+        self.lengths[parameter]
+    }
+
+    pub(crate) fn get(&self, parameter: usize, kind: usize) -> &Parameter<P::Storage> {
+        &self.values[parameter][kind]
+    }
+
+    /// The gradient with weight decay added if the optimizer decays weights.
+    pub(crate) fn gradient(&self, processor: &mut P, decay: &mut TernaryKernel<P>, parameter: usize, weights: &Parameter<P::Storage>, gradient: &Parameter<P::Storage>, hyper: &Parameter<P::Storage>) -> Result<Parameter<P::Storage>, Error> {
+        match self.decayed.get(parameter) {
+            Some(decayed) => {
+                decay(processor, (gradient.clone(), weights.clone(), hyper.clone()), decayed, self.lengths[parameter])?;
+                Ok(decayed.clone())
+            }
+            None => Ok(gradient.clone()),
+        }
+    }
+
+    pub(crate) fn save(&self, processor: &mut P) -> Result<Vec<Tensor<f32>>, Error> {
+        self.values.iter().flatten().map(|b| processor.try_copy_to_cpu(b)).collect()
+    }
+
+    pub(crate) fn load(&mut self, processor: &mut P, buffers: Vec<Tensor<f32>>) -> Result<(), Error> {
+        if buffers.len() % self.kinds != 0 || (!self.lengths.is_empty() && buffers.len() != self.lengths.len() * self.kinds) {
+            return Err(Error::InvalidOptimizerState(format!("expected {} buffers per parameter, got {} in total", self.kinds, buffers.len())));
+        }
+
+        let mut values = Vec::new();
+        let mut buffers = buffers.into_iter();
+        while buffers.len() > 0 {
+            values.push(buffers.by_ref().take(self.kinds).map(|b| processor.try_alloc(b)).collect::<Result<Vec<_>, _>>()?);
+        }
+
+        self.lengths = values.iter().map(|v| processor.try_copy_to_cpu(&v[0]).map(|t| t.data.len() as u32)).collect::<Result<_, _>>()?;
+        self.values = values;
+        self.decayed = Vec::new();
+        Ok(())
+    }
+}
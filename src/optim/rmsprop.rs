@@ -0,0 +1,133 @@
+//! RMSProp, gradient descent scaled by a running average of the squared gradients.
+
+use crate::{core::{operation::Compilable, processor::{Executable, Processor}, type_traits::{FromMut, MemoryMapable}, types::Void}, kernel, types::tensor::Tensor, Error};
+
+use super::{build, decay_gradient, BinaryKernel, Buffers, DecayGradient, DecayGradientInputs, DecayGradientProgramm, Optimizer, OptimizerState, Parameter, TernaryKernel};
+
+const SQUARE_AVERAGE: usize = 0;
+const MOMENTUM: usize = 1;
+
+/// Hyperparameters: learning rate, weight decay, alpha, epsilon and momentum.
+#[kernel]
+pub fn rmsprop_square_average(pos: Pos, gradient: &Tensor<f32>, hyper: &Tensor<f32>, average: &mut Tensor<f32>) {
+    let g = gradient[pos.x];
+    average[pos.x] = hyper[2] * average[pos.x] + (1.0 - hyper[2]) * g * g;
+}
+
+#[kernel]
+pub fn rmsprop_momentum(pos: Pos, gradient: &Tensor<f32>, average: &Tensor<f32>, hyper: &Tensor<f32>, momentum: &mut Tensor<f32>) {
+    momentum[pos.x] = hyper[4] * momentum[pos.x] + gradient[pos.x] / (average[pos.x].sqrt() + hyper[3]);
+}
+
+#[kernel]
+// kernels have no compound assignment
+#[allow(clippy::assign_op_pattern)]
+pub fn rmsprop_step(pos: Pos, momentum: &Tensor<f32>, hyper: &Tensor<f32>, parameter: &mut Tensor<f32>) {
+    parameter[pos.x] = parameter[pos.x] - hyper[0] * momentum[pos.x];
+}
+
+pub struct RmsProp<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    learning_rate: f32,
+    alpha: f32,
+    epsilon: f32,
+    momentum: f32,
+    weight_decay: f32,
+    step: u64,
+    buffers: Buffers<P>,
+    decay: TernaryKernel<P>,
+    average: BinaryKernel<P>,
+    velocity: TernaryKernel<P>,
+    update: BinaryKernel<P>,
+}
+
+impl<P: Processor + 'static> RmsProp<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    /// RMSProp with an alpha of 0.99, an epsilon of 1e-8 and no momentum.
+    pub fn new(processor: &mut P, learning_rate: f32) -> Result<Self, Error>
+    where
+        DecayGradientProgramm: Compilable<Void, P::Compiler>,
+        RmspropSquareAverageProgramm: Compilable<Void, P::Compiler>,
+        RmspropMomentumProgramm: Compilable<Void, P::Compiler>,
+        RmspropStepProgramm: Compilable<Void, P::Compiler>,
+        P::Executable<DecayGradient>: Executable<P::Storage, DecayGradientInputs<P::Storage>>,
+        P::Executable<RmspropSquareAverage>: Executable<P::Storage, RmspropSquareAverageInputs<P::Storage>>,
+        P::Executable<RmspropMomentum>: Executable<P::Storage, RmspropMomentumInputs<P::Storage>>,
+        P::Executable<RmspropStep>: Executable<P::Storage, RmspropStepInputs<P::Storage>>,
+    {
+        Ok(RmsProp {
+            learning_rate,
+            alpha: 0.99,
+            epsilon: 1e-8,
+            momentum: 0.0,
+            weight_decay: 0.0,
+            step: 0,
+            buffers: Buffers::new(2),
+            decay: build(processor, decay_gradient())?,
+            average: build(processor, rmsprop_square_average())?,
+            velocity: build(processor, rmsprop_momentum())?,
+            update: build(processor, rmsprop_step())?,
+        })
+    }
+
+    /// Smoothing constant of the running average.
+    pub fn alpha(mut self, alpha: f32) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn epsilon(mut self, epsilon: f32) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn momentum(mut self, momentum: f32) -> Self {
+        self.momentum = momentum;
+        self
+    }
+
+    /// Adds `weight_decay` times the parameter to its gradient.
+    pub fn weight_decay(mut self, weight_decay: f32) -> Self {
+        self.weight_decay = weight_decay;
+        self
+    }
+}
+
+impl<P: Processor + 'static> Optimizer<P> for RmsProp<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn step(&mut self, processor: &mut P, parameters: &mut [Parameter<P::Storage>], gradients: &[Parameter<P::Storage>]) -> Result<(), Error> {
+        self.buffers.prepare(processor, parameters, gradients, self.weight_decay != 0.0)?;
+
+        let hyper = processor.try_alloc(Tensor { data: vec![self.learning_rate, self.weight_decay, self.alpha, self.epsilon, self.momentum], shape: vec![5] })?;
+
+        for (i, (parameter, gradient)) in parameters.iter_mut().zip(gradients).enumerate() {
+            let length = self.buffers.length(i);
+            let gradient = self.buffers.gradient(processor, &mut self.decay, i, parameter, gradient, &hyper)?;
+            let average = self.buffers.get(i, SQUARE_AVERAGE);
+            let momentum = self.buffers.get(i, MOMENTUM);
+
+            (self.average)(processor, (gradient.clone(), hyper.clone()), average, length)?;
+            (self.velocity)(processor, (gradient, average.clone(), hyper.clone()), momentum, length)?;
+            (self.update)(processor, (momentum.clone(), hyper.clone()), parameter, length)?;
+        }
+
+        self.step += 1;
+        Ok(())
+    }
+
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn state(&self, processor: &mut P) -> Result<OptimizerState, Error> {
+        Ok(OptimizerState { step: self.step, learning_rate: self.learning_rate, buffers: self.buffers.save(processor)? })
+    }
+
+    fn load_state(&mut self, processor: &mut P, state: OptimizerState) -> Result<(), Error> {
+        self.buffers.load(processor, state.buffers)?;
+        self.step = state.step;
+        self.learning_rate = state.learning_rate;
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+//! Learning rates changing over the course of training.
+
+use crate::{core::{processor::Processor, type_traits::MemoryMapable}, types::tensor::Tensor};
+
+use super::Optimizer;
+
+pub trait LrScheduler {
+    /// Learning rate for the step, counted from zero.
+    fn learning_rate(&self, step: u64) -> f32;
+
+    /// Sets the learning rate of the optimizer for the step.
+    fn apply<P: Processor, O: Optimizer<P>>(&self, optimizer: &mut O, step: u64) where Tensor<f32>: MemoryMapable<P::Storage> {
+        optimizer.set_learning_rate(self.learning_rate(step));
+    }
+}
+
+/// Multiplies the learning rate by `gamma` every `step_size` steps.
+#[derive(Clone, Debug)]
+pub struct StepLr {
+    pub initial: f32,
+    pub step_size: u64,
+    pub gamma: f32,
+}
+
+impl StepLr {
+    pub fn new(initial: f32, step_size: u64, gamma: f32) -> Self {
+        StepLr { initial, step_size, gamma }
+    }
+}
+
+impl LrScheduler for StepLr {
+    fn learning_rate(&self, step: u64) -> f32 {
+        self.initial * self.gamma.powi((step / self.step_size.max(1)) as i32)
+    }
+}
+
+/// Multiplies the learning rate by `gamma` every step.
+#[derive(Clone, Debug)]
+pub struct ExponentialLr {
+    pub initial: f32,
+    pub gamma: f32,
+}
+
+impl ExponentialLr {
+    pub fn new(initial: f32, gamma: f32) -> Self {
+        ExponentialLr { initial, gamma }
+    }
+}
+
+impl LrScheduler for ExponentialLr {
+    fn learning_rate(&self, step: u64) -> f32 {
+        self.initial * self.gamma.powi(step as i32)
+    }
+}
+
+/// Anneals the learning rate from `initial` to `minimum` along half a cosine over `period` steps,
+/// it stays at `minimum` afterwards.
+#[derive(Clone, Debug)]
+pub struct CosineAnnealingLr {
+    pub initial: f32,
+    pub minimum: f32,
+    pub period: u64,
+}
+
+impl CosineAnnealingLr {
+    pub fn new(initial: f32, minimum: f32, period: u64) -> Self {
+        CosineAnnealingLr { initial, minimum, period }
+    }
+}
+
+impl LrScheduler for CosineAnnealingLr {
+    fn learning_rate(&self, step: u64) -> f32 {
+        let progress = step.min(self.period) as f32 / self.period.max(1) as f32;
+        self.minimum + (self.initial - self.minimum) * (1.0 + (std::f32::consts::PI * progress).cos()) / 2.0
+    }
+}
+
+/// Raises the learning rate linearly to the one of `schedule` over the first `steps` steps.
+#[derive(Clone, Debug)]
+pub struct Warmup<S: LrScheduler> {
+    pub steps: u64,
+    pub schedule: S,
+}
+
+impl<S: LrScheduler> Warmup<S> {
+    pub fn new(steps: u64, schedule: S) -> Self {
+        Warmup { steps, schedule }
+    }
+}
+
+impl<S: LrScheduler> LrScheduler for Warmup<S> {
+    fn learning_rate(&self, step: u64) -> f32 {
+        let learning_rate = self.schedule.learning_rate(step);
+
+        if step < self.steps {
+            learning_rate * (step + 1) as f32 / self.steps as f32
+        } else {
+            learning_rate
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Stochastic gradient descent with optional momentum and Nesterov momentum.
+
+use crate::{core::{operation::Compilable, processor::{Executable, Processor}, type_traits::{FromMut, MemoryMapable}, types::Void}, kernel, types::tensor::Tensor, Error};
+
+use super::{build, decay_gradient, BinaryKernel, Buffers, DecayGradient, DecayGradientInputs, DecayGradientProgramm, Optimizer, OptimizerState, Parameter, TernaryKernel};
+
+const VELOCITY: usize = 0;
+
+/// Hyperparameters: learning rate, weight decay, momentum and 1 for Nesterov momentum.
+#[kernel]
+pub fn sgd_velocity(pos: Pos, gradient: &Tensor<f32>, hyper: &Tensor<f32>, velocity: &mut Tensor<f32>) {
+    velocity[pos.x] = hyper[2] * velocity[pos.x] + gradient[pos.x];
+}
+
+#[kernel]
+// kernels have no compound assignment
+#[allow(clippy::assign_op_pattern)]
+pub fn sgd_step(pos: Pos, gradient: &Tensor<f32>, velocity: &Tensor<f32>, hyper: &Tensor<f32>, parameter: &mut Tensor<f32>) {
+    let v = velocity[pos.x];
+    let nesterov = gradient[pos.x] + hyper[2] * v;
+    parameter[pos.x] = parameter[pos.x] - hyper[0] * (v + hyper[3] * (nesterov - v));
+}
+
+pub struct Sgd<P: Processor> where Tensor<f32>: MemoryMapable<P::Storage> {
+    learning_rate: f32,
+    momentum: f32,
+    nesterov: bool,
+    weight_decay: f32,
+    step: u64,
+    buffers: Buffers<P>,
+    decay: TernaryKernel<P>,
+    velocity: BinaryKernel<P>,
+    update: TernaryKernel<P>,
+}
+
+impl<P: Processor + 'static> Sgd<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    /// Plain gradient descent, without momentum and weight decay.
+    pub fn new(processor: &mut P, learning_rate: f32) -> Result<Self, Error>
+    where
+        DecayGradientProgramm: Compilable<Void, P::Compiler>,
+        SgdVelocityProgramm: Compilable<Void, P::Compiler>,
+        SgdStepProgramm: Compilable<Void, P::Compiler>,
+        P::Executable<DecayGradient>: Executable<P::Storage, DecayGradientInputs<P::Storage>>,
+        P::Executable<SgdVelocity>: Executable<P::Storage, SgdVelocityInputs<P::Storage>>,
+        P::Executable<SgdStep>: Executable<P::Storage, SgdStepInputs<P::Storage>>,
+    {
+        Ok(Sgd {
+            learning_rate,
+            momentum: 0.0,
+            nesterov: false,
+            weight_decay: 0.0,
+            step: 0,
+            buffers: Buffers::new(1),
+            decay: build(processor, decay_gradient())?,
+            velocity: build(processor, sgd_velocity())?,
+            update: build(processor, sgd_step())?,
+        })
+    }
+
+    pub fn momentum(mut self, momentum: f32) -> Self {
+        self.momentum = momentum;
+        self
+    }
+
+    /// Uses Nesterov momentum, the gradient is applied on top of the updated velocity.
+    pub fn nesterov(mut self, nesterov: bool) -> Self {
+        self.nesterov = nesterov;
+        self
+    }
+
+    /// Adds `weight_decay` times the parameter to its gradient.
+    pub fn weight_decay(mut self, weight_decay: f32) -> Self {
+        self.weight_decay = weight_decay;
+        self
+    }
+}
+
+impl<P: Processor + 'static> Optimizer<P> for Sgd<P> where Tensor<f32>: MemoryMapable<P::Storage> {
+    fn step(&mut self, processor: &mut P, parameters: &mut [Parameter<P::Storage>], gradients: &[Parameter<P::Storage>]) -> Result<(), Error> {
+        self.buffers.prepare(processor, parameters, gradients, self.weight_decay != 0.0)?;
+
+        let nesterov = if self.nesterov { 1.0 } else { 0.0 };
+        let hyper = processor.try_alloc(Tensor { data: vec![self.learning_rate, self.weight_decay, self.momentum, nesterov], shape: vec![4] })?;
+
+        for (i, (parameter, gradient)) in parameters.iter_mut().zip(gradients).enumerate() {
+            let length = self.buffers.length(i);
+            let gradient = self.buffers.gradient(processor, &mut self.decay, i, parameter, gradient, &hyper)?;
+            let velocity = self.buffers.get(i, VELOCITY);
+
+            (self.velocity)(processor, (gradient.clone(), hyper.clone()), velocity, length)?;
+            (self.update)(processor, (gradient, velocity.clone(), hyper.clone()), parameter, length)?;
+        }
+
+        self.step += 1;
+        Ok(())
+    }
+
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn state(&self, processor: &mut P) -> Result<OptimizerState, Error> {
+        Ok(OptimizerState { step: self.step, learning_rate: self.learning_rate, buffers: self.buffers.save(processor)? })
+    }
+
+    fn load_state(&mut self, processor: &mut P, state: OptimizerState) -> Result<(), Error> {
+        self.buffers.load(processor, state.buffers)?;
+        self.step = state.step;
+        self.learning_rate = state.learning_rate;
+        Ok(())
+    }
+}
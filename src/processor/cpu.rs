@@ -2,7 +2,8 @@ use std::{cell::{RefCell, Ref, RefMut}, rc::{Rc}, marker::PhantomData, any::Any}
 
 use rayon::prelude::IntoParallelIterator;
 
-use crate::core::{any_map::AnyMap, processor::{Storage, cpu::CPUFunction, Executable, ProcessorInformation, Processor}, allocated::{ExecutableBindings, Binding, ProgrammInputs}, Buildable, types::{Void, Pos}, type_traits::MemoryMapable};
+use crate::core::{any_map::AnyMap, processor::{Storage, cpu::CPUFunction, Executable, Job, ProcessorInformation, Processor}, allocated::{ExecutableBindings, Binding, ProgrammInputs}, Buildable, types::{Void, Pos}, type_traits::MemoryMapable, debug::{DebugBuffer, DebugRecord}};
+use crate::Error;
 use crate::core::type_traits::FromMut;
 
 use rayon::iter::ParallelIterator;
@@ -10,6 +11,17 @@ use rayon::iter::ParallelIterator;
 pub struct CPUProcessor {
     pub heap: CPUStorage,
     pub counter: usize,
+    pub debug: DebugBuffer,
+}
+
+impl CPUProcessor {
+    pub fn new() -> Self {
+        CPUProcessor {
+            heap: CPUStorage::new(),
+            counter: 0,
+            debug: DebugBuffer::new(),
+        }
+    }
 }
 
 pub struct CPUExecutable<B: ExecutableBindings<CPUStorage>, Fn: CPUFunction<B>, Bu: Buildable<CPUProcessor>> {
@@ -35,10 +47,24 @@ impl CPUStorage {
         CPUStorage(Rc::new(RefCell::new(AnyMap::<usize>::new())))
     }
 
+    /// Number of values currently allocated.
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
     fn insert<V: std::any::Any>(&mut self, key: &<CPUStorage as Storage>::Key, val: V) -> Option<V> {
         self.0.borrow_mut().insert(**key, val)
     }
 
+    /// Removes the entry of an allocation, returning the shared data.
+    fn take<V: std::any::Any>(&mut self, key: &<CPUStorage as Storage>::Key) -> Option<Rc<RefCell<V>>> {
+        self.0.borrow_mut().remove::<Rc<RefCell<V>>>(**key)
+    }
+
     fn get<V: std::any::Any>(&self, key: &<CPUStorage as Storage>::Key) -> Option<Ref<V>> {
         let borrowed = self.0.borrow();
 
@@ -62,7 +88,8 @@ impl Storage for CPUStorage {
     type MappedType<T: Any + Clone + Send + Sync> = T;
 
     fn remove<V: std::any::Any + Clone + Send + Sync>(&mut self, key: &Self::Key) -> Option<Self::MappedType<V>> {
-        self.0.borrow_mut().remove::<Self::MappedType<V>>(**key)
+        let data = self.take::<Self::MappedType<V>>(key)?;
+        Rc::try_unwrap(data).ok().map(RefCell::into_inner)
     }
 }
 
@@ -75,35 +102,46 @@ impl ProcessorInformation for CPUProcessor {
 
 impl Processor for CPUProcessor {
     
-    fn build<B: Buildable<Self>>(&mut self, buildable: B) -> Self::Executable<B> {
-        CPUExecutable {
+    fn try_build<B: Buildable<Self>>(&mut self, buildable: B) -> Result<Self::Executable<B>, Error> {
+        buildable.validate()?;
+        Ok(CPUExecutable {
             function: buildable.get_cpu(),
             bindings: B::CPUBinding::new(),
             _0: PhantomData
-        }
+        })
     }
 
-    fn alloc<T: MemoryMapable<Self::Storage>>(&mut self, val: T) -> Binding<Self::Storage, T> {
+    fn try_alloc<T: MemoryMapable<Self::Storage>>(&mut self, val: T) -> Result<Binding<Self::Storage, T>, Error> {
         let reference = Rc::new(self.counter);
         self.counter += 1;
 
+        let parallelization = val.get_parallelization_info();
         let mapped = val.into_processor_mapped();
         let refered = Rc::new(RefCell::new(mapped));
 
-        let binding = Binding::new(reference.clone(), self.heap.clone(), refered.clone());
+        let binding = Binding::new(reference.clone(), self.heap.clone(), refered.clone(), parallelization);
 
         self.heap.insert(&reference, refered);
         
-        binding
+        Ok(binding)
     }
 
-    fn dispatch<'a, B: Buildable<Self>>(&'a mut self, executable: &'a mut Self::Executable<B>, x: u32, y: u32, z: u32)
+    fn dispatch<'a, B: Buildable<Self>>(&'a mut self, executable: &'a mut Self::Executable<B>, x: u32, y: u32, z: u32) -> Result<(), Error>
+        where <B::Binding as ExecutableBindings<Self::Storage>>::O: 'static, <B::CPUBinding as ExecutableBindings<Self::Storage>>::O: 'static {
+            self.dispatch_scoped(executable, x, y, z, &mut |job| {
+                job();
+                Ok(())
+            })
+    }
+
+    fn dispatch_scoped<B: Buildable<Self>>(&self, executable: &Self::Executable<B>, x: u32, y: u32, z: u32, then: &mut dyn FnMut(Job<'_>) -> Result<(), Error>) -> Result<(), Error>
         where <B::Binding as ExecutableBindings<Self::Storage>>::O: 'static, <B::CPUBinding as ExecutableBindings<Self::Storage>>::O: 'static {
             let bindings = executable.get_bindings_ref();
+            bindings.validate(Some((x, y, z)))?;
 
-            let mut outpt = bindings.get_output();
+            let mut outpt = bindings.get_output()?;
     
-            let inputs = bindings.get_inputs();
+            let inputs = bindings.get_inputs()?;
     
             let raw = inputs.to_raw();
             
@@ -115,30 +153,34 @@ impl Processor for CPUProcessor {
             let pos = Pos {x: 0, y: 0, z: 0};
 
             let cores = rayon::current_num_threads();
-
-            (0..cores)
-                .into_iter()
-                .map(|i| (i, o.clone()))
-                .collect::<Vec<(usize, _)>>()
-                .into_par_iter()
-                .for_each(|(i, mut o)| {
-                    let mut pos = pos.clone();
-                    let func = executable.function.clone();
-                    let iterations = if x % (cores as u32) > 0 {x / (cores as u32) + 1} else {x / (cores as u32)};
-
-                    for x in ((i as u32) * iterations)..((i as u32 +1 as u32)*iterations) {
-                        pos.x = x;
-                        for y in 0..y {
-                            pos.y = y;
-                            for z in 0..z {
-                                pos.z = z;
-            
-                                func.call_cpu(&pos, &inp, &mut o);
+            let debug = &self.debug;
+            let function = &executable.function;
+
+            then(Box::new(move || {
+                (0..cores)
+                    .into_iter()
+                    .map(|i| (i, o.clone()))
+                    .collect::<Vec<(usize, _)>>()
+                    .into_par_iter()
+                    .for_each(|(i, mut o)| {
+                        let mut pos = pos.clone();
+                        let func = function.clone();
+                        let iterations = if x % (cores as u32) > 0 {x / (cores as u32) + 1} else {x / (cores as u32)};
+
+                        for x in ((i as u32) * iterations)..((i as u32 +1 as u32)*iterations).min(x) {
+                            pos.x = x;
+                            for y in 0..y {
+                                pos.y = y;
+                                for z in 0..z {
+                                    pos.z = z;
+                
+                                    func.call_cpu(&pos, &inp, &mut o, debug);
+                                }
                             }
-                        }
 
-                    }
-                });
+                        }
+                    });
+            }))?;
     
             //for x in 0..x {
             //    pos.x = x;
@@ -151,20 +193,30 @@ impl Processor for CPUProcessor {
             //        }
             //    }
             //}
-    }
 
-    fn dealloc<T: MemoryMapable<Self::Storage>>(&mut self, val: Binding<Self::Storage, T>) -> T {
-        
-        let v = {
-            self.heap.remove::<<CPUStorage as Storage>::MappedType<T>>(&val.get_reference())
-        };
+            let failures = self.debug.take_failures();
+            if failures.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::AssertionFailed(failures))
+            }
+    }
 
-        v.unwrap()
+    fn drain_debug(&mut self) -> Vec<DebugRecord> {
+        self.debug.drain()
     }
 
-    fn copy_to_cpu<T: MemoryMapable<Self::Storage>>(&mut self, val: &Binding<Self::Storage, T>) -> T {
-        self.heap.get::<T>(&val.get_reference()).unwrap().clone()
+    fn try_dealloc<T: MemoryMapable<Self::Storage>>(&mut self, val: Binding<Self::Storage, T>) -> Result<T, Error> {
+        self.heap.take::<<CPUStorage as Storage>::MappedType<T>>(&val.get_reference()).ok_or(Error::InvalidBinding)?;
+
+        let v = val.deref().clone();
+        Ok(v)
     }
 
+    fn try_copy_to_cpu<T: MemoryMapable<Self::Storage>>(&mut self, val: &Binding<Self::Storage, T>) -> Result<T, Error> {
+        let data = self.heap.get::<Rc<RefCell<<CPUStorage as Storage>::MappedType<T>>>>(&val.get_reference()).ok_or(Error::InvalidBinding)?;
+        let v = data.borrow().clone();
+        Ok(v)
+    }
    
 }
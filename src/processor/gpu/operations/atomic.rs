@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use crate::core::{types::Void, type_traits::{ReferenceAble, AtomicComputable}, operations::{atomic_add::AtomicAdd, atomic_min::AtomicMin, atomic_max::AtomicMax, atomic_exchange::AtomicExchange, compare_exchange::CompareExchange}};
+
+use super::{GPUOperation, GPUComputable};
+
+impl<R: AtomicComputable + GPUComputable, T: ReferenceAble, I: GPUOperation<u32>, V: GPUOperation<R>> GPUOperation<R> for AtomicAdd<R, T, I, V> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let index = self.index.build(functions);
+        let value = self.value.build(functions);
+
+        format!("atomicAdd(&{}[{}], {})", self.tensor.get_reference(), index, value)
+    }
+}
+
+impl<R: AtomicComputable + GPUComputable, T: ReferenceAble, I: GPUOperation<u32>, V: GPUOperation<R>> GPUOperation<Void> for AtomicAdd<R, T, I, V> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("{};", GPUOperation::<R>::build(self, functions))
+    }
+}
+
+impl<R: AtomicComputable + GPUComputable, T: ReferenceAble, I: GPUOperation<u32>, V: GPUOperation<R>> GPUOperation<R> for AtomicMin<R, T, I, V> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let index = self.index.build(functions);
+        let value = self.value.build(functions);
+
+        format!("atomicMin(&{}[{}], {})", self.tensor.get_reference(), index, value)
+    }
+}
+
+impl<R: AtomicComputable + GPUComputable, T: ReferenceAble, I: GPUOperation<u32>, V: GPUOperation<R>> GPUOperation<Void> for AtomicMin<R, T, I, V> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("{};", GPUOperation::<R>::build(self, functions))
+    }
+}
+
+impl<R: AtomicComputable + GPUComputable, T: ReferenceAble, I: GPUOperation<u32>, V: GPUOperation<R>> GPUOperation<R> for AtomicMax<R, T, I, V> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let index = self.index.build(functions);
+        let value = self.value.build(functions);
+
+        format!("atomicMax(&{}[{}], {})", self.tensor.get_reference(), index, value)
+    }
+}
+
+impl<R: AtomicComputable + GPUComputable, T: ReferenceAble, I: GPUOperation<u32>, V: GPUOperation<R>> GPUOperation<Void> for AtomicMax<R, T, I, V> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("{};", GPUOperation::<R>::build(self, functions))
+    }
+}
+
+impl<R: AtomicComputable + GPUComputable, T: ReferenceAble, I: GPUOperation<u32>, V: GPUOperation<R>> GPUOperation<R> for AtomicExchange<R, T, I, V> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let index = self.index.build(functions);
+        let value = self.value.build(functions);
+
+        format!("atomicExchange(&{}[{}], {})", self.tensor.get_reference(), index, value)
+    }
+}
+
+impl<R: AtomicComputable + GPUComputable, T: ReferenceAble, I: GPUOperation<u32>, V: GPUOperation<R>> GPUOperation<Void> for AtomicExchange<R, T, I, V> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("{};", GPUOperation::<R>::build(self, functions))
+    }
+}
+
+impl<R: AtomicComputable + GPUComputable, T: ReferenceAble, I: GPUOperation<u32>, C: GPUOperation<R>, N: GPUOperation<R>> GPUOperation<R> for CompareExchange<R, T, I, C, N> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let index = self.index.build(functions);
+        let current = self.current.build(functions);
+        let new = self.new.build(functions);
+
+        format!("atomicCompareExchangeWeak(&{}[{}], {}, {}).old_value", self.tensor.get_reference(), index, current, new)
+    }
+}
+
+impl<R: AtomicComputable + GPUComputable, T: ReferenceAble, I: GPUOperation<u32>, C: GPUOperation<R>, N: GPUOperation<R>> GPUOperation<Void> for CompareExchange<R, T, I, C, N> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let index = self.index.build(functions);
+        let current = self.current.build(functions);
+        let new = self.new.build(functions);
+
+        // A member access is not a valid statement in WGSL, so the result struct is dropped as a whole
+        format!("atomicCompareExchangeWeak(&{}[{}], {}, {});", self.tensor.get_reference(), index, current, new)
+    }
+}
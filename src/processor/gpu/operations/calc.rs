@@ -1,6 +1,6 @@
 use std::{collections::HashMap};
 
-use crate::core::{operations::{add::Add, divide::Divide, multiply::Multiply, subtract::Subtract}, type_traits::Calculatable};
+use crate::core::{operations::{abs::Abs, add::Add, cast::Cast, divide::Divide, dot::Dot, exp::Exp, ln::Ln, max::Max, min::Min, multiply::Multiply, sign::Sign, sqrt::Sqrt, subtract::Subtract, tanh::Tanh}, type_traits::{Calculatable, Castable, Float, Signed}};
 
 use super::{GPUOperation, GPUComputable};
 
@@ -35,4 +35,75 @@ impl<R: Calculatable + GPUComputable, LEFT: GPUOperation<R>, RIGHT: GPUOperation
 
         format!("{} - {}", left, right)
     }
-}
\ No newline at end of file
+}
+impl<FROM: Castable<TO> + GPUComputable, TO: GPUComputable, O: GPUOperation<FROM>> GPUOperation<TO> for Cast<FROM, TO, O> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let value = self.value.build(functions);
+
+        format!("{}({})", TO::get_type_info(), value)
+    }
+}
+impl<R: Signed + GPUComputable, O: GPUOperation<R>> GPUOperation<R> for Abs<R, O> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("abs({})", self.value.build(functions))
+    }
+}
+impl<R: Signed + GPUComputable, O: GPUOperation<R>> GPUOperation<R> for Sign<R, O> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("sign({})", self.value.build(functions))
+    }
+}
+impl<R: Float + GPUComputable, O: GPUOperation<R>> GPUOperation<R> for Sqrt<R, O> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("sqrt({})", self.value.build(functions))
+    }
+}
+impl<R: Float + GPUComputable, O: GPUOperation<R>> GPUOperation<R> for Exp<R, O> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("exp({})", self.value.build(functions))
+    }
+}
+impl<R: Float + GPUComputable, O: GPUOperation<R>> GPUOperation<R> for Ln<R, O> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("log({})", self.value.build(functions))
+    }
+}
+impl<R: Float + GPUComputable, O: GPUOperation<R>> GPUOperation<R> for Tanh<R, O> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("tanh({})", self.value.build(functions))
+    }
+}
+impl<R: Calculatable + GPUComputable, LEFT: GPUOperation<R>, RIGHT: GPUOperation<R>> GPUOperation<R> for Max<R, LEFT, RIGHT> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let left = self.left.build(functions);
+        let right = self.right.build(functions);
+
+        format!("max({}, {})", left, right)
+    }
+}
+impl<R: Calculatable + GPUComputable, LEFT: GPUOperation<R>, RIGHT: GPUOperation<R>> GPUOperation<R> for Min<R, LEFT, RIGHT> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let left = self.left.build(functions);
+        let right = self.right.build(functions);
+
+        format!("min({}, {})", left, right)
+    }
+}
+/// WGSL's `dot` only takes vectors, arrays get a helper per element type and length.
+impl<T: Calculatable + GPUComputable, const N: usize, LEFT: GPUOperation<[T; N]>, RIGHT: GPUOperation<[T; N]>> GPUOperation<T> for Dot<T, N, LEFT, RIGHT> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let name = format!("chandra_dot_{}_{}", T::get_type_info(), N);
+        if !functions.contains_key(&name) {
+            let array = <[T; N]>::get_type_info();
+            let ty = T::get_type_info();
+            functions.insert(name.clone(), format!(
+                "fn {}(a: {}, b: {}) -> {} {{\n    var left = a;\n    var right = b;\n    var sum: {} = {}(0);\n    for (var i: u32 = 0u; i < {}u; i++) {{\n        sum = sum + left[i] * right[i];\n    }}\n    return sum;\n}}",
+                name, array, array, ty, ty, ty, N
+            ));
+        }
+        let left = self.left.build(functions);
+        let right = self.right.build(functions);
+
+        format!("{}({}, {})", name, left, right)
+    }
+}
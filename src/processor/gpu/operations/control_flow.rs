@@ -1,13 +1,58 @@
 use std::collections::HashMap;
 
-use crate::core::{types::{Void}, operation::OperationWrapper, operations::{range::Range, returns::Returns, if_else::IfElse, foreach::ForEach}, type_traits::Iterable};
+use crate::core::{types::{Void}, operation::OperationWrapper, operations::{range::Range, returns::{Returns, EarlyReturn}, if_else::IfElse, select::Select, foreach::ForEach, switch::{Switch, Cases, Case, DefaultCase}}, type_traits::Iterable};
 
-use super::{GPUOperation, GPUValue};
+use super::{GPUOperation, GPUValue, GPUComputable};
 
 
 impl<R: GPUValue, O: GPUOperation<R>> GPUOperation<R> for Returns<R, O> {
     fn build(&self, functions: &mut HashMap<String, String>) -> String {
-        format!("return {};", self.operation.build(functions))
+        build_return(self.operation.build(functions))
+    }
+}
+
+impl<R: GPUValue, O: GPUOperation<R>> GPUOperation<Void> for EarlyReturn<R, O> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        build_return(self.operation.build(functions))
+    }
+}
+
+fn build_return(value: String) -> String {
+    if value.is_empty() {
+        "return;".to_string()
+    } else {
+        format!("return {};", value)
+    }
+}
+
+impl<S: GPUComputable + PartialEq, SELECTOR: GPUOperation<S>, CASES: GPUCases<S>> GPUOperation<Void> for Switch<S, SELECTOR, CASES> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let selector = self.selector.build(functions);
+        let cases = self.cases.build_cases(functions);
+        format!("switch ({}) {{\n{}}} \n", selector, cases)
+    }
+}
+
+/// The `case` clauses of a WGSL `switch`.
+pub trait GPUCases<S: GPUComputable>: Cases<S> {
+    fn build_cases(&self, functions: &mut HashMap<String, String>) -> String;
+}
+
+impl<S: GPUComputable + PartialEq, A: GPUOperation<Void>, B: GPUOperation<Void>, NEXT: GPUCases<S>> GPUCases<S> for Case<S, A, B, NEXT> {
+    fn build_cases(&self, functions: &mut HashMap<String, String>) -> String {
+        let values = self.values.iter()
+            .map(|v| GPUOperation::<S>::build(v, functions))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let scope = self.scope.build(functions);
+        let next = self.next.build_cases(functions);
+        format!("case {}: {}\n{}", values, scope, next)
+    }
+}
+
+impl<S: GPUComputable, A: GPUOperation<Void>, B: GPUOperation<Void>> GPUCases<S> for DefaultCase<A, B> {
+    fn build_cases(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("default: {}\n", self.scope.build(functions))
     }
 }
 
@@ -24,6 +69,16 @@ impl<CONDITION: GPUOperation<bool>, A: GPUOperation<Void>, B: GPUOperation<Void>
     }
 }
 
+impl<R: GPUComputable, CONDITION: GPUOperation<bool>, A: GPUOperation<R>, B: GPUOperation<R>> GPUOperation<R> for Select<R, CONDITION, A, B> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let condition = self.condition.build(functions);
+        let then = self.then.build(functions);
+        let els = self.els.build(functions);
+
+        format!("select({}, {}, {})", els, then, condition)
+    }
+}
+
 impl<A: GPUOperation<u32>, B: GPUOperation<u32>> GPUOperation<u32> for Range<A, B> {
     fn build(&self, _functions: &mut HashMap<String, String>) -> String {
         unimplemented!()
@@ -36,6 +91,7 @@ impl<LEFT: GPUOperation<u32>, RIGHT: GPUOperation<u32>, A: GPUOperation<Void>, B
         let end = self.iterable.0.get_boundry().build(functions);
         let block = self.scope.build(functions);
         //for (var i: i32 = 0; i < 4; i++)
-        format!("for (var loop_var: u32 = {}; loop_var < {}; loop_var++) {}", start, end, block)
+        let i = &self.variable.reference;
+        format!("for (var {}: u32 = {}; {} < {}; {}++) {}", i, start, i, end, i, block)
     }
 }
\ No newline at end of file
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use crate::core::{operations::{debug_print::DebugPrint, assert::Assert}, operation::OperationWrapper, type_traits::DebugValues, debug::{Debuggable, DEBUG_RECORD_VALUES}};
+
+use super::{GPUOperation, GPUComputable};
+
+/// Bind group of the debug buffer, the arguments of a kernel use group 0.
+pub const DEBUG_GROUP: u32 = 1;
+/// Binding of the debug buffer inside [`DEBUG_GROUP`].
+pub const DEBUG_BINDING: u32 = 0;
+
+// Record layout matches `crate::core::debug::DebugRecord::from_buffer`.
+fn debug_buffer() -> String {
+    format!("struct ChandraDebug {{
+    head: atomic<u32>,
+    records: array<u32>,
+}}
+@group({}) @binding({}) var<storage, read_write> chandra_debug: ChandraDebug;", DEBUG_GROUP, DEBUG_BINDING)
+}
+const DEBUG_RECORD: &str = "fn chandra_debug_record(header: u32, pos: vec3<u32>, tags: u32, v0: u32, v1: u32, v2: u32, v3: u32) {
+    let capacity = arrayLength(&chandra_debug.records) / 9u;
+    let start = (atomicAdd(&chandra_debug.head, 1u) % capacity) * 9u;
+    chandra_debug.records[start] = header;
+    chandra_debug.records[start + 1u] = pos.x;
+    chandra_debug.records[start + 2u] = pos.y;
+    chandra_debug.records[start + 3u] = pos.z;
+    chandra_debug.records[start + 4u] = tags;
+    chandra_debug.records[start + 5u] = v0;
+    chandra_debug.records[start + 6u] = v1;
+    chandra_debug.records[start + 7u] = v2;
+    chandra_debug.records[start + 8u] = v3;
+}";
+
+pub trait GPUDebugValues: DebugValues {
+    /// Returns the packed tag word and one `u32` expression per value.
+    fn build(&self, functions: &mut HashMap<String, String>) -> (u32, Vec<String>);
+}
+
+fn debug_word<R: Debuggable + GPUComputable, O: GPUOperation<R>>(value: &OperationWrapper<R, O>, functions: &mut HashMap<String, String>) -> (u32, String) {
+    let v = value.build(functions);
+
+    let word = match R::get_type_info().as_str() {
+        "u32" => v,
+        "bool" => format!("select(0u, 1u, {})", v),
+        _ => format!("bitcast<u32>({})", v),
+    };
+
+    (R::DEBUG_TAG, word)
+}
+
+fn pack(values: Vec<(u32, String)>) -> (u32, Vec<String>) {
+    let tags = values.iter()
+        .enumerate()
+        .fold(values.len() as u32, |tags, (i, (tag, _))| tags | (tag << (4 + 4 * i)));
+
+    (tags, values.into_iter().map(|(_, word)| word).collect())
+}
+
+impl GPUDebugValues for () {
+    fn build(&self, _functions: &mut HashMap<String, String>) -> (u32, Vec<String>) {
+        (0, Vec::new())
+    }
+}
+impl<R: Debuggable + GPUComputable, O: GPUOperation<R>> GPUDebugValues for (OperationWrapper<R, O>,) {
+    fn build(&self, functions: &mut HashMap<String, String>) -> (u32, Vec<String>) {
+        pack(vec![debug_word(&self.0, functions)])
+    }
+}
+impl<R: Debuggable + GPUComputable, O: GPUOperation<R>, R2: Debuggable + GPUComputable, O2: GPUOperation<R2>> GPUDebugValues for (OperationWrapper<R, O>, OperationWrapper<R2, O2>) {
+    fn build(&self, functions: &mut HashMap<String, String>) -> (u32, Vec<String>) {
+        pack(vec![debug_word(&self.0, functions), debug_word(&self.1, functions)])
+    }
+}
+impl<R: Debuggable + GPUComputable, O: GPUOperation<R>, R2: Debuggable + GPUComputable, O2: GPUOperation<R2>, R3: Debuggable + GPUComputable, O3: GPUOperation<R3>> GPUDebugValues for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>) {
+    fn build(&self, functions: &mut HashMap<String, String>) -> (u32, Vec<String>) {
+        pack(vec![debug_word(&self.0, functions), debug_word(&self.1, functions), debug_word(&self.2, functions)])
+    }
+}
+impl<R: Debuggable + GPUComputable, O: GPUOperation<R>, R2: Debuggable + GPUComputable, O2: GPUOperation<R2>, R3: Debuggable + GPUComputable, O3: GPUOperation<R3>, R4: Debuggable + GPUComputable, O4: GPUOperation<R4>> GPUDebugValues for (OperationWrapper<R, O>, OperationWrapper<R2, O2>, OperationWrapper<R3, O3>, OperationWrapper<R4, O4>) {
+    fn build(&self, functions: &mut HashMap<String, String>) -> (u32, Vec<String>) {
+        pack(vec![debug_word(&self.0, functions), debug_word(&self.1, functions), debug_word(&self.2, functions), debug_word(&self.3, functions)])
+    }
+}
+
+fn record(header: u32, values: &impl GPUDebugValues, functions: &mut HashMap<String, String>) -> String {
+    if !functions.contains_key("chandra_debug") {
+        functions.insert("chandra_debug".to_string(), debug_buffer());
+        functions.insert("chandra_debug_record".to_string(), DEBUG_RECORD.to_string());
+    }
+
+    let (tags, mut words) = values.build(functions);
+    words.resize(DEBUG_RECORD_VALUES, "0u".to_string());
+
+    format!("chandra_debug_record({}u, pos, {}u, {})", header, tags, words.join(", "))
+}
+
+impl<V: GPUDebugValues> GPUOperation<crate::core::types::Void> for DebugPrint<V> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("{};", record(self.message << 1, &self.values, functions))
+    }
+}
+
+impl<CONDITION: GPUOperation<bool>, V: GPUDebugValues> GPUOperation<crate::core::types::Void> for Assert<CONDITION, V> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let condition = self.condition.build(functions);
+
+        format!("if (!({})) {{\n{};\n}}", condition, record(self.message << 1 | 1, &self.values, functions))
+    }
+}
@@ -2,9 +2,12 @@ use std::collections::HashMap;
 
 use crate::core::{types::{Value, Computable, Void}, operation::{Operation, OperationWrapper}};
 
+pub mod atomic;
 pub mod calc;
 pub mod compare;
 pub mod control_flow;
+pub mod debug;
+pub mod random;
 pub mod structure;
 pub mod variables;
 
@@ -24,6 +27,11 @@ pub trait GPUValue: Value {
 
 pub trait GPUComputable: GPUValue + Computable {
     fn get_type_info() -> String;
+
+    /// The value as a WGSL constructor expression.
+    fn build_value(&self) -> String {
+        format!("{}({:?})", Self::get_type_info(), self)
+    }
 }
 
 impl GPUValue for Void {
@@ -74,4 +82,19 @@ impl GPUComputable for f32 {
     fn get_type_info() -> String {
         "f32".to_string()
     }
-}
\ No newline at end of file
+}
+impl<T: GPUComputable, const N: usize> GPUValue for [T; N] {
+    fn get_return_decl() -> String {
+        format!("-> {}", Self::get_type_info())
+    }
+}
+impl<T: GPUComputable, const N: usize> GPUComputable for [T; N] {
+    fn get_type_info() -> String {
+        format!("array<{}, {}>", T::get_type_info(), N)
+    }
+
+    fn build_value(&self) -> String {
+        let items: Vec<String> = self.iter().map(|v| v.build_value()).collect();
+        format!("{}({})", Self::get_type_info(), items.join(", "))
+    }
+}
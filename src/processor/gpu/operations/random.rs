@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+use crate::core::operations::{bernoulli::Bernoulli, normal::Normal, uniform::Uniform};
+
+use super::GPUOperation;
+
+// WGSL counterparts of `crate::core::random`, keep both in sync.
+const PCG_HASH: &str = "fn chandra_pcg_hash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}";
+const RANDOM: &str = "fn chandra_random(seed: u32, counter: u32) -> u32 {
+    return chandra_pcg_hash(seed ^ chandra_pcg_hash(counter));
+}";
+const UNIFORM: &str = "fn chandra_uniform(seed: u32, counter: u32) -> f32 {
+    return f32(chandra_random(seed, counter) >> 8u) * 5.9604644775390625e-8;
+}";
+const NORMAL: &str = "fn chandra_normal(seed: u32, counter: u32, mean: f32, deviation: f32) -> f32 {
+    let stream = chandra_pcg_hash(seed);
+    var sum: u32 = 0u;
+    for (var i: u32 = 0u; i < 12u; i++) {
+        sum += chandra_random(stream + i, counter) >> 12u;
+    }
+    return mean + deviation * (f32(sum) * 9.5367431640625e-7 - 6.0);
+}";
+const BERNOULLI: &str = "fn chandra_bernoulli(seed: u32, counter: u32, p: f32) -> bool {
+    return chandra_uniform(seed, counter) < p;
+}";
+
+fn include(functions: &mut HashMap<String, String>, helpers: &[(&str, &str)]) {
+    for (name, source) in helpers {
+        if !functions.contains_key(*name) {
+            functions.insert(name.to_string(), source.to_string());
+        }
+    }
+}
+
+impl<SEED: GPUOperation<u32>, COUNTER: GPUOperation<u32>> GPUOperation<f32> for Uniform<SEED, COUNTER> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        include(functions, &[("chandra_pcg_hash", PCG_HASH), ("chandra_random", RANDOM), ("chandra_uniform", UNIFORM)]);
+        let seed = self.seed.build(functions);
+        let counter = self.counter.build(functions);
+
+        format!("chandra_uniform({}, {})", seed, counter)
+    }
+}
+
+impl<SEED: GPUOperation<u32>, COUNTER: GPUOperation<u32>, MEAN: GPUOperation<f32>, STD: GPUOperation<f32>> GPUOperation<f32> for Normal<SEED, COUNTER, MEAN, STD> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        include(functions, &[("chandra_pcg_hash", PCG_HASH), ("chandra_random", RANDOM), ("chandra_normal", NORMAL)]);
+        let seed = self.seed.build(functions);
+        let counter = self.counter.build(functions);
+        let mean = self.mean.build(functions);
+        let std = self.std.build(functions);
+
+        format!("chandra_normal({}, {}, {}, {})", seed, counter, mean, std)
+    }
+}
+
+impl<SEED: GPUOperation<u32>, COUNTER: GPUOperation<u32>, P: GPUOperation<f32>> GPUOperation<bool> for Bernoulli<SEED, COUNTER, P> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        include(functions, &[("chandra_pcg_hash", PCG_HASH), ("chandra_random", RANDOM), ("chandra_uniform", UNIFORM), ("chandra_bernoulli", BERNOULLI)]);
+        let seed = self.seed.build(functions);
+        let counter = self.counter.build(functions);
+        let p = self.p.build(functions);
+
+        format!("chandra_bernoulli({}, {}, {})", seed, counter, p)
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::core::{types::{Void}, operations::{function::Function, call::Call, instruction_list::InstructionList, noop::Noop, scope::Scope, var::Variable}, type_traits::{FunctionInputs, CallMatchFunctionInputs, CallInputs}, operation::OperationWrapper};
+use crate::core::{types::{Void, Either}, operations::{function::Function, call::Call, derivative::CustomDerivative, element_derivatives::ElementDerivatives, instruction_list::InstructionList, noop::Noop, scope::Scope, var::Variable}, type_traits::{FunctionInputs, CallMatchFunctionInputs, CallInputs, Calculatable}, operation::OperationWrapper};
 
 use super::{GPUOperation, GPUValue, GPUComputable};
 
@@ -15,6 +15,71 @@ impl<R: GPUValue, INPUTS: FunctionInputs + GPUFunctionInputs, A: GPUOperation<R>
     }
 }
 
+impl<R: GPUValue, INPUTS: FunctionInputs, A: GPUOperation<R>, P: Clone + std::fmt::Debug> GPUOperation<R> for CustomDerivative<R, INPUTS, A, P> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        self.scope.build(functions)
+    }
+}
+
+/// The tree becomes a function writing the derivative into a private global, so a `return` in the
+/// kernel ends only the position it runs for.
+impl<R: GPUComputable + Calculatable, M: GPUOperation<Void>> GPUOperation<Void> for ElementDerivatives<R, M> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let ty = R::get_type_info();
+        let zero = R::get_zero().build_value();
+        let function = format!("{}_at", self.value);
+
+        let elements: Vec<&str> = self.elements.iter().map(|(element, _)| element.as_str()).collect();
+        let parameters = elements.iter().fold(String::new(), |before, element| format!("{}, {}: u32", before, element));
+        let tree = self.tree.build(functions);
+        functions.insert(self.value.clone(), format!("var<private> {}: {};", self.value, ty));
+        functions.insert(function.clone(), format!("fn {}(pos: vec3<u32>{}) {{\n{} = {};\n{}\n}}", function, parameters, self.value, zero, tree));
+
+        let mut bound = String::new();
+        let mut conditions = Vec::new();
+        let mut index = String::new();
+        let mut count = String::new();
+        for ((element, tensor), dispatched) in self.elements.iter().zip(["pos.x", "pos.y"]) {
+            let length = format!("arrayLength(&{}.data)", tensor);
+            bound = format!("{}let {}: u32 = {};\n", bound, element, dispatched);
+            conditions.push(format!("{} < {}", element, length));
+            if index.is_empty() {
+                index = element.clone();
+                count = length;
+            } else {
+                index = format!("({}) * {} + {}", index, length, element);
+                count = format!("{} * {}", count, length);
+            }
+        }
+
+        let (x, y, z) = self.grid;
+        let call = format!("{}(vec3<u32>(chandra_x, chandra_y, chandra_z), {});", function, elements.join(", "));
+        let (before, each, after) = if self.per_position {
+            let position = format!("(chandra_x * {}u + chandra_y) * {}u + chandra_z", y, z);
+            (String::new(), format!("{}.data[({}) * {} + {}] = {};", self.output, position, count, index, self.value), String::new())
+        } else {
+            (
+                format!("var chandra_sum: {} = {};\n", ty, zero),
+                format!("chandra_sum = chandra_sum + {};", self.value),
+                format!("{}.data[{}] = chandra_sum;\n", self.output, index),
+            )
+        };
+
+        format!("{{
+{}if ({}) {{
+{}for (var chandra_x: u32 = 0u; chandra_x < {}u; chandra_x++) {{
+for (var chandra_y: u32 = 0u; chandra_y < {}u; chandra_y++) {{
+for (var chandra_z: u32 = 0u; chandra_z < {}u; chandra_z++) {{
+{}
+{}
+}}
+}}
+}}
+{}}}
+}}", bound, conditions.join(" && "), before, x, y, z, call, each, after)
+    }
+}
+
 impl<R: GPUValue, INPUTS: FunctionInputs + GPUFunctionInputs, CALLINPUTS: GPUCallInputs + CallMatchFunctionInputs<INPUTS>, A: GPUOperation<R>> GPUOperation<R> for Call<R, INPUTS, CALLINPUTS, A> {
     fn build(&self, functions: &mut HashMap<String, String>) -> String {
         if !functions.contains_key(&self.function.name) {
@@ -41,6 +106,15 @@ impl<R: GPUValue, R2: GPUValue, A: GPUOperation<R>, B: GPUOperation<R2>> GPUOper
     }
 }
 
+impl<R: GPUValue, A: GPUOperation<R>, B: GPUOperation<R>> GPUOperation<R> for Either<A, B> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        match self {
+            Either::A(x) => x.build(functions),
+            Either::B(x) => x.build(functions),
+        }
+    }
+}
+
 impl GPUOperation<Void> for Noop {
     fn build(&self, _functions: &mut HashMap<String, String>) -> String {
         String::new()
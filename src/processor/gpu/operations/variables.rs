@@ -1,15 +1,26 @@
 use std::collections::HashMap;
 
-use crate::core::{type_traits::{IndexAble, GetAndSetable}, operations::{index::Index, assign::Assign, get::Get, set::Set, var::Variable}, types::Void};
+use crate::core::{type_traits::{ReferenceAble, GetAndSetable}, operations::{dimension::Dimension, index::Index, length::Length, assign::Assign, repeat::Repeat, get::Get, set::Set, var::Variable}, types::{Computable, Void}};
 
 use super::{GPUOperation, GPUComputable};
 
-impl<R: GPUComputable, T: IndexAble, O: GPUOperation<u32>> GPUOperation<R> for Index<R, T, O> {
+impl<R: GPUComputable, T: ReferenceAble, O: GPUOperation<u32>> GPUOperation<R> for Index<R, T, O> {
     fn build(&self, functions: &mut HashMap<String, String>) -> String {
         format!("{}[{}]", self.tensor.get_reference(), self.index.build(functions))
     }
 }
 
+impl<C: Computable> GPUOperation<u32> for Length<C> {
+    fn build(&self, _functions: &mut HashMap<String, String>) -> String {
+        format!("arrayLength(&{}.data)", self.tensor.reference)
+    }
+}
+
+impl<C: Computable, O: GPUOperation<u32>> GPUOperation<u32> for Dimension<C, O> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        format!("u32({}.shape[{}])", self.tensor.reference, self.dimension.build(functions))
+    }
+}
 
 impl<R: GPUComputable, Value: GPUOperation<R>> GPUOperation<Void> for Assign<R, Value> {
     fn build(&self, functions: &mut HashMap<String, String>) -> String {
@@ -38,4 +49,17 @@ impl<R: GPUComputable> GPUOperation<R> for Variable<R> {
     fn build(&self, _functions: &mut HashMap<String, String>) -> String {
         self.reference.clone()
     }
-}
\ No newline at end of file
+}
+
+impl<R: GPUComputable> GPUOperation<R> for R {
+    fn build(&self, _functions: &mut HashMap<String, String>) -> String {
+        self.build_value()
+    }
+}
+
+impl<T: GPUComputable, const N: usize, O: GPUOperation<T>> GPUOperation<[T; N]> for Repeat<T, N, O> {
+    fn build(&self, functions: &mut HashMap<String, String>) -> String {
+        let value = self.value.build(functions);
+        format!("{}({})", <[T; N]>::get_type_info(), vec![value; N].join(", "))
+    }
+}
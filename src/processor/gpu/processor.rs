@@ -2,15 +2,33 @@ use std::{rc::Rc, cell::RefCell, collections::HashMap};
 
 use crate::core::{processor::{ProcessorInformation, Storage, Processor, Executable}, types::Void, operation::Compilable, operations::scope::Scope, allocated::ExecutableBindings, Buildable};
 
+use crate::{core::debug::{DebugBuffer, DebugRecord}, Error};
+
 use super::operations::GPUOperation;
 
 pub struct GPUProcessor {
-
+    pub debug: DebugBuffer,
 }
 
 impl GPUProcessor {
     pub fn new() -> Self {
-        GPUProcessor {  }
+        GPUProcessor { debug: DebugBuffer::new() }
+    }
+
+    /// Decodes the debug buffer of a dispatch of `executable`, read back from
+    /// `@group(DEBUG_GROUP) @binding(DEBUG_BINDING)`. The records are kept for
+    /// [`Processor::drain_debug`], failed assertions are returned as [`Error::AssertionFailed`].
+    pub fn read_debug_buffer(&mut self, executable: &GPUExecutable, words: &[u32]) -> Result<(), Error> {
+        DebugRecord::from_buffer(words, &executable.debug_messages)
+            .into_iter()
+            .for_each(|record| self.debug.record(record));
+
+        let failures = self.debug.take_failures();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::AssertionFailed(failures))
+        }
     }
 }
 
@@ -19,7 +37,8 @@ pub struct GPUStorage {
 
 }
 
-pub struct GPUCompiler(String);
+/// The generated WGSL and the workgroup size of the entry point.
+pub struct GPUCompiler(String, (u32, u32, u32));
 
 impl Storage for GPUStorage {
     type Key = Rc<usize>;
@@ -34,7 +53,21 @@ impl Storage for GPUStorage {
 }
 
 pub struct GPUExecutable {
-    prog: String
+    prog: String,
+    debug_messages: Vec<&'static str>,
+}
+
+impl GPUExecutable {
+    /// The generated WGSL.
+    pub fn get_program(&self) -> &str {
+        &self.prog
+    }
+
+    /// Whether the program writes `debug_print!` or `assert!` records, the debug buffer has to be
+    /// bound at `@group(DEBUG_GROUP) @binding(DEBUG_BINDING)` for every dispatch if it does.
+    pub fn uses_debug_buffer(&self) -> bool {
+        self.prog.contains("var<storage, read_write> chandra_debug")
+    }
 }
 
 impl<B: ExecutableBindings<GPUStorage>> Executable<GPUStorage, B> for GPUExecutable {
@@ -61,34 +94,47 @@ impl<A: GPUOperation<Void>, B: GPUOperation<Void>> Compilable<Void, GPUCompiler>
 
         let funcs = functions.into_iter()
             .fold(String::new(), |before, (_k, v)| format!("{} \n {}", before, v));
-        compiler.0 = format!("{} \n fn main() {}", funcs, main);
+        let (x, y, z) = compiler.1;
+        compiler.0 = format!("{} \n @compute @workgroup_size({}, {}, {}) \n fn main() {}", funcs, x, y, z, main);
     }
 }
 
 impl Processor for GPUProcessor {
-    fn build<B: crate::core::Buildable<Self>>(&mut self, buildable: B) -> Self::Executable<B> {
-        let mut compiler = GPUCompiler(String::new());
+    fn try_build<B: crate::core::Buildable<Self>>(&mut self, buildable: B) -> Result<Self::Executable<B>, crate::Error> {
+        buildable.validate()?;
+        let mut compiler = GPUCompiler(String::new(), buildable.get_workgroup_size());
         buildable.get_main_tree().build(&mut compiler);
         println!("{}", compiler.0);
-        GPUExecutable {
+        Ok(GPUExecutable {
             prog: compiler.0.clone(),
-        }
+            debug_messages: buildable.get_debug_messages(),
+        })
     }
 
-    fn alloc<T: crate::core::type_traits::MemoryMapable<Self::Storage>>(&mut self, val: T) -> crate::core::allocated::Binding<Self::Storage, T> {
+    fn try_alloc<T: crate::core::type_traits::MemoryMapable<Self::Storage>>(&mut self, val: T) -> Result<crate::core::allocated::Binding<Self::Storage, T>, crate::Error> {
         todo!()
     }
 
-    fn dealloc<T: crate::core::type_traits::MemoryMapable<Self::Storage>>(&mut self, val: crate::core::allocated::Binding<Self::Storage, T>) -> T {
+    fn try_dealloc<T: crate::core::type_traits::MemoryMapable<Self::Storage>>(&mut self, val: crate::core::allocated::Binding<Self::Storage, T>) -> Result<T, crate::Error> {
         todo!()
     }
 
-    fn copy_to_cpu<T: crate::core::type_traits::MemoryMapable<Self::Storage>>(&mut self, val: &crate::core::allocated::Binding<Self::Storage, T>) -> T {
+    fn try_copy_to_cpu<T: crate::core::type_traits::MemoryMapable<Self::Storage>>(&mut self, val: &crate::core::allocated::Binding<Self::Storage, T>) -> Result<T, crate::Error> {
         todo!()
     }
 
-    fn dispatch<'a, B: crate::core::Buildable<Self>>(&'a mut self, executable: &'a mut Self::Executable<B>, x: u32, y: u32, z: u32)
+    fn dispatch<'a, B: crate::core::Buildable<Self>>(&'a mut self, executable: &'a mut Self::Executable<B>, x: u32, y: u32, z: u32) -> Result<(), crate::Error>
         where <B::Binding as crate::core::allocated::ExecutableBindings<Self::Storage>>::O: 'static, <B::CPUBinding as crate::core::allocated::ExecutableBindings<crate::processor::cpu::CPUStorage>>::O: 'static {
         todo!()
     }
+
+    /// Dispatching on the GPU isn't implemented yet, so graphs can be built for it but not run.
+    fn dispatch_scoped<B: crate::core::Buildable<Self>>(&self, _executable: &Self::Executable<B>, _x: u32, _y: u32, _z: u32, _then: &mut dyn FnMut(crate::core::processor::Job<'_>) -> Result<(), crate::Error>) -> Result<(), crate::Error>
+        where <B::Binding as crate::core::allocated::ExecutableBindings<Self::Storage>>::O: 'static, <B::CPUBinding as crate::core::allocated::ExecutableBindings<crate::processor::cpu::CPUStorage>>::O: 'static {
+        Err(crate::Error::Unsupported("dispatching a kernel on the GPU".to_string()))
+    }
+
+    fn drain_debug(&mut self) -> Vec<DebugRecord> {
+        self.debug.drain()
+    }
 }
\ No newline at end of file
@@ -1,10 +1,12 @@
 
-use crate::{core::{operation::{Differentiable, Compilable}, types::Void, processor::ProcessorInformation, Buildable, Program}, processor::cpu::CPUProcessor};
+use crate::{core::{operation::{Differentiable, Compilable}, types::Void, processor::ProcessorInformation, Buildable, Program}, processor::cpu::CPUProcessor, Error};
 
 #[derive(Clone, Debug)]
 pub struct Differential<P: Program> where P::MainTree: Differentiable<Void> {
     pub main:< <P as Program>::MainTree as Differentiable<Void>>::Diff,
     pub cpu_fn: <P as Buildable<CPUProcessor>>::CPUFunction,
+    /// Why the derivative can't be built, see [`Buildable::validate`].
+    pub unsupported: Option<String>,
 }
 
 impl<P: ProcessorInformation , B: Buildable<P> + Program> Buildable<P> for Differential<B> where
@@ -23,4 +25,21 @@ impl<P: ProcessorInformation , B: Buildable<P> + Program> Buildable<P> for Diffe
         fn get_main_tree(&self) -> Self::Main {
             self.main.clone()
         }
-    }
\ No newline at end of file
+
+        fn validate(&self) -> Result<(), Error> {
+            match &self.unsupported {
+                Some(message) => Err(Error::NotDifferentiable(message.clone())),
+                None => Ok(()),
+            }
+        }
+    }
+impl<B: Program> Program for Differential<B> where
+    <B as Program>::MainTree: Differentiable<Void>,
+    <<B as Program>::MainTree as Differentiable<Void>>::Diff: Compilable<Void, Void> {
+        type MainTree = <<B as Program>::MainTree as Differentiable<Void>>::Diff;
+        const ORDER: usize = B::ORDER + 1;
+
+        fn get_main_tree(&self) -> Self::MainTree {
+            self.main.clone()
+        }
+    }
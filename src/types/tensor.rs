@@ -1,11 +1,10 @@
 use std::{ops::{Index, IndexMut, Add}, collections::HashMap, sync::Arc};
 
 use parking_lot::RwLock;
-use rand::prelude::Distribution;
 
-use crate::{processor::{cpu::CPUStorage, gpu::processor::GPUStorage}, core::{types::{Computable, Shape}, operations::{add::Add as OAdd, self}, operation::{Operation, OperationWrapper}, type_traits::{IndexAble, Generalizable, MemoryMapable, FromMut, Calculatable}, processor::{Storage}, allocated::{MemoryLayoutDescriptor, ParallelizationDescriptor, StructParallelizationDescriptor, Parallelizable}, guards::region_guard::RegionGuard}};
+use crate::{processor::{cpu::CPUStorage, gpu::processor::GPUStorage}, core::{types::{Computable, Shape}, random::Distribution, operations::{add::Add as OAdd, self}, operation::{Operation, OperationWrapper}, type_traits::{IndexAble, Generalizable, MemoryMapable, FromMut, Calculatable, AtomicComputable, KernelTensor}, processor::{Storage, cpu::DifferentiatedCPUContext}, allocated::{MemoryLayoutDescriptor, ParallelizationDescriptor, StructParallelizationDescriptor, Parallelizable}, guards::region_guard::RegionGuard}};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Tensor<C: Computable> {
     pub data: Vec<C>,
     pub shape: Shape
@@ -18,12 +17,17 @@ impl<C: Computable> Tensor<C> {
             shape
         }
     }
-    pub fn rand(shape: Shape) -> Tensor<f32> {
-        let dist = rand::distributions::Uniform::new(-1.0, 1.0);
-        let mut rng = rand::thread_rng();
-        let data: Vec<f32> = (0..shape.iter().product())
-            .into_iter()
-            .map(|_| dist.sample(&mut rng))
+    /// Holds `data` in row-major order, panics unless `shape` has as many elements.
+    pub fn from_vec(data: Vec<C>, shape: Shape) -> Self {
+        assert_eq!(data.len(), shape.iter().product::<usize>(), "{} elements for the shape {:?}", data.len(), shape);
+        Self { data, shape }
+    }
+    /// Fills a tensor with values drawn from `distribution`.
+    ///
+    /// The element at flat index `i` equals the kernel intrinsic for the same `seed` with counter `i`.
+    pub fn rand(shape: Shape, seed: u32, distribution: Distribution) -> Tensor<f32> {
+        let data: Vec<f32> = (0..shape.iter().product::<usize>() as u32)
+            .map(|i| distribution.sample(seed, i))
             .collect();
 
         Tensor::<f32> {
@@ -33,6 +37,13 @@ impl<C: Computable> Tensor<C> {
     }
 }
 
+/// A tensor with a single dimension.
+impl<C: Computable> From<Vec<C>> for Tensor<C> {
+    fn from(data: Vec<C>) -> Self {
+        Self { shape: vec![data.len()], data }
+    }
+}
+
 #[derive(Clone)]
 pub struct MyCPUTensor<'a, C: Computable> {
     shape: Arc<RwLock<&'a Vec<usize>>>,
@@ -67,6 +78,42 @@ impl<'a, C: Computable> IndexMut<(u32,u32)> for MyCPUTensor<'a, C> {
     }
 }
 
+impl<'a, C: Computable> KernelTensor for MyCPUTensor<'a, C> {
+    fn len(&self) -> u32 {
+        self.shape.read().iter().product::<usize>() as u32
+    }
+
+    fn shape(&self, dimension: u32) -> u32 {
+        self.shape.read()[dimension as usize] as u32
+    }
+}
+
+impl<'a, C: AtomicComputable> MyCPUTensor<'a, C> {
+    pub fn atomic_add(&self, index: u32, val: C) -> C {
+        C::atomic_add(self.atomic(index), val)
+    }
+
+    pub fn atomic_min(&self, index: u32, val: C) -> C {
+        C::atomic_min(self.atomic(index), val)
+    }
+
+    pub fn atomic_max(&self, index: u32, val: C) -> C {
+        C::atomic_max(self.atomic(index), val)
+    }
+
+    pub fn atomic_exchange(&self, index: u32, val: C) -> C {
+        C::atomic_exchange(self.atomic(index), val)
+    }
+
+    pub fn compare_exchange(&self, index: u32, current: C, new: C) -> C {
+        C::compare_exchange(self.atomic(index), current, new)
+    }
+
+    fn atomic(&self, index: u32) -> &C::Atomic {
+        unsafe { C::as_atomic(self.data.as_ptr(index as usize)) }
+    }
+}
+
 // TODO: Make correct and shape independent conversion (Check memory alignment)
 impl<'a, C: Computable + 'static> FromMut<Tensor<C>> for MyCPUTensor<'a, C> {
     type Result<'b> = MyCPUTensor<'b, C>;
@@ -143,9 +190,15 @@ impl<C: Computable> Generalizable for Tensor<C> {
     }
 
     fn get_parallelization_info(&self) -> ParallelizationDescriptor {
+        let this = match self.shape.as_slice() {
+            [] => Parallelizable::Sync,
+            [x] => Parallelizable::X(*x as u64),
+            [x, y, ..] => Parallelizable::XY(*x as u64, *y as u64),
+        };
+
         ParallelizationDescriptor::Struct(
             StructParallelizationDescriptor {
-                this: Parallelizable::X(self.shape[0] as u64),
+                this,
                 fields: HashMap::from([
                     ("shape".to_string(), (0, MemoryLayoutDescriptor::Vector { item_typ: Box::new(u64::get_memory_layout()) }, ParallelizationDescriptor::Data(Parallelizable::Sync))),
                     ("data".to_string(), (1, MemoryLayoutDescriptor::Vector { item_typ: Box::new(C::get_memory_layout()) }, ParallelizationDescriptor::Data(Parallelizable::X(self.shape[0] as u64)))),
@@ -158,18 +211,17 @@ impl<C: Computable> Generalizable for Tensor<C> {
 impl<C: Computable> IndexAble for Tensor<C> {
     type IndexResult = C;
 
-    fn index<O: Operation<u32>>(&self, _index: O) -> Self::IndexResult {
-        todo!()
+    fn get_field() -> Option<String> {
+        Some("data".to_string())
     }
 
-    fn get_reference(&self) -> String {
-        unimplemented!()
+    fn get_element<R: Computable>(context: &DifferentiatedCPUContext, reference: &str, index: usize) -> R {
+        context.tensor::<R>(reference).data[index]
     }
 
-    fn get_field() -> Option<String> {
-        Some("data".to_string())
+    fn set_element<R: Computable>(context: &mut DifferentiatedCPUContext, reference: &str, index: usize, value: R) {
+        context.tensor_mut::<R>(reference).data[index] = value;
     }
-    
 }
 
 impl<T: Computable> BaseTensor for Tensor<T> {
@@ -178,6 +230,16 @@ impl<T: Computable> BaseTensor for Tensor<T> {
     }
 }
 
+impl<C: Computable> KernelTensor for Tensor<C> {
+    fn len(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    fn shape(&self, dimension: u32) -> u32 {
+        self.shape[dimension as usize] as u32
+    }
+}
+
 impl<C: Computable> Index<u32> for Tensor<C> {
     type Output = C;
 
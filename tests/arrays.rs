@@ -0,0 +1,147 @@
+// kernels have no compound assignment, see tests/ui/compound_assign.rs
+#![allow(clippy::assign_op_pattern)]
+
+use chandra::kernel;
+use chandra::core::Program;
+use chandra::core::allocated::MemoryLayoutDescriptor;
+use chandra::core::derivatives::gradient;
+use chandra::core::operation::Operation;
+use chandra::core::operations::var::Variable;
+use chandra::core::processor::{Processor, Executable};
+use chandra::core::processor::cpu::DifferentiatedCPUContext;
+use chandra::core::type_traits::FromMut;
+use chandra::core::types::Computable;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+/// Two columns of `a * b` per invocation, accumulated in a local array.
+#[kernel]
+fn blocked(pos: Pos, a: &Tensor<f32>, b: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let mut acc = [0.0f32; 2];
+    for k in 0..a.len() {
+        acc[0] = acc[0] + a[k] * b[k * 4 + pos.x * 2];
+        acc[1] = acc[1] + a[k] * b[k * 4 + pos.x * 2 + 1];
+    }
+    out[pos.x * 2] = acc[0];
+    out[pos.x * 2 + 1] = acc[1];
+}
+
+#[kernel]
+fn swap(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let (mut low, mut high) = (a[pos.x * 2], a[pos.x * 2 + 1]);
+    (low, high) = (high, low);
+    out[pos.x * 2] = low;
+    out[pos.x * 2 + 1] = high;
+}
+
+#[kernel]
+fn squares(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let mut terms = [0.0f32; 2];
+    terms[0] = a[pos.x] * 2.0;
+    terms[1] = a[pos.x] * a[pos.x];
+    out[pos.x] = terms[0] + terms[1];
+}
+
+/// `x` is only a field of `pos` in the literal, not the array being declared.
+#[kernel]
+fn neighbours(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let x = [a[pos.x], a[pos.x + 1]];
+    out[pos.x] = x[1] - x[0];
+}
+
+#[test]
+fn register_blocked_accumulator_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor { data: vec![1.0f32, 2.0, 3.0], shape: vec![3] });
+    let b = p.alloc(Tensor { data: (0..12).map(|v| v as f32).collect(), shape: vec![3, 4] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![4]));
+
+    let mut e = blocked().build(&mut p);
+    e.get_bindings().bind(&a, &b, &mut out);
+    p.dispatch(&mut e, 2, 1, 1).unwrap();
+
+    let expected: Vec<f32> = (0..4).map(|j| (0..3).map(|k| (k + 1) as f32 * (k * 4 + j) as f32).sum()).collect();
+    assert_eq!(out.deref().data, expected);
+}
+
+#[test]
+fn tuples_destructure_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor { data: vec![1.0f32, 2.0, 3.0, 4.0], shape: vec![4] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![4]));
+
+    let mut e = swap().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+    p.dispatch(&mut e, 2, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![2.0, 1.0, 4.0, 3.0]);
+}
+
+#[test]
+fn array_literals_may_read_fields_named_like_the_array() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor { data: vec![1.0f32, 4.0, 9.0], shape: vec![3] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![2]));
+
+    let mut e = neighbours().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+    p.dispatch(&mut e, 2, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![3.0, 5.0]);
+}
+
+#[test]
+fn local_arrays_evaluate_on_the_host() {
+    let kernel = squares();
+    let mut context = DifferentiatedCPUContext::new();
+    context.set("a", Tensor { data: vec![3.0f32], shape: vec![1] });
+    context.set("out", Tensor::new(0f32, vec![1]));
+    context.set("pos.x", 0u32);
+    context.set("pos.y", 0u32);
+    context.set("pos.z", 0u32);
+
+    Program::get_main_tree(&kernel).evaluate(&mut context);
+    assert_eq!(context.tensor::<f32>("out").data, vec![15.0]);
+    assert_eq!(*context.get::<[f32; 2]>("terms"), [6.0, 9.0]);
+
+    // d/da (2a + a²) = 2 + 2a, through the derivative held in the local array
+    let a = Tensor { data: vec![1.0f32, 2.0], shape: vec![2] };
+    let g = gradient(&kernel, &Variable::new("a"), (a,), (2, 1, 1)).unwrap();
+    assert_eq!(g.data, vec![4.0, 6.0]);
+}
+
+#[test]
+fn array_layout_matches_wgsl() {
+    let layout = <[f32; 4]>::get_memory_layout();
+
+    assert!(matches!(&layout, MemoryLayoutDescriptor::Array { item_length: 4, .. }));
+    assert_eq!(layout.get_byte_size(), 16);
+    assert_eq!(layout.get_wgsl_type().as_deref(), Some("array<f32, 4>"));
+    assert_eq!(<[f32; 2]>::from_bytes([1.5f32, -2.0].to_bytes()), [1.5, -2.0]);
+}
+
+#[test]
+fn wgsl_declares_local_arrays() {
+    let mut g = GPUProcessor::new();
+    let blocked = blocked().build(&mut g).get_program().to_string();
+    let swap = swap().build(&mut g).get_program().to_string();
+
+    assert!(blocked.contains("var acc: array<f32, 2> = array<f32, 2>(f32(0.0), f32(0.0));"), "{}", blocked);
+    assert!(blocked.contains("acc[u32(0)] = acc[u32(0)] + "), "{}", blocked);
+    assert!(swap.contains("var low: f32 = a.data[pos.x * u32(2)];") && !swap.contains("vec2"), "{}", swap);
+    assert!(swap.contains("low = tmp"), "{}", swap);
+
+    for (program, tensors) in [(blocked, ["a", "b", "out"].as_slice()), (swap, ["a", "out"].as_slice())] {
+        let mut source = "struct ChandraF32 { data: array<f32> }\n".to_string();
+        for (binding, tensor) in tensors.iter().enumerate() {
+            source += &format!("@group(0) @binding({}) var<storage, read_write> {}: ChandraF32;\n", binding, tensor);
+        }
+        source += &program.replace("fn main()", "fn main(@builtin(global_invocation_id) pos: vec3<u32>)");
+
+        let module = naga::front::wgsl::parse_str(&source).unwrap();
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+            .validate(&module)
+            .unwrap();
+    }
+}
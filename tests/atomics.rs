@@ -0,0 +1,105 @@
+use chandra::kernel;
+use chandra::core::type_traits::FromMut;
+use chandra::core::operation::Operation;
+use chandra::core::operations::{atomic_add::atomic_add, compare_exchange::compare_exchange, var::Variable};
+use chandra::core::processor::{Processor, Executable};
+use chandra::core::processor::cpu::DifferentiatedCPUContext;
+use chandra::core::Buildable;
+use chandra::core::allocated::{ExecutableBindings, MemoryLayoutDescriptor};
+use chandra::core::guards::region_guard::RegionGuard;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::{GPUProcessor, GPUStorage};
+use chandra::types::tensor::Tensor;
+
+#[kernel]
+fn histogram(pos: Pos, values: &Tensor<u32>, bins: &mut Tensor<u32>) {
+    atomic_add(&mut bins[values[pos.x]], 1);
+}
+
+#[kernel]
+fn scatter_max(pos: Pos, index: &Tensor<u32>, values: &Tensor<i32>, out: &mut Tensor<i32>) {
+    atomic_max(&mut out[index[pos.x]], values[pos.x]);
+}
+
+#[test]
+fn histogram_counts_colliding_bins_on_cpu() {
+    let data: Vec<u32> = (0..1000).map(|i| (i * 7) % 13 % 4).collect();
+    let mut expected = vec![0u32; 4];
+    data.iter().for_each(|v| expected[*v as usize] += 1);
+
+    let mut p = CPUProcessor::new();
+    let values = p.alloc(Tensor { shape: vec![data.len()], data });
+    let mut bins = p.alloc(Tensor::new(0u32, vec![4]));
+
+    let mut e = histogram().build(&mut p);
+    e.get_bindings().bind(&values, &mut bins);
+    p.dispatch(&mut e, 1000, 1, 1).unwrap();
+
+    assert_eq!(bins.deref().data, expected);
+}
+
+#[test]
+fn scatter_keeps_the_largest_colliding_value_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let index = p.alloc(Tensor { data: vec![0, 1, 0, 1, 0, 2], shape: vec![6] });
+    let values = p.alloc(Tensor { data: vec![3, -4, 9, -1, -7, 5], shape: vec![6] });
+    let mut out = p.alloc(Tensor::new(i32::MIN, vec![3]));
+
+    let mut e = scatter_max().build(&mut p);
+    e.get_bindings().bind(&index, &values, &mut out);
+    p.dispatch(&mut e, 6, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![9, -1, 5]);
+}
+
+fn data_layout<B: Buildable<GPUProcessor>>(_kernel: &B, argument: &str) -> MemoryLayoutDescriptor {
+    match <B::Binding as ExecutableBindings<GPUStorage>>::get_layouts().remove(argument) {
+        Some((_, MemoryLayoutDescriptor::Struct(mut fields), _)) => fields.remove("data").unwrap().1,
+        _ => panic!("`{}` is not a tensor argument", argument),
+    }
+}
+
+#[test]
+fn atomic_outputs_are_declared_atomic_in_wgsl() {
+    let mut g = GPUProcessor::new();
+    let e = histogram().build(&mut g);
+    let program = e.get_program();
+
+    assert!(program.contains("atomicAdd(&bins.data[values.data[pos.x]], u32(1));"), "{}", program);
+    assert_eq!(data_layout(&histogram(), "bins").get_wgsl_type().unwrap(), "array<atomic<u32>>");
+    assert_eq!(data_layout(&histogram(), "values").get_wgsl_type().unwrap(), "array<u32>");
+}
+
+#[test]
+fn atomics_evaluate_on_the_host() {
+    let bins = Variable::<Tensor<u32>>::new("bins");
+    let mut context = DifferentiatedCPUContext::new();
+    context.set("bins", Tensor { data: vec![1u32, 5], shape: vec![2] });
+
+    assert_eq!(atomic_add(&bins, 1u32, 2u32).evaluate(&mut context), 5);
+    assert_eq!(compare_exchange(&bins, 0u32, 4u32, 8u32).evaluate(&mut context), 1);
+    assert_eq!(compare_exchange(&bins, 0u32, 1u32, 9u32).evaluate(&mut context), 1);
+    assert_eq!(context.tensor::<u32>("bins").data, vec![9, 7]);
+}
+
+#[test]
+fn atomics_update_local_arrays_on_the_host() {
+    let counts = Variable::<[u32; 2]>::new("counts");
+    let mut context = DifferentiatedCPUContext::new();
+    context.set("counts", [1u32, 5]);
+
+    assert_eq!(atomic_add(&counts, 1u32, 2u32).evaluate(&mut context), 5);
+    assert_eq!(compare_exchange(&counts, 0u32, 1u32, 9u32).evaluate(&mut context), 1);
+    assert_eq!(*context.get::<[u32; 2]>("counts"), [9, 7]);
+}
+
+#[test]
+fn atomic_accesses_share_the_region_lock() {
+    let mut data = vec![0u32; 8];
+    let guard = RegionGuard::new(&mut data, vec![(0, 8)]);
+    let other = guard.clone();
+
+    // with an exclusive lock per access the second guard would wait for the first one forever
+    let (first, second) = (guard.as_ptr(1), other.as_ptr(2));
+    assert_eq!(unsafe { second.offset_from(first) }, 1);
+}
@@ -0,0 +1,97 @@
+use chandra::kernel;
+use chandra::core::derivatives::gradient;
+use chandra::core::operations::var::Variable;
+use chandra::core::processor::{Processor, Executable};
+use chandra::core::type_traits::{Castable, FromMut};
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+#[kernel]
+fn truncate(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<i32>) {
+    out[pos.x] = a[pos.x] as i32;
+}
+
+#[kernel]
+fn ramp(pos: Pos, scale: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = (pos.x as f32) * scale[0];
+}
+
+#[kernel]
+fn widen(pos: Pos, a: &Tensor<i32>, out: &mut Tensor<f32>) {
+    out[pos.x] = (a[pos.x] as u32) as f32;
+}
+
+#[kernel]
+fn through_integer(pos: Pos, x: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = ((x[pos.x] * 2.0) as i32) as f32 + x[pos.x];
+}
+
+#[test]
+fn casts_follow_rust_semantics_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let values = vec![-1.7f32, 2.9, 1e10, f32::NAN];
+    let a = p.alloc(Tensor { shape: vec![values.len()], data: values.clone() });
+    let mut out = p.alloc(Tensor::new(0i32, vec![values.len()]));
+
+    let mut e = truncate().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+    p.dispatch(&mut e, values.len() as u32, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, values.iter().map(|v| *v as i32).collect::<Vec<_>>());
+    assert_eq!(out.deref().data, vec![-1, 2, i32::MAX, 0]);
+}
+
+#[test]
+fn positions_and_integers_convert_to_floats() {
+    let mut p = CPUProcessor::new();
+    let scale = p.alloc(Tensor { data: vec![0.5f32], shape: vec![1] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![4]));
+
+    let mut e = ramp().build(&mut p);
+    e.get_bindings().bind(&scale, &mut out);
+    p.dispatch(&mut e, 4, 1, 1).unwrap();
+    assert_eq!(out.deref().data, vec![0.0, 0.5, 1.0, 1.5]);
+
+    let a = p.alloc(Tensor { data: vec![3i32, -1], shape: vec![2] });
+    let mut wide = p.alloc(Tensor::new(0f32, vec![2]));
+    let mut e = widen().build(&mut p);
+    e.get_bindings().bind(&a, &mut wide);
+    p.dispatch(&mut e, 2, 1, 1).unwrap();
+    assert_eq!(wide.deref().data, vec![3.0, -1i32 as u32 as f32]);
+}
+
+// only conversions between floats let the derivative through
+const _: () = assert!(<f32 as Castable<f32>>::PRESERVES_DERIVATIVE);
+const _: () = assert!(!<f32 as Castable<i32>>::PRESERVES_DERIVATIVE);
+const _: () = assert!(!<u32 as Castable<f32>>::PRESERVES_DERIVATIVE);
+
+#[test]
+fn integer_casts_drop_the_derivative() {
+    // the integer part is piecewise constant, only the `+ x` term is left
+    let x = Tensor { data: vec![1.5f32, -0.5], shape: vec![2] };
+    let g = gradient(&through_integer(), &Variable::new("x"), (x,), (2, 1, 1)).unwrap();
+    assert_eq!(g.data, vec![1.0, 1.0]);
+}
+
+#[test]
+fn wgsl_converts_with_constructors() {
+    let mut g = GPUProcessor::new();
+
+    let e = truncate().build(&mut g);
+    assert!(e.get_program().contains("out.data[pos.x] = i32(a.data[pos.x]);"), "{}", e.get_program());
+    let e = ramp().build(&mut g);
+    assert!(e.get_program().contains("f32(pos.x)"), "{}", e.get_program());
+    let e = widen().build(&mut g);
+    assert!(e.get_program().contains("f32(u32(a.data[pos.x]))"), "{}", e.get_program());
+
+    let source = format!("struct ChandraI32 {{ data: array<i32> }}
+struct ChandraF32 {{ data: array<f32> }}
+@group(0) @binding(0) var<storage, read_write> a: ChandraI32;
+@group(0) @binding(1) var<storage, read_write> out: ChandraF32;
+{}", e.get_program().replace("fn main()", "fn main(@builtin(global_invocation_id) pos: vec3<u32>)"));
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+        .validate(&module)
+        .unwrap();
+}
@@ -0,0 +1,86 @@
+use chandra::{kernel, ChandraFunction};
+use chandra::core::derivatives::gradient;
+use chandra::core::gradcheck::gradcheck;
+use chandra::core::operations::var::Variable;
+use chandra::core::processor::Processor;
+use chandra::core::type_traits::FromMut;
+use chandra::core::DifferentiableProgram;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+mod common;
+use common::assert_close;
+
+#[ChandraFunction]
+fn product(a: f32, b: f32) -> f32 {
+    return a * b;
+}
+
+#[ChandraFunction]
+fn blend(a: f32, b: f32, c: f32) -> f32 {
+    return a * b + c * c * a;
+}
+
+#[ChandraFunction]
+fn affine(a: f32, b: f32, c: f32, d: f32, e: f32) -> f32 {
+    return a * b + c * d - e;
+}
+
+#[kernel]
+fn square(pos: Pos, x: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = product(x[pos.x], x[pos.x]) + product(x[pos.x], x[pos.x] + x[pos.x]);
+}
+
+#[kernel]
+fn blended(pos: Pos, x: &Tensor<f32>, w: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = blend(x[pos.x], w[pos.x], x[pos.x]);
+}
+
+#[kernel]
+fn five(pos: Pos, x: &Tensor<f32>, w: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = affine(x[pos.x], w[pos.x], w[pos.x] * w[pos.x], x[pos.x], 2.0 * x[pos.x]);
+}
+
+#[test]
+fn two_arguments_sum_their_partials() {
+    let x = vec![-1.0, 0.5, 2.0];
+
+    let d = gradient(&square(), &Variable::new("x"), (Tensor::from(x.clone()),), (3, 1, 1)).unwrap();
+
+    assert_close(&d.data, &x.iter().map(|x| 6.0 * x).collect::<Vec<_>>(), 1e-4);
+}
+
+#[test]
+fn three_arguments_follow_the_chain_rule() {
+    let (x, w) = (vec![1.0, -2.0], vec![0.5, 3.0]);
+
+    let inputs = (Tensor::from(x.clone()), Tensor::from(w.clone()));
+    let for_x = gradient(&blended(), &Variable::new("x"), inputs.clone(), (2, 1, 1)).unwrap();
+    let for_w = gradient(&blended(), &Variable::new("w"), inputs, (2, 1, 1)).unwrap();
+
+    // x·w + x³
+    assert_close(&for_x.data, &(0..2).map(|i| w[i] + 3.0 * x[i] * x[i]).collect::<Vec<_>>(), 1e-4);
+    assert_close(&for_w.data, &x, 1e-4);
+}
+
+#[test]
+fn five_arguments_pass_gradcheck() {
+    let mut p = CPUProcessor::new();
+    let inputs = (Tensor::from(vec![1.0, -0.5, 2.0]), Tensor::from(vec![0.25, 1.5, -1.0]));
+
+    for variable in ["x", "w"] {
+        let report = gradcheck(&mut p, five(), Variable::new(variable), inputs.clone(), Tensor::from(vec![0.0; 3]), (3, 1, 1)).unwrap();
+        assert!(report.passed(), "{}", report);
+    }
+}
+
+#[test]
+fn every_partial_is_generated_once() {
+    let mut g = GPUProcessor::new();
+    let e = g.try_build(square().differantiate_for(Variable::<Tensor<f32>>::new("x"))).unwrap();
+    let program = e.get_program();
+
+    assert_eq!(program.matches("fn product_chandra_diff_a(").count(), 1, "{}", program);
+    assert_eq!(program.matches("fn product_chandra_diff_b(").count(), 1, "{}", program);
+}
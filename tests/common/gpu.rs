@@ -0,0 +1,107 @@
+//! Runs WGSL on the first available adapter for the tests comparing the GPU with the host.
+
+use std::{future::Future, sync::Arc, task::{Context, Poll, Wake, Waker}, thread};
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        if let Poll::Ready(value) = future.as_mut().poll(&mut context) {
+            return value;
+        }
+        thread::park();
+    }
+}
+
+/// Dispatches `main` of `source` with `buffers` bound as `(group, binding, words)` storage buffers
+/// and reads the words back. Returns `None` if there is no adapter.
+pub fn run(source: &str, buffers: &mut [(u32, u32, Vec<u32>)], workgroups: (u32, u32, u32)) -> Option<()> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+    let (device, queue) = block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()?;
+
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: None,
+        layout: None,
+        module: &module,
+        entry_point: "main",
+    });
+
+    let storage: Vec<(wgpu::Buffer, wgpu::Buffer)> = buffers.iter()
+        .map(|(_, _, words)| {
+            let size = (words.len() * 4) as u64;
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+            queue.write_buffer(&buffer, 0, &bytes);
+
+            let staging = device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            (buffer, staging)
+        })
+        .collect();
+
+    let groups = buffers.iter().map(|(group, _, _)| *group + 1).max().unwrap_or(0);
+    let bind_groups: Vec<wgpu::BindGroup> = (0..groups)
+        .map(|group| {
+            let entries: Vec<wgpu::BindGroupEntry> = buffers.iter()
+                .zip(storage.iter())
+                .filter(|((g, _, _), _)| *g == group)
+                .map(|((_, binding, _), (buffer, _))| wgpu::BindGroupEntry { binding: *binding, resource: buffer.as_entire_binding() })
+                .collect();
+
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.get_bind_group_layout(group),
+                entries: &entries,
+            })
+        })
+        .collect();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        pass.set_pipeline(&pipeline);
+        for (group, bind_group) in bind_groups.iter().enumerate() {
+            pass.set_bind_group(group as u32, bind_group, &[]);
+        }
+        pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
+    }
+    for (buffer, staging) in storage.iter() {
+        encoder.copy_buffer_to_buffer(buffer, 0, staging, 0, buffer.size());
+    }
+    queue.submit(Some(encoder.finish()));
+
+    for (_, staging) in storage.iter() {
+        staging.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    }
+    device.poll(wgpu::Maintain::Wait);
+
+    for ((_, _, words), (_, staging)) in buffers.iter_mut().zip(storage.iter()) {
+        *words = staging.slice(..).get_mapped_range()
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+    }
+    Some(())
+}
@@ -0,0 +1,16 @@
+//! Helpers shared by the tests. Every test binary uses only a part of them.
+#![allow(dead_code, unused_imports)]
+
+#[cfg(feature = "gpu")]
+mod gpu;
+#[cfg(feature = "gpu")]
+pub use gpu::run;
+
+/// Asserts that every element of `found` is within `tolerance` of `expected`, scaled by the
+/// magnitude of the expected element once it's above one.
+pub fn assert_close(found: &[f32], expected: &[f32], tolerance: f32) {
+    assert_eq!(found.len(), expected.len());
+    for (f, e) in found.iter().zip(expected) {
+        assert!((f - e).abs() <= tolerance * e.abs().max(1.0), "{:?} != {:?}", found, expected);
+    }
+}
@@ -0,0 +1,71 @@
+// kernels have no compound assignment, see tests/ui/compound_assign.rs
+#![allow(clippy::assign_op_pattern)]
+
+use chandra::kernel;
+use chandra::core::processor::{Processor, Executable};
+use chandra::core::type_traits::FromMut;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+const SCALE: f32 = 0.5;
+
+/// Sums rows of `K` elements, scales them and adds the row offset.
+#[kernel]
+fn rows<const K: u32>(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    const OFFSET: u32 = 10;
+    let mut sum: f32 = 0.0;
+    for k in 0..K {
+        sum = sum + a[pos.x * K + k];
+    }
+    out[pos.x] = sum * SCALE + ((pos.x + OFFSET) as f32);
+}
+
+fn host_rows(a: &[f32], k: usize) -> Vec<f32> {
+    a.chunks(k).enumerate().map(|(i, row)| row.iter().sum::<f32>() * SCALE + (i + 10) as f32).collect()
+}
+
+/// Runs `rows::<$k>` on the CPU over `$data`.
+macro_rules! run_rows {
+    ($k:literal, $data:expr) => {{
+        let mut p = CPUProcessor::new();
+        let a = p.alloc(Tensor { data: $data.clone(), shape: vec![$data.len()] });
+        let mut out = p.alloc(Tensor::new(0f32, vec![$data.len() / $k]));
+
+        let mut e = rows::<$k>().build(&mut p);
+        e.get_bindings().bind(&a, &mut out);
+        p.dispatch(&mut e, ($data.len() / $k) as u32, 1, 1).unwrap();
+
+        let data = out.deref().data.clone();
+        data
+    }};
+}
+
+#[test]
+fn const_generics_specialize_on_cpu() {
+    let data: Vec<f32> = (0..12).map(|v| v as f32).collect();
+
+    assert_eq!(run_rows!(2, data), host_rows(&data, 2));
+    assert_eq!(run_rows!(3, data), host_rows(&data, 3));
+}
+
+#[test]
+fn constants_are_baked_into_wgsl() {
+    let mut g = GPUProcessor::new();
+    let two = rows::<2>().build(&mut g).get_program().to_string();
+    let three = rows::<3>().build(&mut g).get_program().to_string();
+
+    assert!(three.contains("k < u32(3)") && three.contains("pos.x * u32(3) + k"), "{}", three);
+    assert!(two.contains("k < u32(2)") && two.contains("pos.x * u32(2) + k"), "{}", two);
+    assert!(three.contains("u32(10)") && three.contains("f32(0.5)"), "{}", three);
+    assert!(!three.contains('K') && !three.contains("OFFSET") && !three.contains("SCALE"), "{}", three);
+
+    let source = format!("struct ChandraF32 {{ data: array<f32> }}
+@group(0) @binding(0) var<storage, read_write> a: ChandraF32;
+@group(0) @binding(1) var<storage, read_write> out: ChandraF32;
+{}", three.replace("fn main()", "fn main(@builtin(global_invocation_id) pos: vec3<u32>)"));
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+        .validate(&module)
+        .unwrap();
+}
@@ -0,0 +1,135 @@
+use chandra::{kernel, ChandraFunction};
+use chandra::core::Program;
+use chandra::core::operation::Operation;
+use chandra::core::type_traits::FromMut;
+use chandra::core::processor::{Processor, Executable};
+use chandra::core::processor::cpu::DifferentiatedCPUContext;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+const MODE_ADD: u32 = 1;
+
+#[ChandraFunction]
+fn limit(v: f32) -> f32 {
+    if v > 2.0 {
+        return 2.0;
+    }
+    for _i in 0..3 {
+        if v < 0.0 {
+            return 0.0;
+        }
+    }
+    return v * 0.5;
+}
+
+#[kernel]
+fn early_return(pos: Pos, depth: &Tensor<u32>, out: &mut Tensor<f32>) {
+    out[pos.x] = 1.0;
+    if depth[pos.x] == 1 {
+        return;
+    }
+    out[pos.x] = 2.0;
+    for _i in 0..4 {
+        if depth[pos.x] == 2 {
+            return;
+        }
+        if depth[pos.x] == 3 {
+            out[pos.x] = 3.0;
+            return;
+        }
+    }
+    out[pos.x] = 4.0;
+}
+
+#[kernel{ const MODE_MUL: u32 = 2; }]
+fn select(pos: Pos, mode: &Tensor<u32>, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let v = a[pos.x];
+    match mode[pos.x] {
+        0 => out[pos.x] = limit(v),
+        MODE_ADD | 5 => out[pos.x] = v + 1.0,
+        MODE_MUL => {
+            out[pos.x] = v * 2.0;
+        }
+        _ => {
+            let k: u32 = match mode[pos.x] {
+                3 => 30u32,
+                _ => 40u32,
+            };
+            if k == 30 {
+                out[pos.x] = 30.0;
+                return;
+            }
+            out[pos.x] = 40.0;
+        }
+    }
+}
+
+#[test]
+fn returns_from_every_depth_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let depth = p.alloc(Tensor { data: vec![0, 1, 2, 3], shape: vec![4] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![4]));
+
+    let mut e = early_return().build(&mut p);
+    e.get_bindings().bind(&depth, &mut out);
+    p.dispatch(&mut e, 4, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![4.0, 1.0, 2.0, 3.0]);
+}
+
+#[test]
+fn returns_from_every_depth_on_the_host() {
+    for (depth, expected) in [(0u32, 4.0f32), (1, 1.0), (2, 2.0), (3, 3.0)] {
+        let mut context = DifferentiatedCPUContext::new();
+        context.set("depth", Tensor { data: vec![depth], shape: vec![1] });
+        context.set("out", Tensor::new(0f32, vec![1]));
+        context.set("pos.x", 0u32);
+        context.set("pos.y", 0u32);
+        context.set("pos.z", 0u32);
+
+        Program::get_main_tree(&early_return()).evaluate(&mut context);
+        assert_eq!(context.tensor::<f32>("out").data, vec![expected], "depth {}", depth);
+    }
+}
+
+#[test]
+fn returns_from_every_depth_in_wgsl() {
+    let mut g = GPUProcessor::new();
+    let e = early_return().build(&mut g);
+    let program = e.get_program();
+
+    assert_eq!(program.matches("return;").count(), 3);
+    assert!(program.contains("for (var _i: u32 = u32(0); _i < u32(4); _i++)"));
+    assert!(program.contains("if (depth.data[pos.x] == u32(3))"));
+}
+
+#[test]
+fn match_selects_constants_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let mode = p.alloc(Tensor { data: vec![0, 1, 2, 3, 4, 5], shape: vec![6] });
+    let a = p.alloc(Tensor::new(1.5f32, vec![6]));
+    let mut out = p.alloc(Tensor::new(0f32, vec![6]));
+
+    let mut e = select().build(&mut p);
+    e.get_bindings().bind(&mode, &a, &mut out);
+    p.dispatch(&mut e, 6, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![0.75, 2.5, 3.0, 30.0, 40.0, 2.5]);
+}
+
+#[test]
+fn match_selects_constants_in_wgsl() {
+    let mut g = GPUProcessor::new();
+    let e = select().build(&mut g);
+    let program = e.get_program();
+
+    assert!(program.contains("case u32(0): {"));
+    assert!(program.contains("case u32(1), u32(5): {"));
+    assert!(program.contains("case u32(2): {"));
+    assert_eq!(program.matches("default: {").count(), 2);
+    assert!(program.contains("var k: u32 = u32(0);"), "{}", program);
+    assert!(program.contains("return f32(2.0);"));
+    assert!(program.contains("return f32(0.0);"));
+    assert!(program.contains("return v * f32(0.5);"));
+}
@@ -0,0 +1,96 @@
+use chandra::{kernel, ChandraFunction};
+use chandra::core::derivatives::{gradient, jacobian};
+use chandra::core::gradcheck::gradcheck;
+use chandra::core::operations::var::Variable;
+use chandra::core::processor::Processor;
+use chandra::core::type_traits::FromMut;
+use chandra::core::DifferentiableProgram;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+#[ChandraFunction]
+fn sigmoid(v: f32) -> f32 {
+    return 1.0 / (1.0 + (0.0 - v).exp());
+}
+
+#[ChandraFunction(derivative = sigmoid)]
+fn softplus(v: f32) -> f32 {
+    return (1.0 + v.exp()).ln();
+}
+
+#[ChandraFunction(non_differentiable)]
+fn detach(v: f32) -> f32 {
+    return v;
+}
+
+#[ChandraFunction]
+fn by_a(_a: f32, b: f32) -> f32 {
+    return b;
+}
+
+#[ChandraFunction]
+fn by_b(a: f32, _b: f32) -> f32 {
+    return a;
+}
+
+#[ChandraFunction(derivative = (by_a, by_b))]
+fn product(a: f32, b: f32) -> f32 {
+    return a * b;
+}
+
+#[kernel]
+fn weighted(pos: Pos, x: &Tensor<f32>, w: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = product(x[pos.x], w[pos.x]);
+}
+
+#[kernel]
+fn smooth(pos: Pos, x: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = 2.0 * softplus(x[pos.x]);
+}
+
+#[kernel]
+fn scaled(pos: Pos, x: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = x[pos.x] * detach(x[pos.x]);
+}
+
+#[test]
+fn custom_derivative_replaces_the_symbolic_one() {
+    let x = vec![-3.0, 0.0, 0.5, 4.0];
+
+    let d = gradient(&smooth(), &Variable::new("x"), (Tensor::from(x.clone()),), (4, 1, 1)).unwrap();
+
+    for (d, x) in d.data.iter().zip(&x) {
+        assert!((d - 2.0 / (1.0 + (-x).exp())).abs() < 1e-5, "{:?}", d);
+    }
+    let report = gradcheck(&mut CPUProcessor::new(), smooth(), Variable::new("x"), (Tensor::from(x),), Tensor::from(vec![0.0; 4]), (4, 1, 1)).unwrap();
+    assert!(report.passed(), "{}", report);
+}
+
+#[test]
+fn custom_derivative_is_called_in_wgsl() {
+    let mut g = GPUProcessor::new();
+    let e = g.try_build(smooth().differantiate_for(Variable::<Tensor<f32>>::new("x"))).unwrap();
+    let program = e.get_program();
+
+    assert!(program.contains("fn sigmoid(v: f32)"), "{}", program);
+    assert!(program.contains("return sigmoid(v);"), "{}", program);
+}
+
+#[test]
+fn non_differentiable_functions_have_no_derivative() {
+    let x = vec![-2.0, 3.0];
+
+    let d = gradient(&scaled(), &Variable::new("x"), (Tensor::from(x.clone()),), (2, 1, 1)).unwrap();
+
+    assert_eq!(d.data, x);
+}
+
+#[test]
+fn custom_derivatives_have_a_partial_per_input() {
+    let (x, w) = (vec![1.0, -2.0], vec![0.5, 3.0]);
+
+    let j = jacobian(&weighted(), &[Variable::new("x"), Variable::new("w")], (Tensor::from(x.clone()), Tensor::from(w.clone())), (2, 1, 1)).unwrap();
+
+    assert_eq!(j.data, vec![w[0], 0.0, x[0], 0.0, 0.0, w[1], 0.0, x[1]]);
+}
@@ -0,0 +1,151 @@
+use chandra::{kernel, Error};
+use chandra::core::{Buildable, Program};
+use chandra::core::debug::{DebugKind, DebugRecord, DebugValue};
+use chandra::core::operation::Operation;
+use chandra::core::processor::{Processor, Executable};
+use chandra::core::processor::cpu::DifferentiatedCPUContext;
+use chandra::core::type_traits::FromMut;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+#[cfg(feature = "gpu")]
+mod common;
+
+#[kernel]
+fn checked(pos: Pos, limit: &Tensor<u32>, out: &mut Tensor<f32>) {
+    debug_print!("visited {}", pos.x);
+    assert!(pos.x < limit[0], "{} is past the limit {}", pos.x, limit[0]);
+    out[pos.x] = 1.0;
+}
+
+fn positions(records: &[DebugRecord], kind: DebugKind) -> Vec<u32> {
+    let mut positions: Vec<u32> = records.iter().filter(|r| r.kind == kind).map(|r| r.pos.x).collect();
+    positions.sort();
+    positions
+}
+
+#[test]
+fn failed_assertions_are_returned_by_dispatch() {
+    let mut p = CPUProcessor::new();
+    let limit = p.alloc(Tensor { data: vec![2u32], shape: vec![1] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![4]));
+
+    let mut e = checked().build(&mut p);
+    e.get_bindings().bind(&limit, &mut out);
+
+    match p.dispatch(&mut e, 4, 1, 1) {
+        Err(Error::AssertionFailed(failures)) => {
+            assert_eq!(positions(&failures, DebugKind::Assertion), vec![2, 3]);
+            assert_eq!(failures[0].to_string().split(") ").nth(1), Some(format!("{} is past the limit 2", failures[0].pos.x).as_str()));
+        }
+        any => panic!("expected failed assertions, got {:?}", any.map(|_| ())),
+    }
+    assert_eq!(out.deref().data, vec![1.0; 4]);
+}
+
+#[test]
+fn printed_records_are_drained_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let limit = p.alloc(Tensor { data: vec![8u32], shape: vec![1] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![4]));
+
+    let mut e = checked().build(&mut p);
+    e.get_bindings().bind(&limit, &mut out);
+    p.dispatch(&mut e, 4, 1, 1).unwrap();
+
+    let records = p.drain_debug();
+    assert_eq!(positions(&records, DebugKind::Print), vec![0, 1, 2, 3]);
+    assert!(records.iter().all(|r| r.message == "visited {}" && r.values == vec![DebugValue::U32(r.pos.x)]));
+    assert!(p.drain_debug().is_empty());
+}
+
+#[test]
+fn host_evaluation_keeps_the_records() {
+    let kernel = checked();
+    let mut context = DifferentiatedCPUContext::new();
+    context.set_debug_messages(Buildable::<CPUProcessor>::get_debug_messages(&kernel));
+    context.set("limit", Tensor { data: vec![1u32], shape: vec![1] });
+    context.set("out", Tensor::new(0f32, vec![2]));
+
+    for x in 0..2u32 {
+        context.set("pos.x", x);
+        context.set("pos.y", 0u32);
+        context.set("pos.z", 0u32);
+        Program::get_main_tree(&kernel).evaluate(&mut context);
+    }
+
+    let failures = context.take_failures();
+    assert_eq!(positions(&failures, DebugKind::Assertion), vec![1]);
+    assert_eq!(failures[0].values, vec![DebugValue::U32(1), DebugValue::U32(1)]);
+    assert_eq!(positions(&context.drain_debug(), DebugKind::Print), vec![0, 1]);
+}
+
+#[test]
+fn debug_buffer_ring_is_decoded_oldest_first() {
+    let messages = ["first {}", "second"];
+    let record = |header: u32, x: u32, value: u32| [header, x, 0, 0, 1 | 2 << 4, value, 0, 0, 0];
+
+    // three records written into a ring of two, the first one was overwritten
+    let mut words = vec![3];
+    words.extend(record(1 << 1 | 1, 2, 0));
+    words.extend(record(0, 1, 7));
+
+    let records = DebugRecord::from_buffer(&words, &messages);
+    assert_eq!(records.len(), 2);
+    assert_eq!((records[0].kind, records[0].pos.x, records[0].message), (DebugKind::Print, 1, "first {}"));
+    assert_eq!(records[0].values, vec![DebugValue::U32(7)]);
+    assert_eq!((records[1].kind, records[1].pos.x, records[1].message), (DebugKind::Assertion, 2, "second"));
+}
+
+/// The generated kernel with its tensors declared, `pos` bound to the invocation id.
+fn standalone(program: &str) -> String {
+    let tensors = "struct ChandraU32 { data: array<u32> }
+struct ChandraF32 { data: array<f32> }
+@group(0) @binding(0) var<storage, read_write> limit: ChandraU32;
+@group(0) @binding(1) var<storage, read_write> out: ChandraF32;";
+
+    format!("{}\n{}", tensors, program.replace("fn main()", "fn main(@builtin(global_invocation_id) pos: vec3<u32>)"))
+}
+
+#[test]
+fn wgsl_binds_the_debug_buffer() {
+    let mut g = GPUProcessor::new();
+    let e = checked().build(&mut g);
+
+    assert!(e.uses_debug_buffer());
+    assert!(e.get_program().contains("@group(1) @binding(0) var<storage, read_write> chandra_debug: ChandraDebug;"));
+
+    let module = naga::front::wgsl::parse_str(&standalone(e.get_program())).unwrap();
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+        .validate(&module)
+        .unwrap();
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn gpu_records_are_read_back() {
+    let mut g = GPUProcessor::new();
+    let e = checked().build(&mut g);
+
+    // a single workgroup of 64 invocations writes 64 prints and 62 failures
+    let capacity = 128;
+    let mut buffers = [
+        (0, 0, vec![2u32]),
+        (0, 1, vec![0u32; 64]),
+        (1, 0, vec![0u32; 1 + capacity * chandra::core::debug::DEBUG_RECORD_WORDS]),
+    ];
+    if common::run(&standalone(e.get_program()), &mut buffers, (1, 1, 1)).is_none() {
+        eprintln!("no GPU adapter, skipping the debug buffer read back");
+        return;
+    }
+
+    match g.read_debug_buffer(&e, &buffers[2].2) {
+        Err(Error::AssertionFailed(failures)) => assert_eq!(positions(&failures, DebugKind::Assertion), (2..64).collect::<Vec<_>>()),
+        any => panic!("expected failed assertions, got {:?}", any),
+    }
+    let records = g.drain_debug();
+    assert_eq!(positions(&records, DebugKind::Print), (0..64).collect::<Vec<_>>());
+    assert!(records.iter().all(|r| r.values[0] == DebugValue::U32(r.pos.x)));
+    assert_eq!(buffers[1].2.iter().map(|w| f32::from_bits(*w)).collect::<Vec<_>>(), vec![1.0; 64]);
+}
@@ -0,0 +1,215 @@
+use chandra::{kernel, Error};
+use chandra::core::allocated::ExecutableBindings;
+use chandra::core::derivatives::{gradient, gradient_program, HostInputs, hessian, hessian_program, jacobian, jacobian_program};
+use chandra::core::processor::{Executable, Processor};
+use chandra::core::operations::var::Variable;
+use chandra::core::type_traits::FromMut;
+use chandra::core::DifferentiableProgram;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+mod common;
+use common::assert_close;
+
+#[kernel]
+fn energy(pos: Pos, x: &Tensor<f32>, w: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = x[pos.x] * x[pos.x] * w[pos.x] + w[pos.x] * w[pos.x] * w[pos.x] - 3.0 * x[pos.x];
+}
+
+#[kernel]
+fn bilinear(pos: Pos, x: &Tensor<f32>, w: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = x[pos.x] * w[pos.x];
+}
+
+#[kernel]
+// kernels have no compound assignment, see tests/ui/compound_assign.rs
+#[allow(clippy::assign_op_pattern)]
+fn dot(pos: Pos, x: &Tensor<f32>, w: &Tensor<f32>, y: &mut Tensor<f32>) {
+    let mut sum = 0.0f32;
+    for k in 0..3 {
+        sum = sum + x[k] * w[k];
+    }
+    y[pos.x] = sum;
+}
+
+#[kernel]
+fn rows(pos: Pos, m: &Tensor<f32>, w: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = m[pos.x * 2] * w[0] + m[pos.x * 2 + 1] * w[1] * w[1];
+}
+
+fn variables() -> [Variable<Tensor<f32>>; 2] {
+    [Variable::new("x"), Variable::new("w")]
+}
+
+#[test]
+fn jacobian_has_a_column_per_element() {
+    let (x, w) = (vec![1.0, -2.0], vec![0.5, 3.0]);
+
+    let j = jacobian(&energy(), &variables(), (Tensor::from(x.clone()), Tensor::from(w.clone())), (2, 1, 1)).unwrap();
+
+    // y[p] only depends on x[p] and w[p]
+    assert_eq!(j.shape, vec![2, 4]);
+    let mut expected = vec![0.0; 8];
+    for p in 0..2 {
+        expected[p * 4 + p] = 2.0 * x[p] * w[p] - 3.0;
+        expected[p * 4 + 2 + p] = x[p] * x[p] + 3.0 * w[p] * w[p];
+    }
+    assert_close(&j.data, &expected, 1e-4);
+}
+
+#[test]
+fn hessian_matches_the_analytic_second_derivatives() {
+    let (x, w) = (vec![1.0, -2.0], vec![0.5, 3.0]);
+
+    let h = hessian(&energy(), &variables(), (Tensor::from(x.clone()), Tensor::from(w.clone())), (2, 1, 1)).unwrap();
+
+    assert_eq!(h.shape, vec![2, 4, 4]);
+    let mut expected = vec![0.0; 32];
+    for p in 0..2 {
+        let (xp, wp) = (p, 2 + p);
+        expected[(p * 4 + xp) * 4 + xp] = 2.0 * w[p];
+        expected[(p * 4 + xp) * 4 + wp] = 2.0 * x[p];
+        expected[(p * 4 + wp) * 4 + xp] = 2.0 * x[p];
+        expected[(p * 4 + wp) * 4 + wp] = 6.0 * w[p];
+    }
+    assert_close(&h.data, &expected, 1e-4);
+}
+
+#[test]
+fn second_derivative_of_a_linear_term_is_zero() {
+    let h = hessian(&bilinear(), &variables(), (Tensor::from(vec![2.0]), Tensor::from(vec![5.0])), (1, 1, 1)).unwrap();
+
+    assert_close(&h.data, &[0.0, 1.0, 1.0, 0.0], 1e-4);
+}
+
+#[test]
+fn hessian_pairs_single_elements() {
+    let h = hessian(&dot(), &variables(), (Tensor::from(vec![1.0, -2.0, 0.5]), Tensor::from(vec![3.0, 4.0, -1.0])), (1, 1, 1)).unwrap();
+
+    // ∂²(x · w) / ∂x[i] ∂w[j] is 1 for i == j only
+    let expected: Vec<f32> = (0..36)
+        .map(|i| (i / 6, i % 6))
+        .map(|(row, column)| if row + 3 == column || column + 3 == row { 1.0 } else { 0.0 })
+        .collect();
+    assert_close(&h.data, &expected, 1e-4);
+}
+
+#[test]
+fn derivative_programs_build_for_the_gpu() {
+    let (x, w) = (Variable::new("x"), Variable::new("w"));
+    let mut g = GPUProcessor::new();
+
+    let jacobian = g.build(jacobian_program(&energy(), &x, (2, 1, 1))).get_program().to_string();
+    let hessian = g.build(hessian_program(&energy(), &x, &w, (2, 1, 1))).get_program().to_string();
+
+    assert!(jacobian.contains("y.data[((chandra_x * 1u + chandra_y) * 1u + chandra_z) * arrayLength(&x.data) + chandra_element]"), "{}", jacobian);
+    assert!(hessian.contains("let chandra_second_element: u32 = pos.y;"), "{}", hessian);
+    for program in [jacobian, hessian] {
+        let source = format!("struct ChandraF32 {{ data: array<f32> }}
+@group(0) @binding(0) var<storage, read_write> x: ChandraF32;
+@group(0) @binding(1) var<storage, read_write> w: ChandraF32;
+@group(0) @binding(2) var<storage, read_write> y: ChandraF32;
+{}", program.replace("fn main()", "fn main(@builtin(global_invocation_id) pos: vec3<u32>)"));
+        let module = naga::front::wgsl::parse_str(&source).unwrap();
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+            .validate(&module)
+            .unwrap();
+    }
+}
+
+#[test]
+fn derivatives_differentiate_again() {
+    let x = Variable::<Tensor<f32>>::new("x");
+    let second = energy().differantiate_for(x.clone()).differantiate_for(x.clone());
+    let third = second.differantiate_for(x);
+
+    let mut g = GPUProcessor::new();
+    assert!(g.try_build(second).is_ok());
+    assert!(g.try_build(third).is_ok());
+}
+
+#[test]
+fn gradient_of_a_dot_product_is_the_other_vector() {
+    let (x, w) = (vec![1.0, -2.0, 0.5], vec![3.0, 4.0, -1.0]);
+
+    let for_w = gradient(&dot(), &Variable::new("w"), (Tensor::from(x.clone()), Tensor::from(w.clone())), (1, 1, 1)).unwrap();
+    let for_x = gradient(&dot(), &Variable::new("x"), (Tensor::from(x.clone()), Tensor::from(w.clone())), (1, 1, 1)).unwrap();
+
+    assert_eq!(for_w.shape, vec![3]);
+    assert_close(&for_w.data, &x, 1e-4);
+    assert_close(&for_x.data, &w, 1e-4);
+}
+
+#[test]
+fn gradient_programs_dispatch_over_the_elements() {
+    let mut p = CPUProcessor::new();
+    let x = p.alloc(Tensor::from(vec![1.0, -2.0, 0.5]));
+    let w = p.alloc(Tensor::from(vec![3.0, 4.0, -1.0]));
+    let g = p.alloc(Tensor::from(vec![0.0; 3]));
+
+    let mut e = p.build(gradient_program(&dot(), &Variable::new("w"), (1, 1, 1)));
+    e.get_bindings().set_arguments((x, w), g.clone());
+    p.dispatch(&mut e, 3, 1, 1).unwrap();
+
+    assert_close(&g.deref().data, &[1.0, -2.0, 0.5], 1e-4);
+}
+
+#[test]
+fn gradient_programs_build_for_the_gpu() {
+    let mut g = GPUProcessor::new();
+    let program = g.build(gradient_program(&energy(), &Variable::new("w"), (2, 1, 1))).get_program().to_string();
+
+    assert!(program.contains("chandra_element < arrayLength(&w.data)"), "{}", program);
+    assert!(program.contains("y.data[chandra_element] = chandra_sum;"), "{}", program);
+
+    let source = format!("struct ChandraF32 {{ data: array<f32> }}
+@group(0) @binding(0) var<storage, read_write> x: ChandraF32;
+@group(0) @binding(1) var<storage, read_write> w: ChandraF32;
+@group(0) @binding(2) var<storage, read_write> y: ChandraF32;
+{}", program.replace("fn main()", "fn main(@builtin(global_invocation_id) pos: vec3<u32>)"));
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+        .validate(&module)
+        .unwrap();
+}
+
+#[test]
+fn gradient_sums_over_every_output_element() {
+    let m = Tensor { shape: vec![3, 2], data: vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0] };
+    let w = vec![0.5, -1.0];
+
+    let g = gradient(&rows(), &Variable::new("w"), (m, Tensor::from(w)), (3, 1, 1)).unwrap();
+
+    // Σ m[i, 0] and Σ m[i, 1] · 2w₁
+    assert_close(&g.data, &[9.0, -24.0], 1e-4);
+}
+
+#[test]
+fn gradient_of_elementwise_kernels_is_the_diagonal_of_the_jacobian() {
+    let (x, w) = (vec![1.0, -2.0], vec![0.5, 3.0]);
+
+    let g = gradient(&energy(), &Variable::new("w"), (Tensor::from(x.clone()), Tensor::from(w.clone())), (2, 1, 1)).unwrap();
+
+    let expected: Vec<f32> = (0..2).map(|i| x[i] * x[i] + 3.0 * w[i] * w[i]).collect();
+    assert_close(&g.data, &expected, 1e-4);
+}
+
+#[test]
+fn rejects_unknown_variables() {
+    let result = jacobian(&energy(), &[Variable::new("v")], (Tensor::from(vec![1.0]), Tensor::from(vec![1.0])), (1, 1, 1));
+
+    assert!(matches!(result, Err(Error::UnknownArgument(argument)) if argument == "v"));
+}
+
+#[test]
+fn host_inputs_take_up_to_eight_arguments() {
+    let mut p = CPUProcessor::new();
+    let inputs = (Tensor::from(vec![0.0f32]), Tensor::from(vec![1.0f32]), Tensor::from(vec![2.0f32]), Tensor::from(vec![3.0f32]), Tensor::from(vec![4.0f32]), Tensor::from(vec![5.0f32]), Tensor::from(vec![6.0f32]), Tensor::from(vec![7.0f32]));
+
+    let bindings = inputs.alloc(&mut p).unwrap();
+
+    assert_eq!(p.heap.len(), 8);
+    assert_eq!(bindings.7.deref().data, vec![7.0]);
+    assert_eq!(inputs.get(5).and_then(|value| value.downcast_ref::<Tensor<f32>>()), Some(&Tensor::from(vec![5.0])));
+}
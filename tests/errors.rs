@@ -0,0 +1,106 @@
+use chandra::{kernel, Error};
+use chandra::core::derivatives::gradient;
+use chandra::core::guards::region_guard::RegionGuard;
+use chandra::core::operations::var::Variable;
+use chandra::core::processor::{Processor, Executable};
+use chandra::core::type_traits::FromMut;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::types::tensor::Tensor;
+
+#[kernel]
+#[shape(a.shape[0] == out.shape[0], grid.x <= out.shape[0])]
+fn scale(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = a[pos.x] * 2.0;
+}
+
+#[kernel]
+fn gather(pos: Pos, index: &Tensor<u32>, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = a[index[pos.x]];
+}
+
+#[test]
+fn dispatching_unbound_arguments_fails() {
+    let mut p = CPUProcessor::new();
+    let mut e = scale().build(&mut p);
+
+    match p.dispatch(&mut e, 1, 1, 1) {
+        Err(Error::Unbound { argument }) => assert_eq!(argument, "a"),
+        any => panic!("expected an unbound argument, got {:?}", any),
+    }
+}
+
+#[test]
+fn deallocated_bindings_are_invalid() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor::new(1f32, vec![2]));
+    let copy = a.clone();
+
+    assert_eq!(p.try_dealloc(a).unwrap().data, vec![1.0, 1.0]);
+    assert!(matches!(p.try_copy_to_cpu(&copy), Err(Error::InvalidBinding)));
+    assert!(matches!(p.try_dealloc(copy), Err(Error::InvalidBinding)));
+}
+
+#[test]
+fn mismatching_shapes_fail_validation() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor::new(1f32, vec![3]));
+    let mut out = p.alloc(Tensor::new(0f32, vec![4]));
+
+    let mut e = scale().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+
+    match p.dispatch(&mut e, 3, 1, 1) {
+        Err(Error::ShapeMismatch { argument, constraint, found }) => {
+            assert_eq!(argument, "a");
+            assert_eq!(constraint, "a.shape[0] == out.shape[0]");
+            assert_eq!(found, vec![3]);
+        }
+        any => panic!("expected a shape mismatch, got {:?}", any),
+    }
+}
+
+#[test]
+fn gradients_of_unknown_or_mistyped_arguments_fail() {
+    let inputs = (Tensor::new(1f32, vec![2]),);
+
+    match gradient(&scale(), &Variable::new("b"), inputs, (2, 1, 1)) {
+        Err(Error::UnknownArgument(argument)) => assert_eq!(argument, "b"),
+        any => panic!("expected an unknown argument, got {:?}", any.map(|_| ())),
+    }
+
+    let gathered = (Tensor::new(1u32, vec![2]), Tensor::new(1f32, vec![2]));
+    match gradient(&gather(), &Variable::<Tensor<f32>>::new("index"), gathered, (2, 1, 1)) {
+        Err(Error::DtypeMismatch { argument, expected, found }) => {
+            assert_eq!(argument, "index");
+            assert_eq!(expected, "Tensor<f32>");
+            assert!(found.ends_with("Tensor<u32>"), "{}", found);
+        }
+        any => panic!("expected a dtype mismatch, got {:?}", any.map(|_| ())),
+    }
+}
+
+#[test]
+fn ranges_across_regions_fail() {
+    let mut data = vec![0u32; 8];
+    let mut guard = RegionGuard::new(&mut data, vec![(0, 4), (4, 8)]);
+
+    assert_eq!(guard.index_range(4..7).unwrap(), &[0, 0, 0]);
+    match guard.index_range(2..6) {
+        Err(Error::CrossRegion { start, end }) => assert_eq!((start, end), (2, 6)),
+        any => panic!("expected a cross region access, got {:?}", any),
+    }
+}
+
+#[test]
+fn set_writes_into_the_region_of_the_index() {
+    let mut data = vec![0u32; 8];
+    {
+        let mut guard = RegionGuard::new(&mut data, vec![(0, 4), (4, 8)]);
+        guard.set(1, 3);
+        guard.set(6, 9);
+        guard.set_range(4, vec![1, 2]);
+        guard.free();
+    }
+
+    assert_eq!(data, vec![0, 3, 0, 0, 1, 2, 9, 0]);
+}
@@ -0,0 +1,102 @@
+use chandra::{kernel, fuse};
+use chandra::core::type_traits::FromMut;
+use chandra::core::processor::{Processor, Executable};
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+#[kernel]
+fn scale(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let v = a[pos.x] * 2.0;
+    out[pos.x] = v;
+}
+
+#[kernel]
+#[shape(x.shape[0] == bias.shape[0])]
+fn add_bias(pos: Pos, x: &Tensor<f32>, bias: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let v = x[pos.x] + bias[pos.x];
+    out[pos.x] = v;
+}
+
+#[kernel]
+fn relu(pos: Pos, x: &Tensor<f32>, out: &mut Tensor<f32>) {
+    if x[pos.x] < 0.0 {
+        out[pos.x] = 0.0;
+        return;
+    }
+    out[pos.x] = x[pos.x];
+}
+
+fuse!(scale_bias_relu = scale, add_bias, relu);
+
+mod activations {
+    use chandra::kernel;
+    use chandra::core::type_traits::FromMut;
+    use chandra::types::tensor::Tensor;
+
+    #[kernel]
+    pub fn square(pos: Pos, x: &Tensor<f32>, out: &mut Tensor<f32>) {
+        out[pos.x] = x[pos.x] * x[pos.x];
+    }
+}
+
+fuse!(scale_square = scale, activations::square);
+
+fn input() -> Vec<f32> {
+    vec![-2.0, -0.5, 0.0, 1.0, 3.0, -7.25]
+}
+
+#[test]
+fn fused_matches_unfused_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let mut a = Tensor::new(0f32, vec![6]);
+    a.data = input();
+    let a = p.alloc(a);
+    let bias = p.alloc(Tensor { data: vec![0.5, 1.0, -1.0, 0.0, 2.0, 20.0], shape: vec![6] });
+
+    let mut scaled = p.alloc(Tensor::new(0f32, vec![6]));
+    let mut biased = p.alloc(Tensor::new(0f32, vec![6]));
+    let mut unfused = p.alloc(Tensor::new(0f32, vec![6]));
+    let mut e = scale().build(&mut p);
+    e.get_bindings().bind(&a, &mut scaled);
+    p.dispatch(&mut e, 6, 1, 1).unwrap();
+    let mut e = add_bias().build(&mut p);
+    e.get_bindings().bind(&scaled, &bias, &mut biased);
+    p.dispatch(&mut e, 6, 1, 1).unwrap();
+    let mut e = relu().build(&mut p);
+    e.get_bindings().bind(&biased, &mut unfused);
+    p.dispatch(&mut e, 6, 1, 1).unwrap();
+
+    let mut fused = p.alloc(Tensor::new(0f32, vec![6]));
+    let mut e = scale_bias_relu().build(&mut p);
+    e.get_bindings().bind(&a, &bias, &mut fused);
+    p.dispatch(&mut e, 6, 1, 1).unwrap();
+
+    assert_eq!(fused.deref().data, unfused.deref().data);
+    assert_eq!(fused.deref().data, vec![0.0, 0.0, 0.0, 2.0, 8.0, 5.5]);
+}
+
+#[test]
+fn fused_keeps_intermediates_in_registers() {
+    let mut g = GPUProcessor::new();
+    let e = scale_bias_relu().build(&mut g);
+    let program = e.get_program();
+
+    assert!(!program.contains("x.data"));
+    assert!(program.contains("var __chandra_fused_1: f32 = v_0;"));
+    assert!(program.contains("var v_1: f32 = __chandra_fused_1 + bias.data[pos.x];"));
+    assert!(program.contains("if (__chandra_fused_2 < f32(0.0)) {"));
+}
+
+#[test]
+fn kernels_fuse_across_modules() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor { data: input(), shape: vec![6] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![6]));
+
+    let mut e = scale_square().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+    p.dispatch(&mut e, 6, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, input().iter().map(|v| (v * 2.0) * (v * 2.0)).collect::<Vec<_>>());
+}
@@ -0,0 +1,105 @@
+use chandra::{kernel, ChandraFunction, Error};
+use chandra::core::gradcheck::{gradcheck, Gradcheck};
+use chandra::core::operations::var::Variable;
+use chandra::core::type_traits::FromMut;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::types::tensor::Tensor;
+
+#[ChandraFunction]
+fn square_plus(v: f32) -> f32 {
+    return v * v + v;
+}
+
+#[kernel]
+fn cubic(pos: Pos, x: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = x[pos.x] * x[pos.x] * x[pos.x] - 2.0 * x[pos.x];
+}
+
+#[kernel]
+fn weighted(pos: Pos, x: &Tensor<f32>, w: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = w[pos.x] * x[pos.x] + square_plus(w[pos.x]);
+}
+
+#[kernel]
+fn step(pos: Pos, x: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = x[pos.x];
+    if x[pos.x] > 0.0 {
+        y[pos.x] = x[pos.x] + 1.0;
+    }
+}
+
+#[kernel]
+fn neighbours(pos: Pos, x: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = x[pos.x] * x[pos.x + 1];
+}
+
+#[test]
+fn polynomial_passes() {
+    let mut p = CPUProcessor::new();
+    let x = Tensor::from(vec![-1.5, 0.0, 0.5, 2.0]);
+
+    let report = gradcheck(&mut p, cubic(), Variable::new("x"), (x.clone(),), Tensor::from(vec![0.0; 4]), (4, 1, 1)).unwrap();
+
+    assert!(report.passed(), "{}", report);
+    assert_eq!(report.elements, 4);
+    for (i, x) in x.data.iter().enumerate() {
+        assert!((report.analytic[i * 4 + i] - (3.0 * x * x - 2.0) as f64).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn checks_every_argument_and_function_calls() {
+    let mut p = CPUProcessor::new();
+    let inputs = (Tensor::from(vec![1.0, -2.0, 3.0]), Tensor::from(vec![0.5, 1.5, -0.5]));
+
+    let for_x = gradcheck(&mut p, weighted(), Variable::new("x"), inputs.clone(), Tensor::from(vec![0.0; 3]), (3, 1, 1)).unwrap();
+    let for_w = gradcheck(&mut p, weighted(), Variable::new("w"), inputs.clone(), Tensor::from(vec![0.0; 3]), (3, 1, 1)).unwrap();
+
+    assert!(for_x.passed(), "{}", for_x);
+    assert!(for_w.passed(), "{}", for_w);
+    assert_eq!(for_x.analytic, vec![0.5, 0.0, 0.0, 0.0, 1.5, 0.0, 0.0, 0.0, -0.5]);
+}
+
+#[test]
+fn checks_every_element_on_its_own() {
+    let mut p = CPUProcessor::new();
+    let x = Tensor::from(vec![1.0, -2.0, 3.0, 0.5]);
+
+    let report = gradcheck(&mut p, neighbours(), Variable::new("x"), (x,), Tensor::from(vec![0.0; 3]), (3, 1, 1)).unwrap();
+
+    // y[0] = x[0] · x[1], so ∂y[0]/∂x[1] = x[0]
+    assert!(report.passed(), "{}", report);
+    assert_eq!(&report.analytic[..4], &[-2.0, 1.0, 0.0, 0.0]);
+}
+
+#[test]
+fn reports_mismatching_elements() {
+    let mut p = CPUProcessor::new();
+    let x = Tensor::from(vec![-1.0, 0.0, 1.0]);
+
+    let report = gradcheck(&mut p, step(), Variable::new("x"), (x.clone(),), Tensor::from(vec![0.0; 3]), (3, 1, 1)).unwrap();
+
+    assert!(!report.passed(), "{}", report);
+    assert_eq!(report.mismatches.len(), 1);
+    assert_eq!(report.mismatches[0].index, 1);
+    assert_eq!(report.mismatches[0].element, 1);
+    assert_eq!(report.mismatches[0].analytic, 1.0);
+    assert!((report.mismatches[0].numeric - 501.0).abs() < 1.0);
+    assert!(report.to_string().contains("[1, 1] analytic 1"), "{}", report);
+
+    let lenient = Gradcheck::new()
+        .epsilon(1e-2)
+        .tolerance(100.0)
+        .check(&mut p, step(), Variable::new("x"), (x,), Tensor::from(vec![0.0; 3]), (3, 1, 1))
+        .unwrap();
+    assert!(lenient.passed(), "{}", lenient);
+}
+
+#[test]
+fn rejects_unknown_arguments() {
+    let mut p = CPUProcessor::new();
+
+    let result = gradcheck(&mut p, cubic(), Variable::new("y"), (Tensor::from(vec![1.0]),), Tensor::from(vec![0.0]), (1, 1, 1));
+
+    assert!(matches!(result, Err(Error::UnknownArgument(argument)) if argument == "y"));
+}
@@ -0,0 +1,135 @@
+// kernels have no compound assignment, see tests/ui/compound_assign.rs
+#![allow(clippy::assign_op_pattern)]
+
+use chandra::kernel;
+use chandra::core::processor::Processor;
+use chandra::core::type_traits::FromMut;
+use chandra::core::graph::{Graph, GraphValues};
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+#[kernel]
+fn scale(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = a[pos.x] * 2.0;
+}
+
+#[kernel]
+fn relu(pos: Pos, x: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = x[pos.x].max(0.0);
+}
+
+#[kernel]
+fn add(pos: Pos, a: &Tensor<f32>, b: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = a[pos.x] + b[pos.x];
+}
+
+/// Records when it starts and stops, counting to `steps[0]` in between.
+#[kernel]
+fn spin(pos: Pos, steps: &Tensor<u32>, out: &mut Tensor<f32>) {
+    debug_print!("start");
+    let mut count: f32 = 0.0;
+    for _i in 0..steps[0] {
+        count = count + 1.0;
+    }
+    debug_print!("stop");
+    out[pos.x] = count;
+}
+
+#[test]
+fn runs_a_diamond_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let mut graph = Graph::new();
+
+    let a = graph.input::<Tensor<f32>>();
+    let scaled = graph.add(&mut p, scale(), (a,), Tensor::new(0f32, vec![4]));
+    let rectified = graph.add(&mut p, relu(), (a,), Tensor::new(0f32, vec![4]));
+    let sum = graph.add(&mut p, add(), (scaled, rectified), Tensor::new(0f32, vec![4]));
+    graph.output(sum);
+
+    assert_eq!(graph.waves(), vec![vec![0, 1], vec![2]]);
+
+    let mut inputs = GraphValues::new();
+    inputs.insert(a, Tensor::from(vec![-2.0, -0.5, 1.0, 3.0]));
+    let mut outputs = graph.run(&mut p, inputs).unwrap();
+
+    assert_eq!(outputs.take(sum).unwrap().data, vec![-4.0, -1.0, 3.0, 9.0]);
+}
+
+#[test]
+fn frees_intermediates() {
+    let mut p = CPUProcessor::new();
+    let mut graph = Graph::new();
+
+    let a = graph.input::<Tensor<f32>>();
+    let scaled = graph.add(&mut p, scale(), (a,), Tensor::new(0f32, vec![3]));
+    let twice = graph.add(&mut p, scale(), (scaled,), Tensor::new(0f32, vec![3]));
+    let out = graph.add(&mut p, relu(), (twice,), Tensor::new(0f32, vec![3]));
+    graph.output(out);
+
+    for _ in 0..2 {
+        let mut inputs = GraphValues::new();
+        inputs.insert(a, Tensor::from(vec![-1.0, 0.5, 2.0]));
+        let mut outputs = graph.run(&mut p, inputs).unwrap();
+
+        assert_eq!(outputs.take(out).unwrap().data, vec![0.0, 2.0, 8.0]);
+        assert_eq!(p.heap.len(), 0);
+    }
+}
+
+#[test]
+fn reports_missing_inputs() {
+    let mut p = CPUProcessor::new();
+    let mut graph = Graph::new();
+
+    let a = graph.input::<Tensor<f32>>();
+    let out = graph.add(&mut p, relu(), (a,), Tensor::new(0f32, vec![3]));
+    graph.output(out);
+
+    assert!(matches!(graph.run(&mut p, GraphValues::new()), Err(chandra::Error::MissingGraphValue(0))));
+}
+
+#[test]
+#[ignore = "overlap depends on the OS scheduling both pool threads, run with --ignored"]
+fn independent_nodes_overlap_on_cpu() {
+    // a pool of its own so that the wave has two threads even on a single core
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+
+    let (messages, first, second) = pool.install(|| {
+        let mut p = CPUProcessor::new();
+        let mut graph = Graph::new();
+
+        let steps = graph.input::<Tensor<u32>>();
+        let first = graph.add(&mut p, spin(), (steps,), Tensor::new(0f32, vec![1]));
+        let second = graph.add(&mut p, spin(), (steps,), Tensor::new(0f32, vec![1]));
+        graph.output(first);
+        graph.output(second);
+
+        assert_eq!(graph.waves(), vec![vec![0, 1]]);
+
+        let mut inputs = GraphValues::new();
+        inputs.insert(steps, Tensor { data: vec![2_000_000u32], shape: vec![1] });
+        let mut outputs = graph.run(&mut p, inputs).unwrap();
+
+        let messages: Vec<&str> = p.drain_debug().iter().map(|r| r.message).collect();
+        (messages, outputs.take(first).unwrap().data, outputs.take(second).unwrap().data)
+    });
+
+    // one after the other would be start, stop, start, stop
+    assert_eq!(messages, vec!["start", "start", "stop", "stop"]);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn waves_of_a_gpu_graph() {
+    let mut g = GPUProcessor::new();
+    let mut graph = Graph::new();
+
+    let a = graph.input::<Tensor<f32>>();
+    let b = graph.input::<Tensor<f32>>();
+    let scaled = graph.add(&mut g, scale(), (a,), Tensor::new(0f32, vec![4]));
+    let sum = graph.add(&mut g, add(), (scaled, b), Tensor::new(0f32, vec![4]));
+    graph.output(sum);
+
+    assert_eq!(graph.waves(), vec![vec![0], vec![1]]);
+}
@@ -0,0 +1,119 @@
+use chandra::kernel;
+use chandra::core::processor::{Processor, Executable};
+use chandra::core::type_traits::FromMut;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+#[kernel]
+fn affine(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let offset: f32 = 1.0;
+    let next = pos.x + 1;
+    out[pos.x] = a[pos.x] * 2.0 - offset + a[next - 1] * 0.5;
+}
+
+#[kernel]
+fn counts(pos: Pos, a: &Tensor<i32>, out: &mut Tensor<i32>) {
+    let total: i32 = a[0] * -2 + a[1] - a[2];
+    out[pos.x] = total + 10;
+}
+
+#[kernel]
+fn flags(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<u32>) {
+    let mut positive = false;
+    if a[pos.x] > 0.0 {
+        positive = true;
+    }
+    if positive {
+        out[pos.x] = 1;
+    } else {
+        out[pos.x] = 0;
+    }
+}
+
+#[kernel]
+fn wide(pos: Pos, a: &Tensor<i64>, out: &mut Tensor<f64>) {
+    out[pos.x] = (a[pos.x] * 3_000_000_000) as f64 / 2.0;
+}
+
+#[test]
+fn literals_take_the_type_of_their_context() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor { data: vec![1.0f32, -2.0, 4.0], shape: vec![3] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![3]));
+
+    let mut e = affine().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+    p.dispatch(&mut e, 3, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![1.5, -6.0, 9.0]);
+}
+
+#[test]
+fn integer_literals_follow_the_declared_type() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor { data: vec![1i32, 2, 3], shape: vec![3] });
+    let mut out = p.alloc(Tensor::new(0i32, vec![2]));
+
+    let mut e = counts().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+    p.dispatch(&mut e, 2, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![7, 7]);
+}
+
+#[test]
+fn bool_literals_evaluate_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor { data: vec![1.0f32, -2.0, 0.0, 3.0], shape: vec![4] });
+    let mut out = p.alloc(Tensor::new(7u32, vec![4]));
+
+    let mut e = flags().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+    p.dispatch(&mut e, 4, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![1, 0, 0, 1]);
+}
+
+#[test]
+fn sixty_four_bit_literals_evaluate_on_cpu() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor { data: vec![1i64, -2], shape: vec![2] });
+    let mut out = p.alloc(Tensor::new(0f64, vec![2]));
+
+    let mut e = wide().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+    p.dispatch(&mut e, 2, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![1.5e9, -3e9]);
+}
+
+/// The generated kernel with `a` and `out` declared, `pos` bound to the invocation id.
+fn standalone(program: &str, input: &str, output: &str) -> String {
+    format!("struct Input {{ data: array<{}> }}
+struct Output {{ data: array<{}> }}
+@group(0) @binding(0) var<storage, read_write> a: Input;
+@group(0) @binding(1) var<storage, read_write> out: Output;
+{}", input, output, program.replace("fn main()", "fn main(@builtin(global_invocation_id) pos: vec3<u32>)"))
+}
+
+#[test]
+fn wgsl_literals_are_typed() {
+    let mut g = GPUProcessor::new();
+    let programs = [
+        (affine().build(&mut g).get_program().to_string(), "f32", "f32", vec!["pos.x + u32(1)", "a.data[pos.x] * f32(2.0)", "a.data[next - u32(1)] * f32(0.5)"]),
+        (counts().build(&mut g).get_program().to_string(), "i32", "i32", vec!["a.data[u32(0)] * i32(-2)", "total + i32(10)"]),
+        (flags().build(&mut g).get_program().to_string(), "f32", "u32", vec!["var positive: bool = bool(false);", "a.data[pos.x] > f32(0.0)", "out.data[pos.x] = u32(1);"]),
+    ];
+
+    for (program, input, output, expected) in programs {
+        for e in expected {
+            assert!(program.contains(e), "{} not in {}", e, program);
+        }
+
+        let module = naga::front::wgsl::parse_str(&standalone(&program, input, output)).unwrap();
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+            .validate(&module)
+            .unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+// kernels have no compound assignment, see tests/ui/compound_assign.rs
+#![allow(clippy::assign_op_pattern)]
+
+use chandra::{kernel, Error};
+use chandra::core::derivatives::{gradient, hessian, jacobian};
+use chandra::core::gradcheck::gradcheck;
+use chandra::core::operation::{Differentiable, Operation};
+use chandra::core::operations::{add::add, assign::assign, greater_than_or_equal::greater_than_or_equal, multiply::multiply, scope::Scope, set::set, until::until, var::Variable};
+use chandra::core::processor::Processor;
+use chandra::core::processor::cpu::DifferentiatedCPUContext;
+use chandra::core::types::Void;
+use chandra::core::type_traits::FromMut;
+use chandra::core::DifferentiableProgram;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+mod common;
+use common::assert_close;
+
+#[kernel]
+fn power(pos: Pos, x: &Tensor<f32>, y: &mut Tensor<f32>) {
+    let mut p = 1.0f32;
+    for _k in 0..4 {
+        p = p * x[pos.x];
+    }
+    y[pos.x] = p;
+}
+
+#[kernel]
+fn horner(pos: Pos, x: &Tensor<f32>, c: &Tensor<f32>, y: &mut Tensor<f32>) {
+    let mut acc = 0.0f32;
+    for k in 0..4 {
+        acc = acc * x[pos.x] + c[k];
+    }
+    y[pos.x] = acc;
+}
+
+/// Both powers are carried between iterations in a local array.
+#[kernel]
+fn powers(pos: Pos, x: &Tensor<f32>, y: &mut Tensor<f32>) {
+    let mut p = [1.0f32; 2];
+    for _k in 0..3 {
+        p[0] = p[0] * x[pos.x];
+        p[1] = p[1] * p[0];
+    }
+    y[pos.x] = p[0] + p[1];
+}
+
+/// The output element carries the product, its value is lost once it holds the derivative.
+#[kernel]
+fn power_in_place(pos: Pos, x: &Tensor<f32>, y: &mut Tensor<f32>) {
+    y[pos.x] = 1.0;
+    for _k in 0..3 {
+        y[pos.x] = y[pos.x] * x[pos.x];
+    }
+}
+
+#[test]
+fn power_loop_matches_the_analytic_derivative() {
+    let x = vec![-1.5, 0.5, 2.0];
+
+    let d = gradient(&power(), &Variable::new("x"), (Tensor::from(x.clone()),), (3, 1, 1)).unwrap();
+    let dd = hessian(&power(), &[Variable::new("x")], (Tensor::from(x.clone()),), (3, 1, 1)).unwrap();
+
+    assert_close(&d.data, &x.iter().map(|x| 4.0 * x * x * x).collect::<Vec<_>>(), 1e-3);
+    // y[p] only depends on x[p]
+    let mut expected = vec![0.0; 27];
+    for (p, x) in x.iter().enumerate() {
+        expected[(p * 3 + p) * 3 + p] = 12.0 * x * x;
+    }
+    assert_close(&dd.data, &expected, 1e-3);
+}
+
+#[test]
+fn polynomial_evaluation_differentiates_for_value_and_coefficients() {
+    // 2x³ - x² + 3x + 5
+    let c = vec![2.0, -1.0, 3.0, 5.0];
+    let x = vec![-1.0, 0.5, 2.0];
+    let inputs = (Tensor::from(x.clone()), Tensor::from(c));
+
+    let j = jacobian(&horner(), &[Variable::new("x"), Variable::new("c")], inputs.clone(), (3, 1, 1)).unwrap();
+
+    // a column per element of x, then one per coefficient
+    assert_eq!(j.shape, vec![3, 7]);
+    let mut expected = vec![0.0; 21];
+    for (p, x) in x.iter().enumerate() {
+        expected[p * 7 + p] = 6.0 * x * x - 2.0 * x + 3.0;
+        expected[p * 7 + 3..p * 7 + 7].copy_from_slice(&[x * x * x, x * x, *x, 1.0]);
+    }
+    assert_close(&j.data, &expected, 1e-3);
+
+    let mut p = CPUProcessor::new();
+    for variable in ["x", "c"] {
+        let report = gradcheck(&mut p, horner(), Variable::new(variable), inputs.clone(), Tensor::from(vec![0.0; 3]), (3, 1, 1)).unwrap();
+        assert!(report.passed(), "{}", report);
+    }
+}
+
+#[test]
+fn local_arrays_carry_derivatives_through_loops() {
+    // p[0] = x³ and p[1] = x · x² · x³ = x⁶
+    let x = vec![-1.5, 0.5, 1.25];
+
+    let d = gradient(&powers(), &Variable::new("x"), (Tensor::from(x.clone()),), (3, 1, 1)).unwrap();
+    assert_close(&d.data, &x.iter().map(|x: &f32| 3.0 * x * x + 6.0 * x.powi(5)).collect::<Vec<_>>(), 1e-3);
+
+    let mut p = CPUProcessor::new();
+    let report = gradcheck(&mut p, powers(), Variable::new("x"), (Tensor::from(x),), Tensor::from(vec![0.0; 3]), (3, 1, 1)).unwrap();
+    assert!(report.passed(), "{}", report);
+}
+
+#[test]
+fn tensor_elements_are_not_loop_carried() {
+    let result = jacobian(&power_in_place(), &[Variable::new("x")], (Tensor::from(vec![2.0]),), (1, 1, 1));
+    assert!(matches!(result, Err(Error::NotDifferentiable(message)) if message.starts_with("`y` is read after it was written")));
+
+    let mut p = CPUProcessor::new();
+    assert!(matches!(p.try_build(power_in_place().differantiate_for(Variable::<Tensor<f32>>::new("x"))), Err(Error::NotDifferentiable(_))));
+}
+
+#[test]
+fn loop_derivatives_build_for_the_gpu() {
+    let x = Variable::<Tensor<f32>>::new("x");
+    let first = power().differantiate_for(x.clone());
+    let second = first.differantiate_for(x);
+
+    let mut g = GPUProcessor::new();
+    let program = g.try_build(second).unwrap().get_program().to_string();
+
+    assert!(program.contains("var p_d0_x: f32"), "{}", program);
+    assert!(program.contains("var p_d0_x_d1_x: f32"), "{}", program);
+}
+
+#[test]
+fn until_loops_carry_derivatives() {
+    // p = x^n, stepping n until it reaches 3
+    let x = Variable::<f32>::new("x");
+    let (p, init_p) = assign("p".into(), 1.0f32);
+    let (n, init_n) = assign("n".into(), 0u32);
+    let body = Scope::new()
+        .include(set(&p, multiply(p.clone(), x.clone())))
+        .include(set(&n, add(n.clone(), 1u32)));
+    let program = Scope::new::<Void>()
+        .include(init_p)
+        .include(init_n)
+        .include(until(greater_than_or_equal(n, 3u32), body));
+
+    let derivative = program.auto_diff_for(x, &mut Default::default());
+    let mut context = DifferentiatedCPUContext::new();
+    context.set("x", 1.5f32);
+    derivative.evaluate(&mut context);
+
+    assert_eq!(*context.get::<f32>("p"), 1.5 * 1.5 * 1.5);
+    assert_eq!(*context.get::<f32>("p_d0_x"), 3.0 * 1.5 * 1.5);
+}
@@ -0,0 +1,194 @@
+use chandra::core::derivatives::gradient;
+use chandra::core::operations::var::Variable;
+use chandra::core::processor::{Executable, Processor};
+use chandra::nn::{loss, Batch, Loss, Reduction};
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+use chandra::Error;
+
+mod common;
+use common::assert_close;
+
+fn run(p: &mut CPUProcessor, loss: &mut Loss<CPUProcessor>, input: &Tensor<f32>, target: &Tensor<f32>) -> (Tensor<f32>, Tensor<f32>) {
+    let input = Batch::new(p, input.clone()).unwrap();
+    let target = Batch::new(p, target.clone()).unwrap();
+    let value = loss.forward(p, &input, &target).unwrap().to_cpu(p).unwrap();
+    let gradient = loss.backward(p, &input, &target).unwrap().to_cpu(p).unwrap();
+    (value, gradient)
+}
+
+/// Central finite differences of the summed loss with respect to every input element.
+fn numeric(p: &mut CPUProcessor, loss: &mut Loss<CPUProcessor>, input: &Tensor<f32>, target: &Tensor<f32>) -> Vec<f32> {
+    let epsilon = 1e-2;
+
+    (0..input.data.len()).map(|i| {
+        let mut shifted = input.clone();
+        shifted.data[i] += epsilon;
+        let above = run(p, loss, &shifted, target).0.data[0];
+        shifted.data[i] -= 2.0 * epsilon;
+        let below = run(p, loss, &shifted, target).0.data[0];
+        (above - below) / (2.0 * epsilon)
+    }).collect()
+}
+
+fn elementwise() -> (Tensor<f32>, Tensor<f32>) {
+    (Tensor::from_vec(vec![0.2, 0.9, 0.45, 0.7, 0.3, 0.6], vec![2, 3]), Tensor::from_vec(vec![0.0, 1.0, 1.0, 0.5, 0.1, 0.0], vec![2, 3]))
+}
+
+#[test]
+fn losses_match_host_math() {
+    let mut p = CPUProcessor::new();
+    let (input, target) = elementwise();
+    let pairs: Vec<(f32, f32)> = input.data.iter().copied().zip(target.data.iter().copied()).collect();
+
+    let cases: Vec<(Loss<CPUProcessor>, Vec<f32>)> = vec![
+        (Loss::mse(&mut p).unwrap(), pairs.iter().map(|(i, t)| (i - t) * (i - t)).collect()),
+        (Loss::mae(&mut p).unwrap(), pairs.iter().map(|(i, t)| (i - t).abs()).collect()),
+        (Loss::huber(&mut p, 0.3).unwrap(), pairs.iter().map(|(i, t)| if (i - t).abs() <= 0.3 { 0.5 * (i - t) * (i - t) } else { 0.3 * ((i - t).abs() - 0.15) }).collect()),
+        (Loss::binary_cross_entropy(&mut p).unwrap(), pairs.iter().map(|(i, t)| -(t * i.ln() + (1.0 - t) * (1.0 - i).ln())).collect()),
+        (Loss::kl_divergence(&mut p).unwrap(), pairs.iter().map(|(i, t)| if *t > 0.0 { t * (t.ln() - i) } else { 0.0 }).collect()),
+    ];
+
+    for (loss, terms) in cases {
+        let mut loss = loss.reduction(Reduction::None);
+        assert_close(&run(&mut p, &mut loss, &input, &target).0.data, &terms, 1e-5);
+
+        let mut loss = loss.reduction(Reduction::Sum);
+        assert_close(&run(&mut p, &mut loss, &input, &target).0.data, &[terms.iter().sum()], 1e-5);
+
+        let mut loss = loss.reduction(Reduction::Mean);
+        let (value, _) = run(&mut p, &mut loss, &input, &target);
+        assert_eq!(value.shape, vec![1]);
+        assert_close(&value.data, &[terms.iter().sum::<f32>() / 6.0], 1e-5);
+    }
+
+    let logits = Tensor::from_vec(vec![1.0f32, 2.0, 3.0, 0.5, -1.0, 0.0], vec![2, 3]);
+    let classes = Tensor::from_vec(vec![0.0, 0.0, 1.0, 1.0, 0.0, 0.0], vec![2, 3]);
+    let expected: Vec<f32> = logits.data.chunks(3).zip(classes.data.chunks(3)).map(|(l, c)| {
+        let normalizer = l.iter().map(|e| e.exp()).sum::<f32>().ln();
+        l.iter().zip(c).map(|(l, c)| c * (normalizer - l)).sum()
+    }).collect();
+
+    let mut cross_entropy = Loss::cross_entropy(&mut p).unwrap().reduction(Reduction::None);
+    assert_close(&run(&mut p, &mut cross_entropy, &logits, &classes).0.data, &expected, 1e-5);
+    let mut cross_entropy = cross_entropy.reduction(Reduction::Mean);
+    assert_close(&run(&mut p, &mut cross_entropy, &logits, &classes).0.data, &[(expected[0] + expected[1]) / 2.0], 1e-5);
+}
+
+#[test]
+fn gradients_match_auto_diff() {
+    let mut p = CPUProcessor::new();
+    let (input, target) = elementwise();
+    let x = Variable::new("input");
+    // summed, so the scale in `hyper[0]` is one
+    let inputs = |parameter: f32| (input.clone(), target.clone(), Tensor::from_vec(vec![1.0, parameter], vec![2]));
+
+    let cases: Vec<(Loss<CPUProcessor>, Tensor<f32>)> = vec![
+        (Loss::mse(&mut p).unwrap(), gradient(&loss::mean_squared_error::<f32>(), &x, inputs(0.0), (6, 1, 1)).unwrap()),
+        (Loss::mae(&mut p).unwrap(), gradient(&loss::mean_absolute_error::<f32>(), &x, inputs(0.0), (6, 1, 1)).unwrap()),
+        (Loss::huber(&mut p, 0.3).unwrap(), gradient(&loss::huber::<f32>(), &x, inputs(0.3), (6, 1, 1)).unwrap()),
+        (Loss::binary_cross_entropy(&mut p).unwrap(), gradient(&loss::binary_cross_entropy::<f32>(), &x, inputs(1e-7), (6, 1, 1)).unwrap()),
+        (Loss::kl_divergence(&mut p).unwrap(), gradient(&loss::kl_divergence::<f32>(), &x, inputs(0.0), (6, 1, 1)).unwrap()),
+    ];
+
+    for (loss, expected) in cases {
+        let mut loss = loss.reduction(Reduction::Sum);
+        assert_close(&run(&mut p, &mut loss, &input, &target).1.data, &expected.data, 1e-4);
+    }
+
+    // The cross entropy of a row depends on every logit of the row, one thread per row.
+    let logits = Tensor::from_vec(vec![1.0f32, 2.0, 3.0, 0.5, -1.0, 0.0], vec![2, 3]);
+    let classes = Tensor::from_vec(vec![0.0, 0.0, 1.0, 0.2, 0.3, 0.5], vec![2, 3]);
+    let expected = gradient(&loss::cross_entropy::<f32>(), &x, (logits.clone(), classes.clone(), Tensor::from_vec(vec![1.0, 0.0], vec![2])), (2, 1, 1)).unwrap();
+
+    let mut cross_entropy = Loss::cross_entropy(&mut p).unwrap().reduction(Reduction::Sum);
+    assert_close(&run(&mut p, &mut cross_entropy, &logits, &classes).1.data, &expected.data, 1e-4);
+}
+
+#[test]
+fn gradients_match_finite_differences() {
+    let mut p = CPUProcessor::new();
+    let (input, target) = elementwise();
+
+    let losses = vec![
+        Loss::mse(&mut p).unwrap(),
+        Loss::mae(&mut p).unwrap(),
+        Loss::huber(&mut p, 0.3).unwrap(),
+        Loss::binary_cross_entropy(&mut p).unwrap(),
+        Loss::kl_divergence(&mut p).unwrap(),
+    ];
+
+    for mut loss in losses {
+        for reduction in [Reduction::Mean, Reduction::Sum] {
+            loss = loss.reduction(reduction);
+            let analytic = run(&mut p, &mut loss, &input, &target).1;
+            assert_close(&analytic.data, &numeric(&mut p, &mut loss, &input, &target), 2e-2);
+        }
+    }
+
+    let logits = Tensor::from_vec(vec![1.0f32, 2.0, 3.0, 0.5, -1.0, 0.0], vec![2, 3]);
+    let classes = Tensor::from_vec(vec![0.0, 0.0, 1.0, 0.2, 0.3, 0.5], vec![2, 3]);
+    let mut cross_entropy = Loss::cross_entropy(&mut p).unwrap();
+    let analytic = run(&mut p, &mut cross_entropy, &logits, &classes).1;
+    assert_close(&analytic.data, &numeric(&mut p, &mut cross_entropy, &logits, &classes), 2e-2);
+}
+
+#[test]
+fn cross_entropy_is_stable_for_large_logits() {
+    let mut p = CPUProcessor::new();
+    let mut cross_entropy = Loss::cross_entropy(&mut p).unwrap().reduction(Reduction::None);
+
+    let logits = Tensor::from_vec(vec![1000.0, 0.0, -1000.0, 0.0, 1000.0, 999.0], vec![2, 3]);
+    let classes = Tensor::from_vec(vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0], vec![2, 3]);
+    let (value, gradient) = run(&mut p, &mut cross_entropy, &logits, &classes);
+
+    assert_close(&value.data, &[0.0, (1.0 + (-1.0f32).exp()).ln()], 1e-3);
+    assert!(gradient.data.iter().all(|g| g.is_finite()));
+}
+
+#[test]
+fn rejects_mismatched_shapes() {
+    let mut p = CPUProcessor::new();
+    let input = Batch::new(&mut p, Tensor::new(0.5, vec![2, 3])).unwrap();
+    let target = Batch::new(&mut p, Tensor::new(0.5, vec![3, 2])).unwrap();
+    let flat = Batch::new(&mut p, Tensor::new(0.5, vec![6])).unwrap();
+
+    let mut mse = Loss::mse(&mut p).unwrap();
+    assert!(matches!(mse.forward(&mut p, &input, &target), Err(Error::ShapeMismatch { .. })));
+    assert!(matches!(mse.backward(&mut p, &input, &target), Err(Error::ShapeMismatch { .. })));
+
+    let mut cross_entropy = Loss::cross_entropy(&mut p).unwrap();
+    assert!(matches!(cross_entropy.forward(&mut p, &flat, &flat), Err(Error::ShapeMismatch { .. })));
+}
+
+#[test]
+fn kernels_are_generic_over_the_element_type() {
+    let mut p = CPUProcessor::new();
+    let input = p.alloc(Tensor { data: vec![1.0f64, 2.0], shape: vec![2] });
+    let target = p.alloc(Tensor { data: vec![0.5f64, 3.0], shape: vec![2] });
+    let hyper = p.alloc(Tensor { data: vec![1.0f64, 0.5], shape: vec![2] });
+    let mut gradient = p.alloc(Tensor::new(0.0f64, vec![2]));
+
+    let mut kernel = p.build(loss::huber_gradient::<f64>());
+    kernel.get_bindings().bind(&input, &target, &hyper, &mut gradient);
+    p.dispatch(&mut kernel, 2, 1, 1).unwrap();
+    drop(kernel);
+
+    assert_eq!(gradient.deref().data, vec![0.5, -0.5]);
+}
+
+/// The GPU can't allocate yet, so only the kernels are built.
+#[test]
+fn losses_build_for_the_gpu() {
+    let mut g = GPUProcessor::new();
+
+    assert!(g.try_build(loss::cross_entropy::<f32>()).is_ok());
+    assert!(g.try_build(loss::binary_cross_entropy_gradient::<f32>()).is_ok());
+    assert!(Loss::mse(&mut g).is_ok());
+    assert!(Loss::mae(&mut g).is_ok());
+    assert!(Loss::huber(&mut g, 1.0).is_ok());
+    assert!(Loss::binary_cross_entropy(&mut g).is_ok());
+    assert!(Loss::cross_entropy(&mut g).is_ok());
+    assert!(Loss::kl_divergence(&mut g).is_ok());
+}
@@ -0,0 +1,146 @@
+use chandra::kernel;
+use chandra::core::processor::{Processor, Executable};
+use chandra::core::type_traits::FromMut;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+const VALUES: [f32; 6] = [-2.5, -1.0, -0.25, 0.0, 0.5, 3.0];
+
+#[kernel]
+fn unary(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let v = a[pos.x];
+    out[pos.x * 5] = v.abs();
+    out[pos.x * 5 + 1] = v.abs().sqrt();
+    out[pos.x * 5 + 2] = v.exp();
+    out[pos.x * 5 + 3] = (v.abs() + 1.0).ln();
+    out[pos.x * 5 + 4] = v.tanh();
+}
+
+#[kernel]
+fn binary(pos: Pos, a: &Tensor<f32>, b: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x * 2] = a[pos.x].max(b[pos.x]);
+    out[pos.x * 2 + 1] = a[pos.x].min(b[pos.x]);
+}
+
+#[kernel]
+fn integers(pos: Pos, a: &Tensor<i32>, out: &mut Tensor<i32>) {
+    out[pos.x * 3] = a[pos.x].abs();
+    out[pos.x * 3 + 1] = a[pos.x].max(0);
+    out[pos.x * 3 + 2] = a[pos.x].min(0);
+}
+
+#[kernel]
+fn sizes(pos: Pos, m: &Tensor<f32>, out: &mut Tensor<u32>) {
+    out[pos.x * 3] = m.len();
+    out[pos.x * 3 + 1] = m.shape(0);
+    out[pos.x * 3 + 2] = m.shape(1);
+}
+
+#[kernel]
+fn dots(pos: Pos, a: &Tensor<f32>, b: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let x = [a[pos.x * 3], a[pos.x * 3 + 1], a[pos.x * 3 + 2]];
+    let y = [b[pos.x * 3], b[pos.x * 3 + 1], b[pos.x * 3 + 2]];
+    out[pos.x] = x.dot(y);
+}
+
+#[test]
+fn float_methods_match_rust() {
+    let mut p = CPUProcessor::new();
+    let a = p.alloc(Tensor::from(VALUES.to_vec()));
+    let mut out = p.alloc(Tensor::new(0f32, vec![VALUES.len() * 5]));
+
+    let mut e = unary().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+    p.dispatch(&mut e, VALUES.len() as u32, 1, 1).unwrap();
+
+    let expected: Vec<f32> = VALUES.iter()
+        .flat_map(|v| [v.abs(), v.abs().sqrt(), v.exp(), (v.abs() + 1.0).ln(), v.tanh()])
+        .collect();
+    assert_eq!(out.deref().data, expected);
+}
+
+#[test]
+fn max_and_min_match_rust() {
+    let mut p = CPUProcessor::new();
+    let other: Vec<f32> = VALUES.iter().rev().cloned().collect();
+    let a = p.alloc(Tensor::from(VALUES.to_vec()));
+    let b = p.alloc(Tensor::from(other.clone()));
+    let mut out = p.alloc(Tensor::new(0f32, vec![VALUES.len() * 2]));
+
+    let mut e = binary().build(&mut p);
+    e.get_bindings().bind(&a, &b, &mut out);
+    p.dispatch(&mut e, VALUES.len() as u32, 1, 1).unwrap();
+
+    let expected: Vec<f32> = VALUES.iter().zip(&other).flat_map(|(a, b)| [a.max(*b), a.min(*b)]).collect();
+    assert_eq!(out.deref().data, expected);
+
+    let values = vec![-3i32, 0, 4];
+    let a = p.alloc(Tensor::from(values.clone()));
+    let mut out = p.alloc(Tensor::new(0i32, vec![values.len() * 3]));
+    let mut e = integers().build(&mut p);
+    e.get_bindings().bind(&a, &mut out);
+    p.dispatch(&mut e, values.len() as u32, 1, 1).unwrap();
+
+    let expected: Vec<i32> = values.iter().flat_map(|v| [v.abs(), (*v).max(0), (*v).min(0)]).collect();
+    assert_eq!(out.deref().data, expected);
+}
+
+#[test]
+fn tensor_sizes_match_the_shape() {
+    let mut p = CPUProcessor::new();
+    let m = p.alloc(Tensor::new(0f32, vec![2, 5]));
+    let mut out = p.alloc(Tensor::new(0u32, vec![3]));
+
+    let mut e = sizes().build(&mut p);
+    e.get_bindings().bind(&m, &mut out);
+    p.dispatch(&mut e, 1, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![10, 2, 5]);
+}
+
+#[test]
+fn dot_matches_rust() {
+    let mut p = CPUProcessor::new();
+    let (x, y) = (vec![1.0f32, 2.0, 3.0, -1.0, 0.5, 4.0], vec![0.5f32, -1.0, 2.0, 3.0, 2.0, 0.25]);
+    let a = p.alloc(Tensor::from(x.clone()));
+    let b = p.alloc(Tensor::from(y.clone()));
+    let mut out = p.alloc(Tensor::new(0f32, vec![2]));
+
+    let mut e = dots().build(&mut p);
+    e.get_bindings().bind(&a, &b, &mut out);
+    p.dispatch(&mut e, 2, 1, 1).unwrap();
+
+    let expected: Vec<f32> = x.chunks(3).zip(y.chunks(3)).map(|(x, y)| x.iter().zip(y).map(|(a, b)| a * b).sum()).collect();
+    assert_eq!(out.deref().data, expected);
+}
+
+#[test]
+fn wgsl_uses_the_builtins() {
+    let mut g = GPUProcessor::new();
+    let programs = [
+        (unary().build(&mut g).get_program().to_string(), vec!["abs(", "sqrt(", "exp(", "log(", "tanh("]),
+        (binary().build(&mut g).get_program().to_string(), vec!["max(", "min("]),
+        (sizes().build(&mut g).get_program().to_string(), vec!["arrayLength(&m.data)", "m.shape["]),
+        (dots().build(&mut g).get_program().to_string(), vec!["array<f32, 3>"]),
+    ];
+
+    for (program, expected) in &programs {
+        for e in expected {
+            assert!(program.contains(e), "{} not in {}", e, program);
+        }
+    }
+
+    // the element-wise kernels with their tensors declared
+    for program in [&programs[0].0, &programs[1].0] {
+        let source = format!("struct ChandraF32 {{ data: array<f32> }}
+@group(0) @binding(0) var<storage, read_write> a: ChandraF32;
+@group(0) @binding(1) var<storage, read_write> b: ChandraF32;
+@group(0) @binding(2) var<storage, read_write> out: ChandraF32;
+{}", program.replace("fn main()", "fn main(@builtin(global_invocation_id) pos: vec3<u32>)"));
+        let module = naga::front::wgsl::parse_str(&source).unwrap();
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+            .validate(&module)
+            .unwrap();
+    }
+}
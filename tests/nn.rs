@@ -0,0 +1,350 @@
+use std::slice;
+
+use chandra::core::derivatives::jacobian;
+use chandra::core::operations::var::Variable;
+use chandra::core::processor::Processor;
+use chandra::core::random::Distribution;
+use chandra::core::type_traits::FromMut;
+use chandra::nn::{activation, conv, dropout, embedding, linear, norm, Activation, Batch, BatchNorm, Conv2d, Dropout, Embedding, Layer, LayerNorm, Linear, Sequential};
+use chandra::optim::{Optimizer, Sgd};
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+use chandra::{kernel, Error};
+
+mod common;
+use common::assert_close;
+
+/// `sum(layer(input) * weights)`, whose gradient with respect to the output is `weights`.
+fn weighted_sum<L: Layer<CPUProcessor>>(p: &mut CPUProcessor, layer: &mut L, input: &Tensor<f32>, weights: &Tensor<f32>) -> f32 {
+    let input = Batch::new(p, input.clone()).unwrap();
+    let output = layer.forward(p, &input).unwrap().to_cpu(p).unwrap();
+    output.data.iter().zip(&weights.data).map(|(o, w)| o * w).sum()
+}
+
+/// Compares the gradients of the input and of every parameter with central finite differences.
+fn check_gradients<L: Layer<CPUProcessor>>(p: &mut CPUProcessor, layer: &mut L, input: Tensor<f32>) {
+    let epsilon = 1e-2;
+
+    let batch = Batch::new(p, input.clone()).unwrap();
+    let output = layer.forward(p, &batch).unwrap();
+    let weights = Tensor::<f32>::rand(output.shape.clone(), 7, Distribution::Uniform { low: -1.0, high: 1.0 });
+    let gradient = Batch::new(p, weights.clone()).unwrap();
+    let input_gradient = layer.backward(p, &gradient).unwrap().to_cpu(p).unwrap();
+    let gradients: Vec<_> = layer.gradients().iter().map(|g| p.copy_to_cpu(g)).collect();
+
+    let mut numeric = Vec::new();
+    for i in 0..input.data.len() {
+        let (mut plus, mut minus) = (input.clone(), input.clone());
+        plus.data[i] += epsilon;
+        minus.data[i] -= epsilon;
+        numeric.push((weighted_sum(p, layer, &plus, &weights) - weighted_sum(p, layer, &minus, &weights)) / (2.0 * epsilon));
+    }
+    assert_close(&input_gradient.data, &numeric, 2e-2);
+
+    for (mut parameter, analytic) in layer.parameters().into_iter().zip(gradients) {
+        let mut numeric = Vec::new();
+        for i in 0..analytic.data.len() {
+            let value = parameter.deref().data[i];
+            parameter.deref_mut().data[i] = value + epsilon;
+            let plus = weighted_sum(p, layer, &input, &weights);
+            parameter.deref_mut().data[i] = value - epsilon;
+            let minus = weighted_sum(p, layer, &input, &weights);
+            parameter.deref_mut().data[i] = value;
+            numeric.push((plus - minus) / (2.0 * epsilon));
+        }
+        assert_close(&analytic.data, &numeric, 2e-2);
+    }
+}
+
+fn random(shape: Vec<usize>, seed: u32) -> Tensor<f32> {
+    Tensor::<f32>::rand(shape, seed, Distribution::Normal { mean: 0.0, std: 1.0 })
+}
+
+/// Runs the backward pass of `layer` for a random gradient of its output. Returns that gradient, the
+/// gradient of the input and the gradients of the parameters together with their values.
+fn backward<L: Layer<CPUProcessor>>(p: &mut CPUProcessor, layer: &mut L, input: &Tensor<f32>) -> (Tensor<f32>, Tensor<f32>, Vec<(Tensor<f32>, Tensor<f32>)>) {
+    let batch = Batch::new(p, input.clone()).unwrap();
+    let output = layer.forward(p, &batch).unwrap();
+    let upstream = Tensor::<f32>::rand(output.shape.clone(), 11, Distribution::Uniform { low: -1.0, high: 1.0 });
+    let gradient = Batch::new(p, upstream.clone()).unwrap();
+    let input_gradient = layer.backward(p, &gradient).unwrap().to_cpu(p).unwrap();
+    let parameters = layer.parameters().iter().zip(layer.gradients()).map(|(v, g)| (p.copy_to_cpu(v), p.copy_to_cpu(&g))).collect();
+    (upstream, input_gradient, parameters)
+}
+
+/// `upstream * jacobian`, the gradient a backward pass returns for the gradient `upstream` of the output.
+fn backpropagate(jacobian: &Tensor<f32>, upstream: &Tensor<f32>) -> Vec<f32> {
+    let elements = jacobian.shape[1];
+    (0..elements)
+        .map(|e| upstream.data.iter().enumerate().map(|(p, u)| u * jacobian.data[p * elements + e]).sum())
+        .collect()
+}
+
+/// A grid with a position for every element of `output`.
+fn grid(output: &Tensor<f32>) -> (u32, u32, u32) {
+    (output.data.len() as u32, 1, 1)
+}
+
+/// [`norm::batch_norm_statistics`], [`norm::batch_norm_normalize`] and [`norm::channel_affine`] in a
+/// single kernel, so the gradient of the whole forward pass can be derived.
+#[kernel]
+fn batch_normalization(pos: Pos, input: &Tensor<f32>, affine: &Tensor<f32>, hyper: &Tensor<f32>, output: &mut Tensor<f32>) {
+    let channels = affine.shape(1);
+    let batch = input.shape(0);
+    let inner = input.len() / (batch * channels);
+    let outer = pos.x / inner;
+    let channel = outer - (outer / channels) * channels;
+    let count = (batch * inner) as f32;
+    let mut sum = 0.0f32;
+    for n in 0..batch {
+        for i in 0..inner {
+            sum = sum + input[(n * channels + channel) * inner + i];
+        }
+    }
+    let mean = sum / count;
+    let mut squares = 0.0f32;
+    for n in 0..batch {
+        for i in 0..inner {
+            let centered = input[(n * channels + channel) * inner + i] - mean;
+            squares = squares + centered * centered;
+        }
+    }
+    let normalized = (input[pos.x] - mean) / (squares / count + hyper[0]).sqrt();
+    output[pos.x] = affine[channel] * normalized + affine[channels + channel];
+}
+
+/// The forward pass of [`LayerNorm`] in a single kernel, like [`batch_normalization`].
+#[kernel]
+fn layer_normalization(pos: Pos, input: &Tensor<f32>, affine: &Tensor<f32>, hyper: &Tensor<f32>, output: &mut Tensor<f32>) {
+    let features = input.shape(1);
+    let row = pos.x / features;
+    let column = pos.x - row * features;
+    let mut sum = 0.0f32;
+    for i in 0..features {
+        sum = sum + input[row * features + i];
+    }
+    let mean = sum / features as f32;
+    let mut squares = 0.0f32;
+    for i in 0..features {
+        let centered = input[row * features + i] - mean;
+        squares = squares + centered * centered;
+    }
+    let normalized = (input[pos.x] - mean) / (squares / features as f32 + hyper[0]).sqrt();
+    output[pos.x] = affine[column] * normalized + affine[features + column];
+}
+
+#[test]
+fn linear_matches_host_math() {
+    let mut p = CPUProcessor::new();
+    let mut linear = Linear::from_tensors(&mut p, Tensor::from_vec(vec![1.0, 2.0, 0.0, -1.0, 0.5, 0.5], vec![3, 2]), Tensor::from_vec(vec![0.0, 1.0, -1.0], vec![3])).unwrap();
+
+    let input = Batch::new(&mut p, Tensor::from_vec(vec![1.0, 2.0, 3.0, 4.0], vec![2, 2])).unwrap();
+    let output = linear.forward(&mut p, &input).unwrap();
+    assert_eq!(output.shape, vec![2, 3]);
+    assert_close(&output.to_cpu(&mut p).unwrap().data, &[5.0, -1.0, 0.5, 11.0, -3.0, 2.5], 1e-6);
+
+    let gradient = Batch::new(&mut p, Tensor::from_vec(vec![1.0, 0.0, 0.0, 0.0, 1.0, 2.0], vec![2, 3])).unwrap();
+    let input_gradient = linear.backward(&mut p, &gradient).unwrap();
+    assert_close(&input_gradient.to_cpu(&mut p).unwrap().data, &[1.0, 2.0, 1.0, 0.0], 1e-6);
+
+    let gradients: Vec<_> = linear.gradients().iter().map(|g| p.copy_to_cpu(g).data).collect();
+    assert_close(&gradients[0], &[1.0, 2.0, 3.0, 4.0, 6.0, 8.0], 1e-6);
+    assert_close(&gradients[1], &[1.0, 1.0, 2.0], 1e-6);
+}
+
+#[test]
+fn gradients_match_finite_differences() {
+    let mut p = CPUProcessor::new();
+
+    let mut linear = Linear::new(&mut p, 3, 2, 1).unwrap();
+    check_gradients(&mut p, &mut linear, random(vec![4, 3], 2));
+
+    let mut conv = Conv2d::new(&mut p, 2, 3, 3, 3).unwrap().stride(2).padding(1);
+    check_gradients(&mut p, &mut conv, random(vec![2, 2, 5, 4], 4));
+
+    let mut batch_norm = BatchNorm::new(&mut p, 2).unwrap();
+    check_gradients(&mut p, &mut batch_norm, random(vec![3, 2, 2], 5));
+
+    let mut layer_norm = LayerNorm::new(&mut p, 4).unwrap();
+    check_gradients(&mut p, &mut layer_norm, random(vec![3, 4], 6));
+
+    for mut activation in [Activation::leaky_relu(&mut p, 0.1).unwrap(), Activation::sigmoid(&mut p).unwrap(), Activation::tanh(&mut p).unwrap()] {
+        check_gradients(&mut p, &mut activation, random(vec![2, 5], 8));
+    }
+}
+
+#[test]
+fn backward_passes_match_the_derivatives_of_the_forward_kernels() {
+    let mut p = CPUProcessor::new();
+    let (input, weight) = (Variable::new("input"), Variable::new("weight"));
+
+    let x = random(vec![4, 3], 2);
+    let mut layer = Linear::new(&mut p, 3, 2, 1).unwrap();
+    let (upstream, input_gradient, parameters) = backward(&mut p, &mut layer, &x);
+    let (w, weight_gradient) = &parameters[0];
+    let j = jacobian(&linear::linear_forward(), slice::from_ref(&input), (x.clone(), w.clone()), grid(&upstream)).unwrap();
+    assert_close(&input_gradient.data, &backpropagate(&j, &upstream), 1e-5);
+    let j = jacobian(&linear::linear_forward(), slice::from_ref(&weight), (x, w.clone()), grid(&upstream)).unwrap();
+    assert_close(&weight_gradient.data, &backpropagate(&j, &upstream), 1e-5);
+
+    let x = random(vec![2, 2, 5, 4], 4);
+    let mut layer = Conv2d::new(&mut p, 2, 3, 3, 3).unwrap().stride(2).padding(1);
+    let (upstream, input_gradient, parameters) = backward(&mut p, &mut layer, &x);
+    let (w, weight_gradient) = &parameters[0];
+    let geometry = Tensor::from_vec(vec![2.0, 1.0], vec![2]);
+    let j = jacobian(&conv::convolution_forward(), slice::from_ref(&input), (x.clone(), w.clone(), geometry.clone()), grid(&upstream)).unwrap();
+    assert_close(&input_gradient.data, &backpropagate(&j, &upstream), 1e-5);
+    let j = jacobian(&conv::convolution_forward(), slice::from_ref(&weight), (x, w.clone(), geometry), grid(&upstream)).unwrap();
+    assert_close(&weight_gradient.data, &backpropagate(&j, &upstream), 1e-5);
+
+    let hyper = Tensor::from_vec(vec![1e-5], vec![1]);
+    let affine = Variable::new("affine");
+    let x = random(vec![3, 2, 2], 5);
+    let mut layer = BatchNorm::new(&mut p, 2).unwrap();
+    let (upstream, input_gradient, parameters) = backward(&mut p, &mut layer, &x);
+    let (a, affine_gradient) = &parameters[0];
+    let j = jacobian(&batch_normalization(), slice::from_ref(&input), (x.clone(), a.clone(), hyper.clone()), grid(&upstream)).unwrap();
+    assert_close(&input_gradient.data, &backpropagate(&j, &upstream), 1e-4);
+    let j = jacobian(&batch_normalization(), slice::from_ref(&affine), (x, a.clone(), hyper.clone()), grid(&upstream)).unwrap();
+    assert_close(&affine_gradient.data, &backpropagate(&j, &upstream), 1e-5);
+
+    let x = random(vec![3, 4], 6);
+    let mut layer = LayerNorm::new(&mut p, 4).unwrap();
+    let (upstream, input_gradient, parameters) = backward(&mut p, &mut layer, &x);
+    let (a, affine_gradient) = &parameters[0];
+    let j = jacobian(&layer_normalization(), slice::from_ref(&input), (x.clone(), a.clone(), hyper.clone()), grid(&upstream)).unwrap();
+    assert_close(&input_gradient.data, &backpropagate(&j, &upstream), 1e-4);
+    let j = jacobian(&layer_normalization(), slice::from_ref(&affine), (x, a.clone(), hyper), grid(&upstream)).unwrap();
+    assert_close(&affine_gradient.data, &backpropagate(&j, &upstream), 1e-5);
+
+    let indices = Tensor::from_vec(vec![2.0, 0.0, 2.0], vec![3]);
+    let mut layer = Embedding::new(&mut p, 3, 2, 1).unwrap();
+    let (upstream, _, parameters) = backward(&mut p, &mut layer, &indices);
+    let (w, weight_gradient) = &parameters[0];
+    let j = jacobian(&embedding::embedding_forward(), slice::from_ref(&weight), (indices, w.clone()), grid(&upstream)).unwrap();
+    assert_close(&weight_gradient.data, &backpropagate(&j, &upstream), 1e-6);
+
+    let x = random(vec![2, 5], 8);
+    let mut layer = Activation::leaky_relu(&mut p, 0.1).unwrap();
+    let (upstream, input_gradient, _) = backward(&mut p, &mut layer, &x);
+    let j = jacobian(&activation::leaky_relu(), slice::from_ref(&input), (x.clone(), Tensor::from_vec(vec![0.1], vec![1])), grid(&upstream)).unwrap();
+    assert_close(&input_gradient.data, &backpropagate(&j, &upstream), 1e-6);
+    let mut layer = Activation::sigmoid(&mut p).unwrap();
+    let (upstream, input_gradient, _) = backward(&mut p, &mut layer, &x);
+    let j = jacobian(&activation::sigmoid(), slice::from_ref(&input), (x.clone(),), grid(&upstream)).unwrap();
+    assert_close(&input_gradient.data, &backpropagate(&j, &upstream), 1e-5);
+    let mut layer = Activation::tanh(&mut p).unwrap();
+    let (upstream, input_gradient, _) = backward(&mut p, &mut layer, &x);
+    let j = jacobian(&activation::hyperbolic_tangent(), slice::from_ref(&input), (x,), grid(&upstream)).unwrap();
+    assert_close(&input_gradient.data, &backpropagate(&j, &upstream), 1e-5);
+}
+
+#[test]
+fn batch_norm_tracks_running_statistics() {
+    let mut p = CPUProcessor::new();
+    let mut norm = BatchNorm::new(&mut p, 1).unwrap().momentum(0.5);
+
+    let input = Batch::new(&mut p, Tensor::from_vec(vec![1.0, 3.0], vec![2, 1])).unwrap();
+    let output = norm.forward(&mut p, &input).unwrap();
+    assert_close(&output.to_cpu(&mut p).unwrap().data, &[-1.0, 1.0], 1e-4);
+    // Mean 2 and unbiased variance 2, halfway from 0 and 1.
+    assert_close(&p.copy_to_cpu(norm.running()).data, &[1.0, 1.5], 1e-6);
+
+    norm.train(false);
+    let output = norm.forward(&mut p, &input).unwrap();
+    let scale = 1.0 / (1.5f32 + 1e-5).sqrt();
+    assert_close(&output.to_cpu(&mut p).unwrap().data, &[0.0, 2.0 * scale], 1e-6);
+}
+
+#[test]
+fn dropout_masks_while_training() {
+    let mut p = CPUProcessor::new();
+    let mut dropout = Dropout::new(&mut p, 0.5, 3).unwrap();
+
+    let input = Batch::new(&mut p, Tensor::new(1.0, vec![100, 10])).unwrap();
+    let output = dropout.forward(&mut p, &input).unwrap().to_cpu(&mut p).unwrap();
+    let kept = output.data.iter().filter(|v| **v != 0.0).count();
+    assert!(output.data.iter().all(|v| *v == 0.0 || *v == 2.0));
+    assert!((400..600).contains(&kept), "kept {} of 1000", kept);
+
+    let gradient = Batch::new(&mut p, Tensor::new(1.0, vec![100, 10])).unwrap();
+    let input_gradient = dropout.backward(&mut p, &gradient).unwrap().to_cpu(&mut p).unwrap();
+    assert_eq!(input_gradient, output);
+
+    dropout.train(false);
+    let output = dropout.forward(&mut p, &input).unwrap().to_cpu(&mut p).unwrap();
+    assert_eq!(output, Tensor::new(1.0, vec![100, 10]));
+}
+
+#[test]
+fn embedding_gathers_and_scatters_rows() {
+    let mut p = CPUProcessor::new();
+    let mut embedding = Embedding::from_tensor(&mut p, Tensor::from_vec(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0], vec![3, 2])).unwrap();
+
+    let indices = Batch::new(&mut p, Tensor::from_vec(vec![2.0, 0.0, 2.0], vec![3])).unwrap();
+    let output = embedding.forward(&mut p, &indices).unwrap();
+    assert_eq!(output.to_cpu(&mut p).unwrap().data, vec![4.0, 5.0, 0.0, 1.0, 4.0, 5.0]);
+
+    let gradient = Batch::new(&mut p, Tensor::from_vec(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0], vec![3, 2])).unwrap();
+    embedding.backward(&mut p, &gradient).unwrap();
+    assert_eq!(p.copy_to_cpu(&embedding.gradients()[0]).data, vec![3.0, 4.0, 0.0, 0.0, 6.0, 8.0]);
+}
+
+#[test]
+fn sequential_learns_a_regression() {
+    let mut p = CPUProcessor::new();
+    let mut model = Sequential::new()
+        .with(Linear::new(&mut p, 2, 8, 1).unwrap())
+        .with(Activation::tanh(&mut p).unwrap())
+        .with(Linear::new(&mut p, 8, 1, 2).unwrap());
+    let mut sgd = Sgd::new(&mut p, 0.1).unwrap().momentum(0.9);
+
+    let inputs = Tensor::from_vec(vec![0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0], vec![4, 2]);
+    let targets = [0.5, 1.0, -1.0, 0.0];
+    let input = Batch::new(&mut p, inputs).unwrap();
+
+    let mut losses = Vec::new();
+    for _ in 0..200 {
+        let output = model.forward(&mut p, &input).unwrap().to_cpu(&mut p).unwrap();
+        losses.push(output.data.iter().zip(targets).map(|(o, t)| (o - t).powi(2)).sum::<f32>() / 4.0);
+
+        let gradient = output.data.iter().zip(targets).map(|(o, t)| (o - t) / 2.0).collect();
+        let gradient = Batch::new(&mut p, Tensor::from_vec(gradient, vec![4, 1])).unwrap();
+        model.backward(&mut p, &gradient).unwrap();
+        sgd.step(&mut p, &mut model.parameters(), &model.gradients()).unwrap();
+    }
+
+    assert!(losses[199] < losses[0] / 100.0, "{} -> {}", losses[0], losses[199]);
+}
+
+#[test]
+fn rejects_invalid_use() {
+    let mut p = CPUProcessor::new();
+    let mut linear = Linear::new(&mut p, 3, 2, 1).unwrap();
+
+    let gradient = Batch::new(&mut p, Tensor::new(1.0, vec![1, 2])).unwrap();
+    assert!(matches!(linear.backward(&mut p, &gradient), Err(Error::NoForwardPass)));
+
+    let input = Batch::new(&mut p, Tensor::new(1.0, vec![1, 4])).unwrap();
+    assert!(matches!(linear.forward(&mut p, &input), Err(Error::ShapeMismatch { .. })));
+}
+
+/// The GPU can't allocate yet, so only the kernels are built and the constructors type checked.
+#[test]
+fn layers_build_for_the_gpu() {
+    let mut g = GPUProcessor::new();
+
+    assert!(g.try_build(linear::linear_forward()).is_ok());
+    assert!(g.try_build(conv::convolution_forward()).is_ok());
+    assert!(g.try_build(conv::convolution_input_gradient()).is_ok());
+    assert!(g.try_build(conv::convolution_weight_gradient()).is_ok());
+    assert!(g.try_build(norm::batch_norm_coefficients()).is_ok());
+    assert!(g.try_build(norm::layer_norm_input_gradient()).is_ok());
+    assert!(g.try_build(activation::sigmoid()).is_ok());
+    assert!(g.try_build(dropout::dropout_mask()).is_ok());
+    assert!(g.try_build(embedding::embedding_gradient()).is_ok());
+
+    let _ = (Linear::<GPUProcessor>::new, Conv2d::<GPUProcessor>::new, BatchNorm::<GPUProcessor>::new, LayerNorm::<GPUProcessor>::new);
+    let _ = (Activation::<GPUProcessor>::relu, Activation::<GPUProcessor>::tanh, Dropout::<GPUProcessor>::new, Embedding::<GPUProcessor>::new);
+}
@@ -0,0 +1,136 @@
+use chandra::core::processor::Processor;
+use chandra::optim::{Adam, CosineAnnealingLr, LrScheduler, Optimizer, OptimizerState, RmsProp, Sgd, StepLr, Warmup};
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+mod common;
+use common::assert_close;
+
+/// Runs `steps` updates of f(p) = sum(p^2) / 2, whose gradient is p.
+fn descend<O: Optimizer<CPUProcessor>>(p: &mut CPUProcessor, optimizer: &mut O, start: Vec<f32>, steps: usize) -> Vec<f32> {
+    let mut parameters = vec![p.alloc(Tensor::from(start))];
+    for _ in 0..steps {
+        let gradient = p.alloc(parameters[0].deref().clone());
+        optimizer.step(p, &mut parameters, &[gradient]).unwrap();
+    }
+    let result = parameters[0].deref().data.clone();
+    result
+}
+
+#[test]
+fn sgd_with_momentum() {
+    let mut p = CPUProcessor::new();
+    let mut sgd = Sgd::new(&mut p, 0.1).unwrap().momentum(0.9);
+
+    let (mut x, mut v) = (2.0f32, 0.0f32);
+    for _ in 0..3 {
+        v = 0.9 * v + x;
+        x -= 0.1 * v;
+    }
+
+    assert_close(&descend(&mut p, &mut sgd, vec![2.0, -1.0], 3), &[x, -x / 2.0], 1e-5);
+}
+
+#[test]
+fn sgd_with_nesterov_and_weight_decay() {
+    let mut p = CPUProcessor::new();
+    let mut sgd = Sgd::new(&mut p, 0.1).unwrap().momentum(0.5).nesterov(true).weight_decay(0.1);
+
+    let (mut x, mut v) = (1.0f32, 0.0f32);
+    for _ in 0..2 {
+        let g = x + 0.1 * x;
+        v = 0.5 * v + g;
+        x -= 0.1 * (g + 0.5 * v);
+    }
+
+    assert_close(&descend(&mut p, &mut sgd, vec![1.0], 2), &[x], 1e-5);
+}
+
+#[test]
+fn adam_and_adamw() {
+    let reference = |decoupled: f32| {
+        let (mut x, mut m, mut v) = (1.0f32, 0.0f32, 0.0f32);
+        for t in 1..=3 {
+            let g = x;
+            m = 0.9 * m + 0.1 * g;
+            v = 0.999 * v + 0.001 * g * g;
+            let m_hat = m / (1.0 - 0.9f32.powi(t));
+            let v_hat = v / (1.0 - 0.999f32.powi(t));
+            x -= 0.01 * (m_hat / (v_hat.sqrt() + 1e-8) + decoupled * x);
+        }
+        x
+    };
+
+    let mut p = CPUProcessor::new();
+    let mut adam = Adam::new(&mut p, 0.01).unwrap();
+    assert_close(&descend(&mut p, &mut adam, vec![1.0], 3), &[reference(0.0)], 1e-5);
+
+    let mut adamw = Adam::adamw(&mut p, 0.01, 0.1).unwrap();
+    assert_close(&descend(&mut p, &mut adamw, vec![1.0], 3), &[reference(0.1)], 1e-5);
+}
+
+#[test]
+fn rmsprop_with_momentum() {
+    let mut p = CPUProcessor::new();
+    let mut rmsprop = RmsProp::new(&mut p, 0.01).unwrap().momentum(0.9);
+
+    let (mut x, mut s, mut b) = (3.0f32, 0.0f32, 0.0f32);
+    for _ in 0..3 {
+        s = 0.99 * s + 0.01 * x * x;
+        b = 0.9 * b + x / (s.sqrt() + 1e-8);
+        x -= 0.01 * b;
+    }
+
+    assert_close(&descend(&mut p, &mut rmsprop, vec![3.0], 3), &[x], 1e-5);
+}
+
+#[test]
+fn state_survives_saving_and_loading() {
+    let mut p = CPUProcessor::new();
+    let mut adam = Adam::new(&mut p, 0.01).unwrap();
+    let after_two = descend(&mut p, &mut adam, vec![1.0, 2.0], 2);
+
+    let bytes = adam.state(&mut p).unwrap().to_bytes();
+    let state = OptimizerState::from_bytes(&bytes).unwrap();
+    assert_eq!(state.step, 2);
+    assert_eq!(state.buffers.len(), 2);
+
+    let mut restored = Adam::new(&mut p, 1.0).unwrap();
+    restored.load_state(&mut p, state).unwrap();
+    assert_eq!(restored.learning_rate(), 0.01);
+
+    let continued = descend(&mut p, &mut adam, after_two.clone(), 1);
+    assert_close(&descend(&mut p, &mut restored, after_two, 1), &continued, 1e-5);
+
+    assert!(OptimizerState::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn schedulers() {
+    let step = StepLr::new(1.0, 10, 0.5);
+    assert_eq!(step.learning_rate(9), 1.0);
+    assert_eq!(step.learning_rate(25), 0.25);
+
+    let cosine = CosineAnnealingLr::new(1.0, 0.0, 100);
+    assert!((cosine.learning_rate(50) - 0.5).abs() < 1e-6);
+    assert_eq!(cosine.learning_rate(200), 0.0);
+
+    let warmup = Warmup::new(4, StepLr::new(1.0, 10, 0.5));
+    assert_eq!(warmup.learning_rate(0), 0.25);
+    assert_eq!(warmup.learning_rate(4), 1.0);
+
+    let mut p = CPUProcessor::new();
+    let mut sgd = Sgd::new(&mut p, 1.0).unwrap();
+    step.apply(&mut sgd, 10);
+    assert_eq!(sgd.learning_rate(), 0.5);
+}
+
+#[test]
+fn builds_for_the_gpu() {
+    let mut g = GPUProcessor::new();
+
+    assert!(Sgd::new(&mut g, 0.1).is_ok());
+    assert!(Adam::new(&mut g, 0.1).is_ok());
+    assert!(RmsProp::new(&mut g, 0.1).is_ok());
+}
@@ -0,0 +1,155 @@
+use chandra::{kernel, ChandraFunction};
+use chandra::core::random;
+use chandra::core::type_traits::FromMut;
+use chandra::core::processor::{Processor, Executable};
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+#[cfg(feature = "gpu")]
+mod common;
+
+const SEED: u32 = 1234;
+const SAMPLES: usize = 20000;
+
+#[kernel]
+fn uniforms(pos: Pos, seed: &Tensor<u32>, out: &mut Tensor<f32>) {
+    out[pos.x] = random::uniform(seed[0], pos.x);
+}
+
+#[kernel]
+fn normals(pos: Pos, seed: &Tensor<u32>, out: &mut Tensor<f32>) {
+    out[pos.x] = random::normal(seed[0], pos.x, 2.0, 0.5);
+}
+
+#[kernel]
+fn coins(pos: Pos, seed: &Tensor<u32>, out: &mut Tensor<f32>) {
+    if random::bernoulli(seed[0], pos.x, 0.3) {
+        out[pos.x] = 1.0;
+    } else {
+        out[pos.x] = 0.0;
+    }
+}
+
+#[ChandraFunction]
+fn uniform(v: f32) -> f32 {
+    return v * 2.0;
+}
+
+#[kernel]
+fn doubled(pos: Pos, seed: &Tensor<u32>, out: &mut Tensor<f32>) {
+    out[pos.x] = uniform(random::uniform(seed[0], pos.x));
+}
+
+/// Runs a kernel taking the seed on the CPU for `SAMPLES` positions.
+macro_rules! draw {
+    ($kernel:expr) => {{
+        let mut p = CPUProcessor::new();
+        let seed = p.alloc(Tensor { data: vec![SEED], shape: vec![1] });
+        let mut out = p.alloc(Tensor::new(0f32, vec![SAMPLES]));
+
+        let mut e = $kernel.build(&mut p);
+        e.get_bindings().bind(&seed, &mut out);
+        p.dispatch(&mut e, SAMPLES as u32, 1, 1).unwrap();
+
+        let data = out.deref().data.clone();
+        data
+    }};
+}
+
+fn moments(values: &[f32]) -> (f32, f32) {
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / values.len() as f32;
+    (mean, variance)
+}
+
+#[test]
+fn kernels_draw_the_host_stream() {
+    let expected: Vec<f32> = (0..SAMPLES as u32).map(|i| random::uniform(SEED, i)).collect();
+
+    assert_eq!(draw!(uniforms()), expected);
+    assert_eq!(draw!(doubled()), expected.iter().map(|v| v * 2.0).collect::<Vec<_>>());
+}
+
+#[test]
+fn intrinsics_have_sane_moments() {
+    let u = draw!(uniforms());
+    let (mean, variance) = moments(&u);
+    assert!(u.iter().all(|v| (0.0..1.0).contains(v)));
+    assert!((mean - 0.5).abs() < 0.01, "{}", mean);
+    assert!((variance - 1.0 / 12.0).abs() < 0.005, "{}", variance);
+
+    let n = draw!(normals());
+    let (mean, variance) = moments(&n);
+    assert!(n.iter().all(|v| (v - 2.0).abs() <= 6.0 * 0.5));
+    assert!((mean - 2.0).abs() < 0.02, "{}", mean);
+    assert!((variance - 0.25).abs() < 0.01, "{}", variance);
+    let within_one_std = n.iter().filter(|v| (*v - 2.0).abs() < 0.5).count() as f32 / SAMPLES as f32;
+    assert!((within_one_std - 0.6827).abs() < 0.015, "{}", within_one_std);
+
+    let c = draw!(coins());
+    assert!(c.iter().all(|v| *v == 0.0 || *v == 1.0));
+    assert!((moments(&c).0 - 0.3).abs() < 0.01);
+}
+
+/// The helpers of the generated WGSL with an entry point writing every stream of `SEED` to `out`.
+fn stream_shader() -> String {
+    #[kernel]
+    fn all(pos: Pos, seed: &Tensor<u32>, out: &mut Tensor<f32>) {
+        if random::bernoulli(seed[0], pos.x, 0.5) {
+            out[pos.x] = random::uniform(seed[0], pos.x);
+        } else {
+            out[pos.x] = random::normal(seed[0], pos.x, 0.0, 1.0);
+        }
+    }
+
+    let mut g = GPUProcessor::new();
+    let e = all().build(&mut g);
+    let program = e.get_program();
+    let helpers = &program[..program.find("@compute").unwrap()];
+
+    format!("{}
+@group(0) @binding(0) var<storage, read_write> out: array<u32>;
+@compute @workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) pos: vec3<u32>) {{
+    let n = arrayLength(&out) / 4u;
+    if (pos.x >= n) {{
+        return;
+    }}
+    out[pos.x] = chandra_random({seed}u, pos.x);
+    out[n + pos.x] = bitcast<u32>(chandra_uniform({seed}u, pos.x));
+    out[2u * n + pos.x] = bitcast<u32>(chandra_normal({seed}u, pos.x, 0.0, 1.0));
+    out[3u * n + pos.x] = select(0u, 1u, chandra_bernoulli({seed}u, pos.x, 0.5));
+}}", helpers, seed = SEED)
+}
+
+fn host_stream(n: u32) -> Vec<u32> {
+    let counters = 0..n;
+    counters.clone().map(|i| random::random(SEED, i))
+        .chain(counters.clone().map(|i| random::uniform(SEED, i).to_bits()))
+        .chain(counters.clone().map(|i| random::normal(SEED, i, 0.0, 1.0).to_bits()))
+        .chain(counters.map(|i| random::bernoulli(SEED, i, 0.5) as u32))
+        .collect()
+}
+
+#[test]
+fn wgsl_helpers_validate() {
+    let module = naga::front::wgsl::parse_str(&stream_shader()).unwrap();
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+        .validate(&module)
+        .unwrap();
+}
+
+/// Runs the helpers on the first adapter, there is nothing to compare against without one.
+#[cfg(feature = "gpu")]
+#[test]
+fn wgsl_helpers_match_the_host_stream() {
+    let n = 4096u32;
+    let expected = host_stream(n);
+    let mut buffers = [(0, 0, vec![0u32; expected.len()])];
+
+    match common::run(&stream_shader(), &mut buffers, ((n + 63) / 64, 1, 1)) {
+        Some(()) => assert_eq!(buffers[0].2, expected),
+        None => eprintln!("no GPU adapter, skipping the WGSL stream comparison"),
+    }
+}
@@ -0,0 +1,11 @@
+use chandra::kernel;
+
+const SCALE: f32 = 2.0;
+
+#[kernel]
+fn scale(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let s = SCALE;
+    out[pos.x] = a[pos.x] * s;
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use chandra::kernel;
+
+#[kernel{ use helper }]
+fn copy(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = a[pos.x];
+}
+
+fn main() {}
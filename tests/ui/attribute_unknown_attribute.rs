@@ -0,0 +1,10 @@
+use chandra::kernel;
+
+#[kernel{
+    #![threads(64)]
+}]
+fn unknown(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = a[pos.x];
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use chandra::kernel;
+
+#[kernel]
+fn scale(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let k: f32 = 2.0;
+    let times_k = |x: f32| x * k;
+    out[pos.x] = times_k(a[pos.x]);
+}
+
+fn main() {}
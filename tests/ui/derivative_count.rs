@@ -0,0 +1,13 @@
+use chandra::ChandraFunction;
+
+#[ChandraFunction]
+fn d_a(_a: f32, b: f32) -> f32 {
+    return b;
+}
+
+#[ChandraFunction(derivative = d_a)]
+fn product(a: f32, b: f32) -> f32 {
+    return a * b;
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use chandra::{kernel, fuse};
+use chandra::types::tensor::Tensor;
+
+#[kernel]
+fn scale(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = a[pos.x] * 2.0;
+}
+
+#[kernel]
+fn difference(pos: Pos, x: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = x[pos.x + 1] - x[pos.x];
+}
+
+fuse!(scale_difference = scale, difference);
+
+fn main() {}
@@ -0,0 +1,9 @@
+use chandra::kernel;
+
+#[kernel]
+fn fill(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let half: f32 = 1;
+    out[pos.x] = a[pos.x] * half;
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use chandra::kernel;
+
+#[kernel]
+fn select(pos: Pos, a: &Tensor<u32>, out: &mut Tensor<u32>) {
+    match a[pos.x] {
+        0 => out[pos.x] = 1u32,
+        n => out[pos.x] = n,
+    }
+}
+
+fn main() {}
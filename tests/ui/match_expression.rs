@@ -0,0 +1,11 @@
+use chandra::kernel;
+
+#[kernel]
+fn select(pos: Pos, a: &Tensor<u32>, out: &mut Tensor<u32>) {
+    out[pos.x] = 1u32 + match a[pos.x] {
+        0 => 1u32,
+        _ => 2u32,
+    };
+}
+
+fn main() {}
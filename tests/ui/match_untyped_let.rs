@@ -0,0 +1,12 @@
+use chandra::kernel;
+
+#[kernel]
+fn select(pos: Pos, a: &Tensor<u32>, out: &mut Tensor<u32>) {
+    let v = match a[pos.x] {
+        0 => 1u32,
+        _ => 2u32,
+    };
+    out[pos.x] = v;
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use chandra::kernel;
+
+#[kernel]
+fn split(pos: Pos, a: &Tensor<f32>, left: &mut Tensor<f32>, right: &mut Tensor<f32>) {
+    left[pos.x] = a[pos.x];
+}
+
+fn main() {}
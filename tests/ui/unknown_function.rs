@@ -0,0 +1,8 @@
+use chandra::kernel;
+
+#[kernel]
+fn activate(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = relu(a[pos.x]);
+}
+
+fn main() {}
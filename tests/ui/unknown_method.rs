@@ -0,0 +1,9 @@
+use chandra::kernel;
+
+#[kernel]
+fn round(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let x = a[pos.x];
+    out[pos.x] = x.round();
+}
+
+fn main() {}
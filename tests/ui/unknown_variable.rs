@@ -0,0 +1,8 @@
+use chandra::kernel;
+
+#[kernel]
+fn scale(pos: Pos, a: &Tensor<f32>, out: &mut Tensor<f32>) {
+    out[pos.x] = a[pos.x] * factor;
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use chandra::kernel;
+
+#[kernel]
+fn count(pos: Pos, a: &Tensor<u32>, out: &mut Tensor<u32>) {
+    let i = 0u32;
+    while i < a[pos.x] {
+        out[pos.x] = i;
+    }
+}
+
+fn main() {}
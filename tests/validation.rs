@@ -0,0 +1,126 @@
+use chandra::{kernel, Error};
+use chandra::core::processor::{Processor, Executable};
+use chandra::core::type_traits::FromMut;
+use chandra::processor::cpu::CPUProcessor;
+use chandra::processor::gpu::processor::GPUProcessor;
+use chandra::types::tensor::Tensor;
+
+#[cfg(feature = "gpu")]
+mod common;
+
+#[kernel]
+#[shape(m.shape[1] == v.shape[0], m.shape[0] == out.shape[0], v.shape[0] < 64)]
+#[shape(grid.x == out.shape[0], grid.y == 1)]
+fn matvec(pos: Pos, m: &Tensor<f32>, v: &Tensor<f32>, out: &mut Tensor<f32>) {
+    let row = pos.x * v.len();
+    out[pos.x] = m[row] * v[0] + m[row + 1] * v[1] + m[row + 2] * v[2];
+}
+
+#[test]
+fn valid_shapes_dispatch() {
+    let mut p = CPUProcessor::new();
+    let m = p.alloc(Tensor { data: vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0], shape: vec![2, 3] });
+    let v = p.alloc(Tensor { data: vec![1.0, 0.0, -1.0], shape: vec![3] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![2]));
+
+    let mut e = matvec().build(&mut p);
+    e.get_bindings().try_bind(&m, &v, &mut out).unwrap();
+    p.dispatch(&mut e, 2, 1, 1).unwrap();
+
+    assert_eq!(out.deref().data, vec![-2.0, -2.0]);
+}
+
+#[test]
+fn binding_checks_the_argument_shapes() {
+    let mut p = CPUProcessor::new();
+    let m = p.alloc(Tensor::new(1f32, vec![2, 3]));
+    let v = p.alloc(Tensor::new(1f32, vec![4]));
+    let mut out = p.alloc(Tensor::new(0f32, vec![2]));
+
+    let mut e = matvec().build(&mut p);
+    match e.get_bindings().try_bind(&m, &v, &mut out) {
+        Err(Error::ShapeMismatch { argument, constraint, found }) => {
+            assert_eq!((argument.as_str(), constraint.as_str(), found), ("m", "m.shape[1] == v.shape[0]", vec![2, 3]));
+        }
+        any => panic!("expected a shape mismatch, got {:?}", any),
+    }
+
+    let wide = p.alloc(Tensor::new(1f32, vec![1, 64]));
+    let long = p.alloc(Tensor::new(1f32, vec![64]));
+    let mut one = p.alloc(Tensor::new(0f32, vec![1]));
+    match e.get_bindings().try_bind(&wide, &long, &mut one) {
+        Err(Error::ShapeMismatch { argument, constraint, .. }) => assert_eq!((argument.as_str(), constraint.as_str()), ("v", "v.shape[0] < 64")),
+        any => panic!("expected a shape mismatch, got {:?}", any),
+    }
+}
+
+#[test]
+fn dispatch_checks_the_grid() {
+    let mut p = CPUProcessor::new();
+    let m = p.alloc(Tensor::new(1f32, vec![2, 3]));
+    let v = p.alloc(Tensor::new(1f32, vec![3]));
+    let mut out = p.alloc(Tensor::new(0f32, vec![2]));
+
+    let mut e = matvec().build(&mut p);
+    e.get_bindings().try_bind(&m, &v, &mut out).unwrap();
+
+    for (grid, violated) in [((3, 1, 1), "grid.x == out.shape[0]"), ((2, 2, 1), "grid.y == 1")] {
+        match p.dispatch(&mut e, grid.0, grid.1, grid.2) {
+            Err(Error::InvalidGrid { constraint, grid: found }) => assert_eq!((constraint.as_str(), found), (violated, grid)),
+            any => panic!("expected an invalid grid, got {:?}", any),
+        }
+    }
+    assert_eq!(out.deref().data, vec![0.0, 0.0]);
+}
+
+/// The generated kernel with its tensors declared, `pos` bound to the invocation id.
+fn standalone(program: &str) -> String {
+    let tensors = "struct ChandraF32 { data: array<f32> }
+@group(0) @binding(0) var<storage, read_write> m: ChandraF32;
+@group(0) @binding(1) var<storage, read_write> v: ChandraF32;
+@group(0) @binding(2) var<storage, read_write> out: ChandraF32;";
+
+    format!("{}\n{}", tensors, program.replace("fn main()", "fn main(@builtin(global_invocation_id) pos: vec3<u32>)"))
+}
+
+#[test]
+fn constraints_stay_on_the_host() {
+    let mut g = GPUProcessor::new();
+    let e = matvec().build(&mut g);
+    let program = e.get_program();
+
+    assert!(program.contains("pos.x * arrayLength(&v.data)"), "{}", program);
+    assert!(program.contains("out.data[pos.x] = "), "{}", program);
+    assert!(!program.contains("shape") && !program.contains("grid"), "{}", program);
+
+    let module = naga::front::wgsl::parse_str(&standalone(program)).unwrap();
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+        .validate(&module)
+        .unwrap();
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn gpu_matches_the_validated_dispatch() {
+    let mut g = GPUProcessor::new();
+    let e = matvec().build(&mut g);
+
+    // one workgroup of 64 rows, the grid the constraints accept for a 64 element output
+    let bits = |values: Vec<f32>| values.into_iter().map(f32::to_bits).collect::<Vec<_>>();
+    let m: Vec<f32> = (0..64 * 3).map(|i| i as f32).collect();
+    let mut buffers = [(0, 0, bits(m.clone())), (0, 1, bits(vec![1.0, 0.0, -1.0])), (0, 2, vec![0u32; 64])];
+    if common::run(&standalone(e.get_program()), &mut buffers, (1, 1, 1)).is_none() {
+        eprintln!("no GPU adapter, skipping the validated dispatch comparison");
+        return;
+    }
+
+    let mut p = CPUProcessor::new();
+    let m = p.alloc(Tensor { data: m, shape: vec![64, 3] });
+    let v = p.alloc(Tensor { data: vec![1.0, 0.0, -1.0], shape: vec![3] });
+    let mut out = p.alloc(Tensor::new(0f32, vec![64]));
+    let mut c = matvec().build(&mut p);
+    c.get_bindings().try_bind(&m, &v, &mut out).unwrap();
+    p.dispatch(&mut c, 64, 1, 1).unwrap();
+
+    assert_eq!(buffers[2].2, bits(out.deref().data.clone()));
+}